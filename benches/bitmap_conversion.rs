@@ -0,0 +1,66 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crabgrab::feature::bitmap::{FrameBitmap, FrameBitmapBgraUnorm8x4, FrameBitmapFormat, FrameBitmapRgbaF16x4, FrameBitmapRgbaUnormPacked1010102, FrameBitmapYCbCr, VideoRange, YCbCrMatrix};
+use half::f16;
+
+const SIZES: [(&str, usize, usize); 2] = [("1080p", 1920, 1080), ("4k", 3840, 2160)];
+
+// A cheap deterministic pattern, so the conversions see varied pixel values
+fn pattern(x: usize, y: usize) -> u32 {
+    let value = (x as u32).wrapping_mul(0x9E37_79B9) ^ (y as u32).wrapping_mul(0x85EB_CA6B);
+    value ^ (value >> 15)
+}
+
+fn bgra_bitmap(width: usize, height: usize) -> FrameBitmap {
+    let data = (0..width * height).map(|i| pattern(i % width, i / width).to_le_bytes()).collect();
+    FrameBitmap::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 { data, width, height })
+}
+
+fn packed_1010102_bitmap(width: usize, height: usize) -> FrameBitmap {
+    let data = (0..width * height).map(|i| pattern(i % width, i / width)).collect();
+    FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 { data, width, height })
+}
+
+fn f16_bitmap(width: usize, height: usize) -> FrameBitmap {
+    let data = (0..width * height).map(|i| {
+        let [r, g, b, _] = pattern(i % width, i / width).to_le_bytes();
+        [f16::from_f32(r as f32 / 200.0), f16::from_f32(g as f32 / 255.0), f16::from_f32(b as f32 / 255.0), f16::ONE]
+    }).collect();
+    FrameBitmap::RgbaF16x4(FrameBitmapRgbaF16x4 { data, width, height })
+}
+
+fn ycbcr_bitmap(width: usize, height: usize) -> FrameBitmap {
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let luma_data: Vec<u8> = (0..width * height).map(|i| pattern(i % width, i / width) as u8).collect();
+    let chroma_data: Vec<[u8; 2]> = (0..chroma_width * chroma_height).map(|i| {
+        let [cb, cr, _, _] = pattern(i % chroma_width, i / chroma_width).to_le_bytes();
+        [cb, cr]
+    }).collect();
+    FrameBitmap::YCbCr(FrameBitmapYCbCr::new(luma_data, (width, height), chroma_data, (chroma_width, chroma_height), YCbCrMatrix::Bt709, VideoRange::Video).unwrap())
+}
+
+fn bench_conversion(c: &mut Criterion, name: &str, source: fn(usize, usize) -> FrameBitmap, target: FrameBitmapFormat) {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(20);
+    for (size_name, width, height) in SIZES {
+        let bitmap = source(width, height);
+        group.throughput(Throughput::Elements((width * height) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size_name), &bitmap, |b, bitmap| {
+            b.iter(|| bitmap.convert(target).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn conversion_benches(c: &mut Criterion) {
+    let ycbcr = FrameBitmapFormat::YCbCr { matrix: YCbCrMatrix::Bt709, range: VideoRange::Video };
+    bench_conversion(c, "bgra_to_rgba", bgra_bitmap, FrameBitmapFormat::RgbaUnorm8x4);
+    bench_conversion(c, "ycbcr_to_bgra", ycbcr_bitmap, FrameBitmapFormat::BgraUnorm8x4);
+    bench_conversion(c, "ycbcr_to_rgba", ycbcr_bitmap, FrameBitmapFormat::RgbaUnorm8x4);
+    bench_conversion(c, "1010102_to_bgra", packed_1010102_bitmap, FrameBitmapFormat::BgraUnorm8x4);
+    bench_conversion(c, "f16_to_bgra", f16_bitmap, FrameBitmapFormat::BgraUnorm8x4);
+    bench_conversion(c, "bgra_to_ycbcr", bgra_bitmap, ycbcr);
+    bench_conversion(c, "ycbcr_to_f16", ycbcr_bitmap, FrameBitmapFormat::RgbaF16x4);
+}
+
+criterion_group!(benches, conversion_benches);
+criterion_main!(benches);
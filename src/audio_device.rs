@@ -0,0 +1,118 @@
+use std::{error::Error, fmt::Display};
+
+use crate::{frame::{AudioChannelCount, AudioSampleRate}, platform::platform_impl::ImplAudioDevice};
+
+/// Represents an error that occurred when enumerating audio devices
+#[derive(Debug, Clone)]
+pub enum AudioDeviceError {
+    Other(String)
+}
+
+impl Display for AudioDeviceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Other(message) => f.write_fmt(format_args!("AudioDeviceError::Other(\"{}\")", message))
+        }
+    }
+}
+
+impl Error for AudioDeviceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+
+    fn description(&self) -> &str {
+        "description() is deprecated; use Display"
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        self.source()
+    }
+}
+
+/// Whether an audio device records or plays audio
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AudioDeviceKind {
+    /// A device that records audio, like a microphone
+    Input,
+    /// A device that plays audio, like speakers or headphones
+    Output,
+}
+
+/// The format an audio device natively runs at
+/// 
+/// Captured audio is converted from this format to the format in the `AudioCaptureConfig`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AudioDeviceFormat {
+    /// The number of samples per second, per channel
+    pub samples_per_second: u32,
+    /// The number of channels
+    pub channel_count: usize,
+}
+
+impl AudioDeviceFormat {
+    /// Get the matching capture sample rate, if it's one of the supported rates
+    pub fn sample_rate(&self) -> Option<AudioSampleRate> {
+        AudioSampleRate::from_samples_per_second(self.samples_per_second as f64)
+    }
+
+    /// Get the matching capture channel layout, if there is one for this number of channels
+    pub fn channel_layout(&self) -> Option<AudioChannelCount> {
+        AudioChannelCount::from_count(self.channel_count)
+    }
+}
+
+/// Represents an audio input or output device
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct AudioDevice {
+    pub(crate) impl_audio_device: ImplAudioDevice,
+}
+
+impl AudioDevice {
+    /// Get the currently connected audio input devices
+    pub fn input_devices() -> Result<Vec<AudioDevice>, AudioDeviceError> {
+        Ok(ImplAudioDevice::input_devices()?.into_iter().map(|impl_audio_device| AudioDevice { impl_audio_device }).collect())
+    }
+
+    /// Get the system default audio input device, if there is one
+    pub fn default_input_device() -> Result<Option<AudioDevice>, AudioDeviceError> {
+        Ok(Self::input_devices()?.into_iter().find(AudioDevice::is_default))
+    }
+
+    /// Get the currently connected audio output devices
+    /// 
+    /// Note: On MacOS, system audio is always captured from the mix of all output devices, so output devices can't be chosen for capture
+    pub fn output_devices() -> Result<Vec<AudioDevice>, AudioDeviceError> {
+        Ok(ImplAudioDevice::output_devices()?.into_iter().map(|impl_audio_device| AudioDevice { impl_audio_device }).collect())
+    }
+
+    /// Get the system default audio output device, if there is one
+    pub fn default_output_device() -> Result<Option<AudioDevice>, AudioDeviceError> {
+        Ok(Self::output_devices()?.into_iter().find(AudioDevice::is_default))
+    }
+
+    /// Get the unique, persistent identifier of the device
+    pub fn id(&self) -> String {
+        self.impl_audio_device.id.clone()
+    }
+
+    /// Get the human-readable name of the device
+    pub fn name(&self) -> String {
+        self.impl_audio_device.name.clone()
+    }
+
+    /// Check whether this was the system default device when it was enumerated
+    pub fn is_default(&self) -> bool {
+        self.impl_audio_device.is_default
+    }
+
+    /// Get whether this is an input or output device
+    pub fn kind(&self) -> AudioDeviceKind {
+        self.impl_audio_device.kind
+    }
+
+    /// Get the format the device natively runs at, if it could be determined
+    pub fn native_format(&self) -> Option<AudioDeviceFormat> {
+        self.impl_audio_device.native_format
+    }
+}
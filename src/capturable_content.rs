@@ -1,6 +1,8 @@
-use std::{error::Error, fmt::{Debug, Display}};
+use std::{collections::HashMap, error::Error, fmt::{Debug, Display}, path::PathBuf, pin::Pin, sync::mpsc, task::{Context, Poll}, time::Duration};
 
-use crate::{platform::platform_impl::{ImplCapturableApplication, ImplCapturableContent, ImplCapturableContentFilter, ImplCapturableDisplay, ImplCapturableWindow}, util::Rect};
+use futures::{channel::mpsc as futures_mpsc, Stream};
+
+use crate::{platform::platform_impl::{ImplCapturableApplication, ImplCapturableContent, ImplCapturableContentFilter, ImplCapturableContentNotifier, ImplCapturableDisplay, ImplCapturableWindow}, util::{Rect, Size}};
 
 /// Represents an error that occurred when enumerating capturable content
 #[derive(Debug, Clone)]
@@ -30,18 +32,151 @@ impl Error for CapturableContentError {
     }
 }
 
+// The regex a filter matches titles against - uninhabited without the `regex` feature, so the filter's
+// fields are the same whether or not the feature is enabled
+#[cfg(feature = "regex")]
+type TitleRegex = regex::Regex;
+#[cfg(not(feature = "regex"))]
+#[derive(Clone)]
+enum TitleRegex {}
+
 #[derive(Clone)]
 /// Selects the kind of windows to enumerate for capture
+///
+/// Start from `CapturableWindowFilter::DEFAULT` and narrow it with the `with_*` methods.
 pub struct CapturableWindowFilter {
     /// Desktop windows are elements of the desktop environment, E.G. the dock on MacOS or the start bar on Windows.
     pub desktop_windows: bool,
     /// Whether to restrict to onscreen windows
     pub onscreen_only: bool,
+    /// If Some, only windows whose title contains this string are enumerated
+    pub title_contains: Option<String>,
+    // If Some, only windows whose title matches this regular expression are enumerated - set with `with_title_regex`
+    #[cfg_attr(not(feature = "regex"), allow(dead_code))]
+    title_regex: Option<TitleRegex>,
+    /// If Some, only windows of the application with this identifier are enumerated (compared case-insensitively)
+    pub application_identifier: Option<String>,
+    /// If Some, only windows owned by the process with this pid are enumerated
+    pub pid: Option<i32>,
+    /// If Some, only windows at least this large in both dimensions are enumerated
+    pub min_size: Option<Size>,
+    /// Whether to exclude windows owned by the current process
+    pub exclude_current_process: bool,
+    /// Whether to exclude windows with empty titles
+    pub exclude_empty_titles: bool,
 }
 
 impl Default for CapturableWindowFilter {
     fn default() -> Self {
-        Self { desktop_windows: false, onscreen_only: true }
+        Self::DEFAULT
+    }
+}
+
+impl CapturableWindowFilter {
+    /// Onscreen, non-desktop windows with no further restrictions
+    pub const DEFAULT: Self = CapturableWindowFilter {
+        desktop_windows: false,
+        onscreen_only: true,
+        title_contains: None,
+        title_regex: None,
+        application_identifier: None,
+        pid: None,
+        min_size: None,
+        exclude_current_process: false,
+        exclude_empty_titles: false,
+    };
+
+    /// Set whether to include desktop windows
+    pub fn with_desktop_windows(self, desktop_windows: bool) -> Self {
+        Self { desktop_windows, ..self }
+    }
+
+    /// Set whether to restrict to onscreen windows
+    pub fn with_onscreen_only(self, onscreen_only: bool) -> Self {
+        Self { onscreen_only, ..self }
+    }
+
+    /// Only include windows whose title contains the given string
+    pub fn with_title_contains(self, title: impl Into<String>) -> Self {
+        Self { title_contains: Some(title.into()), ..self }
+    }
+
+    /// Only include windows whose title matches the given regular expression
+    #[cfg(feature = "regex")]
+    pub fn with_title_regex(self, title_regex: regex::Regex) -> Self {
+        Self { title_regex: Some(title_regex), ..self }
+    }
+
+    /// Only include windows of the application with the given identifier
+    pub fn with_application_identifier(self, application_identifier: impl Into<String>) -> Self {
+        Self { application_identifier: Some(application_identifier.into()), ..self }
+    }
+
+    /// Only include windows owned by the process with the given pid
+    pub fn with_pid(self, pid: i32) -> Self {
+        Self { pid: Some(pid), ..self }
+    }
+
+    /// Only include windows at least as wide and as tall as the given size
+    pub fn with_min_size(self, min_size: Size) -> Self {
+        Self { min_size: Some(min_size), ..self }
+    }
+
+    /// Exclude windows owned by the current process
+    pub fn with_exclude_current_process(self) -> Self {
+        Self { exclude_current_process: true, ..self }
+    }
+
+    /// Exclude windows with empty titles
+    pub fn with_exclude_empty_titles(self) -> Self {
+        Self { exclude_empty_titles: true, ..self }
+    }
+
+    /// Apply the predicates of this filter which aren't handled natively by the OS
+    pub(crate) fn matches(&self, window: &CapturableWindow) -> bool {
+        let needs_title = self.exclude_empty_titles || self.title_contains.is_some();
+        #[cfg(feature = "regex")]
+        let needs_title = needs_title || self.title_regex.is_some();
+        if needs_title {
+            let title = window.title();
+            if self.exclude_empty_titles && title.is_empty() {
+                return false;
+            }
+            if let Some(title_contains) = &self.title_contains {
+                if !title.contains(title_contains.as_str()) {
+                    return false;
+                }
+            }
+            #[cfg(feature = "regex")]
+            if let Some(title_regex) = &self.title_regex {
+                if !title_regex.is_match(&title) {
+                    return false;
+                }
+            }
+        }
+        if let Some(min_size) = &self.min_size {
+            let size = window.rect().size;
+            if size.width < min_size.width || size.height < min_size.height {
+                return false;
+            }
+        }
+        if self.pid.is_some() || self.exclude_current_process || self.application_identifier.is_some() {
+            let application = window.application();
+            if let Some(pid) = self.pid {
+                if application.pid() != pid {
+                    return false;
+                }
+            }
+            if self.exclude_current_process && application.pid() == std::process::id() as i32 {
+                return false;
+            }
+            if let Some(application_identifier) = &self.application_identifier {
+                if !application.identifier().eq_ignore_ascii_case(application_identifier) {
+                    return false;
+                }
+            }
+        }
+        true
     }
 }
 
@@ -86,6 +221,7 @@ impl CapturableContentFilter {
         windows: Some(CapturableWindowFilter {
             desktop_windows: true,
             onscreen_only: false,
+            ..CapturableWindowFilter::DEFAULT
         }),
         displays: false,
         impl_capturable_content_filter: ImplCapturableContentFilter::DEFAULT,
@@ -96,6 +232,7 @@ impl CapturableContentFilter {
         windows: Some(CapturableWindowFilter {
             desktop_windows: true,
             onscreen_only: false,
+            ..CapturableWindowFilter::DEFAULT
         }),
         displays: true,
         impl_capturable_content_filter: ImplCapturableContentFilter::DEFAULT,
@@ -103,20 +240,14 @@ impl CapturableContentFilter {
 
     /// Only normal windows - no modal panels, not the dock on macos, etc.
     pub const NORMAL_WINDOWS: Self = CapturableContentFilter {
-        windows: Some(CapturableWindowFilter {
-            desktop_windows: false,
-            onscreen_only: true
-        }),
+        windows: Some(CapturableWindowFilter::DEFAULT),
         displays: false,
         impl_capturable_content_filter: ImplCapturableContentFilter::NORMAL_WINDOWS,
     };
 
     /// Only normal windows and displays
     pub const EVERYTHING_NORMAL: Self = CapturableContentFilter {
-        windows: Some(CapturableWindowFilter {
-            desktop_windows: false,
-            onscreen_only: true,
-        }),
+        windows: Some(CapturableWindowFilter::DEFAULT),
         displays: true,
         impl_capturable_content_filter: ImplCapturableContentFilter::NORMAL_WINDOWS,
     };
@@ -207,6 +338,222 @@ impl CapturableContent {
     pub fn displays<'a>(&'a self) -> CapturableDisplayIterator<'a> {
         CapturableDisplayIterator { content: self, i: 0 }
     }
+
+    /// Find the window in this content matching a previously stored window identifier
+    /// 
+    /// The native window handle is tried first. If it is no longer valid (for example, after the application or the system restarted),
+    /// a window from the same application with the same title is chosen. Otherwise, the window is considered gone, and `None` is returned.
+    pub fn find_window(&self, id: &CapturableWindowId) -> Option<CapturableWindow> {
+        let same_application = |window: &CapturableWindow| window.application().identifier() == id.application_identifier;
+        if let Some(window) = self.windows().find(|window| window.impl_capturable_window.native_id() == id.native_id && same_application(window)) {
+            return Some(window);
+        }
+        self.windows().find(|window| same_application(window) && window.title() == id.title)
+    }
+
+    /// Find the display in this content matching a previously stored display identifier
+    /// 
+    /// The native display handle is tried first. If it is no longer valid, a display with the same hardware identifier (derived from
+    /// the display's EDID) is chosen, preferring the one closest to the stored position, and finally a display with the same rectangle.
+    pub fn find_display(&self, id: &CapturableDisplayId) -> Option<CapturableDisplay> {
+        let hardware_id_matches = |display: &CapturableDisplay| display.impl_capturable_display.hardware_id() == id.hardware_id;
+        if let Some(display) = self.displays().find(|display| display.impl_capturable_display.native_id() == id.native_id && hardware_id_matches(display)) {
+            return Some(display);
+        }
+        if id.hardware_id.is_some() {
+            let distance_squared = |display: &CapturableDisplay| {
+                let origin = display.rect().origin;
+                (origin.x - id.rect.origin.x).powi(2) + (origin.y - id.rect.origin.y).powi(2)
+            };
+            let closest_display = self.displays()
+                .filter(hardware_id_matches)
+                .min_by(|a, b| distance_squared(a).total_cmp(&distance_squared(b)));
+            if closest_display.is_some() {
+                return closest_display;
+            }
+        }
+        self.displays().find(|display| display.rect() == id.rect)
+    }
+
+    /// Watch for changes to the capturable content matching the given filter
+    /// 
+    /// Events are reported relative to the content enumerated when watching starts. See `CapturableContentWatcher` for details,
+    /// including the polling used for window changes on MacOS.
+    pub fn watch(filter: CapturableContentFilter) -> CapturableContentWatcher {
+        let (trigger_tx, trigger_rx) = mpsc::channel();
+        let (event_tx, event_rx) = futures_mpsc::unbounded();
+        let notifier = ImplCapturableContentNotifier::new(move || {
+            let _ = trigger_tx.send(());
+        });
+        std::thread::spawn(move || CapturableContentWatcher::watch_thread(filter, trigger_rx, event_tx));
+        CapturableContentWatcher {
+            receiver: event_rx,
+            _notifier: notifier,
+        }
+    }
+
+    /// Get the capturable displays in this content which the given window overlaps
+    /// 
+    /// Note: This requires that displays were enumerated by the content filter
+    pub fn displays_for_window(&self, window: &CapturableWindow) -> Vec<CapturableDisplay> {
+        let window_rect = window.frame_rect();
+        self.displays()
+            .filter(|display| display.rect().intersects(&window_rect))
+            .collect()
+    }
+}
+
+/// A change in capturable content, as reported by a `CapturableContentWatcher`
+#[derive(Debug, Clone)]
+pub enum CapturableContentEvent {
+    /// A window became capturable
+    WindowCreated(CapturableWindow),
+    /// A window is no longer capturable
+    /// 
+    /// Note: The window will generally no longer be valid, so only the information in the event is reliable
+    WindowDestroyed {
+        window: CapturableWindow,
+        title: String,
+        rect: Rect,
+    },
+    /// A window's title changed
+    WindowRetitled {
+        window: CapturableWindow,
+        old_title: String,
+        new_title: String,
+    },
+    /// A window was moved or resized
+    WindowMoved {
+        window: CapturableWindow,
+        old_rect: Rect,
+        new_rect: Rect,
+    },
+    /// A display became capturable
+    DisplayAdded(CapturableDisplay),
+    /// A display is no longer capturable
+    DisplayRemoved(CapturableDisplay),
+    /// A display was moved, resized, or otherwise reconfigured
+    DisplayReconfigured {
+        display: CapturableDisplay,
+        old_rect: Rect,
+        new_rect: Rect,
+    },
+}
+
+struct CapturableContentSnapshot {
+    windows: Vec<(u64, CapturableWindow, String, Rect)>,
+    displays: Vec<(u64, CapturableDisplay, Rect)>,
+}
+
+impl CapturableContentSnapshot {
+    fn new(content: &CapturableContent) -> Self {
+        Self {
+            windows: content.windows().map(|window| (window.impl_capturable_window.native_id(), window.clone(), window.title(), window.rect())).collect(),
+            displays: content.displays().map(|display| (display.impl_capturable_display.native_id(), display.clone(), display.rect())).collect(),
+        }
+    }
+
+    fn diff(&self, previous: &Self) -> Vec<CapturableContentEvent> {
+        let mut events = Vec::new();
+        let previous_windows: HashMap<u64, &(u64, CapturableWindow, String, Rect)> = previous.windows.iter().map(|entry| (entry.0, entry)).collect();
+        let current_windows: HashMap<u64, &(u64, CapturableWindow, String, Rect)> = self.windows.iter().map(|entry| (entry.0, entry)).collect();
+        for (_, window, title, rect) in previous.windows.iter().filter(|entry| !current_windows.contains_key(&entry.0)) {
+            events.push(CapturableContentEvent::WindowDestroyed { window: window.clone(), title: title.clone(), rect: *rect });
+        }
+        for (native_id, window, title, rect) in self.windows.iter() {
+            match previous_windows.get(native_id) {
+                None => events.push(CapturableContentEvent::WindowCreated(window.clone())),
+                Some((_, _, old_title, old_rect)) => {
+                    if old_title != title {
+                        events.push(CapturableContentEvent::WindowRetitled { window: window.clone(), old_title: old_title.clone(), new_title: title.clone() });
+                    }
+                    if old_rect != rect {
+                        events.push(CapturableContentEvent::WindowMoved { window: window.clone(), old_rect: *old_rect, new_rect: *rect });
+                    }
+                }
+            }
+        }
+        let previous_displays: HashMap<u64, &(u64, CapturableDisplay, Rect)> = previous.displays.iter().map(|entry| (entry.0, entry)).collect();
+        let current_displays: HashMap<u64, &(u64, CapturableDisplay, Rect)> = self.displays.iter().map(|entry| (entry.0, entry)).collect();
+        for (_, display, _) in previous.displays.iter().filter(|entry| !current_displays.contains_key(&entry.0)) {
+            events.push(CapturableContentEvent::DisplayRemoved(display.clone()));
+        }
+        for (native_id, display, rect) in self.displays.iter() {
+            match previous_displays.get(native_id) {
+                None => events.push(CapturableContentEvent::DisplayAdded(display.clone())),
+                Some((_, _, old_rect)) => {
+                    if old_rect != rect {
+                        events.push(CapturableContentEvent::DisplayReconfigured { display: display.clone(), old_rect: *old_rect, new_rect: *rect });
+                    }
+                }
+            }
+        }
+        events
+    }
+}
+
+/// A stream of changes to capturable content, created with `CapturableContent::watch`
+/// 
+/// The content is re-enumerated in a background thread when the OS reports a change (WinEvent hooks on Windows,
+/// NSWorkspace notifications and display reconfiguration callbacks on MacOS), and polled every `POLL_INTERVAL` as a fallback
+/// for changes the OS doesn't report. Watching stops when the watcher is dropped.
+/// 
+/// Note: MacOS has no notifications for individual windows, only for applications launching, terminating, hiding or unhiding,
+/// space switches and display reconfigurations. Windows being created, destroyed, moved or retitled within a running application
+/// are only found by polling, so they're reported up to `POLL_INTERVAL` late. OS notifications are also only delivered while the
+/// main thread runs its run loop; otherwise all changes are found by polling.
+pub struct CapturableContentWatcher {
+    receiver: futures_mpsc::UnboundedReceiver<Result<CapturableContentEvent, CapturableContentError>>,
+    _notifier: Option<ImplCapturableContentNotifier>,
+}
+
+impl Stream for CapturableContentWatcher {
+    type Item = Result<CapturableContentEvent, CapturableContentError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+impl CapturableContentWatcher {
+    /// How often capturable content is polled for changes the OS doesn't report
+    pub const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+    fn watch_thread(filter: CapturableContentFilter, trigger_rx: mpsc::Receiver<()>, event_tx: futures_mpsc::UnboundedSender<Result<CapturableContentEvent, CapturableContentError>>) {
+        let mut previous = match futures::executor::block_on(CapturableContent::new(filter.clone())) {
+            Ok(content) => CapturableContentSnapshot::new(&content),
+            Err(error) => {
+                let _ = event_tx.unbounded_send(Err(error));
+                return;
+            }
+        };
+        loop {
+            if let Err(mpsc::RecvTimeoutError::Disconnected) = trigger_rx.recv_timeout(Self::POLL_INTERVAL) {
+                // No OS notifications are available, so only poll
+                std::thread::sleep(Self::POLL_INTERVAL);
+            }
+            // Coalesce bursts of notifications into a single enumeration
+            while trigger_rx.try_recv().is_ok() {}
+            if event_tx.is_closed() {
+                return;
+            }
+            let current = match futures::executor::block_on(CapturableContent::new(filter.clone())) {
+                Ok(content) => CapturableContentSnapshot::new(&content),
+                Err(error) => {
+                    if event_tx.unbounded_send(Err(error)).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+            for event in current.diff(&previous) {
+                if event_tx.unbounded_send(Ok(event)).is_err() {
+                    return;
+                }
+            }
+            previous = current;
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -215,6 +562,41 @@ pub(crate) enum Capturable {
     Display(CapturableDisplay),
 }
 
+/// A persistable identifier for a capturable window
+/// 
+/// Resolve this back to a window with `CapturableContent::find_window`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CapturableWindowId {
+    native_id: u64,
+    application_identifier: String,
+    title: String,
+}
+
+/// A persistable identifier for a capturable display
+/// 
+/// Resolve this back to a display with `CapturableContent::find_display`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CapturableDisplayId {
+    native_id: u64,
+    hardware_id: Option<String>,
+    rect: Rect,
+}
+
+/// The presentation state of a capturable window
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum CapturableWindowState {
+    /// The window is neither minimized, maximized, nor fullscreen
+    Normal,
+    /// The window is minimized (iconified)
+    Minimized,
+    /// The window is maximized to fill its display's work area
+    Maximized,
+    /// The window covers its entire display
+    Fullscreen,
+}
+
 /// Represents a capturable application window
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct CapturableWindow {
@@ -246,6 +628,68 @@ impl CapturableWindow {
     pub fn is_visible(&self) -> bool {
         self.impl_capturable_window.is_visible()
     }
+
+    /// Gets the virtual screen rectangle of the visible window frame, including decorations like the title bar
+    /// 
+    /// Note: On windows, this excludes the invisible resize borders which are included in `rect()`
+    pub fn frame_rect(&self) -> Rect {
+        self.impl_capturable_window.frame_rect()
+    }
+
+    /// Gets the virtual screen rectangle of the window's client area, excluding decorations
+    /// 
+    /// Note: This is currently unavailable on MacOS
+    pub fn client_rect(&self) -> Option<Rect> {
+        self.impl_capturable_window.client_rect()
+    }
+
+    /// Gets the position of this window in the z-order, where 0 is the front-most window and larger values are further back
+    /// 
+    /// The value counts the windows in front of this one, which includes windows that aren't capturable (such as
+    /// hidden or system windows), so it's only meaningful for comparing windows with each other, and may change as
+    /// windows are opened, closed or raised.
+    /// 
+    /// Note: On MacOS, only on-screen windows have a z-order, and only on-screen windows are counted
+    pub fn z_order(&self) -> Option<usize> {
+        self.impl_capturable_window.z_order()
+    }
+
+    /// Gets the presentation state of the window (minimized, maximized, fullscreen)
+    /// 
+    /// Note: On MacOS, windows are never reported as maximized, and the state of off-screen windows is unknown
+    pub fn state(&self) -> Option<CapturableWindowState> {
+        self.impl_capturable_window.state()
+    }
+
+    /// Gets the window which owns this window, if any (for example, the main window of a dialog)
+    /// 
+    /// Note: This is currently unavailable on MacOS
+    pub fn owner(&self) -> Option<CapturableWindow> {
+        self.impl_capturable_window.owner().map(|impl_capturable_window| CapturableWindow {
+            impl_capturable_window
+        })
+    }
+
+    /// Checks whether this window currently has keyboard focus
+    /// 
+    /// On Windows, this asks the OS whether this is the foreground window.
+    /// 
+    /// Note: On MacOS, the focused window isn't queried from the OS but inferred: it's taken to be the front-most
+    /// on-screen window in the normal window layer. This can be wrong when the active application has no on-screen
+    /// windows, or when a floating panel or a window on another display has focus. `None` is returned if the
+    /// on-screen window list is unavailable.
+    pub fn is_focused(&self) -> Option<bool> {
+        self.impl_capturable_window.is_focused()
+    }
+
+    /// Gets an identifier for this window which can be stored and later resolved with `CapturableContent::find_window`
+    pub fn id(&self) -> CapturableWindowId {
+        CapturableWindowId {
+            native_id: self.impl_capturable_window.native_id(),
+            application_identifier: self.application().identifier(),
+            title: self.title(),
+        }
+    }
 }
 
 /// Represents a capturable display
@@ -261,11 +705,29 @@ impl CapturableDisplay {
     pub fn rect(&self) -> Rect {
         self.impl_capturable_display.rect()
     }
+
+    /// Gets an identifier for this display which can be stored and later resolved with `CapturableContent::find_display`
+    pub fn id(&self) -> CapturableDisplayId {
+        CapturableDisplayId {
+            native_id: self.impl_capturable_display.native_id(),
+            hardware_id: self.impl_capturable_display.hardware_id(),
+            rect: self.rect(),
+        }
+    }
 }
 
 unsafe impl Send for CapturableDisplay {}
 unsafe impl Sync for CapturableDisplay {}
 
+/// An application icon image
+/// 
+/// Pixels are non-premultiplied RGBA, 8 bits per channel, in row-major order
+pub struct CapturableApplicationIcon {
+    pub data: Box<[[u8; 4]]>,
+    pub width: usize,
+    pub height: usize,
+}
+
 /// Represents an application with capturable windows
 pub struct CapturableApplication {
     impl_capturable_application: ImplCapturableApplication
@@ -280,6 +742,9 @@ impl CapturableApplication {
     }
 
     /// Gets the friendly name of the application
+    /// 
+    /// On MacOS, this is the localized application name, and on windows, this is the file description (or product name)
+    /// from the executable's version info, falling back to the identifier
     pub fn name(&self) -> String {
         self.impl_capturable_application.name()
     }
@@ -288,4 +753,14 @@ impl CapturableApplication {
     pub fn pid(&self) -> i32 {
         self.impl_capturable_application.pid()
     }
+
+    /// Gets the full path of the application's executable
+    pub fn executable_path(&self) -> Option<PathBuf> {
+        self.impl_capturable_application.executable_path()
+    }
+
+    /// Gets the application's icon, rendered at the given size (in pixels) in both dimensions
+    pub fn icon(&self, size: usize) -> Result<CapturableApplicationIcon, CapturableContentError> {
+        self.impl_capturable_application.icon(size)
+    }
 }
@@ -3,7 +3,7 @@ use std::{error::Error, fmt::Display};
 
 use crate::platform::platform_impl::{ImplAudioCaptureConfig, ImplCaptureAccessToken, ImplCaptureConfig, ImplCaptureStream};
 use crate::capturable_content::Capturable;
-use crate::prelude::{AudioChannelCount, AudioFrame, AudioSampleRate, CapturableDisplay, CapturableWindow, VideoFrame};
+use crate::prelude::{AudioChannelCount, AudioDevice, AudioFrame, AudioSampleFormat, AudioSampleRate, CapturableDisplay, CapturableWindow, StreamClock, VideoFrame};
 use crate::util::Size;
 
 /// Represents an event in a capture stream
@@ -127,6 +127,10 @@ impl Error for StreamStopError {
 pub struct AudioCaptureConfig {
     pub(crate) sample_rate: AudioSampleRate, 
     pub(crate) channel_count: AudioChannelCount,
+    pub(crate) sample_format: AudioSampleFormat,
+    pub(crate) device: Option<AudioDevice>,
+    pub(crate) capture_microphone: bool,
+    pub(crate) microphone_device: Option<AudioDevice>,
     pub(crate) impl_capture_audio_config: ImplAudioCaptureConfig,
 }
 
@@ -134,13 +138,112 @@ impl AudioCaptureConfig {
     /// Creates a new audio capture config with default settings:
     /// * 24000 Hz
     /// * Mono
+    /// * 32 bit float samples
+    /// * The system default output device, following changes to the default device
+    /// * No microphone capture
     pub fn new() -> Self {
         Self {
             sample_rate: AudioSampleRate::Hz24000,
             channel_count: AudioChannelCount::Mono,
+            sample_format: AudioSampleFormat::F32,
+            device: None,
+            capture_microphone: false,
+            microphone_device: None,
             impl_capture_audio_config: ImplAudioCaptureConfig::new()
         }
     }
+
+    /// Set the sample rate of captured audio
+    /// 
+    /// If the audio device runs at a different rate, audio is resampled to this rate
+    pub fn with_sample_rate(self, sample_rate: AudioSampleRate) -> Self {
+        Self {
+            sample_rate,
+            ..self
+        }
+    }
+
+    /// Set the channel layout of captured audio
+    /// 
+    /// If the audio device has a different layout, audio is up-mixed or down-mixed to this layout
+    pub fn with_channel_count(self, channel_count: AudioChannelCount) -> Self {
+        Self {
+            channel_count,
+            ..self
+        }
+    }
+
+    /// Set the format of captured audio samples
+    pub fn with_sample_format(self, sample_format: AudioSampleFormat) -> Self {
+        Self {
+            sample_format,
+            ..self
+        }
+    }
+
+    /// Capture system audio from a specific device, instead of following the system default output device
+    /// 
+    /// Output devices are captured in loopback mode (what the device plays), and input devices are captured directly.
+    /// 
+    /// Note: This is unsupported on MacOS, where system audio is always captured from the mix of all output devices
+    pub fn with_device(self, device: AudioDevice) -> Self {
+        Self {
+            device: Some(device),
+            ..self
+        }
+    }
+
+    /// Set whether to capture audio from an input device alongside system audio
+    /// 
+    /// Microphone audio is delivered as separate audio frames, with a source of `AudioSource::Microphone`, in the same format as system audio.
+    /// The system default input device is used unless one is chosen with `with_microphone_device`.
+    /// 
+    /// Note: On MacOS, this requires MacOS 15 or later, and is only supported for window capture
+    pub fn with_microphone(self, capture_microphone: bool) -> Self {
+        Self {
+            capture_microphone,
+            ..self
+        }
+    }
+
+    /// Capture audio from a specific input device alongside system audio, instead of following the system default input device
+    pub fn with_microphone_device(self, device: AudioDevice) -> Self {
+        Self {
+            capture_microphone: true,
+            microphone_device: Some(device),
+            ..self
+        }
+    }
+
+    /// Get the sample rate of captured audio
+    pub fn sample_rate(&self) -> AudioSampleRate {
+        self.sample_rate
+    }
+
+    /// Get the channel layout of captured audio
+    pub fn channel_count(&self) -> AudioChannelCount {
+        self.channel_count
+    }
+
+    /// Get the format of captured audio samples
+    pub fn sample_format(&self) -> AudioSampleFormat {
+        self.sample_format
+    }
+
+    /// Get the device chosen for system audio capture, or None if the system default output device is used
+    pub fn device(&self) -> Option<&AudioDevice> {
+        self.device.as_ref()
+    }
+
+    /// Check whether input device audio is captured alongside system audio
+    pub fn captures_microphone(&self) -> bool {
+        self.capture_microphone
+    }
+
+    /// Get the input device chosen for microphone capture, or None if the system default device is used
+    pub fn microphone_device(&self) -> Option<&AudioDevice> {
+        self.microphone_device.as_ref()
+    }
 }
 
 /// The pixel format of returned video frames
@@ -164,7 +267,8 @@ pub enum CapturePixelFormat {
 /// Configuration settings for a capture stream
 #[derive(Clone, Debug)]
 pub struct CaptureConfig {
-    pub(crate) target: Capturable,
+    /// The captured window or display, or None for audio-only capture
+    pub(crate) target: Option<Capturable>,
     pub(crate) output_size: Size,
     pub(crate) show_cursor: bool,
     pub(crate) pixel_format: CapturePixelFormat,
@@ -214,7 +318,7 @@ impl CaptureConfig {
     pub fn with_window(window: CapturableWindow, pixel_format: CapturePixelFormat) -> Result<CaptureConfig, CaptureConfigError> {
         let rect = window.rect();
         Ok(CaptureConfig {
-            target: Capturable::Window(window),
+            target: Some(Capturable::Window(window)),
             pixel_format,
             output_size: rect.size,
             show_cursor: false,
@@ -228,7 +332,7 @@ impl CaptureConfig {
     pub fn with_display(display: CapturableDisplay, pixel_format: CapturePixelFormat) -> CaptureConfig {
         let rect = display.rect();
         CaptureConfig {
-            target: Capturable::Display(display),
+            target: Some(Capturable::Display(display)),
             pixel_format,
             output_size: rect.size,
             show_cursor: false,
@@ -238,6 +342,36 @@ impl CaptureConfig {
         }
     }
 
+    /// Create a capture configuration that only captures audio
+    /// 
+    /// Streams with this configuration only produce `StreamEvent::Audio` events (and `StreamEvent::End`)
+    /// 
+    /// Note: On MacOS, audio is captured with ScreenCaptureKit, which still requires screen recording permission
+    pub fn audio_only(audio_config: AudioCaptureConfig) -> CaptureConfig {
+        CaptureConfig {
+            target: None,
+            pixel_format: CapturePixelFormat::Bgra8888,
+            output_size: Size { width: 0.0, height: 0.0 },
+            show_cursor: false,
+            impl_capture_config: ImplCaptureConfig::new(),
+            capture_audio: Some(audio_config),
+            buffer_count: 3,
+        }
+    }
+
+    /// Configure audio capture alongside the captured window or display
+    pub fn with_audio(self, audio_config: AudioCaptureConfig) -> Self {
+        Self {
+            capture_audio: Some(audio_config),
+            ..self
+        }
+    }
+
+    /// Check whether this configuration only captures audio
+    pub fn is_audio_only(&self) -> bool {
+        self.target.is_none()
+    }
+
     /// Configure the buffer count - the number of frames in the capture queue.
     /// 
     /// Higher numbers mean higher latency, but smoother performance
@@ -268,6 +402,7 @@ impl CaptureConfig {
 /// Represents an active capture stream
 pub struct CaptureStream {
     pub(crate) impl_capture_stream: ImplCaptureStream,
+    pub(crate) clock: StreamClock,
 }
 
 unsafe impl Send for CaptureStream {}
@@ -314,11 +449,18 @@ impl CaptureStream {
     /// Start a new capture stream with the given stream callback
     pub fn new(token: CaptureAccessToken, config: CaptureConfig, callback: impl FnMut(Result<StreamEvent, StreamError>) + Send + 'static) -> Result<Self, StreamCreateError> {
         let boxed_callback = Box::new(callback);
+        let clock = StreamClock::new();
         Ok(Self {
-            impl_capture_stream: ImplCaptureStream::new(token.impl_capture_access_token, config, boxed_callback)?
+            impl_capture_stream: ImplCaptureStream::new(token.impl_capture_access_token, config, clock, boxed_callback)?,
+            clock,
         })
     }
 
+    /// Get the clock that the origin times of this stream's audio and video frames are measured from
+    pub fn clock(&self) -> StreamClock {
+        self.clock
+    }
+
     /// Stop the capture
     pub fn stop(&mut self) -> Result<(), StreamStopError> {
         self.impl_capture_stream.stop()
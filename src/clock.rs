@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{frame::AudioSampleRate, platform::platform_impl::host_time_now};
+
+/// The timebase shared by every audio and video frame of a capture stream
+///
+/// Frame origin times are measured from the start of this clock, using the same system host clock for audio and video,
+/// so frames from the same stream can be aligned directly by their origin times.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StreamClock {
+    start_instant: Instant,
+    start_system_time: SystemTime,
+    // The platform host time at `start_instant`
+    start_host_time: Duration,
+}
+
+impl StreamClock {
+    /// Start a new clock at the current time
+    pub(crate) fn new() -> Self {
+        Self::from_host_time(host_time_now())
+    }
+
+    /// Start a new clock now, taking `start_host_time` as the platform host time at this instant
+    pub(crate) fn from_host_time(start_host_time: Duration) -> Self {
+        Self {
+            start_host_time,
+            start_instant: Instant::now(),
+            start_system_time: SystemTime::now(),
+        }
+    }
+
+    /// Convert a platform host time (from the same source as `host_time_now()`) to a time since the start of this clock
+    pub(crate) fn stream_time_from_host_time(&self, host_time: Duration) -> Duration {
+        host_time.saturating_sub(self.start_host_time)
+    }
+
+    /// The monotonic instant the clock started at
+    pub fn start_instant(&self) -> Instant {
+        self.start_instant
+    }
+
+    /// The wall-clock time the clock started at
+    pub fn start_system_time(&self) -> SystemTime {
+        self.start_system_time
+    }
+
+    /// The current time since the start of the clock
+    pub fn now(&self) -> Duration {
+        self.start_instant.elapsed()
+    }
+
+    /// Convert a time since the start of the clock to a monotonic instant
+    pub fn to_instant(&self, stream_time: Duration) -> Instant {
+        self.start_instant + stream_time
+    }
+
+    /// Convert a time since the start of the clock to a wall-clock time
+    pub fn to_system_time(&self, stream_time: Duration) -> SystemTime {
+        self.start_system_time + stream_time
+    }
+
+    /// Convert a monotonic instant to a time since the start of the clock
+    ///
+    /// Instants before the start of the clock saturate to zero
+    pub fn from_instant(&self, instant: Instant) -> Duration {
+        instant.saturating_duration_since(self.start_instant)
+    }
+
+    /// Get the index of the audio sample at a time since the start of the clock, for a given sample rate
+    pub fn sample_index(&self, stream_time: Duration, sample_rate: AudioSampleRate) -> u64 {
+        (stream_time.as_nanos() * sample_rate.samples_per_second() as u128 / 1_000_000_000) as u64
+    }
+
+    /// Get the time since the start of the clock of an audio sample index, for a given sample rate
+    ///
+    /// The time is rounded up to the next nanosecond, so `sample_index` of the result gives back the same index
+    pub fn sample_time(&self, sample_index: u64, sample_rate: AudioSampleRate) -> Duration {
+        let samples_per_second = sample_rate.samples_per_second() as u128;
+        let nanos = (sample_index as u128 * 1_000_000_000).div_ceil(samples_per_second);
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_HOST_TIME: Duration = Duration::from_secs(1000);
+
+    #[test]
+    fn host_times_convert_relative_to_the_start() {
+        let clock = StreamClock::from_host_time(START_HOST_TIME);
+        assert_eq!(clock.stream_time_from_host_time(START_HOST_TIME), Duration::ZERO);
+        assert_eq!(clock.stream_time_from_host_time(START_HOST_TIME + Duration::from_micros(20_833)), Duration::from_micros(20_833));
+        // Host times from before the stream started saturate to its start
+        assert_eq!(clock.stream_time_from_host_time(START_HOST_TIME - Duration::from_millis(5)), Duration::ZERO);
+    }
+
+    #[test]
+    fn host_time_conversion_is_monotonic() {
+        let clock = StreamClock::from_host_time(START_HOST_TIME);
+        let mut previous = Duration::ZERO;
+        for step in 0..1000u64 {
+            let host_time = START_HOST_TIME - Duration::from_millis(10) + Duration::from_micros(step * 37);
+            let stream_time = clock.stream_time_from_host_time(host_time);
+            assert!(stream_time >= previous);
+            previous = stream_time;
+        }
+    }
+
+    #[test]
+    fn host_times_round_trip_through_instants_and_system_times() {
+        let clock = StreamClock::from_host_time(START_HOST_TIME);
+        for offset in [Duration::ZERO, Duration::from_nanos(1), Duration::from_millis(16), Duration::from_secs(3600)] {
+            let stream_time = clock.stream_time_from_host_time(START_HOST_TIME + offset);
+            assert_eq!(stream_time, offset);
+            let instant = clock.to_instant(stream_time);
+            assert_eq!(instant - clock.start_instant(), offset);
+            assert_eq!(clock.from_instant(instant), stream_time);
+            assert_eq!(clock.to_system_time(stream_time).duration_since(clock.start_system_time()).unwrap(), offset);
+        }
+        assert_eq!(clock.from_instant(clock.start_instant() - Duration::from_millis(1)), Duration::ZERO);
+    }
+
+    #[test]
+    fn sample_indices_round_trip_through_sample_times() {
+        let clock = StreamClock::from_host_time(START_HOST_TIME);
+        for sample_rate in [AudioSampleRate::Hz44100, AudioSampleRate::Hz48000, AudioSampleRate::Hz96000] {
+            for sample_index in [0, 1, 2, 441, 44_099, 44_100, 1_234_567, 48_000 * 3600] {
+                let sample_time = clock.sample_time(sample_index, sample_rate);
+                assert_eq!(clock.sample_index(sample_time, sample_rate), sample_index, "{:?} sample {}", sample_rate, sample_index);
+            }
+        }
+        assert_eq!(clock.sample_time(48_000, AudioSampleRate::Hz48000), Duration::from_secs(1));
+        assert_eq!(clock.sample_index(Duration::from_millis(500), AudioSampleRate::Hz44100), 22_050);
+    }
+}
@@ -0,0 +1,289 @@
+use std::time::Duration;
+
+use crate::prelude::{AudioBuffer, AudioBufferError, AudioChannelCount, AudioFrame, AudioSampleRate};
+
+/// A discontinuity in the input of an `AudioChunker`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AudioDiscontinuity {
+    /// Audio was missing from the input
+    Gap {
+        /// The time since the start of the stream that the missing audio begins at
+        origin_time: Duration,
+        /// The duration of the missing audio
+        duration: Duration,
+        /// Whether the gap was filled with silence
+        ///
+        /// Gaps longer than the chunker's maximum gap fill aren't filled - instead, the partial chunk before the gap
+        /// is padded with silence and chunking starts again from the new input
+        filled: bool,
+    },
+    /// Input audio overlapped audio that was already chunked, and the overlapping audio was dropped
+    Overlap {
+        /// The time since the start of the stream that the dropped audio begins at
+        origin_time: Duration,
+        /// The duration of the dropped audio
+        duration: Duration,
+    },
+}
+
+/// The output of an `AudioChunker` for one frame or buffer of input
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct AudioChunks {
+    /// The completed chunks, in order
+    pub chunks: Vec<AudioBuffer>,
+    /// Any discontinuities found in the input
+    pub discontinuities: Vec<AudioDiscontinuity>,
+}
+
+/// Re-buffers audio of varying frame sizes into chunks of exactly the same duration, like the 10 or 20 ms frames expected by speech codecs and models
+///
+/// Chunk timestamps are counted in samples from the first input, so they're exact rather than following the jitter of capture timestamps.
+/// Input timestamps are still compared against the chunked timeline: audio that arrives later than expected is treated as a gap and filled with silence,
+/// and audio that arrives earlier than expected is treated as an overlap and dropped, so the chunks stay aligned to the stream clock.
+#[derive(Clone, Debug)]
+pub struct AudioChunker {
+    sample_rate: AudioSampleRate,
+    channel_count: AudioChannelCount,
+    chunk_frame_count: usize,
+    tolerance: Duration,
+    max_gap_fill: Duration,
+    // Interleaved samples not yet emitted as a chunk
+    pending: Vec<f32>,
+    // The stream sample index of the first pending frame, or None before any input
+    pending_start_index: Option<u64>,
+}
+
+impl AudioChunker {
+    /// Create a chunker producing chunks of `chunk_duration` (rounded to a whole number of samples, at least one)
+    ///
+    /// Defaults to a timestamp tolerance of 5 ms, and filling gaps of up to 1 second
+    pub fn new(sample_rate: AudioSampleRate, channel_count: AudioChannelCount, chunk_duration: Duration) -> Self {
+        let chunk_frame_count = (sample_index(chunk_duration, sample_rate) as usize).max(1);
+        Self {
+            sample_rate,
+            channel_count,
+            chunk_frame_count,
+            tolerance: Duration::from_millis(5),
+            max_gap_fill: Duration::from_secs(1),
+            pending: Vec::new(),
+            pending_start_index: None,
+        }
+    }
+
+    /// Set how far input timestamps may stray from the chunked timeline before they're treated as a gap or overlap
+    pub fn with_tolerance(self, tolerance: Duration) -> Self {
+        Self {
+            tolerance,
+            ..self
+        }
+    }
+
+    /// Set the longest gap that will be filled with silence
+    pub fn with_max_gap_fill(self, max_gap_fill: Duration) -> Self {
+        Self {
+            max_gap_fill,
+            ..self
+        }
+    }
+
+    /// Get the number of samples per channel in each chunk
+    pub fn chunk_frame_count(&self) -> usize {
+        self.chunk_frame_count
+    }
+
+    /// Get the exact duration of each chunk
+    pub fn chunk_duration(&self) -> Duration {
+        sample_time(self.chunk_frame_count as u64, self.sample_rate)
+    }
+
+    /// Get the number of samples per channel waiting for a chunk to complete
+    pub fn pending_frame_count(&self) -> usize {
+        self.pending.len() / self.channel_count.count()
+    }
+
+    /// Add a captured audio frame, returning any chunks it completed
+    pub fn push_frame(&mut self, frame: &mut AudioFrame) -> Result<AudioChunks, AudioBufferError> {
+        self.push_buffer(&frame.to_audio_buffer()?)
+    }
+
+    /// Add a buffer of audio, returning any chunks it completed
+    ///
+    /// The buffer must match the sample rate and channel layout of the chunker
+    pub fn push_buffer(&mut self, buffer: &AudioBuffer) -> Result<AudioChunks, AudioBufferError> {
+        if buffer.sample_rate() != self.sample_rate {
+            return Err(AudioBufferError::Other(format!("Expected audio at {:?}, got {:?}", self.sample_rate, buffer.sample_rate())));
+        }
+        if buffer.channel_count() != self.channel_count {
+            return Err(AudioBufferError::Other(format!("Expected {:?} audio, got {:?}", self.channel_count, buffer.channel_count())));
+        }
+        let channel_count = self.channel_count.count();
+        let mut output = AudioChunks::default();
+        let mut samples = buffer.interleaved();
+        let origin_index = sample_index(buffer.origin_time(), self.sample_rate);
+        match self.pending_start_index {
+            None => self.pending_start_index = Some(origin_index),
+            Some(pending_start_index) => {
+                let expected_index = pending_start_index + self.pending_frame_count() as u64;
+                let tolerance_frames = sample_index(self.tolerance, self.sample_rate);
+                if origin_index > expected_index + tolerance_frames {
+                    let gap_frame_count = origin_index - expected_index;
+                    let filled = sample_time(gap_frame_count, self.sample_rate) <= self.max_gap_fill;
+                    output.discontinuities.push(AudioDiscontinuity::Gap {
+                        origin_time: sample_time(expected_index, self.sample_rate),
+                        duration: sample_time(gap_frame_count, self.sample_rate),
+                        filled,
+                    });
+                    if filled {
+                        self.pending.resize(self.pending.len() + gap_frame_count as usize * channel_count, 0.0);
+                    } else {
+                        output.chunks.extend(self.flush());
+                        self.pending_start_index = Some(origin_index);
+                    }
+                } else if origin_index + tolerance_frames < expected_index {
+                    let overlap_frame_count = ((expected_index - origin_index) as usize).min(buffer.frame_count());
+                    output.discontinuities.push(AudioDiscontinuity::Overlap {
+                        origin_time: sample_time(origin_index, self.sample_rate),
+                        duration: sample_time(overlap_frame_count as u64, self.sample_rate),
+                    });
+                    samples = &samples[overlap_frame_count * channel_count..];
+                }
+            }
+        }
+        self.pending.extend_from_slice(samples);
+        self.drain_chunks(&mut output.chunks)?;
+        Ok(output)
+    }
+
+    /// Pad the pending audio with silence to complete a final chunk, returning it if there was any pending audio
+    ///
+    /// The next input starts a new chunk timeline
+    pub fn flush(&mut self) -> Option<AudioBuffer> {
+        let chunk = match self.pending_start_index {
+            Some(pending_start_index) if !self.pending.is_empty() => {
+                let mut samples = std::mem::take(&mut self.pending);
+                samples.resize(self.chunk_frame_count * self.channel_count.count(), 0.0);
+                AudioBuffer::from_interleaved(samples, self.channel_count, self.sample_rate, sample_time(pending_start_index, self.sample_rate)).ok()
+            },
+            _ => None,
+        };
+        self.reset();
+        chunk
+    }
+
+    /// Discard any pending audio, so the next input starts a new chunk timeline
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.pending_start_index = None;
+    }
+
+    fn drain_chunks(&mut self, chunks: &mut Vec<AudioBuffer>) -> Result<(), AudioBufferError> {
+        let chunk_sample_count = self.chunk_frame_count * self.channel_count.count();
+        let Some(pending_start_index) = self.pending_start_index.as_mut() else {
+            return Ok(());
+        };
+        let mut chunk_start = 0;
+        while self.pending.len() - chunk_start >= chunk_sample_count {
+            let samples = &self.pending[chunk_start..chunk_start + chunk_sample_count];
+            chunks.push(AudioBuffer::from_interleaved(samples, self.channel_count, self.sample_rate, sample_time(*pending_start_index, self.sample_rate))?);
+            *pending_start_index += self.chunk_frame_count as u64;
+            chunk_start += chunk_sample_count;
+        }
+        self.pending.drain(..chunk_start);
+        Ok(())
+    }
+}
+
+// The nearest sample index to a time since the start of the stream
+fn sample_index(time: Duration, sample_rate: AudioSampleRate) -> u64 {
+    ((time.as_nanos() * sample_rate.samples_per_second() as u128 + 500_000_000) / 1_000_000_000) as u64
+}
+
+fn sample_time(sample_index: u64, sample_rate: AudioSampleRate) -> Duration {
+    Duration::from_nanos((sample_index as u128 * 1_000_000_000 / sample_rate.samples_per_second() as u128) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mono audio at 48kHz where each sample is its stream sample index
+    fn buffer(start_index: usize, frame_count: usize) -> AudioBuffer {
+        let samples: Vec<f32> = (start_index..start_index + frame_count).map(|index| index as f32).collect();
+        AudioBuffer::from_interleaved(samples, AudioChannelCount::Mono, AudioSampleRate::Hz48000, sample_time(start_index as u64, AudioSampleRate::Hz48000)).unwrap()
+    }
+
+    fn chunker() -> AudioChunker {
+        AudioChunker::new(AudioSampleRate::Hz48000, AudioChannelCount::Mono, Duration::from_millis(10))
+    }
+
+    #[test]
+    fn exact_size_input_makes_one_chunk() {
+        let mut chunker = chunker();
+        assert_eq!(chunker.chunk_frame_count(), 480);
+        assert_eq!(chunker.chunk_duration(), Duration::from_millis(10));
+        let output = chunker.push_buffer(&buffer(0, 480)).unwrap();
+        assert_eq!(output.chunks, vec![buffer(0, 480)]);
+        assert!(output.discontinuities.is_empty());
+        assert_eq!(chunker.pending_frame_count(), 0);
+        assert_eq!(chunker.flush(), None);
+    }
+
+    #[test]
+    fn partial_input_carries_over() {
+        let mut chunker = chunker();
+        assert!(chunker.push_buffer(&buffer(0, 300)).unwrap().chunks.is_empty());
+        assert_eq!(chunker.pending_frame_count(), 300);
+        let output = chunker.push_buffer(&buffer(300, 300)).unwrap();
+        assert_eq!(output.chunks, vec![buffer(0, 480)]);
+        assert!(output.discontinuities.is_empty());
+        assert_eq!(chunker.pending_frame_count(), 120);
+    }
+
+    #[test]
+    fn flush_pads_the_tail() {
+        let mut chunker = chunker();
+        chunker.push_buffer(&buffer(0, 600)).unwrap();
+        let tail = chunker.flush().unwrap();
+        assert_eq!(tail.origin_time(), Duration::from_millis(10));
+        assert_eq!(tail.frame_count(), 480);
+        assert_eq!(&tail.interleaved()[..120], buffer(480, 120).interleaved());
+        assert!(tail.interleaved()[120..].iter().all(|sample| *sample == 0.0));
+        assert_eq!(chunker.pending_frame_count(), 0);
+        assert_eq!(chunker.flush(), None);
+
+        // The next input starts a new timeline
+        let output = chunker.push_buffer(&buffer(48000, 480)).unwrap();
+        assert_eq!(output.chunks, vec![buffer(48000, 480)]);
+        assert!(output.discontinuities.is_empty());
+    }
+
+    #[test]
+    fn timestamps_advance_by_the_chunk_duration() {
+        let mut chunker = chunker();
+        let output = chunker.push_buffer(&buffer(0, 2000)).unwrap();
+        let origin_times: Vec<Duration> = output.chunks.iter().map(|chunk| chunk.origin_time()).collect();
+        assert_eq!(origin_times, [0, 10, 20, 30].map(Duration::from_millis));
+        assert_eq!(chunker.pending_frame_count(), 80);
+        // Timestamps follow the sample count, even when capture timestamps jitter within the tolerance
+        let jittered = AudioBuffer::from_interleaved(buffer(2000, 400).interleaved(), AudioChannelCount::Mono, AudioSampleRate::Hz48000, Duration::from_micros(43_667)).unwrap();
+        let output = chunker.push_buffer(&jittered).unwrap();
+        assert_eq!(output.chunks, vec![buffer(1920, 480)]);
+        assert!(output.discontinuities.is_empty());
+    }
+
+    #[test]
+    fn gaps_are_filled_with_silence() {
+        let mut chunker = chunker();
+        chunker.push_buffer(&buffer(0, 240)).unwrap();
+        let output = chunker.push_buffer(&buffer(720, 240)).unwrap();
+        assert_eq!(output.discontinuities, vec![AudioDiscontinuity::Gap {
+            origin_time: Duration::from_millis(5),
+            duration: Duration::from_millis(10),
+            filled: true,
+        }]);
+        assert_eq!(output.chunks.len(), 2);
+        assert_eq!(&output.chunks[0].interleaved()[..240], buffer(0, 240).interleaved());
+        assert!(output.chunks[0].interleaved()[240..].iter().all(|sample| *sample == 0.0));
+        assert_eq!(output.chunks[1].origin_time(), Duration::from_millis(10));
+    }
+}
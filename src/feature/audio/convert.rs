@@ -0,0 +1,147 @@
+use crate::prelude::{AudioBuffer, AudioBufferError, AudioChannelCount, AudioFrame, AudioSampleRate};
+
+use super::{AudioChannelMixer, AudioResampler, AudioResamplerQuality};
+
+/// Converts a stream of audio to a different sample rate and channel layout
+///
+/// This combines an `AudioChannelMixer` and an `AudioResampler`, mixing down before resampling and mixing up after,
+/// so the resampler always works on the fewest channels. Output timestamps are carried through from the input, as with `AudioResampler`.
+///
+/// Output is always 32 bit float - use `AudioBuffer::to_interleaved_i16()` or `convert_samples()` for other sample formats.
+#[derive(Clone, Debug)]
+pub struct AudioConverter {
+    input_mixer: Option<AudioChannelMixer>,
+    resampler: AudioResampler,
+    output_mixer: Option<AudioChannelMixer>,
+}
+
+impl AudioConverter {
+    /// Create a converter between two sample rates and channel layouts
+    pub fn new(input_sample_rate: AudioSampleRate, input_channel_count: AudioChannelCount, output_sample_rate: AudioSampleRate, output_channel_count: AudioChannelCount, quality: AudioResamplerQuality) -> Self {
+        let (input_mixer, resampler_channel_count, output_mixer) = if input_channel_count == output_channel_count {
+            (None, input_channel_count, None)
+        } else if output_channel_count.count() < input_channel_count.count() {
+            (Some(AudioChannelMixer::new(input_channel_count, output_channel_count)), output_channel_count, None)
+        } else {
+            (None, input_channel_count, Some(AudioChannelMixer::new(input_channel_count, output_channel_count)))
+        };
+        Self {
+            input_mixer,
+            resampler: AudioResampler::new(input_sample_rate, output_sample_rate, resampler_channel_count, quality),
+            output_mixer,
+        }
+    }
+
+    /// Get the sample rate this converter takes
+    pub fn input_sample_rate(&self) -> AudioSampleRate {
+        self.resampler.input_sample_rate()
+    }
+
+    /// Get the sample rate this converter produces
+    pub fn output_sample_rate(&self) -> AudioSampleRate {
+        self.resampler.output_sample_rate()
+    }
+
+    /// Get the channel layout this converter takes
+    pub fn input_channel_count(&self) -> AudioChannelCount {
+        match &self.input_mixer {
+            Some(mixer) => mixer.input_channel_count(),
+            None => self.resampler.channel_count(),
+        }
+    }
+
+    /// Get the channel layout this converter produces
+    pub fn output_channel_count(&self) -> AudioChannelCount {
+        match &self.output_mixer {
+            Some(mixer) => mixer.output_channel_count(),
+            None => self.resampler.channel_count(),
+        }
+    }
+
+    /// Convert a buffer of audio, returning all of the output that's ready
+    pub fn process(&mut self, buffer: &AudioBuffer) -> Result<AudioBuffer, AudioBufferError> {
+        let resampled = match &self.input_mixer {
+            Some(mixer) => self.resampler.process(&mixer.process(buffer)?)?,
+            None => self.resampler.process(buffer)?,
+        };
+        self.mix_output(resampled)
+    }
+
+    /// Convert a captured audio frame, returning all of the output that's ready
+    pub fn process_frame(&mut self, frame: &mut AudioFrame) -> Result<AudioBuffer, AudioBufferError> {
+        self.process(&frame.to_audio_buffer()?)
+    }
+
+    /// Finish the stream, returning the remaining buffered output and resetting the converter
+    pub fn flush(&mut self) -> Result<AudioBuffer, AudioBufferError> {
+        let resampled = self.resampler.flush()?;
+        self.mix_output(resampled)
+    }
+
+    /// Discard any buffered audio and timing, so the next input starts a new stream
+    pub fn reset(&mut self) {
+        self.resampler.reset();
+    }
+
+    fn mix_output(&self, buffer: AudioBuffer) -> Result<AudioBuffer, AudioBufferError> {
+        match &self.output_mixer {
+            Some(mixer) => mixer.process(&buffer),
+            None => Ok(buffer),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use super::super::convert_samples;
+
+    #[test]
+    fn i16_and_f32_samples_scale_to_full_range() {
+        assert_eq!(convert_samples::<i16, f32>(&[i16::MIN, -16384, 0, 16384]), vec![-1.0, -0.5, 0.0, 0.5]);
+        // 1.0 is just out of range for 16 bit samples, so it clips to the largest positive sample
+        assert_eq!(convert_samples::<f32, i16>(&[-1.0, -0.5, 0.0, 0.5, 1.0]), vec![i16::MIN, -16384, 0, 16384, i16::MAX]);
+        let samples = [i16::MIN, -1000, -1, 0, 1, 32767];
+        assert_eq!(convert_samples::<f32, i16>(&convert_samples::<i16, f32>(&samples)), samples.to_vec());
+    }
+
+    #[test]
+    fn out_of_range_samples_clip() {
+        assert_eq!(convert_samples::<f32, i16>(&[1.5, -2.0, f32::INFINITY]), vec![i16::MAX, i16::MIN, i16::MAX]);
+        assert_eq!(convert_samples::<f32, i32>(&[1.5, -1.5]), vec![i32::MAX, i32::MIN]);
+    }
+
+    fn convert(converter: &mut AudioConverter, samples: Vec<f32>, channel_count: AudioChannelCount) -> AudioBuffer {
+        let buffer = AudioBuffer::from_interleaved(samples, channel_count, AudioSampleRate::Hz48000, Duration::from_millis(5)).unwrap();
+        converter.process(&buffer).unwrap()
+    }
+
+    #[test]
+    fn mono_mixes_up_to_both_front_channels() {
+        let mut converter = AudioConverter::new(AudioSampleRate::Hz48000, AudioChannelCount::Mono, AudioSampleRate::Hz48000, AudioChannelCount::Stereo, AudioResamplerQuality::Balanced);
+        assert_eq!(converter.output_channel_count(), AudioChannelCount::Stereo);
+        let output = convert(&mut converter, vec![0.25, -0.5], AudioChannelCount::Mono);
+        assert_eq!(output.channel_count(), AudioChannelCount::Stereo);
+        assert_eq!(output.interleaved(), &[0.25, 0.25, -0.5, -0.5]);
+        assert_eq!(output.origin_time(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn stereo_mixes_down_to_the_average() {
+        let mut converter = AudioConverter::new(AudioSampleRate::Hz48000, AudioChannelCount::Stereo, AudioSampleRate::Hz48000, AudioChannelCount::Mono, AudioResamplerQuality::Balanced);
+        let output = convert(&mut converter, vec![0.5, 0.25, -1.0, 1.0], AudioChannelCount::Stereo);
+        assert_eq!(output.channel_count(), AudioChannelCount::Mono);
+        assert_eq!(output.interleaved(), &[0.375, 0.0]);
+    }
+
+    #[test]
+    fn mismatched_input_is_rejected() {
+        let mut converter = AudioConverter::new(AudioSampleRate::Hz48000, AudioChannelCount::Stereo, AudioSampleRate::Hz44100, AudioChannelCount::Mono, AudioResamplerQuality::Fast);
+        let mono = AudioBuffer::from_interleaved(vec![0.0; 4], AudioChannelCount::Mono, AudioSampleRate::Hz48000, Duration::ZERO).unwrap();
+        assert!(converter.process(&mono).is_err());
+        let wrong_rate = AudioBuffer::from_interleaved(vec![0.0; 4], AudioChannelCount::Stereo, AudioSampleRate::Hz44100, Duration::ZERO).unwrap();
+        assert!(converter.process(&wrong_rate).is_err());
+    }
+}
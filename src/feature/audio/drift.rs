@@ -0,0 +1,416 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::prelude::{AudioBuffer, AudioBufferError, AudioChannelCount, AudioFrame, AudioSampleRate};
+
+use super::{AudioDiscontinuity, AudioResampler, AudioResamplerQuality};
+
+// Input timestamps further than this from where the previous input ended are treated as dropped or repeated audio rather than drift
+const DISCONTINUITY_THRESHOLD_SECONDS: f64 = 0.1;
+
+// The time over which the accumulated timing error is corrected when resampling
+const CORRECTION_TIME_SECONDS: f64 = 10.0;
+
+// The largest rate adjustment made when resampling, as a fraction of the sample rate (1000 ppm)
+const MAX_RATE_ADJUSTMENT: f64 = 0.001;
+
+/// An estimate of how far an audio sample clock has drifted from the stream clock
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AudioDriftEstimate {
+    /// The audio sample rate, measured against the stream clock
+    pub measured_sample_rate: f64,
+    /// The drift of the audio sample clock in parts per million - positive when it runs fast
+    pub drift_ppm: f64,
+    /// How far the time counted by audio samples has fallen behind the stream clock, in seconds - negative when it's ahead
+    pub offset_seconds: f64,
+}
+
+/// Estimates the drift between the sample clock of captured audio and the stream clock its timestamps (and video timestamps) come from
+///
+/// The sample clock of an audio device is never exactly its nominal rate, so over a long recording the time counted by audio samples
+/// slowly diverges from the stream clock. This fits a line through the sample count and origin time of recent audio,
+/// which smooths out the jitter of individual timestamps, to measure the real sample rate and map sample positions to stream times.
+///
+/// Gaps and overlaps in the input of more than 100 ms (E.G. dropped audio) are skipped over rather than counted as drift.
+#[derive(Clone, Debug)]
+pub struct AudioDriftEstimator {
+    sample_rate: AudioSampleRate,
+    window: Duration,
+    minimum_span: Duration,
+    // (origin time in seconds, sample position) of each input
+    points: VecDeque<(f64, u64)>,
+    // The position of the next input sample, counting skipped gaps
+    sample_position: u64,
+    // The expected origin time in seconds of the next input
+    next_origin_seconds: Option<f64>,
+    // The origin time in seconds of sample position zero
+    start_seconds: Option<f64>,
+}
+
+impl AudioDriftEstimator {
+    /// Create an estimator for audio at a nominal sample rate
+    ///
+    /// Defaults to measuring over the last 60 seconds of audio, with an estimate available after 10 seconds
+    pub fn new(sample_rate: AudioSampleRate) -> Self {
+        Self {
+            sample_rate,
+            window: Duration::from_secs(60),
+            minimum_span: Duration::from_secs(10),
+            points: VecDeque::new(),
+            sample_position: 0,
+            next_origin_seconds: None,
+            start_seconds: None,
+        }
+    }
+
+    /// Set how much recent audio the drift is measured over
+    ///
+    /// Longer windows are less affected by timestamp jitter, but slower to follow changes in drift
+    pub fn with_window(self, window: Duration) -> Self {
+        Self {
+            window,
+            ..self
+        }
+    }
+
+    /// Set how much audio must be measured before an estimate is available
+    pub fn with_minimum_span(self, minimum_span: Duration) -> Self {
+        Self {
+            minimum_span,
+            ..self
+        }
+    }
+
+    /// Get the nominal sample rate of the audio
+    pub fn sample_rate(&self) -> AudioSampleRate {
+        self.sample_rate
+    }
+
+    /// Get the position of the next input sample since the start of the input, including skipped gaps
+    pub fn sample_position(&self) -> u64 {
+        self.sample_position
+    }
+
+    /// Measure a captured audio frame
+    pub fn push_frame(&mut self, frame: &mut AudioFrame) -> Result<Option<AudioDiscontinuity>, AudioBufferError> {
+        Ok(self.push(frame.origin_time(), frame.frame_count()?))
+    }
+
+    /// Measure a buffer of audio
+    pub fn push_buffer(&mut self, buffer: &AudioBuffer) -> Option<AudioDiscontinuity> {
+        self.push(buffer.origin_time(), buffer.frame_count())
+    }
+
+    /// Measure `frame_count` samples of audio beginning at `origin_time`, returning the gap or overlap that was skipped over if there was one
+    pub fn push(&mut self, origin_time: Duration, frame_count: usize) -> Option<AudioDiscontinuity> {
+        let nominal_rate = self.sample_rate.samples_per_second() as f64;
+        let origin_seconds = origin_time.as_secs_f64();
+        let mut discontinuity = None;
+        if let Some(next_origin_seconds) = self.next_origin_seconds {
+            let error_seconds = origin_seconds - next_origin_seconds;
+            if error_seconds > DISCONTINUITY_THRESHOLD_SECONDS {
+                self.sample_position += (error_seconds * nominal_rate).round() as u64;
+                discontinuity = Some(AudioDiscontinuity::Gap {
+                    origin_time: Duration::from_secs_f64(next_origin_seconds),
+                    duration: Duration::from_secs_f64(error_seconds),
+                    filled: false,
+                });
+            } else if error_seconds < -DISCONTINUITY_THRESHOLD_SECONDS {
+                self.sample_position = self.sample_position.saturating_sub((-error_seconds * nominal_rate).round() as u64);
+                discontinuity = Some(AudioDiscontinuity::Overlap {
+                    origin_time,
+                    duration: Duration::from_secs_f64(-error_seconds),
+                });
+            }
+        }
+        self.start_seconds.get_or_insert(origin_seconds);
+        self.points.push_back((origin_seconds, self.sample_position));
+        let window_seconds = self.window.as_secs_f64();
+        while self.points.front().is_some_and(|(front_seconds, _)| origin_seconds - front_seconds > window_seconds) {
+            self.points.pop_front();
+        }
+        self.sample_position += frame_count as u64;
+        self.next_origin_seconds = Some(origin_seconds + frame_count as f64 / nominal_rate);
+        discontinuity
+    }
+
+    /// Get the current drift estimate, if enough audio has been measured
+    pub fn estimate(&self) -> Option<AudioDriftEstimate> {
+        let (slope, mean_seconds, mean_position) = self.fit()?;
+        let nominal_rate = self.sample_rate.samples_per_second() as f64;
+        let clock_seconds = mean_seconds + (self.sample_position as f64 - mean_position) / slope;
+        let sample_seconds = self.start_seconds? + self.sample_position as f64 / nominal_rate;
+        Some(AudioDriftEstimate {
+            measured_sample_rate: slope,
+            drift_ppm: (slope / nominal_rate - 1.0) * 1_000_000.0,
+            offset_seconds: clock_seconds - sample_seconds,
+        })
+    }
+
+    /// Get the stream time of a sample position, following the measured sample rate rather than the jitter of individual timestamps
+    ///
+    /// Returns None until enough audio has been measured
+    pub fn corrected_time(&self, sample_position: u64) -> Option<Duration> {
+        let (slope, mean_seconds, mean_position) = self.fit()?;
+        let seconds = mean_seconds + (sample_position as f64 - mean_position) / slope;
+        Some(Duration::from_secs_f64(seconds.max(0.0)))
+    }
+
+    /// Discard all measurements
+    pub fn reset(&mut self) {
+        self.points.clear();
+        self.sample_position = 0;
+        self.next_origin_seconds = None;
+        self.start_seconds = None;
+    }
+
+    // Least squares fit of sample position against time, as (samples per second, mean time, mean position)
+    fn fit(&self) -> Option<(f64, f64, f64)> {
+        let (first_seconds, first_position) = *self.points.front()?;
+        // Overlaps also rewind time, so the span is measured between the earliest and latest points rather than the first and last
+        let (min_seconds, max_seconds) = self.points.iter().fold((f64::MAX, f64::MIN), |(min_seconds, max_seconds), (seconds, _)| (min_seconds.min(*seconds), max_seconds.max(*seconds)));
+        if max_seconds - min_seconds < self.minimum_span.as_secs_f64() {
+            return None;
+        }
+        // Fit relative to the first point to keep precision over long recordings
+        // Overlaps rewind the sample position, so later points can be before the first one
+        let count = self.points.len() as f64;
+        let relative = |(seconds, position): &(f64, u64)| (seconds - first_seconds, (*position as i64 - first_position as i64) as f64);
+        let (sum_x, sum_y) = self.points.iter().map(relative).fold((0.0, 0.0), |(sum_x, sum_y), (x, y)| (sum_x + x, sum_y + y));
+        let (mean_x, mean_y) = (sum_x / count, sum_y / count);
+        let (covariance, variance) = self.points.iter().map(relative).fold((0.0, 0.0), |(covariance, variance), (x, y)| {
+            (covariance + (x - mean_x) * (y - mean_y), variance + (x - mean_x) * (x - mean_x))
+        });
+        if variance <= 0.0 || covariance <= 0.0 {
+            return None;
+        }
+        Some((covariance / variance, mean_x + first_seconds, mean_y + first_position as f64))
+    }
+}
+
+/// How an `AudioDriftCorrector` keeps audio in sync with the stream clock
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AudioDriftCorrection {
+    /// Leave the audio untouched, and replace its timestamps with ones that follow the stream clock
+    ///
+    /// Use this when the muxer places audio by timestamp
+    Timestamps,
+    /// Resample the audio by tiny amounts so its sample count follows the stream clock at the nominal sample rate
+    ///
+    /// Use this when the muxer or encoder places audio by sample count
+    Resample(AudioResamplerQuality),
+}
+
+/// Keeps captured audio in sync with the stream clock (and so with video) over long recordings
+///
+/// This measures drift with an `AudioDriftEstimator`, then either corrects the timestamps of the audio,
+/// or resamples it by up to 1000 ppm so that it stays within a few milliseconds of the stream clock indefinitely.
+#[derive(Clone, Debug)]
+pub struct AudioDriftCorrector {
+    estimator: AudioDriftEstimator,
+    correction: AudioDriftCorrection,
+    channel_count: AudioChannelCount,
+    resampler: Option<AudioResampler>,
+    // The stream time covered by resampled output so far, in seconds
+    resampled_seconds: f64,
+    // The corrected stream time of the first input, fixed once the first estimate is available
+    timeline_start_seconds: Option<f64>,
+}
+
+impl AudioDriftCorrector {
+    /// Create a corrector for audio with the given sample rate and channel layout
+    pub fn new(sample_rate: AudioSampleRate, channel_count: AudioChannelCount, correction: AudioDriftCorrection) -> Self {
+        let resampler = match correction {
+            AudioDriftCorrection::Timestamps => None,
+            AudioDriftCorrection::Resample(quality) => {
+                let mut resampler = AudioResampler::new(sample_rate, sample_rate, channel_count, quality);
+                resampler.set_rate_adjustment(1.0);
+                Some(resampler)
+            },
+        };
+        Self {
+            estimator: AudioDriftEstimator::new(sample_rate),
+            correction,
+            channel_count,
+            resampler,
+            resampled_seconds: 0.0,
+            timeline_start_seconds: None,
+        }
+    }
+
+    /// Use a configured drift estimator
+    ///
+    /// The estimator's sample rate must match the corrector's
+    pub fn with_estimator(self, estimator: AudioDriftEstimator) -> Self {
+        Self {
+            estimator,
+            ..self
+        }
+    }
+
+    /// Get the drift estimator
+    pub fn estimator(&self) -> &AudioDriftEstimator {
+        &self.estimator
+    }
+
+    /// Get the kind of correction applied
+    pub fn correction(&self) -> AudioDriftCorrection {
+        self.correction
+    }
+
+    /// Correct a captured audio frame, returning the corrected audio that's ready
+    pub fn process_frame(&mut self, frame: &mut AudioFrame) -> Result<AudioBuffer, AudioBufferError> {
+        self.process(&frame.to_audio_buffer()?)
+    }
+
+    /// Correct a buffer of audio, returning the corrected audio that's ready
+    ///
+    /// When correcting timestamps, the output is the input with a new origin time.
+    /// When resampling, the output is delayed by the resampler's latency until `flush()` is called.
+    pub fn process(&mut self, buffer: &AudioBuffer) -> Result<AudioBuffer, AudioBufferError> {
+        if buffer.sample_rate() != self.estimator.sample_rate() {
+            return Err(AudioBufferError::Other(format!("Expected audio at {:?}, got {:?}", self.estimator.sample_rate(), buffer.sample_rate())));
+        }
+        if buffer.channel_count() != self.channel_count {
+            return Err(AudioBufferError::Other(format!("Expected {:?} audio, got {:?}", self.channel_count, buffer.channel_count())));
+        }
+        let discontinuity = self.estimator.push_buffer(buffer);
+        let start_position = self.estimator.sample_position() - buffer.frame_count() as u64;
+        let Some(resampler) = self.resampler.as_mut() else {
+            let origin_time = self.estimator.corrected_time(start_position).unwrap_or(buffer.origin_time());
+            return AudioBuffer::from_interleaved(buffer.interleaved(), buffer.channel_count(), buffer.sample_rate(), origin_time);
+        };
+        // The resampler's timeline jumps over gaps too, so they don't need correcting
+        if let Some(AudioDiscontinuity::Gap { duration, .. }) = discontinuity {
+            self.resampled_seconds += duration.as_secs_f64();
+        }
+        if let Some(AudioDiscontinuity::Overlap { duration, .. }) = discontinuity {
+            self.resampled_seconds -= duration.as_secs_f64();
+        }
+        if let (Some(estimate), Some(current_time)) = (self.estimator.estimate(), self.estimator.corrected_time(start_position)) {
+            let nominal_rate = self.estimator.sample_rate().samples_per_second() as f64;
+            let timeline_start_seconds = *self.timeline_start_seconds.get_or_insert(current_time.as_secs_f64() - start_position as f64 / estimate.measured_sample_rate);
+            // Positive when the resampled output has fallen behind the stream clock
+            let error_seconds = (current_time.as_secs_f64() - timeline_start_seconds) - self.resampled_seconds;
+            let correction = (error_seconds / CORRECTION_TIME_SECONDS).clamp(-MAX_RATE_ADJUSTMENT, MAX_RATE_ADJUSTMENT);
+            let rate_adjustment = (estimate.measured_sample_rate / nominal_rate * (1.0 - correction)).clamp(1.0 - MAX_RATE_ADJUSTMENT, 1.0 + MAX_RATE_ADJUSTMENT);
+            resampler.set_rate_adjustment(rate_adjustment);
+        }
+        let nominal_rate = self.estimator.sample_rate().samples_per_second() as f64;
+        self.resampled_seconds += buffer.frame_count() as f64 / (nominal_rate * resampler.rate_adjustment());
+        resampler.process(buffer)
+    }
+
+    /// Finish the stream, returning any remaining corrected audio and resetting the corrector
+    pub fn flush(&mut self) -> Result<AudioBuffer, AudioBufferError> {
+        self.estimator.reset();
+        self.resampled_seconds = 0.0;
+        self.timeline_start_seconds = None;
+        match self.resampler.as_mut() {
+            Some(resampler) => resampler.flush(),
+            None => AudioBuffer::from_interleaved(Vec::new(), self.channel_count, self.estimator.sample_rate(), Duration::ZERO),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_drift_of_a_fast_clock() {
+        let mut estimator = AudioDriftEstimator::new(AudioSampleRate::Hz48000);
+        // 100 ppm fast: each 480 frame packet takes slightly less than 10 ms of stream time
+        let real_rate = 48000.0 * 1.0001;
+        for index in 0..2000 {
+            estimator.push(Duration::from_secs_f64(1.0 + (index * 480) as f64 / real_rate), 480);
+        }
+        let estimate = estimator.estimate().unwrap();
+        assert!((estimate.drift_ppm - 100.0).abs() < 1.0, "{:?}", estimate);
+        assert!((estimate.measured_sample_rate - real_rate).abs() < 0.1, "{:?}", estimate);
+    }
+
+    #[test]
+    fn needs_the_minimum_span() {
+        let mut estimator = AudioDriftEstimator::new(AudioSampleRate::Hz48000).with_minimum_span(Duration::from_secs(5));
+        for index in 0..400 {
+            estimator.push(Duration::from_secs_f64(index as f64 * 0.01), 480);
+        }
+        assert_eq!(estimator.estimate(), None);
+    }
+
+    #[test]
+    fn overlap_before_the_window_start() {
+        let mut estimator = AudioDriftEstimator::new(AudioSampleRate::Hz48000)
+            .with_window(Duration::from_secs(10))
+            .with_minimum_span(Duration::from_secs(1));
+        for index in 0..2000 {
+            estimator.push(Duration::from_secs_f64(index as f64 * 0.01), 480);
+        }
+        // Audio repeats from 15 seconds ago, rewinding the sample position to before the first point still in the window
+        let discontinuity = estimator.push(Duration::from_secs(5), 480);
+        assert!(matches!(discontinuity, Some(AudioDiscontinuity::Overlap { .. })), "{:?}", discontinuity);
+        assert_eq!(estimator.sample_position(), 48000 * 5 + 480);
+        for index in 1..100 {
+            estimator.push(Duration::from_secs_f64(5.0 + index as f64 * 0.01), 480);
+        }
+        let estimate = estimator.estimate().unwrap();
+        assert!(estimate.drift_ppm.abs() < 1.0, "{:?}", estimate);
+    }
+
+    #[test]
+    fn gaps_are_skipped() {
+        let mut estimator = AudioDriftEstimator::new(AudioSampleRate::Hz48000).with_minimum_span(Duration::from_secs(1));
+        for index in 0..200 {
+            estimator.push(Duration::from_secs_f64(index as f64 * 0.01), 480);
+        }
+        let discontinuity = estimator.push(Duration::from_secs(3), 480);
+        assert!(matches!(discontinuity, Some(AudioDiscontinuity::Gap { .. })), "{:?}", discontinuity);
+        assert_eq!(estimator.sample_position(), 48000 * 3 + 480);
+        assert!(estimator.estimate().unwrap().drift_ppm.abs() < 1.0);
+    }
+
+    // Runs `seconds` of stream time of a ramp through a corrector, from a device whose sample clock runs `drift_ppm` fast,
+    // returning the (input, output) frame counts
+    fn correct(correction: AudioDriftCorrection, drift_ppm: f64, seconds: usize) -> (usize, usize) {
+        let mut corrector = AudioDriftCorrector::new(AudioSampleRate::Hz48000, AudioChannelCount::Mono, correction);
+        let real_rate = 48000.0 * (1.0 + drift_ppm / 1_000_000.0);
+        let (mut input_frame_count, mut output_frame_count) = (0, 0);
+        while (input_frame_count as f64) < real_rate * seconds as f64 {
+            let samples: Vec<f32> = (input_frame_count..input_frame_count + 480).map(|index| (index % 480) as f32 / 480.0).collect();
+            let buffer = AudioBuffer::from_interleaved(samples, AudioChannelCount::Mono, AudioSampleRate::Hz48000, Duration::from_secs_f64(input_frame_count as f64 / real_rate)).unwrap();
+            let output = corrector.process(&buffer).unwrap();
+            if correction == AudioDriftCorrection::Timestamps {
+                assert_eq!(output.interleaved(), buffer.interleaved());
+                assert!(output.origin_time().abs_diff(buffer.origin_time()) < Duration::from_micros(50), "{:?} {:?}", output.origin_time(), buffer.origin_time());
+            }
+            input_frame_count += 480;
+            output_frame_count += output.frame_count();
+        }
+        output_frame_count += corrector.flush().unwrap().frame_count();
+        (input_frame_count, output_frame_count)
+    }
+
+    #[test]
+    fn timestamp_correction_leaves_samples_unchanged() {
+        for drift_ppm in [0.0, 100.0] {
+            let (input_frame_count, output_frame_count) = correct(AudioDriftCorrection::Timestamps, drift_ppm, 30);
+            assert_eq!(input_frame_count, output_frame_count);
+        }
+    }
+
+    #[test]
+    fn resampling_leaves_undrifted_audio_unchanged() {
+        let (input_frame_count, output_frame_count) = correct(AudioDriftCorrection::Resample(AudioResamplerQuality::Fast), 0.0, 60);
+        assert!(input_frame_count.abs_diff(output_frame_count) <= 1, "{} {}", input_frame_count, output_frame_count);
+    }
+
+    #[test]
+    fn resampling_drops_and_inserts_drifted_samples() {
+        // 200 ppm of a minute at 48kHz is 576 samples
+        for drift_ppm in [200.0, -200.0] {
+            let (input_frame_count, output_frame_count) = correct(AudioDriftCorrection::Resample(AudioResamplerQuality::Fast), drift_ppm, 60);
+            let dropped_frame_count = input_frame_count as f64 - output_frame_count as f64;
+            assert!((dropped_frame_count - 576.0 * drift_ppm.signum()).abs() < 10.0, "{} ppm dropped {}", drift_ppm, dropped_frame_count);
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use std::time::Duration;
+
+use crate::prelude::{AudioBuffer, AudioBufferError, AudioChannelData, AudioChannelDataSamples, AudioFrame, AudioSample};
+
+/// Convert a linear amplitude (where 1.0 is full scale) to decibels relative to full scale
+pub fn amplitude_to_dbfs(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        f32::NEG_INFINITY
+    } else {
+        20.0 * amplitude.log10()
+    }
+}
+
+/// Convert decibels relative to full scale to a linear amplitude (where 1.0 is full scale)
+pub fn dbfs_to_amplitude(dbfs: f32) -> f32 {
+    10.0f32.powf(dbfs / 20.0)
+}
+
+/// The level of one channel of audio
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct AudioChannelLevel {
+    /// The root mean square amplitude, where 1.0 is full scale
+    pub rms: f32,
+    /// The peak absolute amplitude, where 1.0 is full scale
+    pub peak: f32,
+}
+
+impl AudioChannelLevel {
+    /// Get the root mean square level in dBFS
+    pub fn rms_dbfs(&self) -> f32 {
+        amplitude_to_dbfs(self.rms)
+    }
+
+    /// Get the peak level in dBFS
+    pub fn peak_dbfs(&self) -> f32 {
+        amplitude_to_dbfs(self.peak)
+    }
+
+    fn from_samples(samples: impl Iterator<Item = f32>) -> Self {
+        let mut sum_of_squares = 0.0f64;
+        let mut peak = 0.0f32;
+        let mut count = 0usize;
+        for sample in samples {
+            sum_of_squares += (sample as f64) * (sample as f64);
+            peak = peak.max(sample.abs());
+            count += 1;
+        }
+        let rms = if count == 0 { 0.0 } else { (sum_of_squares / count as f64).sqrt() as f32 };
+        Self { rms, peak }
+    }
+}
+
+/// The levels of each channel of a frame or buffer of audio
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioLevels {
+    /// The time since the start of the stream that the measured audio begins at
+    pub origin_time: Duration,
+    /// The duration of the measured audio
+    pub duration: Duration,
+    /// The level of each channel, in channel order
+    pub channels: Vec<AudioChannelLevel>,
+}
+
+impl AudioLevels {
+    /// Get the highest root mean square amplitude of any channel
+    pub fn rms(&self) -> f32 {
+        self.channels.iter().fold(0.0, |rms, channel| rms.max(channel.rms))
+    }
+
+    /// Get the highest peak amplitude of any channel
+    pub fn peak(&self) -> f32 {
+        self.channels.iter().fold(0.0, |peak, channel| peak.max(channel.peak))
+    }
+
+    /// Measure the levels of each channel of a captured audio frame
+    pub fn from_frame(frame: &mut AudioFrame) -> Result<Self, AudioBufferError> {
+        let mut channels = Vec::with_capacity(frame.channel_count().count());
+        for channel in 0..frame.channel_count().count() {
+            let level = match frame.audio_channel_buffer(channel)? {
+                AudioChannelData::F32(samples) => channel_samples_level(&samples),
+                AudioChannelData::I32(samples) => channel_samples_level(&samples),
+                AudioChannelData::I16(samples) => channel_samples_level(&samples),
+            };
+            channels.push(level);
+        }
+        Ok(Self {
+            origin_time: frame.origin_time(),
+            duration: frame.duration(),
+            channels,
+        })
+    }
+
+    /// Measure the levels of each channel of an audio buffer
+    pub fn from_buffer(buffer: &AudioBuffer) -> Self {
+        let channels = (0..buffer.channel_count().count())
+            .map(|channel| AudioChannelLevel::from_samples(buffer.channel(channel).expect("channel index in range")))
+            .collect();
+        Self {
+            origin_time: buffer.origin_time(),
+            duration: buffer.duration(),
+            channels,
+        }
+    }
+}
+
+fn channel_samples_level<T: AudioSample>(samples: &AudioChannelDataSamples<'_, T>) -> AudioChannelLevel {
+    match samples.as_slice() {
+        Some(slice) => AudioChannelLevel::from_samples(slice.iter().map(|sample| sample.to_f32())),
+        None => AudioChannelLevel::from_samples(samples.iter().map(|sample| sample.to_f32())),
+    }
+}
+
+/// Whether audio is currently silent or active
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AudioActivity {
+    Silent,
+    Active,
+}
+
+/// A change between silent and active audio
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AudioActivityChange {
+    /// The new activity state
+    pub activity: AudioActivity,
+    /// The time since the start of the stream that the change happened at
+    ///
+    /// For a change to silence, this is when the audio first dropped below the silence threshold, which is earlier than when the change was detected
+    pub time: Duration,
+}
+
+/// The result of analyzing one frame or buffer of audio
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioLevelAnalysis {
+    /// The levels of each channel
+    pub levels: AudioLevels,
+    /// The activity state after this audio
+    pub activity: AudioActivity,
+    /// The change in activity detected in this audio, if there was one
+    pub activity_change: Option<AudioActivityChange>,
+}
+
+/// Measures audio levels frame by frame, and detects when audio becomes silent or active
+///
+/// Audio becomes active as soon as the RMS level of any channel rises above the activation threshold,
+/// and becomes silent once it has stayed below the silence threshold for the hang time.
+/// Keeping the silence threshold below the activation threshold stops the state from flickering around a single level.
+///
+/// The analyzer starts out silent. Changes are reported in the `activity_change` of each analysis, so they can be forwarded from a stream's callback.
+#[derive(Clone, Debug)]
+pub struct AudioLevelAnalyzer {
+    activation_threshold_dbfs: f32,
+    silence_threshold_dbfs: f32,
+    hang_time: Duration,
+    activity: AudioActivity,
+    // The time audio dropped below the silence threshold, while still active
+    quiet_since: Option<Duration>,
+}
+
+impl Default for AudioLevelAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioLevelAnalyzer {
+    /// Create an analyzer with an activation threshold of -50 dBFS, a silence threshold of -60 dBFS, and 500 ms of hang time
+    pub fn new() -> Self {
+        Self {
+            activation_threshold_dbfs: -50.0,
+            silence_threshold_dbfs: -60.0,
+            hang_time: Duration::from_millis(500),
+            activity: AudioActivity::Silent,
+            quiet_since: None,
+        }
+    }
+
+    /// Set the RMS level (in dBFS) that audio must rise above to become active
+    pub fn with_activation_threshold(self, activation_threshold_dbfs: f32) -> Self {
+        Self {
+            activation_threshold_dbfs,
+            ..self
+        }
+    }
+
+    /// Set the RMS level (in dBFS) that audio must stay below to become silent
+    pub fn with_silence_threshold(self, silence_threshold_dbfs: f32) -> Self {
+        Self {
+            silence_threshold_dbfs,
+            ..self
+        }
+    }
+
+    /// Set how long audio must stay below the silence threshold before it becomes silent
+    pub fn with_hang_time(self, hang_time: Duration) -> Self {
+        Self {
+            hang_time,
+            ..self
+        }
+    }
+
+    /// Get the current activity state
+    pub fn activity(&self) -> AudioActivity {
+        self.activity
+    }
+
+    /// Return to the initial silent state
+    pub fn reset(&mut self) {
+        self.activity = AudioActivity::Silent;
+        self.quiet_since = None;
+    }
+
+    /// Measure the levels of a captured audio frame and update the activity state
+    pub fn analyze_frame(&mut self, frame: &mut AudioFrame) -> Result<AudioLevelAnalysis, AudioBufferError> {
+        Ok(self.analyze_levels(AudioLevels::from_frame(frame)?))
+    }
+
+    /// Measure the levels of an audio buffer and update the activity state
+    pub fn analyze_buffer(&mut self, buffer: &AudioBuffer) -> AudioLevelAnalysis {
+        self.analyze_levels(AudioLevels::from_buffer(buffer))
+    }
+
+    /// Update the activity state from already measured levels
+    pub fn analyze_levels(&mut self, levels: AudioLevels) -> AudioLevelAnalysis {
+        let rms_dbfs = amplitude_to_dbfs(levels.rms());
+        let activity_change = match self.activity {
+            AudioActivity::Silent => {
+                if rms_dbfs > self.activation_threshold_dbfs {
+                    Some(AudioActivityChange { activity: AudioActivity::Active, time: levels.origin_time })
+                } else {
+                    None
+                }
+            },
+            AudioActivity::Active => {
+                if rms_dbfs < self.silence_threshold_dbfs {
+                    let quiet_since = *self.quiet_since.get_or_insert(levels.origin_time);
+                    let quiet_until = levels.origin_time + levels.duration;
+                    if quiet_until.saturating_sub(quiet_since) >= self.hang_time {
+                        Some(AudioActivityChange { activity: AudioActivity::Silent, time: quiet_since })
+                    } else {
+                        None
+                    }
+                } else {
+                    self.quiet_since = None;
+                    None
+                }
+            },
+        };
+        if let Some(change) = activity_change {
+            self.activity = change.activity;
+            self.quiet_since = None;
+        }
+        AudioLevelAnalysis {
+            levels,
+            activity: self.activity,
+            activity_change,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+    use super::*;
+    use crate::prelude::{AudioChannelCount, AudioSampleRate};
+
+    // 10ms of stereo audio at 48kHz, with the same samples in both channels
+    fn buffer(origin_ms: u64, sample: impl Fn(usize) -> f32) -> AudioBuffer {
+        let samples: Vec<f32> = (0..480).flat_map(|index| [sample(index), sample(index)]).collect();
+        AudioBuffer::from_interleaved(samples, AudioChannelCount::Stereo, AudioSampleRate::Hz48000, Duration::from_millis(origin_ms)).unwrap()
+    }
+
+    fn sine(amplitude: f32) -> impl Fn(usize) -> f32 {
+        // 1kHz, so a 10ms buffer holds exactly 10 cycles
+        move |index| amplitude * (2.0 * PI * 1000.0 * index as f32 / 48000.0).sin()
+    }
+
+    #[test]
+    fn silence_has_no_level() {
+        let levels = AudioLevels::from_buffer(&buffer(0, |_| 0.0));
+        assert_eq!(levels.channels, vec![AudioChannelLevel::default(); 2]);
+        assert_eq!(levels.rms(), 0.0);
+        assert_eq!(levels.channels[0].peak_dbfs(), f32::NEG_INFINITY);
+        assert_eq!(levels.duration, Duration::from_millis(10));
+    }
+
+    #[test]
+    fn full_scale_square_wave_is_0_dbfs() {
+        let levels = AudioLevels::from_buffer(&buffer(0, |index| if (index / 24) % 2 == 0 { 1.0 } else { -1.0 }));
+        for channel in &levels.channels {
+            assert_eq!(channel.peak, 1.0);
+            assert!((channel.rms - 1.0).abs() < 1e-6);
+            assert!(channel.rms_dbfs().abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn sine_wave_rms_is_peak_over_root_two() {
+        let levels = AudioLevels::from_buffer(&buffer(0, sine(0.5)));
+        for channel in &levels.channels {
+            assert!((channel.peak - 0.5).abs() < 1e-3, "peak {}", channel.peak);
+            assert!((channel.rms - 0.5 * FRAC_1_SQRT_2).abs() < 1e-4, "rms {}", channel.rms);
+            assert!((channel.peak_dbfs() - amplitude_to_dbfs(0.5)).abs() < 0.01);
+        }
+        assert!((dbfs_to_amplitude(amplitude_to_dbfs(0.25)) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn activity_holds_through_the_hang_time() {
+        let mut analyzer = AudioLevelAnalyzer::new().with_hang_time(Duration::from_millis(30));
+        assert_eq!(analyzer.analyze_buffer(&buffer(0, |_| 0.0)).activity_change, None);
+
+        let analysis = analyzer.analyze_buffer(&buffer(10, sine(0.1)));
+        assert_eq!(analysis.activity_change, Some(AudioActivityChange { activity: AudioActivity::Active, time: Duration::from_millis(10) }));
+
+        // 20ms of silence is within the hang time, and loud audio starts the hang time again
+        for origin_ms in [20, 30] {
+            assert_eq!(analyzer.analyze_buffer(&buffer(origin_ms, |_| 0.0)).activity_change, None);
+        }
+        assert_eq!(analyzer.analyze_buffer(&buffer(40, sine(0.1))).activity_change, None);
+        for origin_ms in [50, 60] {
+            let analysis = analyzer.analyze_buffer(&buffer(origin_ms, |_| 0.0));
+            assert_eq!(analysis.activity, AudioActivity::Active);
+            assert_eq!(analysis.activity_change, None);
+        }
+
+        // Once the hang time has passed, the change is timed from when the audio went quiet
+        let analysis = analyzer.analyze_buffer(&buffer(70, |_| 0.0));
+        assert_eq!(analysis.activity_change, Some(AudioActivityChange { activity: AudioActivity::Silent, time: Duration::from_millis(50) }));
+        assert_eq!(analyzer.activity(), AudioActivity::Silent);
+    }
+
+    #[test]
+    fn levels_between_the_thresholds_keep_the_current_state() {
+        // -55 dBFS RMS is between the default thresholds
+        let amplitude = dbfs_to_amplitude(-55.0) / FRAC_1_SQRT_2;
+        let mut analyzer = AudioLevelAnalyzer::new();
+        assert_eq!(analyzer.analyze_buffer(&buffer(0, sine(amplitude))).activity, AudioActivity::Silent);
+        analyzer.analyze_buffer(&buffer(10, sine(0.5)));
+        for origin_ms in (20..1000).step_by(10) {
+            assert_eq!(analyzer.analyze_buffer(&buffer(origin_ms, sine(amplitude))).activity, AudioActivity::Active);
+        }
+        analyzer.reset();
+        assert_eq!(analyzer.activity(), AudioActivity::Silent);
+    }
+}
@@ -0,0 +1,194 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::prelude::{AudioBuffer, AudioBufferError, AudioChannelCount, AudioChannelPosition};
+
+/// Mixes audio between channel layouts, using a matrix of gains from each input channel to each output channel
+///
+/// The default matrix follows the usual ITU-R BS.775 downmix coefficients:
+/// center and surround channels are folded into the front channels at -3 dB, the low frequency channel is dropped,
+/// and mono is the average of left and right. Upmixing copies mono to both front channels, and leaves any channels
+/// that have no source silent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AudioChannelMixer {
+    input_channel_count: AudioChannelCount,
+    output_channel_count: AudioChannelCount,
+    // Row-major, one row of input gains per output channel
+    coefficients: Box<[f32]>,
+}
+
+impl AudioChannelMixer {
+    /// Create a mixer from one channel layout to another, using the default mix matrix
+    pub fn new(input_channel_count: AudioChannelCount, output_channel_count: AudioChannelCount) -> Self {
+        Self {
+            input_channel_count,
+            output_channel_count,
+            coefficients: default_coefficients(input_channel_count, output_channel_count).into_boxed_slice(),
+        }
+    }
+
+    /// Create a mixer from one channel layout to another with a custom mix matrix
+    ///
+    /// The coefficients are row-major, with one row of `input_channel_count.count()` gains for each output channel
+    pub fn with_coefficients(input_channel_count: AudioChannelCount, output_channel_count: AudioChannelCount, coefficients: impl Into<Box<[f32]>>) -> Result<Self, AudioBufferError> {
+        let coefficients = coefficients.into();
+        let expected_count = input_channel_count.count() * output_channel_count.count();
+        if coefficients.len() != expected_count {
+            return Err(AudioBufferError::Other(format!("Expected {} mix coefficients, got {}", expected_count, coefficients.len())));
+        }
+        Ok(Self {
+            input_channel_count,
+            output_channel_count,
+            coefficients,
+        })
+    }
+
+    /// Get the channel layout this mixer takes
+    pub fn input_channel_count(&self) -> AudioChannelCount {
+        self.input_channel_count
+    }
+
+    /// Get the channel layout this mixer produces
+    pub fn output_channel_count(&self) -> AudioChannelCount {
+        self.output_channel_count
+    }
+
+    /// Get the gain from an input channel to an output channel
+    pub fn coefficient(&self, output_channel: usize, input_channel: usize) -> f32 {
+        self.coefficients[output_channel * self.input_channel_count.count() + input_channel]
+    }
+
+    /// Mix interleaved input samples, appending the interleaved output samples to `output`
+    ///
+    /// Any trailing partial input frame is ignored
+    pub fn process_interleaved(&self, input: &[f32], output: &mut Vec<f32>) {
+        let input_channels = self.input_channel_count.count();
+        let output_channels = self.output_channel_count.count();
+        if self.is_identity() {
+            output.extend_from_slice(&input[..input.len() - input.len() % input_channels]);
+            return;
+        }
+        output.reserve(input.len() / input_channels * output_channels);
+        for input_frame in input.chunks_exact(input_channels) {
+            for gains in self.coefficients.chunks_exact(input_channels) {
+                output.push(gains.iter().zip(input_frame.iter()).map(|(gain, sample)| gain * sample).sum());
+            }
+        }
+    }
+
+    // Whether each output channel is exactly its matching input channel, so mixing is a copy
+    fn is_identity(&self) -> bool {
+        let channel_count = self.input_channel_count.count();
+        self.input_channel_count == self.output_channel_count && self.coefficients.iter().enumerate().all(|(index, gain)| {
+            *gain == if index / channel_count == index % channel_count { 1.0 } else { 0.0 }
+        })
+    }
+
+    /// Mix a buffer of audio to the output channel layout
+    ///
+    /// The sample rate and origin time of the buffer are kept
+    pub fn process(&self, buffer: &AudioBuffer) -> Result<AudioBuffer, AudioBufferError> {
+        if buffer.channel_count() != self.input_channel_count {
+            return Err(AudioBufferError::Other(format!("Expected {:?} audio, got {:?}", self.input_channel_count, buffer.channel_count())));
+        }
+        let mut output = Vec::new();
+        self.process_interleaved(buffer.interleaved(), &mut output);
+        AudioBuffer::from_interleaved(output, self.output_channel_count, buffer.sample_rate(), buffer.origin_time())
+    }
+}
+
+fn default_coefficients(input_channel_count: AudioChannelCount, output_channel_count: AudioChannelCount) -> Vec<f32> {
+    let input_positions = input_channel_count.positions();
+    let output_positions = output_channel_count.positions();
+    // Mono is the average of a stereo downmix
+    if output_channel_count == AudioChannelCount::Mono && input_channel_count != AudioChannelCount::Mono {
+        let stereo = default_coefficients(input_channel_count, AudioChannelCount::Stereo);
+        let (left, right) = stereo.split_at(input_positions.len());
+        return left.iter().zip(right.iter()).map(|(left, right)| (left + right) * 0.5).collect();
+    }
+    let has_position = |position: AudioChannelPosition| output_positions.contains(&position);
+    let mut coefficients = vec![0.0; input_positions.len() * output_positions.len()];
+    for (input_channel, input_position) in input_positions.iter().copied().enumerate() {
+        use AudioChannelPosition::*;
+        let routes = match input_position {
+            position if has_position(position) => vec![(position, 1.0)],
+            FrontCenter if input_channel_count == AudioChannelCount::Mono => vec![(FrontLeft, 1.0), (FrontRight, 1.0)],
+            FrontCenter => vec![(FrontLeft, FRAC_1_SQRT_2), (FrontRight, FRAC_1_SQRT_2)],
+            LowFrequency => vec![],
+            BackLeft if has_position(SideLeft) => vec![(SideLeft, 1.0)],
+            BackRight if has_position(SideRight) => vec![(SideRight, 1.0)],
+            SideLeft if has_position(BackLeft) => vec![(BackLeft, 1.0)],
+            SideRight if has_position(BackRight) => vec![(BackRight, 1.0)],
+            BackLeft | SideLeft => vec![(FrontLeft, FRAC_1_SQRT_2)],
+            BackRight | SideRight => vec![(FrontRight, FRAC_1_SQRT_2)],
+            FrontLeft | FrontRight => vec![],
+        };
+        for (output_position, gain) in routes {
+            if let Some(output_channel) = output_positions.iter().position(|position| *position == output_position) {
+                coefficients[output_channel * input_positions.len() + input_channel] += gain;
+            }
+        }
+    }
+    coefficients
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_samples_eq(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len(), "{:?} != {:?}", actual, expected);
+        for (actual_sample, expected_sample) in actual.iter().zip(expected.iter()) {
+            assert!((actual_sample - expected_sample).abs() < 1e-6, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn same_layout_is_a_copy() {
+        let mixer = AudioChannelMixer::new(AudioChannelCount::Stereo, AudioChannelCount::Stereo);
+        let mut output = Vec::new();
+        // The trailing partial frame is dropped
+        mixer.process_interleaved(&[0.1, 0.2, 0.3, 0.4, 0.5], &mut output);
+        assert_eq!(output, vec![0.1, 0.2, 0.3, 0.4]);
+    }
+
+    #[test]
+    fn downmix_stereo_to_mono() {
+        let mixer = AudioChannelMixer::new(AudioChannelCount::Stereo, AudioChannelCount::Mono);
+        let mut output = Vec::new();
+        mixer.process_interleaved(&[1.0, 0.0, 0.5, 0.5, -1.0, 1.0], &mut output);
+        assert_samples_eq(&output, &[0.5, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn downmix_5_1_to_stereo() {
+        let mixer = AudioChannelMixer::new(AudioChannelCount::Surround5_1, AudioChannelCount::Stereo);
+        let mut output = Vec::new();
+        // Front left, front right, center, LFE, back left, back right
+        mixer.process_interleaved(&[0.1, 0.2, 1.0, 1.0, 0.5, 0.0], &mut output);
+        assert_samples_eq(&output, &[0.1 + FRAC_1_SQRT_2 + 0.5 * FRAC_1_SQRT_2, 0.2 + FRAC_1_SQRT_2]);
+    }
+
+    #[test]
+    fn upmix_mono_to_stereo_and_5_1() {
+        let mut output = Vec::new();
+        AudioChannelMixer::new(AudioChannelCount::Mono, AudioChannelCount::Stereo).process_interleaved(&[0.25, -0.5], &mut output);
+        assert_samples_eq(&output, &[0.25, 0.25, -0.5, -0.5]);
+
+        output.clear();
+        AudioChannelMixer::new(AudioChannelCount::Stereo, AudioChannelCount::Surround5_1).process_interleaved(&[0.25, -0.5], &mut output);
+        assert_samples_eq(&output, &[0.25, -0.5, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn custom_matrix_swaps_channels() {
+        let mixer = AudioChannelMixer::with_coefficients(AudioChannelCount::Stereo, AudioChannelCount::Stereo, vec![0.0, 1.0, 1.0, 0.0]).unwrap();
+        let mut output = Vec::new();
+        mixer.process_interleaved(&[0.1, 0.2, 0.3, 0.4], &mut output);
+        assert_eq!(output, vec![0.2, 0.1, 0.4, 0.3]);
+    }
+
+    #[test]
+    fn custom_matrix_must_match_layouts() {
+        assert!(AudioChannelMixer::with_coefficients(AudioChannelCount::Stereo, AudioChannelCount::Mono, vec![1.0]).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+mod mix;
+mod resample;
+mod convert;
+mod level;
+mod chunk;
+mod drift;
+
+pub use mix::*;
+pub use resample::*;
+pub use convert::*;
+pub use level::*;
+pub use chunk::*;
+pub use drift::*;
+
+use crate::prelude::AudioSample;
+
+/// Convert a slice of audio samples from one sample format to another
+///
+/// Samples are converted through normalized 32 bit floats, clamping any that are out of range for the output format
+pub fn convert_samples<I: AudioSample, O: AudioSample>(input: &[I]) -> Vec<O> {
+    input.iter().map(|sample| O::from_f32(sample.to_f32())).collect()
+}
+
+/// Convert audio samples from one sample format to another into `output`, returning the number of samples converted
+///
+/// At most `output.len()` samples are converted
+pub fn convert_samples_into<I: AudioSample, O: AudioSample>(input: &[I], output: &mut [O]) -> usize {
+    let count = input.len().min(output.len());
+    for (output_sample, input_sample) in output[..count].iter_mut().zip(input.iter()) {
+        *output_sample = O::from_f32(input_sample.to_f32());
+    }
+    count
+}
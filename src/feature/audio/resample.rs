@@ -0,0 +1,397 @@
+use std::{f64::consts::PI, time::Duration};
+
+use crate::prelude::{AudioBuffer, AudioBufferError, AudioChannelCount, AudioFrame, AudioSampleRate};
+
+// The number of filter phases between two input samples - coefficients between phases are linearly interpolated
+const PHASE_COUNT: usize = 256;
+
+// Input timestamps further than this from where the previous input ended are treated as a discontinuity
+const DISCONTINUITY_THRESHOLD_SECONDS: f64 = 0.01;
+
+/// The quality of an audio resampler, trading CPU time and latency for passband width and stopband attenuation
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AudioResamplerQuality {
+    /// A 16 tap filter, with the passband up to 85% of the output nyquist frequency
+    ///
+    /// Suitable for speech and previews
+    Fast,
+    /// A 32 tap filter, with the passband up to 91% of the output nyquist frequency
+    Balanced,
+    /// A 64 tap filter, with the passband up to 95% of the output nyquist frequency
+    ///
+    /// Transparent for music
+    High,
+}
+
+impl AudioResamplerQuality {
+    // (Half the filter length in taps, kaiser window beta, passband as a fraction of nyquist)
+    fn parameters(&self) -> (usize, f64, f64) {
+        match self {
+            Self::Fast =>     ( 8,  6.0, 0.85),
+            Self::Balanced => (16,  8.0, 0.91),
+            Self::High =>     (32, 10.0, 0.95),
+        }
+    }
+}
+
+/// A streaming, arbitrary ratio audio resampler using a windowed sinc polyphase filter
+///
+/// Audio is fed in buffer by buffer, and the resampler keeps enough history to filter continuously across buffers.
+/// Each output buffer has an origin time derived from the origin time of the input, so timestamps are carried through.
+/// Output lags input by half the filter length, which is returned once the input ends by calling `flush()`.
+///
+/// If the origin time of an input buffer doesn't follow on from the end of the previous one (a gap or overlap in the stream),
+/// the output timestamps are re-anchored to the new input, while the audio itself stays continuous.
+#[derive(Clone, Debug)]
+pub struct AudioResampler {
+    input_sample_rate: AudioSampleRate,
+    output_sample_rate: AudioSampleRate,
+    channel_count: AudioChannelCount,
+    half_length: usize,
+    // PHASE_COUNT + 1 rows of 2 * half_length taps
+    filter: Box<[f32]>,
+    // Input frames per output frame, at the nominal sample rates
+    nominal_step: f64,
+    // Input frames per output frame, including the rate adjustment
+    step: f64,
+    rate_adjustment: f64,
+    // Whether input is passed through unfiltered, because the rates match and the rate has never been adjusted
+    passthrough: bool,
+    // Interleaved input history, starting half_length - 1 frames before the next output sample
+    history: Vec<f32>,
+    // The position of the next output sample in the history, in input frames
+    position: f64,
+    coefficients: Vec<f32>,
+    // The origin time in seconds of the first input frame, or None before any input
+    origin_seconds: Option<f64>,
+    // The duration of the input so far, at the adjusted input rate
+    input_seconds: f64,
+    output_frame_count: u64,
+}
+
+impl AudioResampler {
+    /// Create a resampler between two sample rates, for audio with the given channel layout
+    pub fn new(input_sample_rate: AudioSampleRate, output_sample_rate: AudioSampleRate, channel_count: AudioChannelCount, quality: AudioResamplerQuality) -> Self {
+        let (base_half_length, beta, passband) = quality.parameters();
+        let input_rate = input_sample_rate.samples_per_second() as f64;
+        let output_rate = output_sample_rate.samples_per_second() as f64;
+        // When downsampling, the cutoff moves down to the output nyquist frequency, and the filter widens to keep its quality
+        let ratio = (output_rate / input_rate).min(1.0);
+        let cutoff = ratio * passband;
+        let half_length = (base_half_length as f64 / ratio).ceil() as usize;
+        let tap_count = half_length * 2;
+        let window_normalization = bessel_i0(beta);
+        let mut filter = vec![0.0f32; (PHASE_COUNT + 1) * tap_count];
+        let mut row_f64 = vec![0.0f64; tap_count];
+        for (phase, row) in filter.chunks_exact_mut(tap_count).enumerate() {
+            let fraction = phase as f64 / PHASE_COUNT as f64;
+            let mut row_sum = 0.0;
+            for (tap, coefficient) in row_f64.iter_mut().enumerate() {
+                // The distance of this tap from the output sample, in input frames
+                let t = tap as f64 - (half_length as f64 - 1.0) - fraction;
+                let x = t / half_length as f64;
+                let window = if x.abs() < 1.0 {
+                    bessel_i0(beta * (1.0 - x * x).sqrt()) / window_normalization
+                } else {
+                    0.0
+                };
+                *coefficient = cutoff * sinc(cutoff * t) * window;
+                row_sum += *coefficient;
+            }
+            // Normalize each phase to unity gain at DC
+            for (coefficient, coefficient_f64) in row.iter_mut().zip(row_f64.iter()) {
+                *coefficient = (coefficient_f64 / row_sum) as f32;
+            }
+        }
+        let mut resampler = Self {
+            input_sample_rate,
+            output_sample_rate,
+            channel_count,
+            half_length,
+            filter: filter.into_boxed_slice(),
+            nominal_step: input_rate / output_rate,
+            step: input_rate / output_rate,
+            rate_adjustment: 1.0,
+            passthrough: input_sample_rate == output_sample_rate,
+            history: Vec::new(),
+            position: 0.0,
+            coefficients: vec![0.0; tap_count],
+            origin_seconds: None,
+            input_seconds: 0.0,
+            output_frame_count: 0,
+        };
+        resampler.reset();
+        resampler
+    }
+
+    /// Get the sample rate this resampler takes
+    pub fn input_sample_rate(&self) -> AudioSampleRate {
+        self.input_sample_rate
+    }
+
+    /// Get the sample rate this resampler produces
+    pub fn output_sample_rate(&self) -> AudioSampleRate {
+        self.output_sample_rate
+    }
+
+    /// Get the channel layout of the resampled audio
+    pub fn channel_count(&self) -> AudioChannelCount {
+        self.channel_count
+    }
+
+    /// Adjust the effective input sample rate, so the input is treated as being at `input_sample_rate * rate_adjustment`
+    ///
+    /// This is used to correct for clock drift, by stretching or squeezing the audio by small amounts (E.G. 1.0001)
+    /// while keeping the nominal output rate. The adjustment applies smoothly from the next output sample.
+    ///
+    /// Resamplers between equal sample rates pass audio through unfiltered until they're first adjusted,
+    /// so adjust the rate (even to 1.0) before the first input to avoid a discontinuity.
+    pub fn set_rate_adjustment(&mut self, rate_adjustment: f64) {
+        self.rate_adjustment = rate_adjustment;
+        self.step = self.nominal_step * rate_adjustment;
+        self.passthrough = false;
+    }
+
+    /// Get the current rate adjustment
+    pub fn rate_adjustment(&self) -> f64 {
+        self.rate_adjustment
+    }
+
+    /// Get the delay between input and output, in input frames
+    pub fn latency_frames(&self) -> usize {
+        if self.passthrough {
+            0
+        } else {
+            self.half_length
+        }
+    }
+
+    /// Discard any buffered audio and timing, so the next input starts a new stream
+    pub fn reset(&mut self) {
+        // Audio before the start of the stream is treated as silence
+        self.history.clear();
+        self.history.resize((self.half_length - 1) * self.channel_count.count(), 0.0);
+        self.position = (self.half_length - 1) as f64;
+        self.origin_seconds = None;
+        self.input_seconds = 0.0;
+        self.output_frame_count = 0;
+    }
+
+    /// Resample a buffer of audio, returning all of the output that's ready
+    ///
+    /// The buffer must match the input sample rate and the channel layout of the resampler
+    pub fn process(&mut self, buffer: &AudioBuffer) -> Result<AudioBuffer, AudioBufferError> {
+        if buffer.sample_rate() != self.input_sample_rate {
+            return Err(AudioBufferError::Other(format!("Expected audio at {:?}, got {:?}", self.input_sample_rate, buffer.sample_rate())));
+        }
+        if buffer.channel_count() != self.channel_count {
+            return Err(AudioBufferError::Other(format!("Expected {:?} audio, got {:?}", self.channel_count, buffer.channel_count())));
+        }
+        if self.passthrough {
+            return Ok(buffer.clone());
+        }
+        let origin_seconds = buffer.origin_time().as_secs_f64();
+        let expected_origin_seconds = self.origin_seconds.map(|start| start + self.input_seconds);
+        match expected_origin_seconds {
+            Some(expected) if (origin_seconds - expected).abs() <= DISCONTINUITY_THRESHOLD_SECONDS => {},
+            _ => self.origin_seconds = Some(origin_seconds - self.input_seconds),
+        }
+        let input_rate = self.input_sample_rate.samples_per_second() as f64 * self.rate_adjustment;
+        self.input_seconds += buffer.frame_count() as f64 / input_rate;
+        let output_origin_time = self.output_origin_time();
+        let mut output = Vec::new();
+        self.process_interleaved(buffer.interleaved(), &mut output);
+        AudioBuffer::from_interleaved(output, self.channel_count, self.output_sample_rate, output_origin_time)
+    }
+
+    /// Resample a captured audio frame, returning all of the output that's ready
+    pub fn process_frame(&mut self, frame: &mut AudioFrame) -> Result<AudioBuffer, AudioBufferError> {
+        self.process(&frame.to_audio_buffer()?)
+    }
+
+    /// Finish the stream, returning the remaining buffered output and resetting the resampler
+    pub fn flush(&mut self) -> Result<AudioBuffer, AudioBufferError> {
+        let output_origin_time = self.output_origin_time();
+        let mut output = Vec::new();
+        if !self.passthrough && self.origin_seconds.is_some() {
+            let channel_count = self.channel_count.count();
+            // Every output sample positioned before the end of the real input is still to come
+            let input_end = (self.history.len() / channel_count) as f64;
+            let remaining_frame_count = ((input_end - self.position) / self.step).ceil().max(0.0) as usize;
+            let silence = vec![0.0; self.half_length * channel_count];
+            while output.len() < remaining_frame_count * channel_count {
+                self.process_interleaved(&silence, &mut output);
+            }
+            output.truncate(remaining_frame_count * channel_count);
+        }
+        self.reset();
+        AudioBuffer::from_interleaved(output, self.channel_count, self.output_sample_rate, output_origin_time)
+    }
+
+    fn output_origin_time(&self) -> Duration {
+        let output_rate = self.output_sample_rate.samples_per_second() as f64;
+        let seconds = self.origin_seconds.unwrap_or(0.0) + self.output_frame_count as f64 / output_rate;
+        Duration::from_secs_f64(seconds.max(0.0))
+    }
+
+    fn process_interleaved(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let channel_count = self.channel_count.count();
+        let tap_count = self.half_length * 2;
+        self.history.extend_from_slice(input);
+        let history_frame_count = self.history.len() / channel_count;
+        loop {
+            let index = self.position as usize;
+            if index + self.half_length >= history_frame_count {
+                break;
+            }
+            let phase = (self.position - index as f64) * PHASE_COUNT as f64;
+            let phase_index = phase as usize;
+            let phase_fraction = (phase - phase_index as f64) as f32;
+            let row_a = &self.filter[phase_index * tap_count..(phase_index + 1) * tap_count];
+            let row_b = &self.filter[(phase_index + 1) * tap_count..(phase_index + 2) * tap_count];
+            for ((coefficient, a), b) in self.coefficients.iter_mut().zip(row_a.iter()).zip(row_b.iter()) {
+                *coefficient = a + (b - a) * phase_fraction;
+            }
+            let start = (index + 1 - self.half_length) * channel_count;
+            let window = &self.history[start..start + tap_count * channel_count];
+            for channel in 0..channel_count {
+                let sample = window[channel..].iter().step_by(channel_count).zip(self.coefficients.iter())
+                    .map(|(sample, coefficient)| sample * coefficient)
+                    .sum();
+                output.push(sample);
+            }
+            self.position += self.step;
+            self.output_frame_count += 1;
+        }
+        // Drop the history that no future output sample can reach
+        let consumed_frame_count = (self.position as usize + 1).saturating_sub(self.half_length).min(history_frame_count);
+        self.history.drain(..consumed_frame_count * channel_count);
+        self.position -= consumed_frame_count as f64;
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// The zeroth order modified bessel function of the first kind, for the kaiser window
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..64 {
+        term *= (half_x / k as f64) * (half_x / k as f64);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resample_all(resampler: &mut AudioResampler, input: &[f32], chunk_frame_count: usize) -> Vec<f32> {
+        let channel_count = resampler.channel_count().count();
+        let input_rate = resampler.input_sample_rate().samples_per_second() as f64;
+        let mut output = Vec::new();
+        for (index, chunk) in input.chunks(chunk_frame_count * channel_count).enumerate() {
+            let origin_time = Duration::from_secs_f64((index * chunk_frame_count) as f64 / input_rate);
+            let buffer = AudioBuffer::from_interleaved(chunk.to_vec(), resampler.channel_count(), resampler.input_sample_rate(), origin_time).unwrap();
+            output.extend_from_slice(resampler.process(&buffer).unwrap().interleaved());
+        }
+        output.extend_from_slice(resampler.flush().unwrap().interleaved());
+        output
+    }
+
+    #[test]
+    fn output_length_matches_rate_ratio() {
+        let rates = [
+            (AudioSampleRate::Hz48000, AudioSampleRate::Hz44100),
+            (AudioSampleRate::Hz44100, AudioSampleRate::Hz48000),
+            (AudioSampleRate::Hz16000, AudioSampleRate::Hz48000),
+            (AudioSampleRate::Hz96000, AudioSampleRate::Hz24000),
+        ];
+        for (input_rate, output_rate) in rates {
+            for quality in [AudioResamplerQuality::Fast, AudioResamplerQuality::Balanced, AudioResamplerQuality::High] {
+                let mut resampler = AudioResampler::new(input_rate, output_rate, AudioChannelCount::Stereo, quality);
+                let input_frame_count = input_rate.samples_per_second() as usize / 10;
+                let output = resample_all(&mut resampler, &vec![0.0; input_frame_count * 2], 480);
+                let expected_frame_count = input_frame_count as f64 * output_rate.samples_per_second() as f64 / input_rate.samples_per_second() as f64;
+                let output_frame_count = output.len() / 2;
+                assert_eq!(output.len() % 2, 0);
+                assert!((output_frame_count as f64 - expected_frame_count).abs() <= 1.0, "{:?} -> {:?} at {:?}: {} frames, expected {}", input_rate, output_rate, quality, output_frame_count, expected_frame_count);
+            }
+        }
+    }
+
+    #[test]
+    fn dc_passes_with_unity_gain() {
+        for quality in [AudioResamplerQuality::Fast, AudioResamplerQuality::Balanced, AudioResamplerQuality::High] {
+            let mut resampler = AudioResampler::new(AudioSampleRate::Hz48000, AudioSampleRate::Hz44100, AudioChannelCount::Mono, quality);
+            let output = resample_all(&mut resampler, &vec![0.5; 4800], 480);
+            // Skip the filter's ramp up at the start and ramp down at the end
+            let settled = &output[resampler.latency_frames() * 2..output.len() - 100];
+            for sample in settled {
+                assert!((sample - 0.5).abs() < 1e-3, "{:?}: DC output {} should be 0.5", quality, sample);
+            }
+        }
+    }
+
+    fn output_origins(resampler: &mut AudioResampler, input_origins: &[Duration], chunk_frame_count: usize) -> Vec<(Duration, usize)> {
+        input_origins.iter().map(|origin_time| {
+            let buffer = AudioBuffer::from_interleaved(vec![0.0; chunk_frame_count], AudioChannelCount::Mono, resampler.input_sample_rate(), *origin_time).unwrap();
+            let output = resampler.process(&buffer).unwrap();
+            (output.origin_time(), output.frame_count())
+        }).collect()
+    }
+
+    #[test]
+    fn output_timestamps_follow_the_input() {
+        let mut resampler = AudioResampler::new(AudioSampleRate::Hz48000, AudioSampleRate::Hz44100, AudioChannelCount::Mono, AudioResamplerQuality::Balanced);
+        let start = Duration::from_secs(2);
+        let input_origins: Vec<Duration> = (0..8).map(|index| start + Duration::from_millis(10) * index).collect();
+        let outputs = output_origins(&mut resampler, &input_origins, 480);
+        // Each output buffer starts right after the previous one, in output samples since the first input
+        let start_sample_index = (start.as_secs_f64() * 44100.0).round() as u64;
+        let mut output_frame_count = 0;
+        for (origin_time, frame_count) in outputs {
+            let sample_index = (origin_time.as_secs_f64() * 44100.0).round() as u64;
+            assert_eq!(sample_index, start_sample_index + output_frame_count as u64);
+            let expected_origin_seconds = start.as_secs_f64() + output_frame_count as f64 / 44100.0;
+            assert!((origin_time.as_secs_f64() - expected_origin_seconds).abs() < 1e-6, "{:?} should be {}", origin_time, expected_origin_seconds);
+            output_frame_count += frame_count;
+        }
+        // About 80ms of output, less the filter's latency
+        assert!((output_frame_count as i64 - 3528).abs() <= resampler.latency_frames() as i64 + 1);
+    }
+
+    #[test]
+    fn output_timestamps_jump_with_input_gaps() {
+        let continuous_origins: Vec<Duration> = (0..6).map(|index| Duration::from_millis(10) * index).collect();
+        // The same input, but with 50ms missing before the fourth buffer
+        let gap = Duration::from_millis(50);
+        let gapped_origins: Vec<Duration> = continuous_origins.iter().enumerate().map(|(index, origin_time)| if index >= 3 { *origin_time + gap } else { *origin_time }).collect();
+        let new_resampler = || AudioResampler::new(AudioSampleRate::Hz48000, AudioSampleRate::Hz44100, AudioChannelCount::Mono, AudioResamplerQuality::Balanced);
+        let continuous = output_origins(&mut new_resampler(), &continuous_origins, 480);
+        let gapped = output_origins(&mut new_resampler(), &gapped_origins, 480);
+        for (index, ((continuous_origin, continuous_count), (gapped_origin, gapped_count))) in continuous.iter().zip(gapped.iter()).enumerate() {
+            assert_eq!(continuous_count, gapped_count);
+            let expected_shift = if index >= 3 { gap.as_secs_f64() } else { 0.0 };
+            assert!((gapped_origin.as_secs_f64() - continuous_origin.as_secs_f64() - expected_shift).abs() < 1e-6, "buffer {}", index);
+        }
+    }
+
+    #[test]
+    fn same_rate_passes_through() {
+        let mut resampler = AudioResampler::new(AudioSampleRate::Hz48000, AudioSampleRate::Hz48000, AudioChannelCount::Mono, AudioResamplerQuality::Balanced);
+        let input: Vec<f32> = (0..960).map(|index| (index as f32 * 0.01).sin()).collect();
+        assert_eq!(resample_all(&mut resampler, &input, 480), input);
+    }
+}
@@ -0,0 +1,665 @@
+use std::sync::OnceLock;
+
+use half::f16;
+
+use super::simd;
+use super::{FrameBitmap, FrameBitmapBgraUnorm8x4, FrameBitmapError, FrameBitmapRgbaF16x4, FrameBitmapRgbaUnorm8x4, FrameBitmapRgbaUnormPacked1010102, FrameBitmapYCbCr, VideoRange, YCbCrMatrix};
+
+/// A pixel format that a `FrameBitmap` can hold, and be converted to
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FrameBitmapFormat {
+    BgraUnorm8x4,
+    RgbaUnorm8x4,
+    RgbaUnormPacked1010102,
+    RgbaF16x4,
+    /// Dual-planar YCbCr - conversions to YCbCr produce 4:2:0 chroma, like the V420 and F420 capture formats
+    YCbCr {
+        matrix: YCbCrMatrix,
+        range: VideoRange,
+    },
+}
+
+impl FrameBitmap {
+    /// Get the pixel format of the bitmap
+    pub fn format(&self) -> FrameBitmapFormat {
+        match self {
+            Self::BgraUnorm8x4(_) => FrameBitmapFormat::BgraUnorm8x4,
+            Self::RgbaUnorm8x4(_) => FrameBitmapFormat::RgbaUnorm8x4,
+            Self::RgbaUnormPacked1010102(_) => FrameBitmapFormat::RgbaUnormPacked1010102,
+            Self::RgbaF16x4(_) => FrameBitmapFormat::RgbaF16x4,
+            Self::YCbCr(bitmap) => FrameBitmapFormat::YCbCr { matrix: bitmap.matrix, range: bitmap.range },
+        }
+    }
+
+    /// Get the width of the bitmap in pixels (the luma width for YCbCr bitmaps)
+    pub fn width(&self) -> usize {
+        match self {
+            Self::BgraUnorm8x4(bitmap) => bitmap.width,
+            Self::RgbaUnorm8x4(bitmap) => bitmap.width,
+            Self::RgbaUnormPacked1010102(bitmap) => bitmap.width,
+            Self::RgbaF16x4(bitmap) => bitmap.width,
+            Self::YCbCr(bitmap) => bitmap.luma_width,
+        }
+    }
+
+    /// Get the height of the bitmap in pixels (the luma height for YCbCr bitmaps)
+    pub fn height(&self) -> usize {
+        match self {
+            Self::BgraUnorm8x4(bitmap) => bitmap.height,
+            Self::RgbaUnorm8x4(bitmap) => bitmap.height,
+            Self::RgbaUnormPacked1010102(bitmap) => bitmap.height,
+            Self::RgbaF16x4(bitmap) => bitmap.height,
+            Self::YCbCr(bitmap) => bitmap.luma_height,
+        }
+    }
+
+    /// Check that the bitmap's data matches its dimensions
+    pub fn validate(&self) -> Result<(), FrameBitmapError> {
+        let check_plane = |name: &str, length: usize, width: usize, height: usize| {
+            if length == width * height {
+                Ok(())
+            } else {
+                Err(FrameBitmapError::InvalidBitmap(format!("{} has {} pixels, but is {}x{}", name, length, width, height)))
+            }
+        };
+        match self {
+            Self::BgraUnorm8x4(bitmap) => check_plane("bitmap", bitmap.data.len(), bitmap.width, bitmap.height),
+            Self::RgbaUnorm8x4(bitmap) => check_plane("bitmap", bitmap.data.len(), bitmap.width, bitmap.height),
+            Self::RgbaUnormPacked1010102(bitmap) => check_plane("bitmap", bitmap.data.len(), bitmap.width, bitmap.height),
+            Self::RgbaF16x4(bitmap) => check_plane("bitmap", bitmap.data.len(), bitmap.width, bitmap.height),
+            Self::YCbCr(bitmap) => bitmap.validate(),
+        }
+    }
+
+    /// Convert the bitmap to another pixel format
+    ///
+    /// - RGB formats are converted with rounding, and alpha is carried through (YCbCr has no alpha, so it's opaque)
+    /// - `RgbaF16x4` is linear light, so it's encoded with the sRGB transfer function when converted to other formats, clipping values outside [0, 1]
+    /// - YCbCr is converted with its matrix and range, using the nearest chroma sample for each pixel,
+    ///   and conversions to YCbCr average each 2x2 block of pixels for chroma
+    ///
+    /// Converting to the bitmap's own format makes a copy. Conversions to 8 bit BGRA and RGBA from the other 8 bit format, packed 10 bit
+    /// and 4:2:0 YCbCr use SSE2, AVX2 or NEON when the CPU supports them, with identical results to the scalar code.
+    /// Conversions to YCbCr, and to or from `RgbaF16x4`, are scalar only.
+    pub fn convert(&self, target: FrameBitmapFormat) -> Result<FrameBitmap, FrameBitmapError> {
+        self.validate()?;
+        if self.format() == target {
+            return Ok(self.clone());
+        }
+        match (self, target) {
+            (Self::BgraUnorm8x4(bitmap), FrameBitmapFormat::RgbaUnorm8x4) => {
+                Ok(Self::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 {
+                    data: swizzle_red_blue_to_box(&bitmap.data),
+                    width: bitmap.width,
+                    height: bitmap.height,
+                }))
+            },
+            (Self::RgbaUnorm8x4(bitmap), FrameBitmapFormat::BgraUnorm8x4) => {
+                Ok(Self::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 {
+                    data: swizzle_red_blue_to_box(&bitmap.data),
+                    width: bitmap.width,
+                    height: bitmap.height,
+                }))
+            },
+            (source, FrameBitmapFormat::BgraUnorm8x4) => {
+                let (width, height) = (source.width(), source.height());
+                Ok(Self::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 {
+                    data: convert_to_unorm8x4::<true>(source),
+                    width,
+                    height,
+                }))
+            },
+            (source, FrameBitmapFormat::RgbaUnorm8x4) => {
+                let (width, height) = (source.width(), source.height());
+                Ok(Self::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 {
+                    data: convert_to_unorm8x4::<false>(source),
+                    width,
+                    height,
+                }))
+            },
+            (source, FrameBitmapFormat::RgbaUnormPacked1010102) => {
+                let (width, height) = (source.width(), source.height());
+                let mut data = vec![0u32; width * height];
+                let mut row = vec![[0.0f32; 4]; width];
+                for (y, data_row) in data.chunks_exact_mut(width.max(1)).enumerate().take(height) {
+                    read_row(source, y, &mut row);
+                    for (pixel, rgba) in data_row.iter_mut().zip(row.iter()) {
+                        *pixel = pack_1010102(rgba);
+                    }
+                }
+                Ok(Self::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+                    data: data.into_boxed_slice(),
+                    width,
+                    height,
+                }))
+            },
+            (source, FrameBitmapFormat::RgbaF16x4) => {
+                let (width, height) = (source.width(), source.height());
+                let mut data = vec![[f16::ZERO; 4]; width * height];
+                let mut row = vec![[0.0f32; 4]; width];
+                for (y, data_row) in data.chunks_exact_mut(width.max(1)).enumerate().take(height) {
+                    read_row(source, y, &mut row);
+                    for (pixel, rgba) in data_row.iter_mut().zip(row.iter()) {
+                        *pixel = [
+                            f16::from_f32(srgb_decode(rgba[0])),
+                            f16::from_f32(srgb_decode(rgba[1])),
+                            f16::from_f32(srgb_decode(rgba[2])),
+                            f16::from_f32(rgba[3]),
+                        ];
+                    }
+                }
+                Ok(Self::RgbaF16x4(FrameBitmapRgbaF16x4 {
+                    data: data.into_boxed_slice(),
+                    width,
+                    height,
+                }))
+            },
+            (source, FrameBitmapFormat::YCbCr { matrix, range }) => {
+                Ok(Self::YCbCr(convert_to_ycbcr(source, matrix, range)))
+            },
+        }
+    }
+}
+
+impl FrameBitmapYCbCr {
+    /// Create a YCbCr bitmap from its luma and chroma planes, with the (width, height) of each plane in pixels
+    ///
+    /// The chroma plane may be subsampled by any amount, such as to half width and height for 4:2:0
+    pub fn new(luma_data: impl Into<Box<[u8]>>, luma_size: (usize, usize), chroma_data: impl Into<Box<[[u8; 2]]>>, chroma_size: (usize, usize), matrix: YCbCrMatrix, range: VideoRange) -> Result<Self, FrameBitmapError> {
+        let bitmap = Self {
+            luma_data: luma_data.into(),
+            luma_width: luma_size.0,
+            luma_height: luma_size.1,
+            chroma_data: chroma_data.into(),
+            chroma_width: chroma_size.0,
+            chroma_height: chroma_size.1,
+            range,
+            matrix,
+        };
+        bitmap.validate()?;
+        Ok(bitmap)
+    }
+
+    // Check that the planes' data matches their sizes, and that the chroma plane fits the luma plane
+    fn validate(&self) -> Result<(), FrameBitmapError> {
+        let check_plane = |name: &str, length: usize, width: usize, height: usize| {
+            if length == width * height {
+                Ok(())
+            } else {
+                Err(FrameBitmapError::InvalidBitmap(format!("{} has {} pixels, but is {}x{}", name, length, width, height)))
+            }
+        };
+        check_plane("luma plane", self.luma_data.len(), self.luma_width, self.luma_height)?;
+        check_plane("chroma plane", self.chroma_data.len(), self.chroma_width, self.chroma_height)?;
+        let chroma_empty = self.chroma_width == 0 || self.chroma_height == 0;
+        let luma_empty = self.luma_width == 0 || self.luma_height == 0;
+        if chroma_empty != luma_empty || self.chroma_width > self.luma_width || self.chroma_height > self.luma_height {
+            return Err(FrameBitmapError::InvalidBitmap(format!("chroma plane size {}x{} doesn't fit luma plane size {}x{}", self.chroma_width, self.chroma_height, self.luma_width, self.luma_height)));
+        }
+        Ok(())
+    }
+
+    /// Get the horizontal and vertical chroma subsampling factors, such as (2, 2) for 4:2:0
+    ///
+    /// Chroma planes with an odd luma dimension round up, so the factors are rounded to the nearest whole number
+    pub fn chroma_subsampling(&self) -> (usize, usize) {
+        let subsampling = |luma_size: usize, chroma_size: usize| {
+            if chroma_size == 0 { 1 } else { ((luma_size as f64 / chroma_size as f64).round() as usize).max(1) }
+        };
+        (subsampling(self.luma_width, self.chroma_width), subsampling(self.luma_height, self.chroma_height))
+    }
+}
+
+// Converts between YCbCr and display-encoded RGB in [0, 1]
+pub(crate) struct YCbCrTransform {
+    y_offset: f32,
+    y_scale: f32,
+    c_scale: f32,
+    kr: f32,
+    kg: f32,
+    kb: f32,
+    r_cr: f32,
+    g_cb: f32,
+    g_cr: f32,
+    b_cb: f32,
+}
+
+impl YCbCrTransform {
+    pub(crate) fn new(matrix: YCbCrMatrix, range: VideoRange) -> Self {
+        let (kr, kb) = match matrix {
+            YCbCrMatrix::Bt601 => (0.299, 0.114),
+            YCbCrMatrix::Bt709 => (0.2126, 0.0722),
+        };
+        let kg = 1.0 - kr - kb;
+        let (y_offset, y_scale, c_scale) = match range {
+            VideoRange::Video => (16.0, 219.0, 224.0),
+            VideoRange::Full => (0.0, 255.0, 255.0),
+        };
+        Self {
+            y_offset,
+            y_scale,
+            c_scale,
+            kr,
+            kg,
+            kb,
+            r_cr: 2.0 * (1.0 - kr),
+            g_cb: -2.0 * (1.0 - kb) * kb / kg,
+            g_cr: -2.0 * (1.0 - kr) * kr / kg,
+            b_cb: 2.0 * (1.0 - kb),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn to_rgb(&self, y: u8, cb: u8, cr: u8) -> [f32; 3] {
+        self.code_to_rgb(y as f32, cb as f32, cr as f32)
+    }
+
+    // Like `to_rgb`, but from unrounded 8 bit code values, so higher bit depths keep their precision
+    #[inline]
+    pub(crate) fn code_to_rgb(&self, y: f32, cb: f32, cr: f32) -> [f32; 3] {
+        let y = (y - self.y_offset) / self.y_scale;
+        let cb = (cb - 128.0) / self.c_scale;
+        let cr = (cr - 128.0) / self.c_scale;
+        [
+            y + self.r_cr * cr,
+            y + self.g_cb * cb + self.g_cr * cr,
+            y + self.b_cb * cb,
+        ]
+    }
+
+    #[inline]
+    pub(crate) fn luma(&self, rgb: [f32; 3]) -> u8 {
+        unorm_to_u8_with(self.luma_code(rgb))
+    }
+
+    #[inline]
+    pub(crate) fn chroma(&self, rgb: [f32; 3]) -> [u8; 2] {
+        self.chroma_code(rgb).map(unorm_to_u8_with)
+    }
+
+    // The unrounded 8 bit luma code value of a color
+    #[inline]
+    pub(crate) fn luma_code(&self, rgb: [f32; 3]) -> f32 {
+        let y = self.kr * rgb[0] + self.kg * rgb[1] + self.kb * rgb[2];
+        y * self.y_scale + self.y_offset
+    }
+
+    // The unrounded 8 bit chroma code values of a color
+    #[inline]
+    pub(crate) fn chroma_code(&self, rgb: [f32; 3]) -> [f32; 2] {
+        let y = self.kr * rgb[0] + self.kg * rgb[1] + self.kb * rgb[2];
+        let cb = (rgb[2] - y) / self.b_cb;
+        let cr = (rgb[0] - y) / self.r_cr;
+        [cb * self.c_scale + 128.0, cr * self.c_scale + 128.0]
+    }
+}
+
+// Converts 8 bit YCbCr to RGB in [0, 255], with the range scaling folded into the coefficients.
+// The SIMD kernels do exactly the same arithmetic, in the same order, as `pixel`
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct YCbCrToUnorm8 {
+    pub(crate) y_offset: f32,
+    pub(crate) y_mul: f32,
+    pub(crate) r_cr: f32,
+    pub(crate) g_cb: f32,
+    pub(crate) g_cr: f32,
+    pub(crate) b_cb: f32,
+}
+
+impl YCbCrToUnorm8 {
+    pub(crate) fn new(matrix: YCbCrMatrix, range: VideoRange) -> Self {
+        let transform = YCbCrTransform::new(matrix, range);
+        let c_mul = 255.0 / transform.c_scale;
+        Self {
+            y_offset: transform.y_offset,
+            y_mul: 255.0 / transform.y_scale,
+            r_cr: transform.r_cr * c_mul,
+            g_cb: transform.g_cb * c_mul,
+            g_cr: transform.g_cr * c_mul,
+            b_cb: transform.b_cb * c_mul,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn pixel(&self, y: u8, cb: u8, cr: u8) -> [u8; 3] {
+        let y = (y as f32 - self.y_offset) * self.y_mul;
+        let cb = cb as f32 - 128.0;
+        let cr = cr as f32 - 128.0;
+        [
+            unorm_to_u8_with(y + self.r_cr * cr),
+            unorm_to_u8_with(y + (self.g_cb * cb + self.g_cr * cr)),
+            unorm_to_u8_with(y + self.b_cb * cb),
+        ]
+    }
+}
+
+// Round and clamp a value already scaled to [0, 255]
+#[inline]
+fn unorm_to_u8_with(value: f32) -> u8 {
+    (value + 0.5).clamp(0.0, 255.0) as u8
+}
+
+#[inline]
+fn unorm_to_u8(value: f32) -> u8 {
+    unorm_to_u8_with(value * 255.0)
+}
+
+pub(crate) fn srgb_encode(linear: f32) -> f32 {
+    let linear = linear.clamp(0.0, 1.0);
+    if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub(crate) fn srgb_decode(encoded: f32) -> f32 {
+    let encoded = encoded.clamp(0.0, 1.0);
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Lookup tables from the bits of a linear f16 to an sRGB encoded u8, and of a linear f16 to a u8 (for alpha)
+pub(crate) type F16ToU8Tables = (Box<[u8]>, Box<[u8]>);
+
+pub(crate) fn f16_to_u8_tables() -> &'static F16ToU8Tables {
+    static TABLES: OnceLock<F16ToU8Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let mut srgb_table = vec![0u8; 1 << 16];
+        let mut linear_table = vec![0u8; 1 << 16];
+        for bits in 0..(1usize << 16) {
+            let value = f16::from_bits(bits as u16).to_f32();
+            // NaN compares false, so it becomes zero
+            let value = if value.is_nan() { 0.0 } else { value };
+            srgb_table[bits] = unorm_to_u8(srgb_encode(value));
+            linear_table[bits] = unorm_to_u8(value);
+        }
+        (srgb_table.into_boxed_slice(), linear_table.into_boxed_slice())
+    })
+}
+
+#[inline]
+pub(crate) fn unpack_1010102(pixel: u32) -> [u16; 4] {
+    [
+        (pixel & 0x3FF) as u16,
+        ((pixel >> 10) & 0x3FF) as u16,
+        ((pixel >> 20) & 0x3FF) as u16,
+        (pixel >> 30) as u16,
+    ]
+}
+
+#[inline]
+pub(crate) fn pack_1010102(rgba: &[f32; 4]) -> u32 {
+    let to_10_bits = |value: f32| (value.clamp(0.0, 1.0) * 1023.0 + 0.5) as u32;
+    let alpha = (rgba[3].clamp(0.0, 1.0) * 3.0 + 0.5) as u32;
+    to_10_bits(rgba[0]) | (to_10_bits(rgba[1]) << 10) | (to_10_bits(rgba[2]) << 20) | (alpha << 30)
+}
+
+// Convert a 10 bit value to 8 bits with rounding (no 10 bit value scales to exactly half way, so there are no ties,
+// and the result matches the integer `(value * 255 + 511) / 1023` for every input)
+#[inline]
+pub(crate) fn ten_bits_to_u8(value: u16) -> u8 {
+    (value as f32 * (255.0 / 1023.0) + 0.5) as u8
+}
+
+// Convert a 2 bit alpha value to 8 bits
+#[inline]
+pub(crate) fn two_bits_to_u8(value: u16) -> u8 {
+    (value * 85) as u8
+}
+
+fn swizzle_red_blue_to_box(data: &[[u8; 4]]) -> Box<[[u8; 4]]> {
+    let mut output = vec![[0u8; 4]; data.len()];
+    simd::swizzle_red_blue(data, &mut output);
+    output.into_boxed_slice()
+}
+
+// Convert any bitmap to 8 bit BGRA (if BGRA is true) or RGBA
+fn convert_to_unorm8x4<const BGRA: bool>(source: &FrameBitmap) -> Box<[[u8; 4]]> {
+    let (width, height) = (source.width(), source.height());
+    let mut data = vec![[0u8; 4]; width * height];
+    let order = |rgba: [u8; 4]| if BGRA { [rgba[2], rgba[1], rgba[0], rgba[3]] } else { rgba };
+    match source {
+        FrameBitmap::BgraUnorm8x4(bitmap) if BGRA => data.copy_from_slice(&bitmap.data),
+        FrameBitmap::RgbaUnorm8x4(bitmap) if !BGRA => data.copy_from_slice(&bitmap.data),
+        FrameBitmap::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 { data: source_data, .. }) |
+        FrameBitmap::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 { data: source_data, .. }) => {
+            simd::swizzle_red_blue(source_data, &mut data);
+        },
+        FrameBitmap::RgbaUnormPacked1010102(bitmap) => {
+            simd::packed_1010102_to_unorm8x4::<BGRA>(&bitmap.data, &mut data);
+        },
+        FrameBitmap::RgbaF16x4(bitmap) => {
+            let (srgb_table, linear_table) = f16_to_u8_tables();
+            for (pixel, source_pixel) in data.iter_mut().zip(bitmap.data.iter()) {
+                *pixel = order([
+                    srgb_table[source_pixel[0].to_bits() as usize],
+                    srgb_table[source_pixel[1].to_bits() as usize],
+                    srgb_table[source_pixel[2].to_bits() as usize],
+                    linear_table[source_pixel[3].to_bits() as usize],
+                ]);
+            }
+        },
+        FrameBitmap::YCbCr(bitmap) => {
+            let transform = YCbCrToUnorm8::new(bitmap.matrix, bitmap.range);
+            // With half width chroma, the nearest chroma sample to each pixel is at half its index
+            let half_width_chroma = bitmap.chroma_width == width.div_ceil(2);
+            for (y, row) in data.chunks_exact_mut(width.max(1)).enumerate().take(height) {
+                let luma_row = &bitmap.luma_data[y * width..(y + 1) * width];
+                let chroma_y = y * bitmap.chroma_height / height;
+                let chroma_row = &bitmap.chroma_data[chroma_y * bitmap.chroma_width..(chroma_y + 1) * bitmap.chroma_width];
+                if half_width_chroma {
+                    simd::ycbcr_420_to_unorm8x4::<BGRA>(luma_row, chroma_row, &transform, row);
+                    continue;
+                }
+                for (x, (pixel, luma)) in row.iter_mut().zip(luma_row.iter()).enumerate() {
+                    let [cb, cr] = chroma_row[x * bitmap.chroma_width / width];
+                    let [r, g, b] = transform.pixel(*luma, cb, cr);
+                    *pixel = order([r, g, b, 255]);
+                }
+            }
+        },
+    }
+    data.into_boxed_slice()
+}
+
+// Read one row of any bitmap as display-encoded RGBA in [0, 1]
+pub(crate) fn read_row(source: &FrameBitmap, y: usize, row: &mut [[f32; 4]]) {
+    let width = source.width();
+    let from_u8 = |value: u8| value as f32 / 255.0;
+    match source {
+        FrameBitmap::BgraUnorm8x4(bitmap) => {
+            for (pixel, source_pixel) in row.iter_mut().zip(bitmap.data[y * width..(y + 1) * width].iter()) {
+                *pixel = [from_u8(source_pixel[2]), from_u8(source_pixel[1]), from_u8(source_pixel[0]), from_u8(source_pixel[3])];
+            }
+        },
+        FrameBitmap::RgbaUnorm8x4(bitmap) => {
+            for (pixel, source_pixel) in row.iter_mut().zip(bitmap.data[y * width..(y + 1) * width].iter()) {
+                *pixel = source_pixel.map(from_u8);
+            }
+        },
+        FrameBitmap::RgbaUnormPacked1010102(bitmap) => {
+            for (pixel, source_pixel) in row.iter_mut().zip(bitmap.data[y * width..(y + 1) * width].iter()) {
+                let [r, g, b, a] = unpack_1010102(*source_pixel);
+                *pixel = [r as f32 / 1023.0, g as f32 / 1023.0, b as f32 / 1023.0, a as f32 / 3.0];
+            }
+        },
+        FrameBitmap::RgbaF16x4(bitmap) => {
+            for (pixel, source_pixel) in row.iter_mut().zip(bitmap.data[y * width..(y + 1) * width].iter()) {
+                let alpha = source_pixel[3].to_f32();
+                let alpha = if alpha.is_nan() { 0.0 } else { alpha.clamp(0.0, 1.0) };
+                *pixel = [srgb_encode(source_pixel[0].to_f32()), srgb_encode(source_pixel[1].to_f32()), srgb_encode(source_pixel[2].to_f32()), alpha];
+            }
+        },
+        FrameBitmap::YCbCr(bitmap) => {
+            let transform = YCbCrTransform::new(bitmap.matrix, bitmap.range);
+            let luma_row = &bitmap.luma_data[y * width..(y + 1) * width];
+            let chroma_y = y * bitmap.chroma_height / bitmap.luma_height;
+            let chroma_row = &bitmap.chroma_data[chroma_y * bitmap.chroma_width..(chroma_y + 1) * bitmap.chroma_width];
+            for (x, (pixel, luma)) in row.iter_mut().zip(luma_row.iter()).enumerate() {
+                let [cb, cr] = chroma_row[x * bitmap.chroma_width / width];
+                let [r, g, b] = transform.to_rgb(*luma, cb, cr);
+                *pixel = [r.clamp(0.0, 1.0), g.clamp(0.0, 1.0), b.clamp(0.0, 1.0), 1.0];
+            }
+        },
+    }
+}
+
+// Convert any bitmap to 4:2:0 YCbCr
+fn convert_to_ycbcr(source: &FrameBitmap, matrix: YCbCrMatrix, range: VideoRange) -> FrameBitmapYCbCr {
+    let transform = YCbCrTransform::new(matrix, range);
+    let (width, height) = (source.width(), source.height());
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut luma_data = vec![0u8; width * height];
+    let mut chroma_data = vec![[0u8; 2]; chroma_width * chroma_height];
+    let mut rows = [vec![[0.0f32; 4]; width], vec![[0.0f32; 4]; width]];
+    for chroma_y in 0..chroma_height {
+        let row_count = if chroma_y * 2 + 1 < height { 2 } else { 1 };
+        for (row_index, row) in rows.iter_mut().enumerate().take(row_count) {
+            let y = chroma_y * 2 + row_index;
+            read_row(source, y, row);
+            for (luma, rgba) in luma_data[y * width..(y + 1) * width].iter_mut().zip(row.iter()) {
+                *luma = transform.luma([rgba[0], rgba[1], rgba[2]]);
+            }
+        }
+        for chroma_x in 0..chroma_width {
+            let mut sum = [0.0f32; 3];
+            let mut count = 0.0;
+            for row in &rows[..row_count] {
+                for rgba in &row[chroma_x * 2..(chroma_x * 2 + 2).min(width)] {
+                    sum = [sum[0] + rgba[0], sum[1] + rgba[1], sum[2] + rgba[2]];
+                    count += 1.0;
+                }
+            }
+            chroma_data[chroma_y * chroma_width + chroma_x] = transform.chroma(sum.map(|component| component / count));
+        }
+    }
+    FrameBitmapYCbCr {
+        luma_data: luma_data.into_boxed_slice(),
+        luma_width: width,
+        luma_height: height,
+        chroma_data: chroma_data.into_boxed_slice(),
+        chroma_width,
+        chroma_height,
+        range,
+        matrix,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ycbcr_pixel(y: u8, cb: u8, cr: u8, matrix: YCbCrMatrix, range: VideoRange) -> FrameBitmap {
+        FrameBitmap::YCbCr(FrameBitmapYCbCr::new(vec![y; 4], (2, 2), vec![[cb, cr]], (1, 1), matrix, range).unwrap())
+    }
+
+    fn to_rgba(bitmap: &FrameBitmap) -> Vec<[u8; 4]> {
+        match bitmap.convert(FrameBitmapFormat::RgbaUnorm8x4).unwrap() {
+            FrameBitmap::RgbaUnorm8x4(bitmap) => bitmap.data.into_vec(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn assert_close(actual: [u8; 4], expected: [u8; 4], tolerance: u8) {
+        for (actual_component, expected_component) in actual.iter().zip(expected.iter()) {
+            assert!(actual_component.abs_diff(*expected_component) <= tolerance, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn ycbcr_black_and_white() {
+        for matrix in [YCbCrMatrix::Bt601, YCbCrMatrix::Bt709] {
+            for (range, black, white) in [(VideoRange::Video, 16, 235), (VideoRange::Full, 0, 255)] {
+                assert_eq!(to_rgba(&ycbcr_pixel(black, 128, 128, matrix, range))[0], [0, 0, 0, 255], "{:?} {:?}", matrix, range);
+                assert_eq!(to_rgba(&ycbcr_pixel(white, 128, 128, matrix, range))[0], [255, 255, 255, 255], "{:?} {:?}", matrix, range);
+                // Video range codes outside the nominal range clip
+                if range == VideoRange::Video {
+                    assert_eq!(to_rgba(&ycbcr_pixel(0, 128, 128, matrix, range))[0], [0, 0, 0, 255]);
+                    assert_eq!(to_rgba(&ycbcr_pixel(255, 128, 128, matrix, range))[0], [255, 255, 255, 255]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ycbcr_primaries() {
+        // Reference codes for 100% red, green and blue
+        let cases = [
+            (YCbCrMatrix::Bt601, VideoRange::Video, [(81, 90, 240), (145, 54, 34), (41, 240, 110)]),
+            (YCbCrMatrix::Bt709, VideoRange::Video, [(63, 102, 240), (173, 42, 26), (32, 240, 118)]),
+            (YCbCrMatrix::Bt601, VideoRange::Full, [(76, 85, 255), (150, 44, 21), (29, 255, 107)]),
+            (YCbCrMatrix::Bt709, VideoRange::Full, [(54, 99, 255), (182, 30, 12), (18, 255, 116)]),
+        ];
+        let primaries = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        for (matrix, range, codes) in cases {
+            for ((y, cb, cr), expected) in codes.into_iter().zip(primaries) {
+                assert_close(to_rgba(&ycbcr_pixel(y, cb, cr, matrix, range))[0], expected, 2);
+                // And back again
+                let rgba = FrameBitmap::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 { data: vec![expected; 4].into_boxed_slice(), width: 2, height: 2 });
+                let FrameBitmap::YCbCr(ycbcr) = rgba.convert(FrameBitmapFormat::YCbCr { matrix, range }).unwrap() else { unreachable!() };
+                assert!(ycbcr.luma_data[0].abs_diff(y) <= 1, "{:?} {:?}: luma {} != {}", matrix, range, ycbcr.luma_data[0], y);
+                assert!(ycbcr.chroma_data[0][0].abs_diff(cb) <= 1 && ycbcr.chroma_data[0][1].abs_diff(cr) <= 1, "{:?} {:?}: chroma {:?} != {:?}", matrix, range, ycbcr.chroma_data[0], [cb, cr]);
+            }
+        }
+    }
+
+    #[test]
+    fn bgra_rgba_swizzle_round_trip() {
+        let data: Vec<[u8; 4]> = (0..37u8).map(|index| [index, index.wrapping_mul(7), index.wrapping_mul(13), 255 - index]).collect();
+        let bgra = FrameBitmap::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 { data: data.clone().into_boxed_slice(), width: 37, height: 1 });
+        let rgba = bgra.convert(FrameBitmapFormat::RgbaUnorm8x4).unwrap();
+        let FrameBitmap::RgbaUnorm8x4(rgba_bitmap) = &rgba else { unreachable!() };
+        for (rgba_pixel, bgra_pixel) in rgba_bitmap.data.iter().zip(data.iter()) {
+            assert_eq!(*rgba_pixel, [bgra_pixel[2], bgra_pixel[1], bgra_pixel[0], bgra_pixel[3]]);
+        }
+        let FrameBitmap::BgraUnorm8x4(round_trip) = rgba.convert(FrameBitmapFormat::BgraUnorm8x4).unwrap() else { unreachable!() };
+        assert_eq!(&round_trip.data[..], &data[..]);
+    }
+
+    #[test]
+    fn packed_1010102_round_trip() {
+        // Every 8 bit value survives a round trip through 10 bits exactly
+        let data: Vec<[u8; 4]> = (0..=255u8).map(|value| [value, 255 - value, value / 2, [0, 85, 170, 255][value as usize % 4]]).collect();
+        let rgba = FrameBitmap::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 { data: data.clone().into_boxed_slice(), width: 16, height: 16 });
+        let packed = rgba.convert(FrameBitmapFormat::RgbaUnormPacked1010102).unwrap();
+        assert_eq!(to_rgba(&packed), data);
+
+        // 10 bit values survive a round trip through f16 to within one code
+        let data: Vec<u32> = (0..1024u32).map(|value| value | ((1023 - value) << 10) | ((value * 7 % 1024) << 20) | ((value % 4) << 30)).collect();
+        let packed = FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 { data: data.clone().into_boxed_slice(), width: 32, height: 32 });
+        let FrameBitmap::RgbaUnormPacked1010102(round_trip) = packed.convert(FrameBitmapFormat::RgbaF16x4).unwrap().convert(FrameBitmapFormat::RgbaUnormPacked1010102).unwrap() else { unreachable!() };
+        for (actual, expected) in round_trip.data.iter().zip(data.iter()) {
+            let (actual, expected) = (unpack_1010102(*actual), unpack_1010102(*expected));
+            for (actual_component, expected_component) in actual.iter().zip(expected.iter()) {
+                assert!(actual_component.abs_diff(*expected_component) <= 1, "{:?} != {:?}", actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn f16_round_trip() {
+        let values = [0.0f32, 0.001, 0.018, 0.05, 0.2, 0.5, 0.73, 1.0];
+        let data: Vec<[f16; 4]> = values.iter().map(|value| [f16::from_f32(*value), f16::from_f32(1.0 - value), f16::from_f32(value * 0.5), f16::ONE]).collect();
+        let f16_bitmap = FrameBitmap::RgbaF16x4(FrameBitmapRgbaF16x4 { data: data.clone().into_boxed_slice(), width: values.len(), height: 1 });
+        let FrameBitmap::RgbaF16x4(round_trip) = f16_bitmap.convert(FrameBitmapFormat::RgbaUnormPacked1010102).unwrap().convert(FrameBitmapFormat::RgbaF16x4).unwrap() else { unreachable!() };
+        for (actual, expected) in round_trip.data.iter().zip(data.iter()) {
+            for (actual_component, expected_component) in actual.iter().zip(expected.iter()) {
+                // One 10 bit sRGB step is at most about 0.0035 in linear light, near white
+                assert!((actual_component.to_f32() - expected_component.to_f32()).abs() <= 0.004, "{:?} != {:?}", actual, expected);
+            }
+        }
+        // Linear light is encoded with the sRGB transfer function, so 0.5 becomes 188 and 0.25 becomes 137
+        assert_eq!(to_rgba(&f16_bitmap)[5], [188, 188, 137, 255]);
+    }
+
+    #[test]
+    fn ycbcr_constructor_validates_planes() {
+        assert!(FrameBitmapYCbCr::new(vec![0; 6], (3, 2), vec![[128, 128]; 2], (2, 1), YCbCrMatrix::Bt709, VideoRange::Video).is_ok());
+        assert!(FrameBitmapYCbCr::new(vec![0; 5], (3, 2), vec![[128, 128]; 2], (2, 1), YCbCrMatrix::Bt709, VideoRange::Video).is_err());
+        assert!(FrameBitmapYCbCr::new(vec![0; 6], (3, 2), vec![[128, 128]; 8], (4, 2), YCbCrMatrix::Bt709, VideoRange::Video).is_err());
+    }
+}
@@ -5,7 +5,7 @@ use std::fmt::Display;
 
 use half::f16;
 
-use crate::prelude::VideoFrame;
+use crate::prelude::{Rect, VideoFrame};
 #[cfg(target_os = "macos")]
 use crate::platform::macos::frame::MacosVideoFrame;
 #[cfg(target_os = "macos")]
@@ -28,14 +28,41 @@ use windows::Win32::System::WinRT::Direct3D11::IDirect3DDxgiInterfaceAccess;
 #[cfg(target_os = "windows")]
 use windows::Win32::Graphics::Direct3D11::D3D11_USAGE_DYNAMIC;
 
+mod convert;
+mod simd;
+mod scale;
+mod transform;
+mod view;
+mod pool;
+mod planar;
+
+pub use convert::*;
+pub use scale::*;
+pub use transform::*;
+pub use view::*;
+pub use pool::*;
+pub use planar::*;
+
 /// A Bgra8888 format bitmap
+#[derive(Clone, Debug)]
 pub struct FrameBitmapBgraUnorm8x4 {
     pub data: Box<[[u8; 4]]>,
     pub width:  usize,
     pub height: usize,
 }
 
+/// A Rgba8888 format bitmap
+#[derive(Clone, Debug)]
+pub struct FrameBitmapRgbaUnorm8x4 {
+    pub data: Box<[[u8; 4]]>,
+    pub width:  usize,
+    pub height: usize,
+}
+
 /// A Rgba1010102 format bitmap
+/// 
+/// Each pixel is packed with red in the lowest 10 bits, then green, blue, and 2 bits of alpha in the highest bits (like `DXGI_FORMAT_R10G10B10A2_UNORM`)
+#[derive(Clone, Debug)]
 pub struct FrameBitmapRgbaUnormPacked1010102 {
     pub data: Box<[u32]>,
     pub width:  usize,
@@ -43,6 +70,9 @@ pub struct FrameBitmapRgbaUnormPacked1010102 {
 }
 
 /// A RgbaF16x4 format bitmap
+/// 
+/// Components are linear light, with 1.0 as SDR white (like scRGB)
+#[derive(Clone, Debug)]
 pub struct FrameBitmapRgbaF16x4 {
     pub data: Box<[[f16; 4]]>,
     pub width:  usize,
@@ -50,18 +80,32 @@ pub struct FrameBitmapRgbaF16x4 {
 }
 
 /// The video range for a YCbCr format bitmap
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum VideoRange {
-    /// Luma: [16, 240], Chroma: [0, 255]
+    /// Luma: [16, 235], Chroma: [16, 240]
     Video,
     /// Luma: [0, 255], Chroma: [0, 255]
     Full,
 }
 
+/// The matrix relating YCbCr to RGB for a YCbCr format bitmap
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum YCbCrMatrix {
+    /// ITU-R BT.601, used for standard definition video
+    Bt601,
+    /// ITU-R BT.709, used for high definition video
+    Bt709,
+}
+
 /// A YCbCr image, corresponding to either V420 or F420 pixel formats.
 /// 
 /// Dual-planar, with luminance (Y) in one plane, and chrominance (CbCr) in another.
 /// Note that each plane may have a different size, as with V420 format, where
 /// the chroma plane is 2 by 2 blocks, but luma is per-pixel
+/// 
+/// Create one with `FrameBitmapYCbCr::new`, so that fields can be added without breaking code outside this crate
+#[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct FrameBitmapYCbCr {
     pub luma_data: Box<[u8]>,
     pub luma_width: usize,
@@ -70,11 +114,17 @@ pub struct FrameBitmapYCbCr {
     pub chroma_width: usize,
     pub chroma_height: usize,
     pub range: VideoRange,
+    pub matrix: YCbCrMatrix,
 }
 
 /// A bitmap image of the selected format
+/// 
+/// More formats may be added, so matches on this should have a wildcard arm
+#[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum FrameBitmap {
     BgraUnorm8x4(FrameBitmapBgraUnorm8x4),
+    RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4),
     RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102),
     RgbaF16x4(FrameBitmapRgbaF16x4),
     YCbCr(FrameBitmapYCbCr),
@@ -85,6 +135,41 @@ pub trait VideoFrameBitmap {
     /// Create a bitmap image from this frame. This usually involves a memory transfer from VRAM to system RAM,
     /// and is an expensive operation.
     fn get_bitmap(&self) -> Result<FrameBitmap, VideoFrameBitmapError>;
+
+    /// Lock this frame's pixels for reading in place, without copying them. The frame's backing
+    /// surface stays locked until the returned view is dropped.
+    ///
+    /// Frames which can't be read in place return an error; use `get_bitmap` for those instead
+    fn lock_bitmap(&self) -> Result<FrameBitmapView<'_>, VideoFrameBitmapError> {
+        Err(VideoFrameBitmapError::Other("Locking this frame's pixels in place isn't supported".to_string()))
+    }
+
+    /// Copy this frame's pixels into an existing bitmap, reusing its buffers when they already have the
+    /// frame's format and size, so that repeated captures don't need to allocate
+    ///
+    /// The default implementation replaces the bitmap with a new one from `get_bitmap`, so it always allocates
+    fn get_bitmap_into(&self, bitmap: &mut FrameBitmap) -> Result<(), VideoFrameBitmapError> {
+        *bitmap = self.get_bitmap()?;
+        Ok(())
+    }
+
+    /// The rectangle of this frame's bitmap holding captured content, in frame pixels with the origin at the top-left
+    ///
+    /// Defaults to `None`, meaning the whole bitmap is content
+    fn bitmap_content_rect(&self) -> Option<Rect> {
+        None
+    }
+
+    /// Create a bitmap image of just the captured content of this frame, cropped to its `bitmap_content_rect`
+    fn get_content_bitmap(&self) -> Result<FrameBitmap, VideoFrameBitmapError> {
+        let bitmap = self.get_bitmap()?;
+        match self.bitmap_content_rect() {
+            Some(content_rect) => bitmap
+                .crop_to_rect(content_rect)
+                .map_err(|error| VideoFrameBitmapError::Other(format!("Failed to crop bitmap to frame content: {}", error))),
+            None => Ok(bitmap),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -115,17 +200,89 @@ impl Error for VideoFrameBitmapError {
     }
 }
 
+#[derive(Clone, Debug)]
+/// Represents an error while processing a frame bitmap
+pub enum FrameBitmapError {
+    /// The size of the bitmap's data didn't match its dimensions
+    InvalidBitmap(String),
+    Other(String),
+}
+
+impl Display for FrameBitmapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidBitmap(error) => f.write_fmt(format_args!("FrameBitmapError::InvalidBitmap(\"{}\")", error)),
+            Self::Other(error) => f.write_fmt(format_args!("FrameBitmapError::Other(\"{}\")", error)),
+        }
+    }
+}
+
+impl Error for FrameBitmapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        None
+    }
+
+    fn description(&self) -> &str {
+        "description() is deprecated; use Display"
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        self.source()
+    }
+}
+
+// Copy a plane of pixels out of a mapped surface, with a single copy when its rows are tightly packed
+fn copy_plane<T: bytemuck::Pod>(source: &[u8], bytes_per_row: usize, width: usize, height: usize) -> Box<[T]> {
+    let row_bytes = width * std::mem::size_of::<T>();
+    if bytes_per_row == row_bytes {
+        return bytemuck::cast_slice::<_, T>(&source[..row_bytes * height]).into();
+    }
+    let mut data = Vec::with_capacity(width * height);
+    for y in 0..height {
+        data.extend_from_slice(bytemuck::cast_slice::<_, T>(&source[(bytes_per_row * y)..(bytes_per_row * y + row_bytes)]));
+    }
+    data.into_boxed_slice()
+}
+
+// Copy a plane of pixels out of a mapped surface into an existing buffer, only reallocating it if its size doesn't match
+fn copy_plane_into<T: bytemuck::Pod>(destination: &mut Box<[T]>, source: &[u8], bytes_per_row: usize, width: usize, height: usize) {
+    if destination.len() != width * height {
+        *destination = copy_plane(source, bytes_per_row, width, height);
+        return;
+    }
+    if destination.is_empty() {
+        return;
+    }
+    let row_bytes = width * std::mem::size_of::<T>();
+    if bytes_per_row == row_bytes {
+        bytemuck::cast_slice_mut::<_, u8>(&mut destination[..]).copy_from_slice(&source[..row_bytes * height]);
+        return;
+    }
+    for (y, row) in destination.chunks_exact_mut(width).enumerate() {
+        bytemuck::cast_slice_mut::<_, u8>(row).copy_from_slice(&source[(bytes_per_row * y)..(bytes_per_row * y + row_bytes)]);
+    }
+}
+
 impl VideoFrameBitmap for VideoFrame {
     fn get_bitmap(&self) -> Result<FrameBitmap, VideoFrameBitmapError> {
+        Ok(self.lock_bitmap()?.to_bitmap())
+    }
+
+    fn get_bitmap_into(&self, bitmap: &mut FrameBitmap) -> Result<(), VideoFrameBitmapError> {
+        self.lock_bitmap()?.copy_into(bitmap);
+        Ok(())
+    }
+
+    fn lock_bitmap(&self) -> Result<FrameBitmapView<'_>, VideoFrameBitmapError> {
         #[cfg(target_os = "windows")]
         {
             let (width, height) = self.impl_video_frame.frame_size;
             match self.get_dx11_surface() {
                 Err(WindowsDx11VideoFrameError::Other(x)) => Err(VideoFrameBitmapError::Other(x)),
                 Ok((surface, pixel_format)) => {
-                    let dxgi_format = match pixel_format {
-                        DirectXPixelFormat::B8G8R8A8UIntNormalized => DXGI_FORMAT_B8G8R8A8_UNORM,
-                        DirectXPixelFormat::R10G10B10A2UIntNormalized => DXGI_FORMAT_R10G10B10A2_UNORM,
+                    let (dxgi_format, format) = match pixel_format {
+                        DirectXPixelFormat::B8G8R8A8UIntNormalized => (DXGI_FORMAT_B8G8R8A8_UNORM, FrameBitmapFormat::BgraUnorm8x4),
+                        DirectXPixelFormat::R10G10B10A2UIntNormalized => (DXGI_FORMAT_R10G10B10A2_UNORM, FrameBitmapFormat::RgbaUnormPacked1010102),
                         _ => return Err(VideoFrameBitmapError::Other("Unknown or unsupported pixel format on DXGISurface".to_string())),
                     };
                     
@@ -143,55 +300,33 @@ impl VideoFrameBitmap for VideoFrame {
                         new_texture_desc.SampleDesc.Quality = 0;
                         new_texture_desc.Usage.0 = D3D11_USAGE_STAGING.0 | D3D11_USAGE_DYNAMIC.0;
                         new_texture_desc.Format = dxgi_format;
-                        let mut staging_texture = Option::<ID3D11Texture2D>::None;
-                        let staging_tex_result = self.impl_video_frame.device.CreateTexture2D(&new_texture_desc as *const _, None, Some(&mut staging_texture as *mut _));
-                        staging_tex_result.map_err(|error| VideoFrameBitmapError::Other(format!("Failed to create texture: {}", error.to_string())))?;
+                        let staging_textures = self.impl_video_frame.staging_textures.clone();
                         let dxgi_interfce_access: IDirect3DDxgiInterfaceAccess = surface.cast()
                             .map_err(|_| VideoFrameBitmapError::Other("Couldn't create surface interface access".to_string()))?;
                         let surface_texture: ID3D11Texture2D = dxgi_interfce_access.GetInterface()
                             .map_err(|_| VideoFrameBitmapError::Other("Couldn't create surface texture from surface IDirect3DDxgiInterfaceAccess".to_string()))?;
                         let device = self.impl_video_frame.device.GetImmediateContext()
                             .map_err(|_| VideoFrameBitmapError::Other("Couldn't get immediate d3d11 context".to_string()))?;
-                        let staging_texture = staging_texture.unwrap();
+                        let staging_texture = staging_textures.take(&self.impl_video_frame.device, &new_texture_desc)?;
                         device.CopyResource(&staging_texture, &surface_texture);
                         let mut mapped_resource = D3D11_MAPPED_SUBRESOURCE::default();
-                        let map_result = device.Map(&staging_texture, 0, D3D11_MAP_READ, 0, Some(&mut mapped_resource as *mut _));
-                        map_result.map_err(|_| VideoFrameBitmapError::Other("Couldn't map staging texture".to_string()))?;
-                        match pixel_format {
-                            DirectXPixelFormat::B8G8R8A8UIntNormalized => {
-                                let mut image_data = vec![[0u8; 4]; width * height];
-                                let bpr = mapped_resource.RowPitch as usize;
-                                let surface_slice = std::slice::from_raw_parts(mapped_resource.pData as *const u8, bpr * height);
-                                for y in 0..height {
-                                    let source_slice = bytemuck::cast_slice::<_, [u8; 4]>(&surface_slice[(bpr * y)..(bpr * y + 4 * width)]);
-                                    image_data[(width * y)..(width * y + width)].copy_from_slice(source_slice);
-                                }
-                                let _ = device.Unmap(&staging_texture, 0);
-                                Ok(FrameBitmap::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 {
-                                    data: image_data.into_boxed_slice(),
-                                    width,
-                                    height,
-                                }))
-                            },
-                            DirectXPixelFormat::R10G10B10A2UIntNormalized => {
-                                let mut image_data = vec![0u32; width * height];
-                                let bpr = mapped_resource.RowPitch as usize;
-                                let surface_slice = std::slice::from_raw_parts(mapped_resource.pData as *const u8, bpr * height);
-                                for y in 0..height {
-                                    let source_slice = bytemuck::cast_slice::<_, u32>(&surface_slice[(bpr * y)..(bpr * y + 4 * width)]);
-                                    image_data[(width * y)..(width * y + width)].copy_from_slice(source_slice);
-                                }
-                                let _ = device.Unmap(&staging_texture, 0);
-                                Ok(FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
-                                    data: image_data.into_boxed_slice(),
-                                    width,
-                                    height,
-                                }))
-                            },
-                            _ => {
-                                Err(VideoFrameBitmapError::Other("Unknown or unsupported pixel format on DXGISurface".to_string()))
-                            }
+                        if device.Map(&staging_texture, 0, D3D11_MAP_READ, 0, Some(&mut mapped_resource as *mut _)).is_err() {
+                            // The texture isn't mapped, so it can go straight back to the cache for the next frame
+                            staging_textures.give_back(staging_texture);
+                            return Err(VideoFrameBitmapError::Other("Couldn't map staging texture".to_string()));
                         }
+                        // From here on, the staging texture is unmapped and returned to the cache when the view drops
+                        let lock = FrameBitmapLock::StagingTexture {
+                            _mapped_texture: MappedStagingTexture { context: device, texture: Some(staging_texture), cache: staging_textures },
+                        };
+                        let plane = FrameBitmapPlaneDesc {
+                            data: mapped_resource.pData as *const u8,
+                            width,
+                            height,
+                            bytes_per_row: mapped_resource.RowPitch as usize,
+                            bytes_per_pixel: 4,
+                        };
+                        Ok(FrameBitmapView::new(format, width, height, [Some(plane), None], lock))
                     }
                 }
             }
@@ -213,59 +348,47 @@ impl VideoFrameBitmap for VideoFrame {
                 let pixel_format = iosurface.get_pixel_format();
                 match pixel_format {
                     Some(CVPixelFormat::BGRA8888) => {
-                        let bpr = iosurface.get_bytes_per_row();
-                        let height = iosurface.get_height();
                         let width = iosurface.get_width();
-                        let mut image_data = vec![[0; 4]; width * height];
+                        let height = iosurface.get_height();
                         let base_address = lock_gaurd.get_base_address().ok_or(VideoFrameBitmapError::Other("Failed to get base address of iosurface".into()))?;
-                        let iosurface_slice = unsafe { std::slice::from_raw_parts(base_address as *const u8, bpr * height) };
-                        for y in 0..height {
-                            let source_slice = bytemuck::cast_slice::<_, [u8; 4]>(&iosurface_slice[(bpr * y)..(bpr * y + 4 * width)]);
-                            image_data[(width * y)..(width * y + width)].copy_from_slice(source_slice);
-                        }
-                        Ok(FrameBitmap::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 {
-                            data: image_data.into_boxed_slice(),
+                        let plane = FrameBitmapPlaneDesc {
+                            data: base_address as *const u8,
                             width,
                             height,
-                        }))
+                            bytes_per_row: iosurface.get_bytes_per_row(),
+                            bytes_per_pixel: 4,
+                        };
+                        let lock = FrameBitmapLock::IOSurface { _lock_gaurd: lock_gaurd, _iosurface: iosurface };
+                        Ok(unsafe { FrameBitmapView::new(FrameBitmapFormat::BgraUnorm8x4, width, height, [Some(plane), None], lock) })
                     },
                     Some(CVPixelFormat::V420) |
                     Some(CVPixelFormat::F420) => {
-
-                        let luma_bpr = iosurface.get_bytes_per_row_of_plane(0);
-                        let luma_height = iosurface.get_height_of_plane(0);
-                        let luma_width = iosurface.get_width_of_plane(0);
-
-                        let mut luma_image_data = vec![0u8; luma_width * luma_height];
                         let luma_base_address = lock_gaurd.get_base_address_of_plane(0).ok_or(VideoFrameBitmapError::Other("Failed to get base address of iosurface".into()))?;
-                        let luma_iosurface_slice = unsafe { std::slice::from_raw_parts(luma_base_address as *const u8, luma_bpr * luma_height) };
-
-                        for y in 0..luma_height {
-                            let luma_source_slice = &luma_iosurface_slice[(luma_bpr * y)..(luma_bpr * y + luma_width)];
-                            luma_image_data[(luma_width * y)..(luma_width * y + luma_width)].copy_from_slice(luma_source_slice);                            
-                        }
+                        let luma_plane = FrameBitmapPlaneDesc {
+                            data: luma_base_address as *const u8,
+                            width: iosurface.get_width_of_plane(0),
+                            height: iosurface.get_height_of_plane(0),
+                            bytes_per_row: iosurface.get_bytes_per_row_of_plane(0),
+                            bytes_per_pixel: 1,
+                        };
 
-                        let chroma_bpr = iosurface.get_bytes_per_row_of_plane(1);
-                        let chroma_height = iosurface.get_height_of_plane(1);
-                        let chroma_width = iosurface.get_width_of_plane(1);
-                        let mut chroma_image_data = vec![[0u8; 2]; chroma_width * chroma_height];
                         let chroma_base_address = lock_gaurd.get_base_address_of_plane(1).ok_or(VideoFrameBitmapError::Other("Failed to get base address of iosurface".into()))?;
-                        let chroma_iosurface_slice = unsafe { std::slice::from_raw_parts(chroma_base_address as *const u8, chroma_bpr * chroma_height) };
+                        let chroma_plane = FrameBitmapPlaneDesc {
+                            data: chroma_base_address as *const u8,
+                            width: iosurface.get_width_of_plane(1),
+                            height: iosurface.get_height_of_plane(1),
+                            bytes_per_row: iosurface.get_bytes_per_row_of_plane(1),
+                            bytes_per_pixel: 2,
+                        };
 
-                        for y in 0..chroma_height {
-                            let chroma_source_slice = bytemuck::cast_slice::<_, [u8; 2]>(&chroma_iosurface_slice[(chroma_bpr * y)..(chroma_bpr * y + 2 * chroma_width)]);
-                            chroma_image_data[(chroma_width * y)..(chroma_width * y + chroma_width)].copy_from_slice(chroma_source_slice);
-                        }
-
-                        Ok(FrameBitmap::YCbCr(FrameBitmapYCbCr {
-                            luma_data: luma_image_data.into_boxed_slice(),
-                            chroma_data: chroma_image_data.into_boxed_slice(),
-                            luma_width,
-                            luma_height,
-                            chroma_width,
-                            chroma_height,
-                            range: if pixel_format == Some(CVPixelFormat::F420) { VideoRange::Full } else { VideoRange::Video }
-                        }))
+                        let format = FrameBitmapFormat::YCbCr {
+                            // Capture streams set the BT.709 color matrix for YCbCr formats
+                            matrix: YCbCrMatrix::Bt709,
+                            range: if pixel_format == Some(CVPixelFormat::F420) { VideoRange::Full } else { VideoRange::Video },
+                        };
+                        let (width, height) = (luma_plane.width, luma_plane.height);
+                        let lock = FrameBitmapLock::IOSurface { _lock_gaurd: lock_gaurd, _iosurface: iosurface };
+                        Ok(unsafe { FrameBitmapView::new(format, width, height, [Some(luma_plane), Some(chroma_plane)], lock) })
                     },
                     _ => Err(VideoFrameBitmapError::Other("Unknown pixel format on iosurface".to_string()))
                 }
@@ -274,6 +397,10 @@ impl VideoFrameBitmap for VideoFrame {
             }
         }
     }
+
+    fn bitmap_content_rect(&self) -> Option<Rect> {
+        Some(self.content_rect())
+    }
 }
 
 
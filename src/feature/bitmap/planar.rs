@@ -0,0 +1,465 @@
+use half::f16;
+
+use super::convert::{pack_1010102, read_row, srgb_decode, YCbCrTransform};
+use super::{FrameBitmap, FrameBitmapError, FrameBitmapFormat, FrameBitmapRgbaF16x4, FrameBitmapRgbaUnormPacked1010102, FrameBitmapYCbCr, VideoRange, YCbCrMatrix};
+
+/// The plane layout and bit depth of a `FrameBitmapPlanar`
+///
+/// All of these formats have 4:2:0 chroma, with one chroma sample for each 2 by 2 block of pixels
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FrameBitmapPlanarFormat {
+    /// 8 bit, with Y, Cb and Cr each in their own plane
+    I420,
+    /// 8 bit, with Y in one plane, and interleaved Cb and Cr in another
+    Nv12,
+    /// 10 bit, laid out like NV12, with each sample in the high bits of a little-endian `u16`
+    P010,
+}
+
+impl FrameBitmapPlanarFormat {
+    /// The number of planes in this format
+    pub fn plane_count(&self) -> usize {
+        match self {
+            Self::I420 => 3,
+            Self::Nv12 | Self::P010 => 2,
+        }
+    }
+
+    /// The number of significant bits in each sample
+    pub fn bit_depth(&self) -> u32 {
+        match self {
+            Self::I420 | Self::Nv12 => 8,
+            Self::P010 => 10,
+        }
+    }
+
+    /// The size in bytes of each sample
+    pub fn bytes_per_sample(&self) -> usize {
+        match self {
+            Self::I420 | Self::Nv12 => 1,
+            Self::P010 => 2,
+        }
+    }
+
+    /// The number of samples for each pixel of a plane - 2 for interleaved chroma planes, and 1 otherwise
+    pub fn samples_per_pixel(&self, plane: usize) -> usize {
+        match (self, plane) {
+            (Self::Nv12 | Self::P010, 1) => 2,
+            _ => 1,
+        }
+    }
+
+    // The plane and sample offset of the Cb (0) or Cr (1) component
+    fn chroma_location(&self, component: usize) -> (usize, usize) {
+        match self {
+            Self::I420 => (1 + component, 0),
+            Self::Nv12 | Self::P010 => (1, component),
+        }
+    }
+
+    fn read_sample(&self, row: &[u8], index: usize) -> u16 {
+        match self.bytes_per_sample() {
+            1 => row[index] as u16,
+            _ => u16::from_le_bytes([row[index * 2], row[index * 2 + 1]]) >> (16 - self.bit_depth()),
+        }
+    }
+
+    fn write_sample(&self, row: &mut [u8], index: usize, value: u16) {
+        match self.bytes_per_sample() {
+            1 => row[index] = value as u8,
+            _ => row[index * 2..index * 2 + 2].copy_from_slice(&(value << (16 - self.bit_depth())).to_le_bytes()),
+        }
+    }
+}
+
+/// A single plane of a `FrameBitmapPlanar`
+///
+/// Row `y` starts at byte `y * bytes_per_row` of the plane's data, and rows may be padded to meet an alignment
+#[derive(Clone, Debug)]
+pub struct FrameBitmapPlanarPlane {
+    pub data: Box<[u8]>,
+    /// The width of the plane in pixels (so an interleaved chroma plane has twice as many samples per row)
+    pub width: usize,
+    pub height: usize,
+    pub bytes_per_row: usize,
+}
+
+impl FrameBitmapPlanarPlane {
+    fn row(&self, y: usize) -> &[u8] {
+        &self.data[(y * self.bytes_per_row)..((y + 1) * self.bytes_per_row)]
+    }
+
+    fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        &mut self.data[(y * self.bytes_per_row)..((y + 1) * self.bytes_per_row)]
+    }
+}
+
+/// A planar YCbCr bitmap, with a separate row stride for each plane, as used by software video encoders
+///
+/// Planes are in the order Y, Cb, Cr for I420, and Y, CbCr for NV12 and P010. Create one with `FrameBitmapPlanar::new`,
+/// or from another bitmap with `FrameBitmap::to_planar`.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct FrameBitmapPlanar {
+    pub format: FrameBitmapPlanarFormat,
+    pub width: usize,
+    pub height: usize,
+    pub planes: Box<[FrameBitmapPlanarPlane]>,
+    pub range: VideoRange,
+    pub matrix: YCbCrMatrix,
+}
+
+impl FrameBitmapPlanar {
+    /// Create a black planar bitmap, with each plane's rows padded to a multiple of `row_alignment` bytes
+    pub fn new(format: FrameBitmapPlanarFormat, width: usize, height: usize, row_alignment: usize, matrix: YCbCrMatrix, range: VideoRange) -> Result<Self, FrameBitmapError> {
+        if row_alignment == 0 {
+            return Err(FrameBitmapError::Other("Planar bitmap row alignment must be at least 1 byte".to_string()));
+        }
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        let planes = (0..format.plane_count()).map(|plane| {
+            let (plane_width, plane_height) = if plane == 0 { (width, height) } else { (chroma_width, chroma_height) };
+            let bytes_per_row = (plane_width * format.samples_per_pixel(plane) * format.bytes_per_sample()).next_multiple_of(row_alignment);
+            FrameBitmapPlanarPlane {
+                data: vec![0u8; bytes_per_row * plane_height].into_boxed_slice(),
+                width: plane_width,
+                height: plane_height,
+                bytes_per_row,
+            }
+        }).collect();
+        let mut bitmap = Self {
+            format,
+            width,
+            height,
+            planes,
+            range,
+            matrix,
+        };
+        let black_luma = match range {
+            VideoRange::Video => 16.0,
+            VideoRange::Full => 0.0,
+        };
+        for (plane_index, plane) in bitmap.planes.iter_mut().enumerate() {
+            let samples = plane.width * format.samples_per_pixel(plane_index);
+            let value = match plane_index {
+                0 => code_from_8_bit(black_luma, format.bit_depth(), range, false),
+                _ => code_from_8_bit(128.0, format.bit_depth(), range, true),
+            };
+            for y in 0..plane.height {
+                let row = plane.row_mut(y);
+                for index in 0..samples {
+                    format.write_sample(row, index, value);
+                }
+            }
+        }
+        Ok(bitmap)
+    }
+
+    /// Check that the bitmap's planes match its format and dimensions
+    pub fn validate(&self) -> Result<(), FrameBitmapError> {
+        if self.planes.len() != self.format.plane_count() {
+            return Err(FrameBitmapError::InvalidBitmap(format!("{:?} bitmap has {} planes, but should have {}", self.format, self.planes.len(), self.format.plane_count())));
+        }
+        for (plane_index, plane) in self.planes.iter().enumerate() {
+            let (width, height) = if plane_index == 0 { (self.width, self.height) } else { (self.width.div_ceil(2), self.height.div_ceil(2)) };
+            if plane.width != width || plane.height != height {
+                return Err(FrameBitmapError::InvalidBitmap(format!("Plane {} is {}x{}, but should be {}x{}", plane_index, plane.width, plane.height, width, height)));
+            }
+            let row_bytes = width * self.format.samples_per_pixel(plane_index) * self.format.bytes_per_sample();
+            if plane.bytes_per_row < row_bytes {
+                return Err(FrameBitmapError::InvalidBitmap(format!("Plane {} has {} bytes per row, but needs at least {}", plane_index, plane.bytes_per_row, row_bytes)));
+            }
+            if plane.data.len() < plane.bytes_per_row * height {
+                return Err(FrameBitmapError::InvalidBitmap(format!("Plane {} has {} bytes of data, but needs {}", plane_index, plane.data.len(), plane.bytes_per_row * height)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert the planar bitmap to a `FrameBitmap` of the target format
+    ///
+    /// 10 bit bitmaps are converted at full precision to `RgbaUnormPacked1010102` and `RgbaF16x4`, and rounded to 8 bits for other formats
+    pub fn to_bitmap(&self, target: FrameBitmapFormat) -> Result<FrameBitmap, FrameBitmapError> {
+        self.validate()?;
+        let (width, height) = (self.width, self.height);
+        match target {
+            FrameBitmapFormat::RgbaUnormPacked1010102 | FrameBitmapFormat::RgbaF16x4 if self.format.bit_depth() > 8 => {
+                let mut rgb_row = vec![[0.0f32; 3]; width];
+                match target {
+                    FrameBitmapFormat::RgbaUnormPacked1010102 => {
+                        let mut data = vec![0u32; width * height];
+                        for (y, data_row) in data.chunks_exact_mut(width.max(1)).enumerate().take(height) {
+                            self.read_rgb_row(y, &mut rgb_row);
+                            for (pixel, [r, g, b]) in data_row.iter_mut().zip(rgb_row.iter().copied()) {
+                                *pixel = pack_1010102(&[r, g, b, 1.0]);
+                            }
+                        }
+                        Ok(FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+                            data: data.into_boxed_slice(),
+                            width,
+                            height,
+                        }))
+                    },
+                    _ => {
+                        let mut data = vec![[f16::ZERO; 4]; width * height];
+                        for (y, data_row) in data.chunks_exact_mut(width.max(1)).enumerate().take(height) {
+                            self.read_rgb_row(y, &mut rgb_row);
+                            for (pixel, rgb) in data_row.iter_mut().zip(rgb_row.iter()) {
+                                let [r, g, b] = rgb.map(|component| f16::from_f32(srgb_decode(component)));
+                                *pixel = [r, g, b, f16::ONE];
+                            }
+                        }
+                        Ok(FrameBitmap::RgbaF16x4(FrameBitmapRgbaF16x4 {
+                            data: data.into_boxed_slice(),
+                            width,
+                            height,
+                        }))
+                    },
+                }
+            },
+            _ => {
+                let bitmap = self.to_ycbcr();
+                match target {
+                    FrameBitmapFormat::YCbCr { matrix, range } if matrix == self.matrix && range == self.range => Ok(FrameBitmap::YCbCr(bitmap)),
+                    _ => FrameBitmap::YCbCr(bitmap).convert(target),
+                }
+            },
+        }
+    }
+
+    // Gather the planes into an 8 bit, dual-planar bitmap
+    fn to_ycbcr(&self) -> FrameBitmapYCbCr {
+        let format = self.format;
+        let bit_depth = format.bit_depth();
+        let to_u8 = |sample: u16, chroma: bool| (code_to_8_bit(sample, bit_depth, self.range, chroma) + 0.5).clamp(0.0, 255.0) as u8;
+        let luma_plane = &self.planes[0];
+        let mut luma_data = Vec::with_capacity(self.width * self.height);
+        for y in 0..self.height {
+            let row = luma_plane.row(y);
+            luma_data.extend((0..self.width).map(|x| to_u8(format.read_sample(row, x), false)));
+        }
+        let (chroma_width, chroma_height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let (cb_plane, cb_offset) = format.chroma_location(0);
+        let (cr_plane, cr_offset) = format.chroma_location(1);
+        let cb_stride = format.samples_per_pixel(cb_plane);
+        let cr_stride = format.samples_per_pixel(cr_plane);
+        let mut chroma_data = Vec::with_capacity(chroma_width * chroma_height);
+        for y in 0..chroma_height {
+            let (cb_row, cr_row) = (self.planes[cb_plane].row(y), self.planes[cr_plane].row(y));
+            chroma_data.extend((0..chroma_width).map(|x| [
+                to_u8(format.read_sample(cb_row, x * cb_stride + cb_offset), true),
+                to_u8(format.read_sample(cr_row, x * cr_stride + cr_offset), true),
+            ]));
+        }
+        FrameBitmapYCbCr {
+            luma_data: luma_data.into_boxed_slice(),
+            luma_width: self.width,
+            luma_height: self.height,
+            chroma_data: chroma_data.into_boxed_slice(),
+            chroma_width,
+            chroma_height,
+            range: self.range,
+            matrix: self.matrix,
+        }
+    }
+
+    // Read one row as display-encoded RGB, using the nearest chroma sample for each pixel
+    fn read_rgb_row(&self, y: usize, row: &mut [[f32; 3]]) {
+        let format = self.format;
+        let bit_depth = format.bit_depth();
+        let transform = YCbCrTransform::new(self.matrix, self.range);
+        let (cb_plane, cb_offset) = format.chroma_location(0);
+        let (cr_plane, cr_offset) = format.chroma_location(1);
+        let cb_stride = format.samples_per_pixel(cb_plane);
+        let cr_stride = format.samples_per_pixel(cr_plane);
+        let luma_row = self.planes[0].row(y);
+        let (cb_row, cr_row) = (self.planes[cb_plane].row(y / 2), self.planes[cr_plane].row(y / 2));
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let luma = code_to_8_bit(format.read_sample(luma_row, x), bit_depth, self.range, false);
+            let cb = code_to_8_bit(format.read_sample(cb_row, (x / 2) * cb_stride + cb_offset), bit_depth, self.range, true);
+            let cr = code_to_8_bit(format.read_sample(cr_row, (x / 2) * cr_stride + cr_offset), bit_depth, self.range, true);
+            *pixel = transform.code_to_rgb(luma, cb, cr).map(|component| component.clamp(0.0, 1.0));
+        }
+    }
+}
+
+impl FrameBitmap {
+    /// Convert the bitmap to a planar YCbCr bitmap, with each plane's rows padded to a multiple of `row_alignment` bytes
+    ///
+    /// YCbCr bitmaps keep their matrix and range, and 4:2:0 YCbCr bitmaps keep their samples as they are (scaled up for 10 bit formats).
+    /// Other formats are converted with the BT.709 matrix and video range, which most encoders expect - to use another matrix or range,
+    /// `convert` the bitmap to YCbCr first. Chroma is the average of each 2 by 2 block of pixels, and alpha is discarded.
+    /// 10 bit RGB bitmaps keep their full precision when converted to P010.
+    pub fn to_planar(&self, format: FrameBitmapPlanarFormat, row_alignment: usize) -> Result<FrameBitmapPlanar, FrameBitmapError> {
+        self.validate()?;
+        let (matrix, range) = match self {
+            Self::YCbCr(bitmap) => (bitmap.matrix, bitmap.range),
+            _ => (YCbCrMatrix::Bt709, VideoRange::Video),
+        };
+        let (width, height) = (self.width(), self.height());
+        let mut planar = FrameBitmapPlanar::new(format, width, height, row_alignment, matrix, range)?;
+        let bit_depth = format.bit_depth();
+        let (cb_plane, cb_offset) = format.chroma_location(0);
+        let (cr_plane, cr_offset) = format.chroma_location(1);
+        let cb_stride = format.samples_per_pixel(cb_plane);
+        let cr_stride = format.samples_per_pixel(cr_plane);
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        match self {
+            Self::YCbCr(bitmap) if bitmap.chroma_width == chroma_width && bitmap.chroma_height == chroma_height => {
+                let luma_plane = &mut planar.planes[0];
+                for (y, luma_row) in bitmap.luma_data.chunks_exact(width.max(1)).enumerate().take(height) {
+                    let row = luma_plane.row_mut(y);
+                    for (x, luma) in luma_row.iter().enumerate() {
+                        format.write_sample(row, x, code_from_8_bit(*luma as f32, bit_depth, range, false));
+                    }
+                }
+                for (y, chroma_row) in bitmap.chroma_data.chunks_exact(chroma_width.max(1)).enumerate().take(chroma_height) {
+                    for (x, [cb, cr]) in chroma_row.iter().enumerate() {
+                        format.write_sample(planar.planes[cb_plane].row_mut(y), x * cb_stride + cb_offset, code_from_8_bit(*cb as f32, bit_depth, range, true));
+                        format.write_sample(planar.planes[cr_plane].row_mut(y), x * cr_stride + cr_offset, code_from_8_bit(*cr as f32, bit_depth, range, true));
+                    }
+                }
+            },
+            _ => {
+                let transform = YCbCrTransform::new(matrix, range);
+                let mut rows = [vec![[0.0f32; 4]; width], vec![[0.0f32; 4]; width]];
+                for chroma_y in 0..chroma_height {
+                    let row_count = if chroma_y * 2 + 1 < height { 2 } else { 1 };
+                    for (row_index, row) in rows.iter_mut().enumerate().take(row_count) {
+                        let y = chroma_y * 2 + row_index;
+                        read_row(self, y, row);
+                        let luma_row = planar.planes[0].row_mut(y);
+                        for (x, rgba) in row.iter().enumerate() {
+                            format.write_sample(luma_row, x, code_from_8_bit(transform.luma_code([rgba[0], rgba[1], rgba[2]]), bit_depth, range, false));
+                        }
+                    }
+                    for chroma_x in 0..chroma_width {
+                        let mut sum = [0.0f32; 3];
+                        let mut count = 0.0;
+                        for row in &rows[..row_count] {
+                            for rgba in &row[chroma_x * 2..(chroma_x * 2 + 2).min(width)] {
+                                sum = [sum[0] + rgba[0], sum[1] + rgba[1], sum[2] + rgba[2]];
+                                count += 1.0;
+                            }
+                        }
+                        let [cb, cr] = transform.chroma_code(sum.map(|component| component / count));
+                        format.write_sample(planar.planes[cb_plane].row_mut(chroma_y), chroma_x * cb_stride + cb_offset, code_from_8_bit(cb, bit_depth, range, true));
+                        format.write_sample(planar.planes[cr_plane].row_mut(chroma_y), chroma_x * cr_stride + cr_offset, code_from_8_bit(cr, bit_depth, range, true));
+                    }
+                }
+            },
+        }
+        Ok(planar)
+    }
+}
+
+// Scale an unrounded 8 bit code value to a rounded code value of a higher bit depth.
+// Video range scales by a power of two (so 8 bit video range values map exactly), while full range stretches [0, 255] to the whole range
+fn code_from_8_bit(code: f32, bit_depth: u32, range: VideoRange, chroma: bool) -> u16 {
+    let max = ((1u32 << bit_depth) - 1) as f32;
+    let value = match range {
+        VideoRange::Video => code * (1u32 << (bit_depth - 8)) as f32,
+        VideoRange::Full if chroma => (code - 128.0) * (max / 255.0) + (1u32 << (bit_depth - 1)) as f32,
+        VideoRange::Full => code * (max / 255.0),
+    };
+    (value + 0.5).clamp(0.0, max) as u16
+}
+
+// The inverse of `code_from_8_bit`, without rounding
+fn code_to_8_bit(value: u16, bit_depth: u32, range: VideoRange, chroma: bool) -> f32 {
+    let max = ((1u32 << bit_depth) - 1) as f32;
+    let value = value as f32;
+    match range {
+        VideoRange::Video => value / (1u32 << (bit_depth - 8)) as f32,
+        VideoRange::Full if chroma => (value - (1u32 << (bit_depth - 1)) as f32) * (255.0 / max) + 128.0,
+        VideoRange::Full => value * (255.0 / max),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::FrameBitmapRgbaUnorm8x4;
+
+    fn solid_rgba(pixel: [u8; 4], width: usize, height: usize) -> FrameBitmap {
+        FrameBitmap::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 {
+            data: vec![pixel; width * height].into_boxed_slice(),
+            width,
+            height,
+        })
+    }
+
+    fn plane_layout(planar: &FrameBitmapPlanar) -> Vec<(usize, usize, usize, usize)> {
+        planar.planes.iter().map(|plane| (plane.width, plane.height, plane.bytes_per_row, plane.data.len())).collect()
+    }
+
+    #[test]
+    fn odd_sizes_round_chroma_planes_up() {
+        let i420 = FrameBitmapPlanar::new(FrameBitmapPlanarFormat::I420, 5, 3, 1, YCbCrMatrix::Bt709, VideoRange::Video).unwrap();
+        assert_eq!(plane_layout(&i420), vec![(5, 3, 5, 15), (3, 2, 3, 6), (3, 2, 3, 6)]);
+        let nv12 = FrameBitmapPlanar::new(FrameBitmapPlanarFormat::Nv12, 5, 3, 1, YCbCrMatrix::Bt709, VideoRange::Video).unwrap();
+        assert_eq!(plane_layout(&nv12), vec![(5, 3, 5, 15), (3, 2, 6, 12)]);
+        let p010 = FrameBitmapPlanar::new(FrameBitmapPlanarFormat::P010, 5, 3, 1, YCbCrMatrix::Bt709, VideoRange::Video).unwrap();
+        assert_eq!(plane_layout(&p010), vec![(5, 3, 10, 30), (3, 2, 12, 24)]);
+        for planar in [&i420, &nv12, &p010] {
+            planar.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn rows_are_padded_to_the_alignment() {
+        let i420 = FrameBitmapPlanar::new(FrameBitmapPlanarFormat::I420, 7, 5, 16, YCbCrMatrix::Bt709, VideoRange::Video).unwrap();
+        assert_eq!(plane_layout(&i420), vec![(7, 5, 16, 80), (4, 3, 16, 48), (4, 3, 16, 48)]);
+        let p010 = FrameBitmapPlanar::new(FrameBitmapPlanarFormat::P010, 7, 5, 64, YCbCrMatrix::Bt709, VideoRange::Video).unwrap();
+        assert_eq!(plane_layout(&p010), vec![(7, 5, 64, 320), (4, 3, 64, 192)]);
+        assert!(FrameBitmapPlanar::new(FrameBitmapPlanarFormat::Nv12, 7, 5, 0, YCbCrMatrix::Bt709, VideoRange::Video).is_err());
+    }
+
+    #[test]
+    fn rgb_round_trips_through_each_format() {
+        let colors = [[0, 0, 0, 255], [255, 255, 255, 255], [200, 30, 90, 255], [20, 180, 240, 255], [128, 128, 128, 255]];
+        for format in [FrameBitmapPlanarFormat::I420, FrameBitmapPlanarFormat::Nv12, FrameBitmapPlanarFormat::P010] {
+            for color in colors {
+                let planar = solid_rgba(color, 5, 3).to_planar(format, 4).unwrap();
+                let FrameBitmap::RgbaUnorm8x4(back) = planar.to_bitmap(FrameBitmapFormat::RgbaUnorm8x4).unwrap() else {
+                    panic!("expected an RGBA bitmap");
+                };
+                assert_eq!((back.width, back.height), (5, 3));
+                for pixel in back.data.iter() {
+                    for (actual, expected) in pixel.iter().zip(color.iter()) {
+                        assert!(actual.abs_diff(*expected) <= 2, "{:?} {:?} came back as {:?}", format, color, pixel);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn p010_stores_10_bit_samples_in_the_high_bits() {
+        let planar = solid_rgba([255, 255, 255, 255], 2, 2).to_planar(FrameBitmapPlanarFormat::P010, 1).unwrap();
+        // Video range white is 235 in 8 bits, and 940 in 10 bits. Neutral chroma is 512.
+        assert_eq!(&planar.planes[0].data[..2], &(940u16 << 6).to_le_bytes());
+        assert_eq!(&planar.planes[1].data[..4], &[(512u16 << 6).to_le_bytes(), (512u16 << 6).to_le_bytes()].concat()[..]);
+        assert_eq!(planar.format.read_sample(planar.planes[0].row(1), 1), 940);
+    }
+
+    #[test]
+    fn p010_keeps_10_bit_precision() {
+        // A gray of 513 / 1023 is 503.28 in 10 bit video range luma, but would be 504 if it went through 8 bits first
+        let gray = 513u32;
+        let bitmap = FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+            data: vec![gray | (gray << 10) | (gray << 20) | (3 << 30); 4].into_boxed_slice(),
+            width: 2,
+            height: 2,
+        });
+        let planar = bitmap.to_planar(FrameBitmapPlanarFormat::P010, 1).unwrap();
+        assert_eq!(planar.format.read_sample(planar.planes[0].row(0), 0), 503);
+        let FrameBitmap::RgbaUnormPacked1010102(back) = planar.to_bitmap(FrameBitmapFormat::RgbaUnormPacked1010102).unwrap() else {
+            panic!("expected a packed 10 bit bitmap");
+        };
+        for component in 0..3 {
+            let value = (back.data[0] >> (component * 10)) & 0x3FF;
+            assert!(value.abs_diff(gray) <= 1, "component {} came back as {}", component, value);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+
+use crate::prelude::VideoFrame;
+
+use super::{FrameBitmap, FrameBitmapView, VideoFrameBitmap, VideoFrameBitmapError};
+
+/// A pool of frame bitmaps which recycles their buffers, so that steady-state capture loops don't allocate
+///
+/// Bitmaps taken from the pool return to it when dropped. Once the pool holds a bitmap of each frame's
+/// format and size, copying further frames into pooled bitmaps does no heap allocation. On Windows, frames
+/// are read through a staging texture which the capture stream keeps between frames, so no textures are
+/// created either unless the frame size or format changes.
+#[derive(Clone)]
+pub struct FrameBitmapPool {
+    bitmaps: Arc<Mutex<Vec<FrameBitmap>>>,
+    capacity: usize,
+}
+
+impl FrameBitmapPool {
+    /// Create a pool which keeps up to `capacity` unused bitmaps for reuse
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            bitmaps: Arc::new(Mutex::new(Vec::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    /// The maximum number of unused bitmaps this pool keeps
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of unused bitmaps currently in the pool
+    pub fn available(&self) -> usize {
+        self.bitmaps.lock().len()
+    }
+
+    /// Drop all unused bitmaps in the pool, freeing their memory
+    pub fn clear(&self) {
+        self.bitmaps.lock().clear();
+    }
+
+    /// Create a bitmap image from a frame, reusing a bitmap from the pool if possible
+    pub fn get_bitmap(&self, frame: &VideoFrame) -> Result<PooledFrameBitmap, VideoFrameBitmapError> {
+        Ok(self.copy_view(&frame.lock_bitmap()?))
+    }
+
+    /// Copy a locked frame bitmap view into a bitmap, reusing a bitmap from the pool if possible
+    pub fn copy_view(&self, view: &FrameBitmapView<'_>) -> PooledFrameBitmap {
+        let bitmap = {
+            let mut bitmaps = self.bitmaps.lock();
+            // Prefer a bitmap which already matches, so its buffers can be reused as they are
+            let matching = bitmaps.iter().position(|bitmap| {
+                bitmap.format() == view.format() && bitmap.width() == view.width() && bitmap.height() == view.height()
+            });
+            match matching {
+                Some(index) => Some(bitmaps.swap_remove(index)),
+                None => bitmaps.pop(),
+            }
+        };
+        let bitmap = match bitmap {
+            Some(mut bitmap) => {
+                view.copy_into(&mut bitmap);
+                bitmap
+            },
+            None => view.to_bitmap(),
+        };
+        PooledFrameBitmap {
+            bitmap: Some(bitmap),
+            pool: self.clone(),
+        }
+    }
+
+    // Return a bitmap to the pool, or drop it if the pool is full
+    fn recycle(&self, bitmap: FrameBitmap) {
+        let mut bitmaps = self.bitmaps.lock();
+        if bitmaps.len() < self.capacity {
+            bitmaps.push(bitmap);
+        }
+    }
+}
+
+/// A bitmap taken from a `FrameBitmapPool`, which returns to the pool when dropped
+pub struct PooledFrameBitmap {
+    bitmap: Option<FrameBitmap>,
+    pool: FrameBitmapPool,
+}
+
+impl PooledFrameBitmap {
+    /// Take ownership of the bitmap, so that it isn't returned to the pool
+    pub fn into_bitmap(mut self) -> FrameBitmap {
+        self.bitmap.take().expect("A pooled bitmap is only taken when it's consumed")
+    }
+}
+
+impl Deref for PooledFrameBitmap {
+    type Target = FrameBitmap;
+
+    fn deref(&self) -> &Self::Target {
+        self.bitmap.as_ref().expect("A pooled bitmap is only taken when it's consumed")
+    }
+}
+
+impl DerefMut for PooledFrameBitmap {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.bitmap.as_mut().expect("A pooled bitmap is only taken when it's consumed")
+    }
+}
+
+impl Drop for PooledFrameBitmap {
+    fn drop(&mut self) {
+        if let Some(bitmap) = self.bitmap.take() {
+            self.pool.recycle(bitmap);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{FrameBitmapFormat, FrameBitmapLock, FrameBitmapPlaneDesc};
+
+    // A view of tightly packed 8 bit RGBA or BGRA pixels
+    fn view(data: &[u8], format: FrameBitmapFormat, width: usize, height: usize) -> FrameBitmapView<'_> {
+        assert_eq!(data.len(), width * height * 4);
+        let plane = FrameBitmapPlaneDesc {
+            data: data.as_ptr(),
+            width,
+            height,
+            bytes_per_row: width * 4,
+            bytes_per_pixel: 4,
+        };
+        unsafe { FrameBitmapView::new(format, width, height, [Some(plane), None], FrameBitmapLock::Unlocked) }
+    }
+
+    fn data_ptr(bitmap: &FrameBitmap) -> *const [u8; 4] {
+        match bitmap {
+            FrameBitmap::RgbaUnorm8x4(bitmap) => bitmap.data.as_ptr(),
+            FrameBitmap::BgraUnorm8x4(bitmap) => bitmap.data.as_ptr(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn pixels(bitmap: &FrameBitmap) -> Vec<[u8; 4]> {
+        match bitmap {
+            FrameBitmap::RgbaUnorm8x4(bitmap) => bitmap.data.to_vec(),
+            FrameBitmap::BgraUnorm8x4(bitmap) => bitmap.data.to_vec(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn released_bitmaps_are_reused() {
+        let pool = FrameBitmapPool::new(2);
+        let first_data = [1u8; 16];
+        let bitmap = pool.copy_view(&view(&first_data, FrameBitmapFormat::RgbaUnorm8x4, 2, 2));
+        let pointer = data_ptr(&bitmap);
+        assert_eq!(pool.available(), 0);
+        drop(bitmap);
+        assert_eq!(pool.available(), 1);
+
+        let second_data = [2u8; 16];
+        let bitmap = pool.copy_view(&view(&second_data, FrameBitmapFormat::RgbaUnorm8x4, 2, 2));
+        assert_eq!(data_ptr(&bitmap), pointer);
+        assert_eq!(pixels(&bitmap), vec![[2; 4]; 4]);
+        assert_eq!(pool.available(), 0);
+
+        // Bitmaps taken out of the pool don't return to it
+        let _ = bitmap.into_bitmap();
+        assert_eq!(pool.available(), 0);
+    }
+
+    #[test]
+    fn mismatched_bitmaps_are_replaced() {
+        let pool = FrameBitmapPool::new(2);
+        let rgba_data = [1u8; 16];
+        let rgba = pool.copy_view(&view(&rgba_data, FrameBitmapFormat::RgbaUnorm8x4, 2, 2));
+        let bgra_data = [2u8; 12];
+        let bgra = pool.copy_view(&view(&bgra_data, FrameBitmapFormat::BgraUnorm8x4, 3, 1));
+        let (rgba_pointer, bgra_pointer) = (data_ptr(&rgba), data_ptr(&bgra));
+        drop((rgba, bgra));
+        assert_eq!(pool.available(), 2);
+
+        // A matching bitmap is preferred over the most recently released one
+        let rgba = pool.copy_view(&view(&rgba_data, FrameBitmapFormat::RgbaUnorm8x4, 2, 2));
+        assert_eq!(data_ptr(&rgba), rgba_pointer);
+
+        // A different size or format gets a new bitmap, in place of the unmatched one
+        let wide_data = [3u8; 16];
+        let wide = pool.copy_view(&view(&wide_data, FrameBitmapFormat::BgraUnorm8x4, 4, 1));
+        assert_ne!(data_ptr(&wide), bgra_pointer);
+        assert_eq!((wide.format(), wide.width(), wide.height()), (FrameBitmapFormat::BgraUnorm8x4, 4, 1));
+        assert_eq!(pixels(&wide), vec![[3; 4]; 4]);
+        assert_eq!(pool.available(), 0);
+        let empty = pool.copy_view(&view(&rgba_data, FrameBitmapFormat::RgbaUnorm8x4, 2, 2));
+        assert_ne!(data_ptr(&empty), rgba_pointer);
+    }
+
+    #[test]
+    fn full_pool_drops_released_bitmaps() {
+        let pool = FrameBitmapPool::new(1);
+        let data = [0u8; 4];
+        let bitmaps = [(); 3].map(|_| pool.copy_view(&view(&data, FrameBitmapFormat::RgbaUnorm8x4, 1, 1)));
+        drop(bitmaps);
+        assert_eq!(pool.available(), 1);
+        pool.clear();
+        assert_eq!(pool.available(), 0);
+    }
+
+    #[test]
+    fn copying_into_a_matching_bitmap_keeps_its_buffer() {
+        // `get_bitmap_into` on a frame copies its locked view into the caller's bitmap like this
+        let first_data: Vec<u8> = (0..24).collect();
+        let mut bitmap = view(&first_data, FrameBitmapFormat::RgbaUnorm8x4, 3, 2).to_bitmap();
+        let pointer = data_ptr(&bitmap);
+        let second_data: Vec<u8> = (100..124).collect();
+        view(&second_data, FrameBitmapFormat::RgbaUnorm8x4, 3, 2).copy_into(&mut bitmap);
+        assert_eq!(data_ptr(&bitmap), pointer);
+        assert_eq!(pixels(&bitmap)[5], [120, 121, 122, 123]);
+    }
+}
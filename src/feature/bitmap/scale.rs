@@ -0,0 +1,342 @@
+use half::f16;
+
+use super::convert::unpack_1010102;
+use super::{FrameBitmap, FrameBitmapBgraUnorm8x4, FrameBitmapError, FrameBitmapRgbaF16x4, FrameBitmapRgbaUnorm8x4, FrameBitmapRgbaUnormPacked1010102, FrameBitmapYCbCr};
+
+/// The filter used to resample a `FrameBitmap`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FrameBitmapScaleFilter {
+    /// Take the nearest source pixel - fast and exact, but blocky and prone to aliasing
+    Nearest,
+    /// Interpolate between the nearest two source pixels in each direction - smooth when enlarging, but aliases when shrinking by more than half
+    Bilinear,
+    /// Average the source pixels covered by each output pixel - the best choice for shrinking, such as making thumbnails
+    Area,
+    /// A three lobe Lanczos filter - the sharpest choice, for both enlarging and shrinking, at the highest cost
+    Lanczos3,
+}
+
+impl FrameBitmap {
+    /// Resample the bitmap to a new size, in the same pixel format
+    ///
+    /// Pixels with alpha are filtered with premultiplied alpha, so transparent pixels don't bleed their color into their neighbours.
+    /// The planes of YCbCr bitmaps are scaled separately, keeping the chroma subsampling of the source (for example, 4:2:0 stays 4:2:0).
+    /// Results are computed entirely on the CPU, so they're identical on every platform.
+    pub fn scale(&self, width: usize, height: usize, filter: FrameBitmapScaleFilter) -> Result<FrameBitmap, FrameBitmapError> {
+        self.validate()?;
+        if width == 0 || height == 0 {
+            return Err(FrameBitmapError::Other(format!("Can't scale a bitmap to an empty size of {}x{}", width, height)));
+        }
+        if self.width() == 0 || self.height() == 0 {
+            return Err(FrameBitmapError::Other("Can't scale an empty bitmap".to_string()));
+        }
+        let bitmap = match self {
+            Self::BgraUnorm8x4(bitmap) => Self::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 {
+                data: scale_pixels::<_, 4>(&bitmap.data, bitmap.width, bitmap.height, width, height, filter, true, unorm8x4_to_f32, f32_to_unorm8x4),
+                width,
+                height,
+            }),
+            Self::RgbaUnorm8x4(bitmap) => Self::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 {
+                data: scale_pixels::<_, 4>(&bitmap.data, bitmap.width, bitmap.height, width, height, filter, true, unorm8x4_to_f32, f32_to_unorm8x4),
+                width,
+                height,
+            }),
+            Self::RgbaUnormPacked1010102(bitmap) => Self::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+                data: scale_pixels::<_, 4>(&bitmap.data, bitmap.width, bitmap.height, width, height, filter, true, packed_1010102_to_f32, f32_to_packed_1010102),
+                width,
+                height,
+            }),
+            Self::RgbaF16x4(bitmap) => Self::RgbaF16x4(FrameBitmapRgbaF16x4 {
+                data: scale_pixels::<_, 4>(&bitmap.data, bitmap.width, bitmap.height, width, height, filter, true, f16x4_to_f32, f32_to_f16x4),
+                width,
+                height,
+            }),
+            Self::YCbCr(bitmap) => {
+                // Keep the chroma subsampling of the source, rounding up like the capture formats do
+                let (subsampling_x, subsampling_y) = bitmap.chroma_subsampling();
+                let (chroma_width, chroma_height) = (width.div_ceil(subsampling_x), height.div_ceil(subsampling_y));
+                Self::YCbCr(FrameBitmapYCbCr {
+                    luma_data: scale_pixels::<_, 1>(&bitmap.luma_data, bitmap.luma_width, bitmap.luma_height, width, height, filter, false, |luma| [luma as f32], |[luma]| unorm8_from_f32(luma)),
+                    luma_width: width,
+                    luma_height: height,
+                    chroma_data: scale_pixels::<_, 2>(&bitmap.chroma_data, bitmap.chroma_width, bitmap.chroma_height, chroma_width, chroma_height, filter, false, |chroma| chroma.map(|value| value as f32), |chroma| chroma.map(unorm8_from_f32)),
+                    chroma_width,
+                    chroma_height,
+                    range: bitmap.range,
+                    matrix: bitmap.matrix,
+                })
+            },
+        };
+        Ok(bitmap)
+    }
+}
+
+// Channel values are kept in their native scale, except alpha which is always normalized to [0, 1] for premultiplication
+
+fn unorm8x4_to_f32(pixel: [u8; 4]) -> [f32; 4] {
+    [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32, pixel[3] as f32 / 255.0]
+}
+
+fn f32_to_unorm8x4(pixel: [f32; 4]) -> [u8; 4] {
+    [unorm8_from_f32(pixel[0]), unorm8_from_f32(pixel[1]), unorm8_from_f32(pixel[2]), unorm8_from_f32(pixel[3] * 255.0)]
+}
+
+fn packed_1010102_to_f32(pixel: u32) -> [f32; 4] {
+    let [r, g, b, a] = unpack_1010102(pixel);
+    [r as f32, g as f32, b as f32, a as f32 / 3.0]
+}
+
+fn f32_to_packed_1010102(pixel: [f32; 4]) -> u32 {
+    let to_10_bits = |value: f32| (value + 0.5).clamp(0.0, 1023.0) as u32;
+    let alpha = (pixel[3] * 3.0 + 0.5).clamp(0.0, 3.0) as u32;
+    to_10_bits(pixel[0]) | (to_10_bits(pixel[1]) << 10) | (to_10_bits(pixel[2]) << 20) | (alpha << 30)
+}
+
+fn f16x4_to_f32(pixel: [f16; 4]) -> [f32; 4] {
+    pixel.map(f16::to_f32)
+}
+
+fn f32_to_f16x4(pixel: [f32; 4]) -> [f16; 4] {
+    // Color is left unclamped to keep HDR and wide gamut values
+    [f16::from_f32(pixel[0]), f16::from_f32(pixel[1]), f16::from_f32(pixel[2]), f16::from_f32(pixel[3].clamp(0.0, 1.0))]
+}
+
+fn unorm8_from_f32(value: f32) -> u8 {
+    (value + 0.5).clamp(0.0, 255.0) as u8
+}
+
+// The source pixels contributing to one output pixel, and their normalized weights
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+// Find the contributions to each output pixel along one axis, with pixel centers aligned between source and output
+fn contributions(source_size: usize, output_size: usize, filter: FrameBitmapScaleFilter) -> Vec<Contribution> {
+    let scale = source_size as f64 / output_size as f64;
+    (0..output_size).map(|output_index| {
+        let (start, mut weights) = match filter {
+            FrameBitmapScaleFilter::Nearest => {
+                let index = (((output_index as f64 + 0.5) * scale) as usize).min(source_size - 1);
+                (index, vec![1.0])
+            },
+            FrameBitmapScaleFilter::Area => {
+                let low = output_index as f64 * scale;
+                let high = ((output_index + 1) as f64 * scale).min(source_size as f64);
+                let start = low.floor() as usize;
+                let end = (high.ceil() as usize).clamp(start + 1, source_size);
+                let weights = (start..end).map(|index| (high.min(index as f64 + 1.0) - low.max(index as f64)).max(0.0) as f32).collect();
+                (start, weights)
+            },
+            FrameBitmapScaleFilter::Bilinear | FrameBitmapScaleFilter::Lanczos3 => {
+                let (radius, filter_scale): (f64, f64) = match filter {
+                    FrameBitmapScaleFilter::Bilinear => (1.0, 1.0),
+                    _ => (3.0, scale.max(1.0)),
+                };
+                let center = (output_index as f64 + 0.5) * scale;
+                let start = (center - radius * filter_scale).floor().max(0.0) as usize;
+                let end = ((center + radius * filter_scale).ceil() as usize).min(source_size);
+                let weights = (start..end).map(|index| {
+                    let x = (index as f64 + 0.5 - center) / filter_scale;
+                    match filter {
+                        FrameBitmapScaleFilter::Bilinear => (1.0 - x.abs()).max(0.0) as f32,
+                        _ => lanczos3(x) as f32,
+                    }
+                }).collect();
+                (start, weights)
+            },
+        };
+        let total: f32 = weights.iter().sum();
+        if total.abs() > f32::EPSILON {
+            weights.iter_mut().for_each(|weight| *weight /= total);
+        } else {
+            // Only possible when a single source pixel lies exactly on the edge of the filter
+            let nearest = (((output_index as f64 + 0.5) * scale) as usize).min(source_size - 1);
+            return Contribution { start: nearest, weights: vec![1.0] };
+        }
+        Contribution { start, weights }
+    }).collect()
+}
+
+fn lanczos3(x: f64) -> f64 {
+    if x.abs() < 1e-8 {
+        1.0
+    } else if x.abs() < 3.0 {
+        let pi_x = std::f64::consts::PI * x;
+        3.0 * pi_x.sin() * (pi_x / 3.0).sin() / (pi_x * pi_x)
+    } else {
+        0.0
+    }
+}
+
+// Resample a plane of pixels with C channels, going through f32 for every filter but nearest
+#[allow(clippy::too_many_arguments)]
+fn scale_pixels<T: Copy, const C: usize>(
+    data: &[T],
+    source_width: usize,
+    source_height: usize,
+    width: usize,
+    height: usize,
+    filter: FrameBitmapScaleFilter,
+    premultiply: bool,
+    to_f32: impl Fn(T) -> [f32; C],
+    from_f32: impl Fn([f32; C]) -> T,
+) -> Box<[T]> {
+    let horizontal = contributions(source_width, width, filter);
+    let vertical = contributions(source_height, height, filter);
+    if filter == FrameBitmapScaleFilter::Nearest {
+        let mut output = Vec::with_capacity(width * height);
+        for row in &vertical {
+            let source_row = &data[row.start * source_width..(row.start + 1) * source_width];
+            output.extend(horizontal.iter().map(|column| source_row[column.start]));
+        }
+        return output.into_boxed_slice();
+    }
+
+    // Rows are filtered horizontally as the vertical pass first needs them, and kept in a ring just big enough for the vertical filter,
+    // since the rows each output row needs only ever move forward
+    let ring_size = vertical.iter().map(|row| row.weights.len()).max().unwrap_or(1);
+    let mut ring = vec![[0.0f32; C]; ring_size * width];
+    let mut ring_rows = vec![usize::MAX; ring_size];
+    let mut source_row = vec![[0.0f32; C]; source_width];
+    let mut output = Vec::with_capacity(width * height);
+    let mut output_row = vec![[0.0f32; C]; width];
+    for row in &vertical {
+        for source_y in row.start..row.start + row.weights.len() {
+            let slot = source_y % ring_size;
+            if ring_rows[slot] == source_y {
+                continue;
+            }
+            for (pixel, source_pixel) in source_row.iter_mut().zip(data[source_y * source_width..(source_y + 1) * source_width].iter()) {
+                *pixel = to_f32(*source_pixel);
+                if premultiply {
+                    let alpha = pixel[C - 1];
+                    pixel[..C - 1].iter_mut().for_each(|channel| *channel *= alpha);
+                }
+            }
+            for (pixel, column) in ring[slot * width..(slot + 1) * width].iter_mut().zip(horizontal.iter()) {
+                let mut sum = [0.0f32; C];
+                for (weight, source_pixel) in column.weights.iter().zip(source_row[column.start..].iter()) {
+                    for (sum_channel, channel) in sum.iter_mut().zip(source_pixel.iter()) {
+                        *sum_channel += weight * channel;
+                    }
+                }
+                *pixel = sum;
+            }
+            ring_rows[slot] = source_y;
+        }
+
+        output_row.fill([0.0; C]);
+        for (source_y, weight) in (row.start..).zip(row.weights.iter()) {
+            let slot = source_y % ring_size;
+            for (pixel, filtered_pixel) in output_row.iter_mut().zip(ring[slot * width..(slot + 1) * width].iter()) {
+                for (channel, filtered_channel) in pixel.iter_mut().zip(filtered_pixel.iter()) {
+                    *channel += weight * filtered_channel;
+                }
+            }
+        }
+        // Un-premultiply into the output
+        output.extend(output_row.iter().map(|pixel| {
+            let mut pixel = *pixel;
+            if premultiply {
+                let alpha = pixel[C - 1];
+                if alpha > 0.0 {
+                    pixel[..C - 1].iter_mut().for_each(|channel| *channel /= alpha);
+                }
+            }
+            from_f32(pixel)
+        }));
+    }
+    output.into_boxed_slice()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{VideoRange, YCbCrMatrix};
+
+    const FILTERS: [FrameBitmapScaleFilter; 4] = [FrameBitmapScaleFilter::Nearest, FrameBitmapScaleFilter::Bilinear, FrameBitmapScaleFilter::Area, FrameBitmapScaleFilter::Lanczos3];
+
+    fn gray(width: usize, height: usize, values: &[u8]) -> FrameBitmap {
+        FrameBitmap::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 {
+            data: values.iter().map(|value| [*value, *value, *value, 255]).collect(),
+            width,
+            height,
+        })
+    }
+
+    fn rgba_data(bitmap: &FrameBitmap) -> Vec<[u8; 4]> {
+        match bitmap {
+            FrameBitmap::RgbaUnorm8x4(bitmap) => bitmap.data.to_vec(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn gray_values(bitmap: &FrameBitmap) -> Vec<u8> {
+        rgba_data(bitmap).into_iter().map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn output_has_the_requested_size() {
+        let source = gray(4, 3, &[0; 12]);
+        for filter in FILTERS {
+            for (width, height) in [(7, 5), (2, 1), (1, 9), (4, 3)] {
+                let scaled = source.scale(width, height, filter).unwrap();
+                assert_eq!((scaled.width(), scaled.height()), (width, height));
+                assert_eq!(rgba_data(&scaled).len(), width * height);
+            }
+        }
+    }
+
+    #[test]
+    fn same_size_is_identity() {
+        let mut source = gray(3, 2, &[0, 40, 80, 120, 200, 255]);
+        if let FrameBitmap::RgbaUnorm8x4(bitmap) = &mut source {
+            bitmap.data[1] = [10, 20, 30, 128];
+        }
+        for filter in FILTERS {
+            assert_eq!(rgba_data(&source.scale(3, 2, filter).unwrap()), rgba_data(&source), "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn nearest_duplicates_pixels() {
+        let scaled = gray(2, 2, &[10, 20, 30, 40]).scale(4, 4, FrameBitmapScaleFilter::Nearest).unwrap();
+        assert_eq!(gray_values(&scaled), [10, 10, 20, 20, 10, 10, 20, 20, 30, 30, 40, 40, 30, 30, 40, 40]);
+        let scaled = gray(4, 1, &[10, 20, 30, 40]).scale(2, 1, FrameBitmapScaleFilter::Nearest).unwrap();
+        assert_eq!(gray_values(&scaled), [20, 40]);
+    }
+
+    #[test]
+    fn bilinear_interpolates_between_pixel_centers() {
+        let scaled = gray(2, 1, &[0, 100]).scale(4, 1, FrameBitmapScaleFilter::Bilinear).unwrap();
+        assert_eq!(gray_values(&scaled), [0, 25, 75, 100]);
+    }
+
+    #[test]
+    fn odd_sizes() {
+        // Area averages the whole 3x3 block
+        let scaled = gray(3, 3, &[0, 10, 20, 30, 40, 50, 60, 70, 80]).scale(1, 1, FrameBitmapScaleFilter::Area).unwrap();
+        assert_eq!(gray_values(&scaled), [40]);
+        let scaled = gray(1, 1, &[99]).scale(3, 5, FrameBitmapScaleFilter::Lanczos3).unwrap();
+        assert_eq!(gray_values(&scaled), [99; 15]);
+
+        // Chroma of 4:2:0 bitmaps rounds up for odd sizes
+        let source = FrameBitmap::YCbCr(FrameBitmapYCbCr::new(vec![16; 16], (4, 4), vec![[128, 128]; 4], (2, 2), YCbCrMatrix::Bt709, VideoRange::Video).unwrap());
+        match source.scale(5, 3, FrameBitmapScaleFilter::Bilinear).unwrap() {
+            FrameBitmap::YCbCr(bitmap) => {
+                assert_eq!((bitmap.luma_width, bitmap.luma_height, bitmap.chroma_width, bitmap.chroma_height), (5, 3, 3, 2));
+                assert!(bitmap.luma_data.iter().all(|luma| *luma == 16));
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn empty_sizes_are_rejected() {
+        let source = gray(2, 2, &[0; 4]);
+        assert!(source.scale(0, 2, FrameBitmapScaleFilter::Nearest).is_err());
+        assert!(source.scale(2, 0, FrameBitmapScaleFilter::Bilinear).is_err());
+        assert!(gray(0, 0, &[]).scale(2, 2, FrameBitmapScaleFilter::Area).is_err());
+    }
+}
@@ -0,0 +1,598 @@
+// Row kernels for the hot bitmap conversions, with SSE2, AVX2 and NEON implementations chosen at runtime.
+//
+// Every SIMD kernel does the same arithmetic as the scalar code in the same order, so results are identical
+// whichever instruction set is used. Kernels return how many pixels they handled, and the scalar code finishes the row.
+//
+// Only the red/blue swizzle, packed 10 bit to 8 bit and 4:2:0 YCbCr to 8 bit RGB conversions are vectorized. RGB to YCbCr
+// and the F16 conversions stay scalar - they're rarely on the capture path, and the transfer function doesn't vectorize cheaply.
+
+use std::sync::OnceLock;
+
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+#[cfg(target_arch = "aarch64")]
+use std::arch::aarch64::*;
+
+use super::convert::{ten_bits_to_u8, two_bits_to_u8, unpack_1010102, YCbCrToUnorm8};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum SimdLevel {
+    Scalar,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Sse2,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Avx2,
+    #[cfg(target_arch = "aarch64")]
+    Neon,
+}
+
+fn simd_level() -> SimdLevel {
+    static LEVEL: OnceLock<SimdLevel> = OnceLock::new();
+    *LEVEL.get_or_init(|| {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return SimdLevel::Avx2;
+            }
+            if is_x86_feature_detected!("sse2") {
+                return SimdLevel::Sse2;
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return SimdLevel::Neon;
+            }
+        }
+        SimdLevel::Scalar
+    })
+}
+
+#[inline]
+fn order<const BGRA: bool>(r: u8, g: u8, b: u8, a: u8) -> [u8; 4] {
+    if BGRA { [b, g, r, a] } else { [r, g, b, a] }
+}
+
+/// Swap the red and blue channels of 8 bit four channel pixels, converting between BGRA and RGBA
+pub(crate) fn swizzle_red_blue(input: &[[u8; 4]], output: &mut [[u8; 4]]) {
+    let count = input.len().min(output.len());
+    let (input, output) = (&input[..count], &mut output[..count]);
+    let done = match simd_level() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx2 => unsafe { swizzle_red_blue_avx2(input, output) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Sse2 => unsafe { swizzle_red_blue_sse2(input, output) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => unsafe { swizzle_red_blue_neon(input, output) },
+        SimdLevel::Scalar => 0,
+    };
+    for (pixel, input_pixel) in output[done..].iter_mut().zip(input[done..].iter()) {
+        *pixel = [input_pixel[2], input_pixel[1], input_pixel[0], input_pixel[3]];
+    }
+}
+
+/// Convert a row of YCbCr with horizontally subsampled chroma (one chroma sample for every two luma samples) to 8 bit BGRA or RGBA
+pub(crate) fn ycbcr_420_to_unorm8x4<const BGRA: bool>(luma: &[u8], chroma: &[[u8; 2]], transform: &YCbCrToUnorm8, output: &mut [[u8; 4]]) {
+    let count = luma.len().min(output.len()).min(chroma.len() * 2);
+    let (luma, output) = (&luma[..count], &mut output[..count]);
+    let done = match simd_level() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx2 => unsafe { ycbcr_420_to_unorm8x4_avx2::<BGRA>(luma, chroma, transform, output) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Sse2 => unsafe { ycbcr_420_to_unorm8x4_sse2::<BGRA>(luma, chroma, transform, output) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => unsafe { ycbcr_420_to_unorm8x4_neon::<BGRA>(luma, chroma, transform, output) },
+        SimdLevel::Scalar => 0,
+    };
+    for x in done..count {
+        let [cb, cr] = chroma[x / 2];
+        let [r, g, b] = transform.pixel(luma[x], cb, cr);
+        output[x] = order::<BGRA>(r, g, b, 255);
+    }
+}
+
+/// Convert a row of packed 10 bit RGBA to 8 bit BGRA or RGBA
+pub(crate) fn packed_1010102_to_unorm8x4<const BGRA: bool>(input: &[u32], output: &mut [[u8; 4]]) {
+    let count = input.len().min(output.len());
+    let (input, output) = (&input[..count], &mut output[..count]);
+    let done = match simd_level() {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Avx2 => unsafe { packed_1010102_to_unorm8x4_avx2::<BGRA>(input, output) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        SimdLevel::Sse2 => unsafe { packed_1010102_to_unorm8x4_sse2::<BGRA>(input, output) },
+        #[cfg(target_arch = "aarch64")]
+        SimdLevel::Neon => unsafe { packed_1010102_to_unorm8x4_neon::<BGRA>(input, output) },
+        SimdLevel::Scalar => 0,
+    };
+    for (pixel, input_pixel) in output[done..].iter_mut().zip(input[done..].iter()) {
+        let [r, g, b, a] = unpack_1010102(*input_pixel);
+        *pixel = order::<BGRA>(ten_bits_to_u8(r), ten_bits_to_u8(g), ten_bits_to_u8(b), two_bits_to_u8(a));
+    }
+}
+
+// x86
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn swizzle_red_blue_sse2(input: &[[u8; 4]], output: &mut [[u8; 4]]) -> usize {
+    let green_alpha_mask = _mm_set1_epi32(0xFF00FF00u32 as i32);
+    let low_mask = _mm_set1_epi32(0xFF);
+    let mut x = 0;
+    while x + 4 <= input.len() {
+        let pixels = _mm_loadu_si128(input.as_ptr().add(x) as *const __m128i);
+        let green_alpha = _mm_and_si128(pixels, green_alpha_mask);
+        let low = _mm_and_si128(_mm_srli_epi32(pixels, 16), low_mask);
+        let high = _mm_slli_epi32(_mm_and_si128(pixels, low_mask), 16);
+        let swizzled = _mm_or_si128(green_alpha, _mm_or_si128(low, high));
+        _mm_storeu_si128(output.as_mut_ptr().add(x) as *mut __m128i, swizzled);
+        x += 4;
+    }
+    x
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn swizzle_red_blue_avx2(input: &[[u8; 4]], output: &mut [[u8; 4]]) -> usize {
+    let shuffle = _mm256_setr_epi8(
+        2, 1, 0, 3, 6, 5, 4, 7, 10, 9, 8, 11, 14, 13, 12, 15,
+        2, 1, 0, 3, 6, 5, 4, 7, 10, 9, 8, 11, 14, 13, 12, 15,
+    );
+    let mut x = 0;
+    while x + 8 <= input.len() {
+        let pixels = _mm256_loadu_si256(input.as_ptr().add(x) as *const __m256i);
+        _mm256_storeu_si256(output.as_mut_ptr().add(x) as *mut __m256i, _mm256_shuffle_epi8(pixels, shuffle));
+        x += 8;
+    }
+    x
+}
+
+// Round, clamp and truncate four floats in [0, 255] like `unorm_to_u8_with`
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn unorm8_lanes_sse2(value: __m128) -> __m128i {
+    let value = _mm_add_ps(value, _mm_set1_ps(0.5));
+    _mm_cvttps_epi32(_mm_min_ps(_mm_max_ps(value, _mm_setzero_ps()), _mm_set1_ps(255.0)))
+}
+
+// Pack four lanes of each channel (one byte per 32 bit lane) into four pixels
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn pack_pixels_sse2<const BGRA: bool>(r: __m128i, g: __m128i, b: __m128i, a: __m128i) -> __m128i {
+    let (first, third) = if BGRA { (b, r) } else { (r, b) };
+    _mm_or_si128(
+        _mm_or_si128(first, _mm_slli_epi32(g, 8)),
+        _mm_or_si128(_mm_slli_epi32(third, 16), _mm_slli_epi32(a, 24)),
+    )
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn ycbcr_420_to_unorm8x4_sse2<const BGRA: bool>(luma: &[u8], chroma: &[[u8; 2]], transform: &YCbCrToUnorm8, output: &mut [[u8; 4]]) -> usize {
+    let zero = _mm_setzero_si128();
+    let alpha = _mm_set1_epi32(0xFF);
+    let chroma_mask = _mm_set1_epi32(0xFFFF);
+    let chroma_offset = _mm_set1_ps(128.0);
+    let y_offset = _mm_set1_ps(transform.y_offset);
+    let y_mul = _mm_set1_ps(transform.y_mul);
+    let r_cr = _mm_set1_ps(transform.r_cr);
+    let g_cb = _mm_set1_ps(transform.g_cb);
+    let g_cr = _mm_set1_ps(transform.g_cr);
+    let b_cb = _mm_set1_ps(transform.b_cb);
+    let mut x = 0;
+    while x + 8 <= luma.len() {
+        let luma16 = _mm_unpacklo_epi8(_mm_loadl_epi64(luma.as_ptr().add(x) as *const __m128i), zero);
+        // Each 32 bit lane holds one Cb and Cr pair
+        let chroma16 = _mm_unpacklo_epi8(_mm_loadl_epi64(chroma.as_ptr().add(x / 2) as *const __m128i), zero);
+        let cb = _mm_sub_ps(_mm_cvtepi32_ps(_mm_and_si128(chroma16, chroma_mask)), chroma_offset);
+        let cr = _mm_sub_ps(_mm_cvtepi32_ps(_mm_srli_epi32(chroma16, 16)), chroma_offset);
+        let r_chroma = _mm_mul_ps(r_cr, cr);
+        let g_chroma = _mm_add_ps(_mm_mul_ps(g_cb, cb), _mm_mul_ps(g_cr, cr));
+        let b_chroma = _mm_mul_ps(b_cb, cb);
+        let halves = [
+            (_mm_unpacklo_epi16(luma16, zero), _mm_unpacklo_ps(r_chroma, r_chroma), _mm_unpacklo_ps(g_chroma, g_chroma), _mm_unpacklo_ps(b_chroma, b_chroma)),
+            (_mm_unpackhi_epi16(luma16, zero), _mm_unpackhi_ps(r_chroma, r_chroma), _mm_unpackhi_ps(g_chroma, g_chroma), _mm_unpackhi_ps(b_chroma, b_chroma)),
+        ];
+        for (half, (luma32, r_chroma, g_chroma, b_chroma)) in halves.into_iter().enumerate() {
+            let y = _mm_mul_ps(_mm_sub_ps(_mm_cvtepi32_ps(luma32), y_offset), y_mul);
+            let r = unorm8_lanes_sse2(_mm_add_ps(y, r_chroma));
+            let g = unorm8_lanes_sse2(_mm_add_ps(y, g_chroma));
+            let b = unorm8_lanes_sse2(_mm_add_ps(y, b_chroma));
+            let pixels = pack_pixels_sse2::<BGRA>(r, g, b, alpha);
+            _mm_storeu_si128(output.as_mut_ptr().add(x + half * 4) as *mut __m128i, pixels);
+        }
+        x += 8;
+    }
+    x
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn unorm8_lanes_avx2(value: __m256) -> __m256i {
+    let value = _mm256_add_ps(value, _mm256_set1_ps(0.5));
+    _mm256_cvttps_epi32(_mm256_min_ps(_mm256_max_ps(value, _mm256_setzero_ps()), _mm256_set1_ps(255.0)))
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn pack_pixels_avx2<const BGRA: bool>(r: __m256i, g: __m256i, b: __m256i, a: __m256i) -> __m256i {
+    let (first, third) = if BGRA { (b, r) } else { (r, b) };
+    _mm256_or_si256(
+        _mm256_or_si256(first, _mm256_slli_epi32(g, 8)),
+        _mm256_or_si256(_mm256_slli_epi32(third, 16), _mm256_slli_epi32(a, 24)),
+    )
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn ycbcr_420_to_unorm8x4_avx2<const BGRA: bool>(luma: &[u8], chroma: &[[u8; 2]], transform: &YCbCrToUnorm8, output: &mut [[u8; 4]]) -> usize {
+    let alpha = _mm256_set1_epi32(0xFF);
+    let chroma_mask = _mm256_set1_epi32(0xFF);
+    let chroma_offset = _mm256_set1_ps(128.0);
+    let y_offset = _mm256_set1_ps(transform.y_offset);
+    let y_mul = _mm256_set1_ps(transform.y_mul);
+    let r_cr = _mm256_set1_ps(transform.r_cr);
+    let g_cb = _mm256_set1_ps(transform.g_cb);
+    let g_cr = _mm256_set1_ps(transform.g_cr);
+    let b_cb = _mm256_set1_ps(transform.b_cb);
+    let mut x = 0;
+    while x + 16 <= luma.len() {
+        let luma8 = _mm_loadu_si128(luma.as_ptr().add(x) as *const __m128i);
+        // Each 32 bit lane holds one Cb and Cr pair
+        let chroma16 = _mm256_cvtepu16_epi32(_mm_loadu_si128(chroma.as_ptr().add(x / 2) as *const __m128i));
+        let cb = _mm256_sub_ps(_mm256_cvtepi32_ps(_mm256_and_si256(chroma16, chroma_mask)), chroma_offset);
+        let cr = _mm256_sub_ps(_mm256_cvtepi32_ps(_mm256_srli_epi32(chroma16, 8)), chroma_offset);
+        let r_chroma = _mm256_mul_ps(r_cr, cr);
+        let g_chroma = _mm256_add_ps(_mm256_mul_ps(g_cb, cb), _mm256_mul_ps(g_cr, cr));
+        let b_chroma = _mm256_mul_ps(b_cb, cb);
+        // Unpacking works within 128 bit lanes, so the duplicated chroma for each half is spread over both results
+        let (r_low, r_high) = (_mm256_unpacklo_ps(r_chroma, r_chroma), _mm256_unpackhi_ps(r_chroma, r_chroma));
+        let (g_low, g_high) = (_mm256_unpacklo_ps(g_chroma, g_chroma), _mm256_unpackhi_ps(g_chroma, g_chroma));
+        let (b_low, b_high) = (_mm256_unpacklo_ps(b_chroma, b_chroma), _mm256_unpackhi_ps(b_chroma, b_chroma));
+        let halves = [
+            (_mm256_cvtepu8_epi32(luma8), _mm256_permute2f128_ps(r_low, r_high, 0x20), _mm256_permute2f128_ps(g_low, g_high, 0x20), _mm256_permute2f128_ps(b_low, b_high, 0x20)),
+            (_mm256_cvtepu8_epi32(_mm_srli_si128(luma8, 8)), _mm256_permute2f128_ps(r_low, r_high, 0x31), _mm256_permute2f128_ps(g_low, g_high, 0x31), _mm256_permute2f128_ps(b_low, b_high, 0x31)),
+        ];
+        for (half, (luma32, r_chroma, g_chroma, b_chroma)) in halves.into_iter().enumerate() {
+            let y = _mm256_mul_ps(_mm256_sub_ps(_mm256_cvtepi32_ps(luma32), y_offset), y_mul);
+            let r = unorm8_lanes_avx2(_mm256_add_ps(y, r_chroma));
+            let g = unorm8_lanes_avx2(_mm256_add_ps(y, g_chroma));
+            let b = unorm8_lanes_avx2(_mm256_add_ps(y, b_chroma));
+            let pixels = pack_pixels_avx2::<BGRA>(r, g, b, alpha);
+            _mm256_storeu_si256(output.as_mut_ptr().add(x + half * 8) as *mut __m256i, pixels);
+        }
+        x += 16;
+    }
+    x
+}
+
+// Convert four 10 bit values to 8 bits like `ten_bits_to_u8`
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn ten_bit_lanes_to_unorm8_sse2(value: __m128i, scale: __m128, half: __m128) -> __m128i {
+    _mm_cvttps_epi32(_mm_add_ps(_mm_mul_ps(_mm_cvtepi32_ps(value), scale), half))
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse2")]
+unsafe fn packed_1010102_to_unorm8x4_sse2<const BGRA: bool>(input: &[u32], output: &mut [[u8; 4]]) -> usize {
+    let mask = _mm_set1_epi32(0x3FF);
+    let scale = _mm_set1_ps(255.0 / 1023.0);
+    let half = _mm_set1_ps(0.5);
+    let mut x = 0;
+    while x + 4 <= input.len() {
+        let pixels = _mm_loadu_si128(input.as_ptr().add(x) as *const __m128i);
+        let r = ten_bit_lanes_to_unorm8_sse2(_mm_and_si128(pixels, mask), scale, half);
+        let g = ten_bit_lanes_to_unorm8_sse2(_mm_and_si128(_mm_srli_epi32(pixels, 10), mask), scale, half);
+        let b = ten_bit_lanes_to_unorm8_sse2(_mm_and_si128(_mm_srli_epi32(pixels, 20), mask), scale, half);
+        let a = _mm_srli_epi32(pixels, 30);
+        let a = _mm_or_si128(_mm_or_si128(a, _mm_slli_epi32(a, 2)), _mm_or_si128(_mm_slli_epi32(a, 4), _mm_slli_epi32(a, 6)));
+        _mm_storeu_si128(output.as_mut_ptr().add(x) as *mut __m128i, pack_pixels_sse2::<BGRA>(r, g, b, a));
+        x += 4;
+    }
+    x
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn ten_bit_lanes_to_unorm8_avx2(value: __m256i, scale: __m256, half: __m256) -> __m256i {
+    _mm256_cvttps_epi32(_mm256_add_ps(_mm256_mul_ps(_mm256_cvtepi32_ps(value), scale), half))
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn packed_1010102_to_unorm8x4_avx2<const BGRA: bool>(input: &[u32], output: &mut [[u8; 4]]) -> usize {
+    let mask = _mm256_set1_epi32(0x3FF);
+    let scale = _mm256_set1_ps(255.0 / 1023.0);
+    let half = _mm256_set1_ps(0.5);
+    let mut x = 0;
+    while x + 8 <= input.len() {
+        let pixels = _mm256_loadu_si256(input.as_ptr().add(x) as *const __m256i);
+        let r = ten_bit_lanes_to_unorm8_avx2(_mm256_and_si256(pixels, mask), scale, half);
+        let g = ten_bit_lanes_to_unorm8_avx2(_mm256_and_si256(_mm256_srli_epi32(pixels, 10), mask), scale, half);
+        let b = ten_bit_lanes_to_unorm8_avx2(_mm256_and_si256(_mm256_srli_epi32(pixels, 20), mask), scale, half);
+        let a = _mm256_srli_epi32(pixels, 30);
+        let a = _mm256_or_si256(_mm256_or_si256(a, _mm256_slli_epi32(a, 2)), _mm256_or_si256(_mm256_slli_epi32(a, 4), _mm256_slli_epi32(a, 6)));
+        _mm256_storeu_si256(output.as_mut_ptr().add(x) as *mut __m256i, pack_pixels_avx2::<BGRA>(r, g, b, a));
+        x += 8;
+    }
+    x
+}
+
+// aarch64
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn swizzle_red_blue_neon(input: &[[u8; 4]], output: &mut [[u8; 4]]) -> usize {
+    let mut x = 0;
+    while x + 16 <= input.len() {
+        let pixels = vld4q_u8(input.as_ptr().add(x) as *const u8);
+        let swizzled = uint8x16x4_t(pixels.2, pixels.1, pixels.0, pixels.3);
+        vst4q_u8(output.as_mut_ptr().add(x) as *mut u8, swizzled);
+        x += 16;
+    }
+    x
+}
+
+// Round, clamp and truncate four floats in [0, 255] like `unorm_to_u8_with`
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn unorm8_lanes_neon(value: float32x4_t) -> uint32x4_t {
+    let value = vaddq_f32(value, vdupq_n_f32(0.5));
+    vcvtq_u32_f32(vminq_f32(vmaxq_f32(value, vdupq_n_f32(0.0)), vdupq_n_f32(255.0)))
+}
+
+// Narrow two sets of four 32 bit lanes holding bytes into eight bytes
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn narrow_lanes_neon(low: uint32x4_t, high: uint32x4_t) -> uint8x8_t {
+    vmovn_u16(vcombine_u16(vmovn_u32(low), vmovn_u32(high)))
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn ycbcr_420_to_unorm8x4_neon<const BGRA: bool>(luma: &[u8], chroma: &[[u8; 2]], transform: &YCbCrToUnorm8, output: &mut [[u8; 4]]) -> usize {
+    let chroma_offset = vdupq_n_f32(128.0);
+    let y_offset = vdupq_n_f32(transform.y_offset);
+    let y_mul = vdupq_n_f32(transform.y_mul);
+    let r_cr = vdupq_n_f32(transform.r_cr);
+    let g_cb = vdupq_n_f32(transform.g_cb);
+    let g_cr = vdupq_n_f32(transform.g_cr);
+    let b_cb = vdupq_n_f32(transform.b_cb);
+    let alpha = vdupq_n_u8(0xFF);
+    let mut x = 0;
+    while x + 16 <= luma.len() {
+        let luma8 = vld1q_u8(luma.as_ptr().add(x));
+        let luma16 = [vmovl_u8(vget_low_u8(luma8)), vmovl_u8(vget_high_u8(luma8))];
+        let chroma8 = vld2_u8(chroma.as_ptr().add(x / 2) as *const u8);
+        let (cb16, cr16) = (vmovl_u8(chroma8.0), vmovl_u8(chroma8.1));
+        let mut channels = [[vdup_n_u8(0); 2]; 3];
+        for (half, luma16) in luma16.into_iter().enumerate() {
+            let (cb16, cr16) = if half == 0 { (vget_low_u16(cb16), vget_low_u16(cr16)) } else { (vget_high_u16(cb16), vget_high_u16(cr16)) };
+            let cb = vsubq_f32(vcvtq_f32_u32(vmovl_u16(cb16)), chroma_offset);
+            let cr = vsubq_f32(vcvtq_f32_u32(vmovl_u16(cr16)), chroma_offset);
+            let r_chroma = vmulq_f32(r_cr, cr);
+            let g_chroma = vaddq_f32(vmulq_f32(g_cb, cb), vmulq_f32(g_cr, cr));
+            let b_chroma = vmulq_f32(b_cb, cb);
+            let mut lanes = [[vdupq_n_u32(0); 3]; 2];
+            for (quarter, lanes) in lanes.iter_mut().enumerate() {
+                let luma32 = if quarter == 0 { vmovl_u16(vget_low_u16(luma16)) } else { vmovl_u16(vget_high_u16(luma16)) };
+                let (r_chroma, g_chroma, b_chroma) = if quarter == 0 {
+                    (vzip1q_f32(r_chroma, r_chroma), vzip1q_f32(g_chroma, g_chroma), vzip1q_f32(b_chroma, b_chroma))
+                } else {
+                    (vzip2q_f32(r_chroma, r_chroma), vzip2q_f32(g_chroma, g_chroma), vzip2q_f32(b_chroma, b_chroma))
+                };
+                let y = vmulq_f32(vsubq_f32(vcvtq_f32_u32(luma32), y_offset), y_mul);
+                *lanes = [
+                    unorm8_lanes_neon(vaddq_f32(y, r_chroma)),
+                    unorm8_lanes_neon(vaddq_f32(y, g_chroma)),
+                    unorm8_lanes_neon(vaddq_f32(y, b_chroma)),
+                ];
+            }
+            for (index, channel) in channels.iter_mut().enumerate() {
+                channel[half] = narrow_lanes_neon(lanes[0][index], lanes[1][index]);
+            }
+        }
+        let r = vcombine_u8(channels[0][0], channels[0][1]);
+        let g = vcombine_u8(channels[1][0], channels[1][1]);
+        let b = vcombine_u8(channels[2][0], channels[2][1]);
+        let pixels = if BGRA { uint8x16x4_t(b, g, r, alpha) } else { uint8x16x4_t(r, g, b, alpha) };
+        vst4q_u8(output.as_mut_ptr().add(x) as *mut u8, pixels);
+        x += 16;
+    }
+    x
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn ten_bit_lanes_to_unorm8_neon(value: uint32x4_t, scale: float32x4_t, half: float32x4_t) -> uint32x4_t {
+    vcvtq_u32_f32(vaddq_f32(vmulq_f32(vcvtq_f32_u32(value), scale), half))
+}
+
+#[cfg(target_arch = "aarch64")]
+#[target_feature(enable = "neon")]
+unsafe fn packed_1010102_to_unorm8x4_neon<const BGRA: bool>(input: &[u32], output: &mut [[u8; 4]]) -> usize {
+    let mask = vdupq_n_u32(0x3FF);
+    let scale = vdupq_n_f32(255.0 / 1023.0);
+    let half = vdupq_n_f32(0.5);
+    let mut x = 0;
+    while x + 4 <= input.len() {
+        let pixels = vld1q_u32(input.as_ptr().add(x));
+        let r = ten_bit_lanes_to_unorm8_neon(vandq_u32(pixels, mask), scale, half);
+        let g = ten_bit_lanes_to_unorm8_neon(vandq_u32(vshrq_n_u32::<10>(pixels), mask), scale, half);
+        let b = ten_bit_lanes_to_unorm8_neon(vandq_u32(vshrq_n_u32::<20>(pixels), mask), scale, half);
+        let a = vmulq_n_u32(vshrq_n_u32::<30>(pixels), 85);
+        let (first, third) = if BGRA { (b, r) } else { (r, b) };
+        let packed = vorrq_u32(
+            vorrq_u32(first, vshlq_n_u32::<8>(g)),
+            vorrq_u32(vshlq_n_u32::<16>(third), vshlq_n_u32::<24>(a)),
+        );
+        vst1q_u32(output.as_mut_ptr().add(x) as *mut u32, packed);
+        x += 4;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{VideoRange, YCbCrMatrix};
+
+    // Widths around each vector width, so the scalar tail handles one pixel, several pixels, and nothing at all
+    const WIDTHS: [usize; 8] = [0, 1, 3, 7, 16, 17, 33, 100];
+
+    // Deterministic xorshift, so failures reproduce
+    struct Random(u32);
+
+    impl Random {
+        fn next(&mut self) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0
+        }
+
+        fn bytes<const N: usize>(&mut self, count: usize) -> Vec<[u8; N]> {
+            (0..count).map(|_| std::array::from_fn(|_| self.next() as u8)).collect()
+        }
+    }
+
+    fn swizzle_red_blue_scalar(input: &[[u8; 4]]) -> Vec<[u8; 4]> {
+        input.iter().map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]]).collect()
+    }
+
+    fn ycbcr_420_to_unorm8x4_scalar<const BGRA: bool>(luma: &[u8], chroma: &[[u8; 2]], transform: &YCbCrToUnorm8) -> Vec<[u8; 4]> {
+        luma.iter().enumerate().map(|(x, &y)| {
+            let [cb, cr] = chroma[x / 2];
+            let [r, g, b] = transform.pixel(y, cb, cr);
+            order::<BGRA>(r, g, b, 255)
+        }).collect()
+    }
+
+    fn packed_1010102_to_unorm8x4_scalar<const BGRA: bool>(input: &[u32]) -> Vec<[u8; 4]> {
+        input.iter().map(|&pixel| {
+            let [r, g, b, a] = unpack_1010102(pixel);
+            order::<BGRA>(ten_bits_to_u8(r), ten_bits_to_u8(g), ten_bits_to_u8(b), two_bits_to_u8(a))
+        }).collect()
+    }
+
+    // Run a kernel that returns how many pixels it handled, and check that prefix against the scalar result
+    fn assert_kernel_prefix(name: &str, width: usize, expected: &[[u8; 4]], kernel: impl FnOnce(&mut [[u8; 4]]) -> usize) {
+        let mut output = vec![[0u8; 4]; width];
+        let done = kernel(&mut output);
+        assert!(done <= width, "{name} handled {done} of {width} pixels");
+        assert_eq!(output[..done], expected[..done], "{name} at width {width}");
+    }
+
+    fn transforms() -> Vec<YCbCrToUnorm8> {
+        let mut transforms = Vec::new();
+        for matrix in [YCbCrMatrix::Bt601, YCbCrMatrix::Bt709] {
+            for range in [VideoRange::Video, VideoRange::Full] {
+                transforms.push(YCbCrToUnorm8::new(matrix, range));
+            }
+        }
+        transforms
+    }
+
+    #[test]
+    fn ten_bits_to_u8_matches_integer_rounding() {
+        for value in 0..1024u16 {
+            assert_eq!(ten_bits_to_u8(value) as u32, (value as u32 * 255 + 511) / 1023, "value {value}");
+        }
+    }
+
+    #[test]
+    fn swizzle_red_blue_matches_scalar() {
+        let mut random = Random(0x9E37_79B9);
+        for width in WIDTHS {
+            let input = random.bytes::<4>(width);
+            let expected = swizzle_red_blue_scalar(&input);
+            let mut output = vec![[0u8; 4]; width];
+            swizzle_red_blue(&input, &mut output);
+            assert_eq!(output, expected, "dispatch at width {width}");
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            {
+                if is_x86_feature_detected!("sse2") {
+                    assert_kernel_prefix("sse2", width, &expected, |output| unsafe { swizzle_red_blue_sse2(&input, output) });
+                }
+                if is_x86_feature_detected!("avx2") {
+                    assert_kernel_prefix("avx2", width, &expected, |output| unsafe { swizzle_red_blue_avx2(&input, output) });
+                }
+            }
+            #[cfg(target_arch = "aarch64")]
+            {
+                if std::arch::is_aarch64_feature_detected!("neon") {
+                    assert_kernel_prefix("neon", width, &expected, |output| unsafe { swizzle_red_blue_neon(&input, output) });
+                }
+            }
+        }
+    }
+
+    fn check_ycbcr_420<const BGRA: bool>(random: &mut Random, transform: &YCbCrToUnorm8, width: usize) {
+        let luma: Vec<u8> = random.bytes::<1>(width).into_iter().map(|[y]| y).collect();
+        let chroma = random.bytes::<2>(width.div_ceil(2));
+        let expected = ycbcr_420_to_unorm8x4_scalar::<BGRA>(&luma, &chroma, transform);
+        let mut output = vec![[0u8; 4]; width];
+        ycbcr_420_to_unorm8x4::<BGRA>(&luma, &chroma, transform, &mut output);
+        assert_eq!(output, expected, "dispatch at width {width}");
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("sse2") {
+                assert_kernel_prefix("sse2", width, &expected, |output| unsafe { ycbcr_420_to_unorm8x4_sse2::<BGRA>(&luma, &chroma, transform, output) });
+            }
+            if is_x86_feature_detected!("avx2") {
+                assert_kernel_prefix("avx2", width, &expected, |output| unsafe { ycbcr_420_to_unorm8x4_avx2::<BGRA>(&luma, &chroma, transform, output) });
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                assert_kernel_prefix("neon", width, &expected, |output| unsafe { ycbcr_420_to_unorm8x4_neon::<BGRA>(&luma, &chroma, transform, output) });
+            }
+        }
+    }
+
+    #[test]
+    fn ycbcr_420_to_unorm8x4_matches_scalar() {
+        let mut random = Random(0x2545_F491);
+        for transform in transforms() {
+            for width in WIDTHS {
+                check_ycbcr_420::<true>(&mut random, &transform, width);
+                check_ycbcr_420::<false>(&mut random, &transform, width);
+            }
+        }
+    }
+
+    fn check_packed_1010102<const BGRA: bool>(random: &mut Random, width: usize) {
+        let input: Vec<u32> = (0..width).map(|_| random.next()).collect();
+        let expected = packed_1010102_to_unorm8x4_scalar::<BGRA>(&input);
+        let mut output = vec![[0u8; 4]; width];
+        packed_1010102_to_unorm8x4::<BGRA>(&input, &mut output);
+        assert_eq!(output, expected, "dispatch at width {width}");
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("sse2") {
+                assert_kernel_prefix("sse2", width, &expected, |output| unsafe { packed_1010102_to_unorm8x4_sse2::<BGRA>(&input, output) });
+            }
+            if is_x86_feature_detected!("avx2") {
+                assert_kernel_prefix("avx2", width, &expected, |output| unsafe { packed_1010102_to_unorm8x4_avx2::<BGRA>(&input, output) });
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                assert_kernel_prefix("neon", width, &expected, |output| unsafe { packed_1010102_to_unorm8x4_neon::<BGRA>(&input, output) });
+            }
+        }
+    }
+
+    #[test]
+    fn packed_1010102_to_unorm8x4_matches_scalar() {
+        let mut random = Random(0x6C07_8965);
+        for width in WIDTHS {
+            check_packed_1010102::<true>(&mut random, width);
+            check_packed_1010102::<false>(&mut random, width);
+        }
+    }
+}
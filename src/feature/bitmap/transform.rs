@@ -0,0 +1,330 @@
+use crate::prelude::{PixelPoint, PixelRect, PixelSize, Rect};
+
+use super::{FrameBitmap, FrameBitmapBgraUnorm8x4, FrameBitmapError, FrameBitmapRgbaF16x4, FrameBitmapRgbaUnorm8x4, FrameBitmapRgbaUnormPacked1010102, FrameBitmapYCbCr};
+
+/// A clockwise rotation of a bitmap
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FrameBitmapRotation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+/// The direction to mirror a bitmap in
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FrameBitmapFlip {
+    /// Mirror left to right
+    Horizontal,
+    /// Mirror top to bottom
+    Vertical,
+}
+
+impl FrameBitmap {
+    /// Get the bounds of the bitmap, in pixels
+    pub fn bounds(&self) -> PixelRect {
+        PixelRect {
+            origin: PixelPoint::ZERO,
+            size: PixelSize {
+                width: self.width() as u32,
+                height: self.height() as u32,
+            }
+        }
+    }
+
+    /// Crop the bitmap to a rectangle of pixels, which must lie within the bitmap's bounds
+    ///
+    /// YCbCr bitmaps can be cropped at any pixel. When the crop doesn't start on a chroma sample boundary,
+    /// chroma is interpolated between the neighbouring samples so it stays aligned with luma.
+    pub fn crop(&self, rect: PixelRect) -> Result<FrameBitmap, FrameBitmapError> {
+        if rect.is_empty() {
+            return Err(FrameBitmapError::Other("Can't crop a bitmap to an empty rectangle".to_string()));
+        }
+        if !self.bounds().contains_rect(&rect) {
+            return Err(FrameBitmapError::Other(format!("Crop rectangle {:?} doesn't fit in the bitmap bounds {:?}", rect, self.bounds())));
+        }
+        self.transform(PlaneTransform::Crop {
+            x: rect.origin.x as usize,
+            y: rect.origin.y as usize,
+            width: rect.size.width as usize,
+            height: rect.size.height as usize,
+        })
+    }
+
+    /// Crop the bitmap to the pixels covered by a rectangle, clipped to the bitmap's bounds
+    ///
+    /// This crops a frame's bitmap to its content in one call: `bitmap.crop_to_rect(frame.content_rect())`
+    pub fn crop_to_rect(&self, rect: Rect) -> Result<FrameBitmap, FrameBitmapError> {
+        self.crop(rect.round_out().clamped_to(&self.bounds()))
+    }
+
+    /// Rotate the bitmap clockwise
+    pub fn rotate(&self, rotation: FrameBitmapRotation) -> Result<FrameBitmap, FrameBitmapError> {
+        self.transform(PlaneTransform::Rotate(rotation))
+    }
+
+    /// Mirror the bitmap
+    pub fn flip(&self, flip: FrameBitmapFlip) -> Result<FrameBitmap, FrameBitmapError> {
+        self.transform(PlaneTransform::Flip(flip))
+    }
+
+    fn transform(&self, transform: PlaneTransform) -> Result<FrameBitmap, FrameBitmapError> {
+        self.validate()?;
+        let (width, height) = transform.output_size(self.width(), self.height());
+        let bitmap = match self {
+            Self::BgraUnorm8x4(bitmap) => Self::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 {
+                data: transform.apply(&bitmap.data, bitmap.width, bitmap.height),
+                width,
+                height,
+            }),
+            Self::RgbaUnorm8x4(bitmap) => Self::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 {
+                data: transform.apply(&bitmap.data, bitmap.width, bitmap.height),
+                width,
+                height,
+            }),
+            Self::RgbaUnormPacked1010102(bitmap) => Self::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+                data: transform.apply(&bitmap.data, bitmap.width, bitmap.height),
+                width,
+                height,
+            }),
+            Self::RgbaF16x4(bitmap) => Self::RgbaF16x4(FrameBitmapRgbaF16x4 {
+                data: transform.apply(&bitmap.data, bitmap.width, bitmap.height),
+                width,
+                height,
+            }),
+            Self::YCbCr(bitmap) => {
+                let (chroma_data, chroma_width, chroma_height) = transform_chroma(bitmap, transform);
+                Self::YCbCr(FrameBitmapYCbCr {
+                    luma_data: transform.apply(&bitmap.luma_data, bitmap.luma_width, bitmap.luma_height),
+                    luma_width: width,
+                    luma_height: height,
+                    chroma_data,
+                    chroma_width,
+                    chroma_height,
+                    range: bitmap.range,
+                    matrix: bitmap.matrix,
+                })
+            },
+        };
+        Ok(bitmap)
+    }
+}
+
+// A geometric transform of one plane of a bitmap
+#[derive(Copy, Clone, Debug)]
+enum PlaneTransform {
+    Crop {
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    },
+    Rotate(FrameBitmapRotation),
+    Flip(FrameBitmapFlip),
+}
+
+impl PlaneTransform {
+    fn output_size(&self, width: usize, height: usize) -> (usize, usize) {
+        match *self {
+            Self::Crop { width, height, .. } => (width, height),
+            Self::Rotate(FrameBitmapRotation::Rotate90 | FrameBitmapRotation::Rotate270) => (height, width),
+            Self::Rotate(FrameBitmapRotation::Rotate180) | Self::Flip(_) => (width, height),
+        }
+    }
+
+    // Map a position in the output plane to the source plane, in continuous coordinates where pixel (x, y) covers [x, x + 1) x [y, y + 1)
+    fn source_position(&self, x: f64, y: f64, width: usize, height: usize) -> (f64, f64) {
+        let (width, height) = (width as f64, height as f64);
+        match *self {
+            Self::Crop { x: crop_x, y: crop_y, .. } => (x + crop_x as f64, y + crop_y as f64),
+            Self::Rotate(FrameBitmapRotation::Rotate90) => (y, height - x),
+            Self::Rotate(FrameBitmapRotation::Rotate180) => (width - x, height - y),
+            Self::Rotate(FrameBitmapRotation::Rotate270) => (width - y, x),
+            Self::Flip(FrameBitmapFlip::Horizontal) => (width - x, y),
+            Self::Flip(FrameBitmapFlip::Vertical) => (x, height - y),
+        }
+    }
+
+    fn apply<T: Copy>(&self, data: &[T], width: usize, height: usize) -> Box<[T]> {
+        let (output_width, output_height) = self.output_size(width, height);
+        let mut output = Vec::with_capacity(output_width * output_height);
+        if width == 0 || height == 0 {
+            return output.into_boxed_slice();
+        }
+        match *self {
+            Self::Crop { x, y, width: crop_width, height: crop_height } => {
+                for row in data.chunks_exact(width).skip(y).take(crop_height) {
+                    output.extend_from_slice(&row[x..x + crop_width]);
+                }
+            },
+            Self::Rotate(FrameBitmapRotation::Rotate90) => {
+                for output_y in 0..output_height {
+                    output.extend((0..output_width).map(|output_x| data[(height - 1 - output_x) * width + output_y]));
+                }
+            },
+            Self::Rotate(FrameBitmapRotation::Rotate180) => {
+                output.extend(data.iter().rev());
+            },
+            Self::Rotate(FrameBitmapRotation::Rotate270) => {
+                for output_y in 0..output_height {
+                    output.extend((0..output_width).map(|output_x| data[output_x * width + (width - 1 - output_y)]));
+                }
+            },
+            Self::Flip(FrameBitmapFlip::Horizontal) => {
+                for row in data.chunks_exact(width) {
+                    output.extend(row.iter().rev());
+                }
+            },
+            Self::Flip(FrameBitmapFlip::Vertical) => {
+                for row in data.chunks_exact(width).rev() {
+                    output.extend_from_slice(row);
+                }
+            },
+        }
+        output.into_boxed_slice()
+    }
+}
+
+// Transform the chroma plane of a YCbCr bitmap so each chroma sample stays over the same luma pixels.
+// When the luma transform lands on chroma sample boundaries, the chroma plane is transformed exactly like luma.
+// Otherwise (like cropping at an odd pixel of 4:2:0, or mirroring an odd width), each output chroma sample is interpolated
+// from the source chroma at its position in the image.
+fn transform_chroma(bitmap: &FrameBitmapYCbCr, transform: PlaneTransform) -> (Box<[[u8; 2]]>, usize, usize) {
+    let (subsampling_x, subsampling_y) = bitmap.chroma_subsampling();
+    let (output_subsampling_x, output_subsampling_y) = match transform {
+        PlaneTransform::Rotate(FrameBitmapRotation::Rotate90 | FrameBitmapRotation::Rotate270) => (subsampling_y, subsampling_x),
+        _ => (subsampling_x, subsampling_y),
+    };
+    let (luma_width, luma_height) = (bitmap.luma_width, bitmap.luma_height);
+    let (output_luma_width, output_luma_height) = transform.output_size(luma_width, luma_height);
+    let (chroma_width, chroma_height) = (output_luma_width.div_ceil(output_subsampling_x), output_luma_height.div_ceil(output_subsampling_y));
+    if bitmap.chroma_width == 0 || bitmap.chroma_height == 0 {
+        return (Box::new([]), chroma_width, chroma_height);
+    }
+
+    let standard_layout = bitmap.chroma_width == luma_width.div_ceil(subsampling_x) && bitmap.chroma_height == luma_height.div_ceil(subsampling_y);
+    let (width_aligned, height_aligned) = (luma_width % subsampling_x == 0, luma_height % subsampling_y == 0);
+    let aligned = standard_layout && match transform {
+        PlaneTransform::Crop { x, y, .. } => x % subsampling_x == 0 && y % subsampling_y == 0,
+        PlaneTransform::Rotate(FrameBitmapRotation::Rotate90) => height_aligned,
+        PlaneTransform::Rotate(FrameBitmapRotation::Rotate180) => width_aligned && height_aligned,
+        PlaneTransform::Rotate(FrameBitmapRotation::Rotate270) => width_aligned,
+        PlaneTransform::Flip(FrameBitmapFlip::Horizontal) => width_aligned,
+        PlaneTransform::Flip(FrameBitmapFlip::Vertical) => height_aligned,
+    };
+    if aligned {
+        let chroma_transform = match transform {
+            PlaneTransform::Crop { x, y, .. } => PlaneTransform::Crop {
+                x: x / subsampling_x,
+                y: y / subsampling_y,
+                width: chroma_width,
+                height: chroma_height,
+            },
+            other => other,
+        };
+        return (chroma_transform.apply(&bitmap.chroma_data, bitmap.chroma_width, bitmap.chroma_height), chroma_width, chroma_height);
+    }
+
+    let sample = |chroma_x: usize, chroma_y: usize| bitmap.chroma_data[chroma_y * bitmap.chroma_width + chroma_x];
+    let mut chroma_data = Vec::with_capacity(chroma_width * chroma_height);
+    for output_chroma_y in 0..chroma_height {
+        for output_chroma_x in 0..chroma_width {
+            // The center of the luma pixels this chroma sample covers, in the output and then the source
+            let output_x = (output_chroma_x as f64 + 0.5) * output_subsampling_x as f64;
+            let output_y = (output_chroma_y as f64 + 0.5) * output_subsampling_y as f64;
+            let (source_x, source_y) = transform.source_position(output_x, output_y, luma_width, luma_height);
+            // The same position in chroma sample indices, where sample centers are whole numbers
+            let chroma_x = (source_x / subsampling_x as f64 - 0.5).clamp(0.0, (bitmap.chroma_width - 1) as f64);
+            let chroma_y = (source_y / subsampling_y as f64 - 0.5).clamp(0.0, (bitmap.chroma_height - 1) as f64);
+            let (x0, y0) = (chroma_x.floor() as usize, chroma_y.floor() as usize);
+            let (x1, y1) = ((x0 + 1).min(bitmap.chroma_width - 1), (y0 + 1).min(bitmap.chroma_height - 1));
+            let (fraction_x, fraction_y) = (chroma_x - x0 as f64, chroma_y - y0 as f64);
+            let mut output = [0u8; 2];
+            for (component, value) in output.iter_mut().enumerate() {
+                let top = sample(x0, y0)[component] as f64 * (1.0 - fraction_x) + sample(x1, y0)[component] as f64 * fraction_x;
+                let bottom = sample(x0, y1)[component] as f64 * (1.0 - fraction_x) + sample(x1, y1)[component] as f64 * fraction_x;
+                *value = (top * (1.0 - fraction_y) + bottom * fraction_y + 0.5).clamp(0.0, 255.0) as u8;
+            }
+            chroma_data.push(output);
+        }
+    }
+    (chroma_data.into_boxed_slice(), chroma_width, chroma_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::{Point, Size};
+
+    // A 3x2 bitmap where each pixel holds its own index
+    fn numbered() -> FrameBitmap {
+        FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+            data: (0..6).collect(),
+            width: 3,
+            height: 2,
+        })
+    }
+
+    fn pixels(bitmap: &FrameBitmap) -> (usize, usize, Vec<u32>) {
+        match bitmap {
+            FrameBitmap::RgbaUnormPacked1010102(bitmap) => (bitmap.width, bitmap.height, bitmap.data.to_vec()),
+            _ => unreachable!(),
+        }
+    }
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> PixelRect {
+        PixelRect {
+            origin: PixelPoint { x, y },
+            size: PixelSize { width, height },
+        }
+    }
+
+    #[test]
+    fn rotating_90_swaps_dimensions() {
+        // 0 1 2      3 0
+        // 3 4 5  ->  4 1
+        //            5 2
+        assert_eq!(pixels(&numbered().rotate(FrameBitmapRotation::Rotate90).unwrap()), (2, 3, vec![3, 0, 4, 1, 5, 2]));
+        assert_eq!(pixels(&numbered().rotate(FrameBitmapRotation::Rotate270).unwrap()), (2, 3, vec![2, 5, 1, 4, 0, 3]));
+        assert_eq!(pixels(&numbered().rotate(FrameBitmapRotation::Rotate180).unwrap()), (3, 2, vec![5, 4, 3, 2, 1, 0]));
+    }
+
+    #[test]
+    fn four_quarter_turns_are_identity() {
+        let mut bitmap = numbered();
+        for _ in 0..4 {
+            bitmap = bitmap.rotate(FrameBitmapRotation::Rotate90).unwrap();
+        }
+        assert_eq!(pixels(&bitmap), pixels(&numbered()));
+        let rotated = numbered().rotate(FrameBitmapRotation::Rotate90).unwrap().rotate(FrameBitmapRotation::Rotate270).unwrap();
+        assert_eq!(pixels(&rotated), pixels(&numbered()));
+    }
+
+    #[test]
+    fn flipping_twice_is_identity() {
+        assert_eq!(pixels(&numbered().flip(FrameBitmapFlip::Horizontal).unwrap()), (3, 2, vec![2, 1, 0, 5, 4, 3]));
+        assert_eq!(pixels(&numbered().flip(FrameBitmapFlip::Vertical).unwrap()), (3, 2, vec![3, 4, 5, 0, 1, 2]));
+        for flip in [FrameBitmapFlip::Horizontal, FrameBitmapFlip::Vertical] {
+            let flipped = numbered().flip(flip).unwrap().flip(flip).unwrap();
+            assert_eq!(pixels(&flipped), pixels(&numbered()));
+        }
+    }
+
+    #[test]
+    fn crop_takes_the_rectangle() {
+        assert_eq!(pixels(&numbered().crop(rect(1, 0, 2, 2)).unwrap()), (2, 2, vec![1, 2, 4, 5]));
+        assert_eq!(pixels(&numbered().crop(numbered().bounds()).unwrap()), pixels(&numbered()));
+    }
+
+    #[test]
+    fn out_of_bounds_crops_are_rejected() {
+        for rect in [rect(2, 0, 2, 1), rect(0, 1, 1, 2), rect(-1, 0, 2, 2), rect(0, 0, 4, 3), rect(1, 1, 0, 1)] {
+            assert!(numbered().crop(rect).is_err(), "{:?}", rect);
+        }
+        // Rectangles are clipped to the bounds instead when cropping to a content rectangle
+        let cropped = numbered().crop_to_rect(Rect {
+            origin: Point { x: 1.5, y: -1.0 },
+            size: Size { width: 10.0, height: 2.0 },
+        }).unwrap();
+        assert_eq!(pixels(&cropped), (2, 1, vec![1, 2]));
+    }
+}
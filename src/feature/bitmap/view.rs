@@ -0,0 +1,364 @@
+use std::marker::PhantomData;
+
+use crate::prelude::VideoFrame;
+
+use super::{copy_plane, copy_plane_into, FrameBitmap, FrameBitmapBgraUnorm8x4, FrameBitmapFormat, FrameBitmapRgbaF16x4, FrameBitmapRgbaUnorm8x4, FrameBitmapRgbaUnormPacked1010102, FrameBitmapYCbCr};
+
+#[cfg(target_os = "macos")]
+use crate::platform::platform_impl::objc_wrap::{IOSurface, IOSurfaceLockGaurd};
+
+#[cfg(target_os = "windows")]
+use std::sync::Arc;
+#[cfg(target_os = "windows")]
+use parking_lot::Mutex;
+#[cfg(target_os = "windows")]
+use windows::Win32::Graphics::Direct3D11::{ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_TEXTURE2D_DESC};
+
+#[cfg(target_os = "windows")]
+use super::VideoFrameBitmapError;
+
+/// A single plane of pixels in a locked frame bitmap
+///
+/// Rows may be padded, so row `y` starts at byte `y * bytes_per_row` of the plane's data
+#[derive(Copy, Clone, Debug)]
+pub struct FrameBitmapPlane<'a> {
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    bytes_per_row: usize,
+    bytes_per_pixel: usize,
+}
+
+impl<'a> FrameBitmapPlane<'a> {
+    /// The width of this plane in pixels
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of this plane in pixels
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The distance in bytes between the start of consecutive rows (the row pitch)
+    pub fn bytes_per_row(&self) -> usize {
+        self.bytes_per_row
+    }
+
+    /// The size in bytes of a single pixel of this plane
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bytes_per_pixel
+    }
+
+    /// All bytes of this plane, including any padding at the end of each row
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The pixel bytes of row `y`, without padding
+    pub fn row(&self, y: usize) -> &'a [u8] {
+        let start = y * self.bytes_per_row;
+        &self.data[start..(start + self.width * self.bytes_per_pixel)]
+    }
+
+    /// Iterate over the pixel bytes of each row, without padding
+    pub fn rows(&self) -> impl Iterator<Item = &'a [u8]> + 'a {
+        let plane = *self;
+        (0..self.height).map(move |y| plane.row(y))
+    }
+}
+
+// A plane of a locked frame, kept as a raw pointer so the view can own the lock which keeps it valid
+#[derive(Copy, Clone)]
+pub(super) struct FrameBitmapPlaneDesc {
+    pub(super) data: *const u8,
+    pub(super) width: usize,
+    pub(super) height: usize,
+    pub(super) bytes_per_row: usize,
+    pub(super) bytes_per_pixel: usize,
+}
+
+// The staging texture a capture stream's frames are copied into for reading, kept between frames so that
+// locking a frame only creates a new texture when the frame size or format changes
+#[cfg(target_os = "windows")]
+#[derive(Default)]
+pub(crate) struct StagingTextureCache {
+    texture: Mutex<Option<ID3D11Texture2D>>,
+}
+
+#[cfg(target_os = "windows")]
+impl StagingTextureCache {
+    // Take the cached texture if it matches the size and format of `desc`, or create a new one. Textures still
+    // mapped by a live view aren't in the cache, so the texture returned is never one that's being read from.
+    pub(super) fn take(&self, device: &ID3D11Device, desc: &D3D11_TEXTURE2D_DESC) -> Result<ID3D11Texture2D, VideoFrameBitmapError> {
+        if let Some(texture) = self.texture.lock().take() {
+            let mut cached_desc = D3D11_TEXTURE2D_DESC::default();
+            unsafe { texture.GetDesc(&mut cached_desc as *mut _); }
+            if (cached_desc.Width, cached_desc.Height, cached_desc.Format) == (desc.Width, desc.Height, desc.Format) {
+                return Ok(texture);
+            }
+        }
+        let mut texture = Option::<ID3D11Texture2D>::None;
+        unsafe { device.CreateTexture2D(desc as *const _, None, Some(&mut texture as *mut _)) }
+            .map_err(|error| VideoFrameBitmapError::Other(format!("Failed to create texture: {}", error)))?;
+        texture.ok_or_else(|| VideoFrameBitmapError::Other("Failed to create texture".to_string()))
+    }
+
+    // Return a texture to the cache once it's no longer mapped
+    pub(super) fn give_back(&self, texture: ID3D11Texture2D) {
+        *self.texture.lock() = Some(texture);
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub(super) struct MappedStagingTexture {
+    pub(super) context: ID3D11DeviceContext,
+    pub(super) texture: Option<ID3D11Texture2D>,
+    pub(super) cache: Arc<StagingTextureCache>,
+}
+
+#[cfg(target_os = "windows")]
+impl Drop for MappedStagingTexture {
+    fn drop(&mut self) {
+        if let Some(texture) = self.texture.take() {
+            unsafe {
+                self.context.Unmap(&texture, 0);
+            }
+            self.cache.give_back(texture);
+        }
+    }
+}
+
+// The backend buffer lock that keeps a view's planes readable
+pub(super) enum FrameBitmapLock {
+    #[cfg(target_os = "macos")]
+    IOSurface {
+        // Fields drop in order, so the surface is unlocked before it's released
+        _lock_gaurd: IOSurfaceLockGaurd,
+        _iosurface: IOSurface,
+    },
+    #[cfg(target_os = "windows")]
+    StagingTexture {
+        _mapped_texture: MappedStagingTexture,
+    },
+    // Planes in memory owned by the test, which need no lock
+    #[cfg(test)]
+    Unlocked,
+}
+
+/// A borrowed, read-only view of a frame's pixels
+///
+/// The frame's backing surface stays locked (or mapped) for as long as the view is alive, so
+/// the pixels can be read in place without copying the whole frame into system memory.
+/// Drop the view as soon as you're done reading from it.
+pub struct FrameBitmapView<'a> {
+    format: FrameBitmapFormat,
+    width: usize,
+    height: usize,
+    planes: [Option<FrameBitmapPlaneDesc>; 2],
+    _lock: FrameBitmapLock,
+    _frame: PhantomData<&'a VideoFrame>,
+}
+
+impl<'a> FrameBitmapView<'a> {
+    // Safety: each plane's data must be readable for `bytes_per_row * height` bytes until `lock` is dropped
+    pub(super) unsafe fn new(format: FrameBitmapFormat, width: usize, height: usize, planes: [Option<FrameBitmapPlaneDesc>; 2], lock: FrameBitmapLock) -> Self {
+        Self {
+            format,
+            width,
+            height,
+            planes,
+            _lock: lock,
+            _frame: PhantomData,
+        }
+    }
+
+    /// The format of the pixels in this view
+    pub fn format(&self) -> FrameBitmapFormat {
+        self.format
+    }
+
+    /// The width of this view in pixels
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of this view in pixels
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The number of planes in this view - 1 for packed formats, and 2 for YCbCr (luma then chroma)
+    pub fn plane_count(&self) -> usize {
+        self.planes.iter().flatten().count()
+    }
+
+    /// Get a plane of this view, or `None` if there's no plane with this index
+    pub fn plane(&self, index: usize) -> Option<FrameBitmapPlane<'_>> {
+        let desc = (*self.planes.get(index)?)?;
+        let data = unsafe { std::slice::from_raw_parts(desc.data, desc.bytes_per_row * desc.height) };
+        Some(FrameBitmapPlane {
+            data,
+            width: desc.width,
+            height: desc.height,
+            bytes_per_row: desc.bytes_per_row,
+            bytes_per_pixel: desc.bytes_per_pixel,
+        })
+    }
+
+    /// Copy the pixels of this view into an owned bitmap
+    pub fn to_bitmap(&self) -> FrameBitmap {
+        let (width, height) = (self.width, self.height);
+        let plane = self.plane(0).expect("A frame bitmap view always has at least one plane");
+        match self.format {
+            FrameBitmapFormat::BgraUnorm8x4 => FrameBitmap::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 {
+                data: copy_plane(plane.data(), plane.bytes_per_row(), plane.width(), plane.height()),
+                width,
+                height,
+            }),
+            FrameBitmapFormat::RgbaUnorm8x4 => FrameBitmap::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 {
+                data: copy_plane(plane.data(), plane.bytes_per_row(), plane.width(), plane.height()),
+                width,
+                height,
+            }),
+            FrameBitmapFormat::RgbaUnormPacked1010102 => FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+                data: copy_plane(plane.data(), plane.bytes_per_row(), plane.width(), plane.height()),
+                width,
+                height,
+            }),
+            FrameBitmapFormat::RgbaF16x4 => FrameBitmap::RgbaF16x4(FrameBitmapRgbaF16x4 {
+                data: copy_plane(plane.data(), plane.bytes_per_row(), plane.width(), plane.height()),
+                width,
+                height,
+            }),
+            FrameBitmapFormat::YCbCr { matrix, range } => {
+                let chroma_plane = self.plane(1).expect("A YCbCr frame bitmap view always has a chroma plane");
+                FrameBitmap::YCbCr(FrameBitmapYCbCr {
+                    luma_data: copy_plane(plane.data(), plane.bytes_per_row(), plane.width(), plane.height()),
+                    luma_width: plane.width(),
+                    luma_height: plane.height(),
+                    chroma_data: copy_plane(chroma_plane.data(), chroma_plane.bytes_per_row(), chroma_plane.width(), chroma_plane.height()),
+                    chroma_width: chroma_plane.width(),
+                    chroma_height: chroma_plane.height(),
+                    range,
+                    matrix,
+                })
+            }
+        }
+    }
+
+    /// Copy the pixels of this view into an existing bitmap
+    ///
+    /// If the bitmap already has this view's format and size, its buffers are reused and nothing is allocated.
+    /// Otherwise, the bitmap is replaced with a newly allocated one.
+    pub fn copy_into(&self, bitmap: &mut FrameBitmap) {
+        let (width, height) = (self.width, self.height);
+        let plane = self.plane(0).expect("A frame bitmap view always has at least one plane");
+        match (self.format, bitmap) {
+            (FrameBitmapFormat::BgraUnorm8x4, FrameBitmap::BgraUnorm8x4(bitmap)) => {
+                copy_plane_into(&mut bitmap.data, plane.data(), plane.bytes_per_row(), plane.width(), plane.height());
+                (bitmap.width, bitmap.height) = (width, height);
+            },
+            (FrameBitmapFormat::RgbaUnorm8x4, FrameBitmap::RgbaUnorm8x4(bitmap)) => {
+                copy_plane_into(&mut bitmap.data, plane.data(), plane.bytes_per_row(), plane.width(), plane.height());
+                (bitmap.width, bitmap.height) = (width, height);
+            },
+            (FrameBitmapFormat::RgbaUnormPacked1010102, FrameBitmap::RgbaUnormPacked1010102(bitmap)) => {
+                copy_plane_into(&mut bitmap.data, plane.data(), plane.bytes_per_row(), plane.width(), plane.height());
+                (bitmap.width, bitmap.height) = (width, height);
+            },
+            (FrameBitmapFormat::RgbaF16x4, FrameBitmap::RgbaF16x4(bitmap)) => {
+                copy_plane_into(&mut bitmap.data, plane.data(), plane.bytes_per_row(), plane.width(), plane.height());
+                (bitmap.width, bitmap.height) = (width, height);
+            },
+            (FrameBitmapFormat::YCbCr { matrix, range }, FrameBitmap::YCbCr(bitmap)) => {
+                let chroma_plane = self.plane(1).expect("A YCbCr frame bitmap view always has a chroma plane");
+                copy_plane_into(&mut bitmap.luma_data, plane.data(), plane.bytes_per_row(), plane.width(), plane.height());
+                copy_plane_into(&mut bitmap.chroma_data, chroma_plane.data(), chroma_plane.bytes_per_row(), chroma_plane.width(), chroma_plane.height());
+                (bitmap.luma_width, bitmap.luma_height) = (plane.width(), plane.height());
+                (bitmap.chroma_width, bitmap.chroma_height) = (chroma_plane.width(), chroma_plane.height());
+                bitmap.matrix = matrix;
+                bitmap.range = range;
+            },
+            (_, bitmap) => *bitmap = self.to_bitmap(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{VideoFrameBitmap, VideoFrameBitmapError};
+
+    // A frame which can only be copied, not locked
+    struct CopiedFrame;
+
+    impl VideoFrameBitmap for CopiedFrame {
+        fn get_bitmap(&self) -> Result<FrameBitmap, VideoFrameBitmapError> {
+            Ok(FrameBitmap::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 { data: vec![[1, 2, 3, 4]; 6].into(), width: 3, height: 2 }))
+        }
+    }
+
+    // A 3x2 RGBA view of `data`, which has 16 bytes per row - 12 bytes of pixels, then 4 bytes of padding
+    fn padded_view(data: &[u8]) -> FrameBitmapView<'_> {
+        assert_eq!(data.len(), 32);
+        let plane = FrameBitmapPlaneDesc {
+            data: data.as_ptr(),
+            width: 3,
+            height: 2,
+            bytes_per_row: 16,
+            bytes_per_pixel: 4,
+        };
+        unsafe { FrameBitmapView::new(FrameBitmapFormat::RgbaUnorm8x4, 3, 2, [Some(plane), None], FrameBitmapLock::Unlocked) }
+    }
+
+    fn padded_data() -> Vec<u8> {
+        (0..32).map(|index| if index % 16 < 12 { index } else { 0xFF }).collect()
+    }
+
+    #[test]
+    fn lock_bitmap_is_unsupported_by_default() {
+        let frame = CopiedFrame;
+        assert!(matches!(frame.lock_bitmap(), Err(VideoFrameBitmapError::Other(_))));
+        let mut bitmap = FrameBitmap::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 { data: Box::new([]), width: 0, height: 0 });
+        frame.get_bitmap_into(&mut bitmap).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (3, 2));
+    }
+
+    #[test]
+    fn rows_skip_padding() {
+        let data = padded_data();
+        let view = padded_view(&data);
+        assert_eq!((view.format(), view.width(), view.height(), view.plane_count()), (FrameBitmapFormat::RgbaUnorm8x4, 3, 2, 1));
+        assert!(view.plane(1).is_none());
+        let plane = view.plane(0).unwrap();
+        assert_eq!((plane.width(), plane.height(), plane.bytes_per_row(), plane.bytes_per_pixel()), (3, 2, 16, 4));
+        assert_eq!(plane.data().len(), 32);
+        assert_eq!(plane.row(1), &data[16..28]);
+        let rows: Vec<&[u8]> = plane.rows().collect();
+        assert_eq!(rows, [&data[0..12], &data[16..28]]);
+    }
+
+    #[test]
+    fn copies_skip_padding() {
+        let data = padded_data();
+        let view = padded_view(&data);
+        let expected: Vec<[u8; 4]> = [0u8, 4, 8, 16, 20, 24].iter().map(|start| [*start, start + 1, start + 2, start + 3]).collect();
+        match view.to_bitmap() {
+            FrameBitmap::RgbaUnorm8x4(bitmap) => {
+                assert_eq!((bitmap.width, bitmap.height), (3, 2));
+                assert_eq!(bitmap.data.to_vec(), expected);
+            },
+            _ => unreachable!(),
+        }
+
+        // A bitmap of another format is replaced
+        let mut bitmap = FrameBitmap::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 { data: Box::new([]), width: 0, height: 0 });
+        view.copy_into(&mut bitmap);
+        assert_eq!(bitmap.format(), FrameBitmapFormat::RgbaUnorm8x4);
+        match bitmap {
+            FrameBitmap::RgbaUnorm8x4(bitmap) => assert_eq!(bitmap.data.to_vec(), expected),
+            _ => unreachable!(),
+        }
+    }
+}
@@ -62,11 +62,16 @@ impl WindowsDx11VideoFrame for VideoFrame {
 /// A capture stream which can inter-operate with DX11
 pub trait WindowsDx11CaptureStream {
     /// Get the underlying DX11 device used for frame capture
+    /// 
+    /// Audio-only streams create this device on first use, and panic if it can't be created
     fn get_dx11_device(&self) -> ID3D11Device;
 }
 
 impl WindowsDx11CaptureStream for CaptureStream {
     fn get_dx11_device(&self) -> ID3D11Device {
-        self.impl_capture_stream.d3d11_device.clone()
+        match self.impl_capture_stream.graphics_devices() {
+            Ok(graphics_devices) => graphics_devices.d3d11_device.clone(),
+            Err(error) => panic!("Failed to create DX11 device for audio-only capture stream: {}", error),
+        }
     }
 }
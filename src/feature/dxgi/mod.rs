@@ -90,15 +90,19 @@ pub trait WindowsDxgiCaptureStream {
     /// Get the DXGI adapter used by the capture stream for frame generation
     fn get_dxgi_adapter(&self) -> Result<windows::Win32::Graphics::Dxgi::IDXGIAdapter, WindowsDxgiCaptureStreamError>;
     /// Get the DXGI device used by the capture stream for frame generation
+    /// 
+    /// Audio-only streams create this device on first use, and panic if it can't be created
     fn get_dxgi_device(&self) -> windows::Win32::Graphics::Dxgi::IDXGIDevice;
 }
 
 impl WindowsDxgiCaptureStream for CaptureStream {
     fn get_dxgi_adapter(&self) -> Result<windows::Win32::Graphics::Dxgi::IDXGIAdapter, WindowsDxgiCaptureStreamError> {
-        if let Some(dxgi_adapter) = self.impl_capture_stream.dxgi_adapter.clone() {
+        let graphics_devices = self.impl_capture_stream.graphics_devices()
+            .map_err(|error| WindowsDxgiCaptureStreamError::NoAdapter(error.to_string()))?;
+        if let Some(dxgi_adapter) = graphics_devices.dxgi_adapter.clone() {
             Ok(dxgi_adapter)
         } else {
-            match &self.impl_capture_stream.dxgi_adapter_error {
+            match &graphics_devices.dxgi_adapter_error {
                 Some(error) => Err(WindowsDxgiCaptureStreamError::NoAdapter(error.clone())),
                 None => unreachable!("Should have dxgi_adapter_error if dxgi_adapter is None")
             }
@@ -106,6 +110,9 @@ impl WindowsDxgiCaptureStream for CaptureStream {
     }
 
     fn get_dxgi_device(&self) -> windows::Win32::Graphics::Dxgi::IDXGIDevice {
-        self.impl_capture_stream.dxgi_device.clone()
+        match self.impl_capture_stream.graphics_devices() {
+            Ok(graphics_devices) => graphics_devices.dxgi_device.clone(),
+            Err(error) => panic!("Failed to create DXGI device for audio-only capture stream: {}", error),
+        }
     }
 }
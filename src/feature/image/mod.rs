@@ -0,0 +1,192 @@
+#![cfg(feature = "image")]
+
+use ::image::metadata::CicpTransferCharacteristics;
+use ::image::{DynamicImage, ImageBuffer, Rgba, Rgba32FImage, RgbaImage};
+use half::f16;
+
+use crate::feature::bitmap::{FrameBitmap, FrameBitmapError, FrameBitmapFormat, FrameBitmapRgbaF16x4, FrameBitmapRgbaUnorm8x4, FrameBitmapRgbaUnormPacked1010102};
+
+/// Convert a frame bitmap to an image
+///
+/// - `BgraUnorm8x4` and `RgbaUnorm8x4` become `ImageRgba8`, swizzling BGRA in place
+/// - `RgbaUnormPacked1010102` becomes `ImageRgba16`, with each 10 bit component (and the 2 bit alpha) scaled to the full 16 bit range
+/// - `RgbaF16x4` becomes `ImageRgba32F`, with its linear light values kept as they are (including values above 1.0), and the image's transfer function set to linear
+/// - `YCbCr` is converted to RGB with its matrix and range, and becomes `ImageRgba8`
+impl TryFrom<FrameBitmap> for DynamicImage {
+    type Error = FrameBitmapError;
+
+    fn try_from(bitmap: FrameBitmap) -> Result<Self, Self::Error> {
+        bitmap.validate()?;
+        let (width, height) = image_size(bitmap.width(), bitmap.height())?;
+        let size_error = || FrameBitmapError::Other("Bitmap data doesn't match the image size".to_string());
+        match bitmap {
+            FrameBitmap::BgraUnorm8x4(bitmap) => {
+                let data = bitmap.data.into_vec().into_flattened();
+                RgbaImage::from_raw_bgra(width, height, data).map(DynamicImage::ImageRgba8).ok_or_else(size_error)
+            },
+            FrameBitmap::RgbaUnorm8x4(bitmap) => {
+                let data = bitmap.data.into_vec().into_flattened();
+                RgbaImage::from_raw(width, height, data).map(DynamicImage::ImageRgba8).ok_or_else(size_error)
+            },
+            FrameBitmap::RgbaUnormPacked1010102(bitmap) => {
+                let data = bitmap.data.iter().flat_map(|pixel| {
+                    let ten_bits_to_u16 = |value: u32| ((value << 6) | (value >> 4)) as u16;
+                    [
+                        ten_bits_to_u16(pixel & 0x3FF),
+                        ten_bits_to_u16((pixel >> 10) & 0x3FF),
+                        ten_bits_to_u16((pixel >> 20) & 0x3FF),
+                        ((pixel >> 30) * 0x5555) as u16,
+                    ]
+                }).collect();
+                ImageBuffer::<Rgba<u16>, Vec<u16>>::from_raw(width, height, data).map(DynamicImage::ImageRgba16).ok_or_else(size_error)
+            },
+            FrameBitmap::RgbaF16x4(bitmap) => {
+                let data = bitmap.data.iter().flatten().map(|component| component.to_f32()).collect();
+                let mut image = Rgba32FImage::from_raw(width, height, data).map(DynamicImage::ImageRgba32F).ok_or_else(size_error)?;
+                image.set_transfer_function(CicpTransferCharacteristics::Linear);
+                Ok(image)
+            },
+            bitmap @ FrameBitmap::YCbCr(_) => {
+                let FrameBitmap::RgbaUnorm8x4(bitmap) = bitmap.convert(FrameBitmapFormat::RgbaUnorm8x4)? else {
+                    return Err(FrameBitmapError::Other("Converting to RgbaUnorm8x4 produced a different format".to_string()));
+                };
+                let data = bitmap.data.into_vec().into_flattened();
+                RgbaImage::from_raw(width, height, data).map(DynamicImage::ImageRgba8).ok_or_else(size_error)
+            },
+        }
+    }
+}
+
+/// Convert an image to a frame bitmap, such as for feeding synthetic frames through bitmap processing
+///
+/// - 8 bit images become `RgbaUnorm8x4`
+/// - 16 bit images become `RgbaUnormPacked1010102`, rounding each component to 10 bits and alpha to 2 bits
+/// - 32 bit float images become `RgbaF16x4`, treating their values as linear light
+///
+/// Images without alpha are opaque, and grayscale images are expanded to RGB.
+impl From<DynamicImage> for FrameBitmap {
+    fn from(image: DynamicImage) -> Self {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        match image {
+            DynamicImage::ImageLuma16(_) |
+            DynamicImage::ImageLumaA16(_) |
+            DynamicImage::ImageRgb16(_) |
+            DynamicImage::ImageRgba16(_) => {
+                let u16_to_ten_bits = |value: u16| ((value as u32 * 1023 + 32767) / 65535) & 0x3FF;
+                let data = image.into_rgba16().pixels().map(|pixel| {
+                    let [r, g, b, a] = pixel.0;
+                    u16_to_ten_bits(r) | (u16_to_ten_bits(g) << 10) | (u16_to_ten_bits(b) << 20) | (((a as u32 * 3 + 32767) / 65535) << 30)
+                }).collect();
+                FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+                    data,
+                    width,
+                    height,
+                })
+            },
+            DynamicImage::ImageRgb32F(_) |
+            DynamicImage::ImageRgba32F(_) => {
+                let data = image.into_rgba32f().pixels().map(|pixel| pixel.0.map(f16::from_f32)).collect();
+                FrameBitmap::RgbaF16x4(FrameBitmapRgbaF16x4 {
+                    data,
+                    width,
+                    height,
+                })
+            },
+            image => {
+                let data = image.into_rgba8().into_raw();
+                FrameBitmap::RgbaUnorm8x4(FrameBitmapRgbaUnorm8x4 {
+                    data: bytemuck::cast_slice(&data).into(),
+                    width,
+                    height,
+                })
+            },
+        }
+    }
+}
+
+fn image_size(width: usize, height: usize) -> Result<(u32, u32), FrameBitmapError> {
+    match (u32::try_from(width), u32::try_from(height)) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(FrameBitmapError::Other(format!("Bitmap size {}x{} is too large for an image", width, height))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feature::bitmap::FrameBitmapBgraUnorm8x4;
+
+    #[test]
+    fn bgra_is_swizzled_to_rgba() {
+        let bitmap = FrameBitmap::BgraUnorm8x4(FrameBitmapBgraUnorm8x4 {
+            data: vec![[1, 2, 3, 4], [5, 6, 7, 8]].into_boxed_slice(),
+            width: 2,
+            height: 1,
+        });
+        let DynamicImage::ImageRgba8(image) = DynamicImage::try_from(bitmap).unwrap() else {
+            panic!("expected an 8 bit RGBA image");
+        };
+        assert_eq!(image.dimensions(), (2, 1));
+        assert_eq!(image.into_raw(), vec![3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+
+    #[test]
+    fn packed_1010102_replicates_bits_to_16_bits() {
+        let bitmap = FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+            data: vec![1023 | (512 << 20) | (3 << 30), 1 | (1 << 30)].into_boxed_slice(),
+            width: 2,
+            height: 1,
+        });
+        let DynamicImage::ImageRgba16(image) = DynamicImage::try_from(bitmap).unwrap() else {
+            panic!("expected a 16 bit RGBA image");
+        };
+        assert_eq!(image.into_raw(), vec![65535, 0, 32800, 65535, 64, 0, 0, 0x5555]);
+    }
+
+    #[test]
+    fn f16_values_above_one_are_kept() {
+        let pixel = [2.0, 0.5, 4.5, 1.0];
+        let bitmap = FrameBitmap::RgbaF16x4(FrameBitmapRgbaF16x4 {
+            data: vec![pixel.map(f16::from_f32)].into_boxed_slice(),
+            width: 1,
+            height: 1,
+        });
+        let DynamicImage::ImageRgba32F(image) = DynamicImage::try_from(bitmap).unwrap() else {
+            panic!("expected a 32 bit float RGBA image");
+        };
+        assert_eq!(image.into_raw(), pixel.to_vec());
+    }
+
+    #[test]
+    fn rgba8_round_trips() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8 * 80, y as u8 * 200, 17, 255 - x as u8])));
+        let bitmap = FrameBitmap::from(image.clone());
+        assert!(matches!(bitmap, FrameBitmap::RgbaUnorm8x4(_)));
+        assert_eq!(DynamicImage::try_from(bitmap).unwrap(), image);
+    }
+
+    #[test]
+    fn every_10_bit_value_round_trips_through_rgba16() {
+        let data = (0..1024u32).map(|value| value | ((1023 - value) << 10) | ((value / 2) << 20) | ((value % 4) << 30)).collect::<Vec<_>>();
+        let bitmap = FrameBitmap::RgbaUnormPacked1010102(FrameBitmapRgbaUnormPacked1010102 {
+            data: data.clone().into_boxed_slice(),
+            width: 1024,
+            height: 1,
+        });
+        let image = DynamicImage::try_from(bitmap).unwrap();
+        let FrameBitmap::RgbaUnormPacked1010102(back) = FrameBitmap::from(image) else {
+            panic!("expected a packed 10 bit bitmap");
+        };
+        assert_eq!(back.data.into_vec(), data);
+    }
+
+    #[test]
+    fn rgba32f_round_trips() {
+        let image = DynamicImage::ImageRgba32F(Rgba32FImage::from_fn(2, 2, |x, y| Rgba([x as f32 * 3.0, y as f32 * 0.25, 0.5, 1.0])));
+        let FrameBitmap::RgbaF16x4(bitmap) = FrameBitmap::from(image.clone()) else {
+            panic!("expected an f16 bitmap");
+        };
+        let back = DynamicImage::try_from(FrameBitmap::RgbaF16x4(bitmap)).unwrap();
+        assert_eq!(back.to_rgba32f().into_raw(), image.to_rgba32f().into_raw());
+    }
+}
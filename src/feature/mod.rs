@@ -30,5 +30,13 @@ pub mod wgpu;
 /// Screenshot utility function
 /// (requires `screenshot` feature)
 pub mod screenshot;
+#[cfg(feature = "audio")]
+/// Audio resampling, channel mixing, sample format conversion, level metering, chunking and drift correction
+/// (requires `audio` feature)
+pub mod audio;
+#[cfg(feature = "image")]
+/// Bitmap <-> `image` crate conversion
+/// (requires `image` feature)
+pub mod image;
 //#[cfg(feature = "content_picker")]
 //pub mod content_picker;
@@ -6,17 +6,19 @@ use crate::frame::VideoFrame;
 use crate::platform::macos::frame::{MacosSCStreamVideoFrame, MacosVideoFrame};
 use crate::platform::macos::objc_wrap::{CGSize, NSArray, SCContentFilter, SCScreenshotManager, SCStreamColorMatrix, SCStreamConfiguration, SCStreamPixelFormat};
 use crate::platform::platform_impl::objc_wrap::CGMainDisplayID;
-use crate::prelude::{Capturable, CaptureAccessToken, CaptureConfig, CapturePixelFormat};
+use crate::prelude::{Capturable, CaptureAccessToken, CaptureConfig, CapturePixelFormat, StreamClock};
 
 /// Take a screenshot of the capturable content given a configuration
 pub async fn take_screenshot(token: CaptureAccessToken, config: CaptureConfig) -> Result<VideoFrame, ScreenshotError> {
     let _ = token;
+    let clock = StreamClock::new();
     // Force core graphics initialization
     unsafe { CGMainDisplayID() };
     let mut stream_config = SCStreamConfiguration::new();
     let filter = match &config.target {
-        Capturable::Window(window) => SCContentFilter::new_with_desktop_independent_window(&window.impl_capturable_window.window),
-        Capturable::Display(display) => SCContentFilter::new_with_display_excluding_apps_excepting_windows(display.impl_capturable_display.display.clone(), NSArray::new(), NSArray::new())
+        Some(Capturable::Window(window)) => SCContentFilter::new_with_desktop_independent_window(&window.impl_capturable_window.window),
+        Some(Capturable::Display(display)) => SCContentFilter::new_with_display_excluding_apps_excepting_windows(display.impl_capturable_display.display.clone(), NSArray::new(), NSArray::new()),
+        None => return Err(ScreenshotError::Other("Can't take a screenshot with an audio-only capture config".into())),
     };
     stream_config.set_scales_to_fit(false);
     let (pixel_format, set_color_matrix) = match config.pixel_format {
@@ -49,6 +51,7 @@ pub async fn take_screenshot(token: CaptureAccessToken, config: CaptureConfig) -
                     impl_video_frame: MacosVideoFrame::SCStream(
                         MacosSCStreamVideoFrame {
                             sample_buffer,
+                            clock,
                             capture_time,
                             dictionary: RefCell::new(None),
                             frame_id: 0,
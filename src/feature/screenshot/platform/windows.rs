@@ -6,6 +6,9 @@ use crate::prelude::{CaptureConfig, CaptureStream, StreamEvent, CaptureAccessTok
 
 /// Take a screenshot of the capturable content given a configuration
 pub async fn take_screenshot(token: CaptureAccessToken, config: CaptureConfig) -> Result<VideoFrame, ScreenshotError> {
+    if config.is_audio_only() {
+        return Err(ScreenshotError::Other("Can't take a screenshot with an audio-only capture config".into()));
+    }
     let (tx, rx) = oneshot::channel();
     let mut tx = Some(tx);
     let mut capture_stream = CaptureStream::new(token, config, move |event_result| {
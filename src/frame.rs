@@ -1,22 +1,152 @@
 #![allow(unused)]
-use std::{marker::PhantomData, time::{Duration, Instant}, fmt::Debug};
+use std::{marker::PhantomData, time::{Duration, Instant, SystemTime}, fmt::Debug};
 
-use crate::{platform::platform_impl::{ImplAudioFrame, ImplVideoFrame}, util::*};
+use crate::{clock::StreamClock, platform::platform_impl::{ImplAudioFrame, ImplVideoFrame}, util::*};
 
 /// The rate to capture audio samples
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AudioSampleRate {
     Hz8000,
     Hz16000,
     Hz24000,
+    Hz44100,
     Hz48000,
+    Hz96000,
 }
 
-/// The number of audio channels to capture
-#[derive(Copy, Clone, Debug)]
+impl AudioSampleRate {
+    /// The number of samples per second, per channel
+    pub fn samples_per_second(&self) -> u32 {
+        match self {
+            Self::Hz8000  =>  8000,
+            Self::Hz16000 => 16000,
+            Self::Hz24000 => 24000,
+            Self::Hz44100 => 44100,
+            Self::Hz48000 => 48000,
+            Self::Hz96000 => 96000,
+        }
+    }
+
+    /// Get the sample rate matching a number of samples per second, if it's one of the supported rates
+    pub fn from_samples_per_second(samples_per_second: f64) -> Option<Self> {
+        [Self::Hz8000, Self::Hz16000, Self::Hz24000, Self::Hz44100, Self::Hz48000, Self::Hz96000].into_iter()
+            .find(|rate| (rate.samples_per_second() as f64 - samples_per_second).abs() < 1.0)
+    }
+}
+
+/// The number of audio channels to capture, and their layout
+/// 
+/// Channels are always delivered in the order given by `positions()`, which matches the WAVE (and WASAPI) channel order
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AudioChannelCount {
+    /// One channel: front center
     Mono,
-    Stereo
+    /// Two channels: front left, front right
+    Stereo,
+    /// Six channels: front left, front right, front center, low frequency, back left, back right
+    Surround5_1,
+    /// Eight channels: front left, front right, front center, low frequency, back left, back right, side left, side right
+    Surround7_1,
+}
+
+impl AudioChannelCount {
+    /// The number of channels in this layout
+    pub fn count(&self) -> usize {
+        self.positions().len()
+    }
+
+    /// The speaker positions of the channels in this layout, in channel order
+    pub fn positions(&self) -> &'static [AudioChannelPosition] {
+        use AudioChannelPosition::*;
+        match self {
+            Self::Mono => &[FrontCenter],
+            Self::Stereo => &[FrontLeft, FrontRight],
+            Self::Surround5_1 => &[FrontLeft, FrontRight, FrontCenter, LowFrequency, BackLeft, BackRight],
+            Self::Surround7_1 => &[FrontLeft, FrontRight, FrontCenter, LowFrequency, BackLeft, BackRight, SideLeft, SideRight],
+        }
+    }
+
+    /// Get the channel layout with the given number of channels, if there is one
+    pub fn from_count(count: usize) -> Option<Self> {
+        match count {
+            1 => Some(Self::Mono),
+            2 => Some(Self::Stereo),
+            6 => Some(Self::Surround5_1),
+            8 => Some(Self::Surround7_1),
+            _ => None,
+        }
+    }
+}
+
+/// The speaker position of an audio channel
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AudioChannelPosition {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    LowFrequency,
+    BackLeft,
+    BackRight,
+    SideLeft,
+    SideRight,
+}
+
+/// The format of captured audio samples
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AudioSampleFormat {
+    /// 32 bit floating point samples, range: [-1.0, 1.0]
+    F32,
+    /// 16 bit signed integer samples, range: [-32768, 32767]
+    I16,
+}
+
+/// The source of captured audio
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AudioSource {
+    /// Audio played by the system (or captured application)
+    System,
+    /// Audio recorded from an input device, like a microphone
+    Microphone,
+}
+
+/// A type of audio sample, which can be converted to and from normalized 32 bit float samples
+pub trait AudioSample: Copy + Send + Sync + 'static {
+    /// Convert the sample to a float in the range [-1.0, 1.0]
+    fn to_f32(self) -> f32;
+    /// Convert a float in the range [-1.0, 1.0] to a sample, clamping out-of-range values
+    ///
+    /// Integer formats scale by the same power of two both ways, so converting a sample to a float and back is lossless
+    fn from_f32(value: f32) -> Self;
+}
+
+impl AudioSample for f32 {
+    fn to_f32(self) -> f32 {
+        self
+    }
+
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+}
+
+impl AudioSample for i32 {
+    fn to_f32(self) -> f32 {
+        (self as f64 / 2147483648.0) as f32
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (value as f64 * 2147483648.0).round().clamp(i32::MIN as f64, i32::MAX as f64) as i32
+    }
+}
+
+impl AudioSample for i16 {
+    fn to_f32(self) -> f32 {
+        self as f32 / 32768.0
+    }
+
+    fn from_f32(value: f32) -> Self {
+        (value * 32768.0).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
 }
 
 /// Represents audio channel data in an audio frame
@@ -26,6 +156,28 @@ pub enum AudioChannelData<'data> {
     I16(AudioChannelDataSamples<'data, i16>),
 }
 
+impl AudioChannelData<'_> {
+    /// Get the number of samples in this channel
+    pub fn length(&self) -> usize {
+        match self {
+            Self::F32(samples) => samples.length(),
+            Self::I32(samples) => samples.length(),
+            Self::I16(samples) => samples.length(),
+        }
+    }
+
+    /// Copy the samples of this channel into `destination` as normalized floats, returning the number of samples copied
+    /// 
+    /// At most `destination.len()` samples are copied
+    pub fn copy_into_f32(&self, destination: &mut [f32]) -> usize {
+        match self {
+            Self::F32(samples) => samples.copy_into_f32(destination),
+            Self::I32(samples) => samples.copy_into_f32(destination),
+            Self::I16(samples) => samples.copy_into_f32(destination),
+        }
+    }
+}
+
 /// Wraps a "slice" of audio data for one channel, handling data stride
 pub struct AudioChannelDataSamples<'data, T> {
     pub(crate) data: *const u8,
@@ -34,20 +186,108 @@ pub struct AudioChannelDataSamples<'data, T> {
     pub(crate) phantom_lifetime: PhantomData<&'data T>,
 }
 
-impl<T: Copy> AudioChannelDataSamples<'_, T> {
+impl<'data, T: Copy> AudioChannelDataSamples<'data, T> {
     /// Get the nth sample for this channel data
+    /// 
+    /// Panics if `n` is out of bounds; use `try_get` to check instead
     pub fn get(&self, n: usize) -> T {
+        match self.try_get(n) {
+            Some(sample) => sample,
+            None => panic!("audio sample index {} out of bounds for channel of length {}", n, self.length),
+        }
+    }
+
+    /// Get the nth sample for this channel data, or `None` if `n` is out of bounds
+    pub fn try_get(&self, n: usize) -> Option<T> {
+        if n >= self.length {
+            return None;
+        }
         let ptr = self.data.wrapping_add(self.stride * n);
-        unsafe { *(ptr as *const T) }
+        Some(unsafe { (ptr as *const T).read_unaligned() })
     }
 
     /// Get the length of this sample buffer
     pub fn length(&self) -> usize {
         self.length
     }
+
+    /// Get the samples as a slice, if they are contiguous in memory (E.G. non-interleaved audio)
+    pub fn as_slice(&self) -> Option<&'data [T]> {
+        if self.length == 0 {
+            return Some(&[]);
+        }
+        if self.stride != std::mem::size_of::<T>() || (self.data as usize) % std::mem::align_of::<T>() != 0 {
+            return None;
+        }
+        Some(unsafe { std::slice::from_raw_parts(self.data as *const T, self.length) })
+    }
+
+    /// Iterate over the samples of this channel
+    pub fn iter(&self) -> AudioChannelDataSamplesIter<'_, 'data, T> {
+        AudioChannelDataSamplesIter {
+            samples: self,
+            i: 0,
+        }
+    }
+}
+
+impl<T: AudioSample> AudioChannelDataSamples<'_, T> {
+    /// Copy the samples into `destination` as normalized floats, returning the number of samples copied
+    /// 
+    /// At most `destination.len()` samples are copied
+    pub fn copy_into_f32(&self, destination: &mut [f32]) -> usize {
+        let count = self.length.min(destination.len());
+        match self.as_slice() {
+            Some(slice) => {
+                for (destination_sample, sample) in destination.iter_mut().zip(slice[..count].iter()) {
+                    *destination_sample = sample.to_f32();
+                }
+            },
+            None => {
+                for (destination_sample, sample) in destination.iter_mut().zip(self.iter()) {
+                    *destination_sample = sample.to_f32();
+                }
+            }
+        }
+        count
+    }
+}
+
+/// An iterator over the samples of one audio channel
+pub struct AudioChannelDataSamplesIter<'samples, 'data, T> {
+    samples: &'samples AudioChannelDataSamples<'data, T>,
+    i: usize,
+}
+
+impl<T: Copy> Iterator for AudioChannelDataSamplesIter<'_, '_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.samples.try_get(self.i)?;
+        self.i += 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.samples.length - self.i;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: Copy> ExactSizeIterator for AudioChannelDataSamplesIter<'_, '_, T> {
+}
+
+impl<'samples, 'data, T: Copy> IntoIterator for &'samples AudioChannelDataSamples<'data, T> {
+    type Item = T;
+    type IntoIter = AudioChannelDataSamplesIter<'samples, 'data, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 
 /// Represents an error getting the data for an audio channel
+#[derive(Debug, Clone)]
 pub enum AudioBufferError {
     // The audio sample format was not supported
     UnsupportedFormat,
@@ -56,13 +296,111 @@ pub enum AudioBufferError {
     Other(String)
 }
 
+/// An owned buffer of audio, with interleaved 32 bit float samples in the channel order of its `AudioChannelCount`
+/// 
+/// Unlike an `AudioFrame`, this doesn't hold onto any OS resources, so it can be kept or sent anywhere
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioBuffer {
+    samples: Box<[f32]>,
+    channel_count: AudioChannelCount,
+    sample_rate: AudioSampleRate,
+    origin_time: Duration,
+}
+
+impl AudioBuffer {
+    /// Create an audio buffer from interleaved samples
+    /// 
+    /// Returns an error if the number of samples isn't a multiple of the channel count
+    pub fn from_interleaved(samples: impl Into<Box<[f32]>>, channel_count: AudioChannelCount, sample_rate: AudioSampleRate, origin_time: Duration) -> Result<Self, AudioBufferError> {
+        let samples = samples.into();
+        if samples.len() % channel_count.count() != 0 {
+            return Err(AudioBufferError::Other(format!("Sample count {} is not a multiple of the channel count {}", samples.len(), channel_count.count())));
+        }
+        Ok(Self {
+            samples,
+            channel_count,
+            sample_rate,
+            origin_time,
+        })
+    }
+
+    /// Get the sample rate of the audio
+    pub fn sample_rate(&self) -> AudioSampleRate {
+        self.sample_rate
+    }
+
+    /// Get the channel layout of the audio
+    pub fn channel_count(&self) -> AudioChannelCount {
+        self.channel_count
+    }
+
+    /// Get the number of samples per channel
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channel_count.count()
+    }
+
+    /// Get the duration of the audio
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frame_count() as f64 / self.sample_rate.samples_per_second() as f64)
+    }
+
+    /// Get the time since the start of the stream that this audio begins at
+    pub fn origin_time(&self) -> Duration {
+        self.origin_time
+    }
+
+    /// Get the interleaved samples
+    pub fn interleaved(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// Get the interleaved samples, mutably
+    pub fn interleaved_mut(&mut self) -> &mut [f32] {
+        &mut self.samples
+    }
+
+    /// Take the interleaved samples
+    pub fn into_interleaved(self) -> Box<[f32]> {
+        self.samples
+    }
+
+    /// Iterate over the samples of one channel
+    pub fn channel(&self, channel: usize) -> Result<impl ExactSizeIterator<Item = f32> + '_, AudioBufferError> {
+        let channel_count = self.channel_count.count();
+        if channel >= channel_count {
+            return Err(AudioBufferError::InvalidChannel);
+        }
+        Ok(self.samples.chunks_exact(channel_count).map(move |frame| frame[channel]))
+    }
+
+    /// Copy the samples of one channel into `destination`, returning the number of samples copied
+    /// 
+    /// At most `destination.len()` samples are copied
+    pub fn copy_channel_into(&self, channel: usize, destination: &mut [f32]) -> Result<usize, AudioBufferError> {
+        let mut count = 0;
+        for (destination_sample, sample) in destination.iter_mut().zip(self.channel(channel)?) {
+            *destination_sample = sample;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Convert the samples to interleaved 16 bit integer samples
+    pub fn to_interleaved_i16(&self) -> Vec<i16> {
+        self.samples.iter().map(|sample| i16::from_f32(*sample)).collect()
+    }
+}
+
 pub(crate) trait AudioCaptureFrame {
     fn sample_rate(&self) -> AudioSampleRate;
     fn channel_count(&self) -> AudioChannelCount;
+    fn sample_format(&self) -> AudioSampleFormat;
+    fn source(&self) -> AudioSource;
     fn audio_channel_buffer(&mut self, channel: usize) -> Result<AudioChannelData<'_>, AudioBufferError>;
     fn duration(&self) -> Duration;
     fn origin_time(&self) -> Duration;
     fn frame_id(&self) -> u64;
+    fn stream_clock(&self) -> StreamClock;
 }
 
 /// A frame of captured audio
@@ -90,6 +428,16 @@ impl AudioFrame {
         self.impl_audio_frame.channel_count()
     }
 
+    /// Get the format of the captured audio samples
+    pub fn sample_format(&self) -> AudioSampleFormat {
+        self.impl_audio_frame.sample_format()
+    }
+
+    /// Get the source of the captured audio
+    pub fn source(&self) -> AudioSource {
+        self.impl_audio_frame.source()
+    }
+
     /// Get the data buffer for the captured audio channel
     pub fn audio_channel_buffer(&mut self, channel: usize) -> Result<AudioChannelData<'_>, AudioBufferError> {
         self.impl_audio_frame.audio_channel_buffer(channel)
@@ -102,7 +450,62 @@ impl AudioFrame {
 
     /// Get the time since the start of the stream that this audio frame begins at
     pub fn origin_time(&self) -> Duration {
-        self.impl_audio_frame.duration()
+        self.impl_audio_frame.origin_time()
+    }
+
+    /// Get the clock of the stream this frame was captured from
+    /// 
+    /// This is shared with the stream's video frames, so audio and video can be aligned by their origin times
+    pub fn stream_clock(&self) -> StreamClock {
+        self.impl_audio_frame.stream_clock()
+    }
+
+    /// Get the monotonic instant that this audio frame begins at
+    pub fn origin_instant(&self) -> Instant {
+        self.stream_clock().to_instant(self.origin_time())
+    }
+
+    /// Get the wall-clock time that this audio frame begins at
+    pub fn origin_system_time(&self) -> SystemTime {
+        self.stream_clock().to_system_time(self.origin_time())
+    }
+
+    /// Get the index (since the start of the stream clock) of the first sample in this frame
+    pub fn origin_sample_index(&self) -> u64 {
+        self.stream_clock().sample_index(self.origin_time(), self.sample_rate())
+    }
+
+    /// Get the number of samples per channel in this frame
+    pub fn frame_count(&mut self) -> Result<usize, AudioBufferError> {
+        Ok(self.audio_channel_buffer(0)?.length())
+    }
+
+    /// Copy the samples of one channel into `destination` as normalized floats, returning the number of samples copied
+    /// 
+    /// At most `destination.len()` samples are copied
+    pub fn copy_channel_into(&mut self, channel: usize, destination: &mut [f32]) -> Result<usize, AudioBufferError> {
+        Ok(self.audio_channel_buffer(channel)?.copy_into_f32(destination))
+    }
+
+    /// Copy all channels of this frame into a vector of interleaved, normalized float samples
+    pub fn to_interleaved_f32(&mut self) -> Result<Vec<f32>, AudioBufferError> {
+        let channel_count = self.channel_count().count();
+        let frame_count = self.frame_count()?;
+        let mut interleaved = vec![0.0f32; frame_count * channel_count];
+        let mut channel_samples = vec![0.0f32; frame_count];
+        for channel in 0..channel_count {
+            let copied = self.copy_channel_into(channel, &mut channel_samples)?;
+            for (frame, sample) in interleaved.chunks_exact_mut(channel_count).zip(channel_samples[..copied].iter()) {
+                frame[channel] = *sample;
+            }
+        }
+        Ok(interleaved)
+    }
+
+    /// Copy this frame into an owned audio buffer
+    pub fn to_audio_buffer(&mut self) -> Result<AudioBuffer, AudioBufferError> {
+        let samples = self.to_interleaved_f32()?;
+        AudioBuffer::from_interleaved(samples, self.channel_count(), self.sample_rate(), self.origin_time())
     }
 
     /// Get the sequence id of this frame (monotonically increasing)
@@ -120,7 +523,9 @@ pub(crate) trait VideoCaptureFrame {
     fn origin_time(&self) -> Duration;
     fn capture_time(&self) -> Instant;
     fn frame_id(&self) -> u64;
+    fn stream_clock(&self) -> StreamClock;
     fn content_rect(&self) -> Rect;
+    fn screen_rect(&self) -> Rect;
 }
 
 /// A frame of captured video
@@ -149,6 +554,23 @@ impl VideoFrame {
         self.impl_video_frame.origin_time()
     }
 
+    /// Get the clock of the stream this frame was captured from
+    /// 
+    /// This is shared with the stream's audio frames, so audio and video can be aligned by their origin times
+    pub fn stream_clock(&self) -> StreamClock {
+        self.impl_video_frame.stream_clock()
+    }
+
+    /// Get the monotonic instant that this frame was generated
+    pub fn origin_instant(&self) -> Instant {
+        self.stream_clock().to_instant(self.origin_time())
+    }
+
+    /// Get the wall-clock time that this frame was generated
+    pub fn origin_system_time(&self) -> SystemTime {
+        self.stream_clock().to_system_time(self.origin_time())
+    }
+
     /// Get the raw size of the frame
     /// 
     /// For planar image formats, this is the size of the largest plane
@@ -162,9 +584,37 @@ impl VideoFrame {
     }
 
     /// Get the rectangle of the frame representing containing the captured contents
+    /// 
+    /// This is in pixels of the frame (the same units as `size`), with the origin at the top-left of the frame, on both Windows and MacOS
     pub fn content_rect(&self) -> Rect {
         self.impl_video_frame.content_rect()
     }
+
+    /// Get the rectangle on screen that the captured contents of this frame occupied when it was captured
+    /// 
+    /// On Windows this is in physical pixels of the virtual screen, and on MacOS this is in points of the global display space (with the origin at the top-left of the main display)
+    pub fn screen_rect(&self) -> Rect {
+        self.impl_video_frame.screen_rect()
+    }
+
+    /// Get the transform mapping points in this frame's pixels to points on screen
+    /// 
+    /// This accounts for the content rectangle, any scaling of the output, and the display's scale factor
+    pub fn frame_to_screen_transform(&self) -> CoordinateTransform {
+        CoordinateTransform::from_rects(self.content_rect(), self.screen_rect())
+    }
+
+    /// Map a point in this frame's pixels to a point on screen (see `screen_rect` for the screen coordinate space)
+    pub fn frame_to_screen(&self, point: Point) -> Point {
+        self.frame_to_screen_transform().transform_point(point)
+    }
+
+    /// Map a point on screen (see `screen_rect` for the screen coordinate space) to a point in this frame's pixels
+    /// 
+    /// Note: The resulting point may be outside of the frame's content rectangle
+    pub fn screen_to_frame(&self, point: Point) -> Point {
+        self.frame_to_screen_transform().inverse().transform_point(point)
+    }
 }
 
 impl Debug for VideoFrame {
@@ -172,3 +622,114 @@ impl Debug for VideoFrame {
         f.debug_struct("VideoFrame").finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_samples_get_is_bounds_checked() {
+        // Two interleaved channels, reading the second
+        let data = [1i16, -2, 3, -4, 5, -6];
+        let samples = AudioChannelDataSamples::<i16> {
+            data: data[1..].as_ptr() as *const u8,
+            stride: 2 * std::mem::size_of::<i16>(),
+            length: 3,
+            phantom_lifetime: PhantomData,
+        };
+        assert_eq!(samples.get(0), -2);
+        assert_eq!(samples.get(2), -6);
+        assert_eq!(samples.try_get(2), Some(-6));
+        assert_eq!(samples.try_get(3), None);
+        assert_eq!(samples.iter().collect::<Vec<_>>(), vec![-2, -4, -6]);
+        assert_eq!(samples.as_slice(), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn channel_samples_get_panics_out_of_bounds() {
+        let data = [1i16, 2];
+        let samples = AudioChannelDataSamples::<i16> {
+            data: data.as_ptr() as *const u8,
+            stride: std::mem::size_of::<i16>(),
+            length: 2,
+            phantom_lifetime: PhantomData,
+        };
+        samples.get(2);
+    }
+
+    #[test]
+    fn channel_data_converts_each_format_to_f32() {
+        let data = [i16::MIN, 0, 16384];
+        let channel = AudioChannelData::I16(AudioChannelDataSamples {
+            data: data.as_ptr() as *const u8,
+            stride: std::mem::size_of::<i16>(),
+            length: 3,
+            phantom_lifetime: PhantomData,
+        });
+        let mut destination = [9.0f32; 4];
+        assert_eq!(channel.copy_into_f32(&mut destination), 3);
+        assert_eq!(destination, [-1.0, 0.0, 0.5, 9.0]);
+        let data = [i32::MIN, 1 << 30];
+        let channel = AudioChannelData::I32(AudioChannelDataSamples {
+            data: data.as_ptr() as *const u8,
+            stride: std::mem::size_of::<i32>(),
+            length: 2,
+            phantom_lifetime: PhantomData,
+        });
+        let mut destination = [0.0f32; 1];
+        assert_eq!(channel.copy_into_f32(&mut destination), 1);
+        assert_eq!(destination, [-1.0]);
+    }
+
+    fn stereo_buffer() -> AudioBuffer {
+        AudioBuffer::from_interleaved(vec![0.5, -0.5, 0.25, -0.25, 2.0, -2.0], AudioChannelCount::Stereo, AudioSampleRate::Hz48000, Duration::from_millis(10)).unwrap()
+    }
+
+    #[test]
+    fn audio_buffer_reads_channels_in_bounds() {
+        let buffer = stereo_buffer();
+        assert_eq!(buffer.frame_count(), 3);
+        assert_eq!(buffer.origin_time(), Duration::from_millis(10));
+        assert_eq!(buffer.duration(), Duration::from_secs_f64(3.0 / 48000.0));
+        assert_eq!(buffer.channel(0).unwrap().collect::<Vec<_>>(), vec![0.5, 0.25, 2.0]);
+        assert_eq!(buffer.channel(1).unwrap().collect::<Vec<_>>(), vec![-0.5, -0.25, -2.0]);
+        let mut destination = [0.0; 2];
+        assert_eq!(buffer.copy_channel_into(1, &mut destination).unwrap(), 2);
+        assert_eq!(destination, [-0.5, -0.25]);
+    }
+
+    #[test]
+    fn audio_buffer_rejects_missing_channels() {
+        let buffer = stereo_buffer();
+        assert!(matches!(buffer.channel(2), Err(AudioBufferError::InvalidChannel)));
+        assert!(matches!(buffer.copy_channel_into(2, &mut [0.0; 4]), Err(AudioBufferError::InvalidChannel)));
+    }
+
+    #[test]
+    fn audio_buffer_rejects_samples_that_dont_fill_every_channel() {
+        let result = AudioBuffer::from_interleaved(vec![0.0; 7], AudioChannelCount::Stereo, AudioSampleRate::Hz48000, Duration::ZERO);
+        assert!(matches!(result, Err(AudioBufferError::Other(_))));
+        let result = AudioBuffer::from_interleaved(vec![0.0; 12], AudioChannelCount::Surround5_1, AudioSampleRate::Hz48000, Duration::ZERO);
+        assert_eq!(result.unwrap().frame_count(), 2);
+    }
+
+    #[test]
+    fn integer_samples_round_trip_through_f32() {
+        for sample in i16::MIN..=i16::MAX {
+            assert_eq!(i16::from_f32(sample.to_f32()), sample);
+        }
+        // 32 bit samples only round trip exactly when they fit in a float's 24 bit mantissa
+        for sample in [i32::MIN, -3 << 24, -256, 0, 256, 1 << 30] {
+            assert_eq!(i32::from_f32(sample.to_f32()), sample);
+        }
+        assert_eq!(i32::from_f32(i32::MAX.to_f32()), i32::MAX);
+        assert_eq!(i16::from_f32(-1.0), i16::MIN);
+        assert_eq!(i16::from_f32(1.0), i16::MAX);
+    }
+
+    #[test]
+    fn audio_buffer_clips_when_converting_to_i16() {
+        assert_eq!(stereo_buffer().to_interleaved_i16(), vec![16384, -16384, 8192, -8192, i16::MAX, i16::MIN]);
+    }
+}
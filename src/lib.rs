@@ -17,12 +17,25 @@
 //! 
 //! ### Bitmap output
 //! 
-//! - **`bitmap`** - enables creating raw bitmap copies of frames in system memory
+//! - **`bitmap`** - enables creating raw bitmap copies of frames in system memory or reading them in place, and converting (including to planar I420, NV12 and P010 for encoders), scaling, cropping, rotating and flipping them on the CPU
+//! - **`image`** - enables converting frame bitmaps to and from `image::DynamicImage`
 //! 
 //! ### Screenshots
 //! 
 //! - **`screenshot`** - provides an easy-to-use function wrapping `CaptureStream` for single-frame capture
 //! 
+//! ### Serialization
+//! 
+//! - **`serde`** - enables serializing and deserializing capturable content identifiers and geometry types
+//! 
+//! ### Window filtering
+//! 
+//! - **`regex`** - enables filtering capturable windows by title with a regular expression
+//! 
+//! ### Audio processing
+//! 
+//! - **`audio`** - enables resampling, channel mixing, sample format conversion, level metering, fixed-size chunking and drift correction of captured audio
+//! 
 //! ## Example
 //! 
 //! ```
@@ -91,10 +104,14 @@ pub mod feature;
 pub mod util;
 /// Audio and video frames
 pub mod frame;
+/// The shared audio and video timebase of a capture stream
+pub mod clock;
 /// The actual capture stream and related constructs
 pub mod capture_stream;
 /// Enumeration of capturable items
 pub mod capturable_content;
+/// Enumeration of audio devices
+pub mod audio_device;
 
 /// Everything
 pub mod prelude;
\ No newline at end of file
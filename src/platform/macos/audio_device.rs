@@ -0,0 +1,45 @@
+use crate::audio_device::{AudioDeviceError, AudioDeviceFormat, AudioDeviceKind};
+
+use super::objc_wrap::CoreAudioDevice;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub(crate) struct MacosAudioDevice {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) is_default: bool,
+    pub(crate) kind: AudioDeviceKind,
+    pub(crate) native_format: Option<AudioDeviceFormat>,
+}
+
+impl MacosAudioDevice {
+    fn enumerate(kind: AudioDeviceKind) -> Result<Vec<Self>, AudioDeviceError> {
+        let input = kind == AudioDeviceKind::Input;
+        let default_device = CoreAudioDevice::default_device(input);
+        // Devices with no channels in a direction don't support that direction
+        Ok(CoreAudioDevice::all_devices().into_iter().filter_map(|device| {
+            let channel_count = device.channel_count(input);
+            if channel_count == 0 {
+                return None;
+            }
+            let native_format = device.nominal_sample_rate().map(|samples_per_second| AudioDeviceFormat {
+                samples_per_second: samples_per_second.round() as u32,
+                channel_count,
+            });
+            Some(Self {
+                id: device.uid()?,
+                name: device.name().unwrap_or_default(),
+                is_default: default_device == Some(device),
+                kind,
+                native_format,
+            })
+        }).collect())
+    }
+
+    pub(crate) fn input_devices() -> Result<Vec<Self>, AudioDeviceError> {
+        Self::enumerate(AudioDeviceKind::Input)
+    }
+
+    pub(crate) fn output_devices() -> Result<Vec<Self>, AudioDeviceError> {
+        Self::enumerate(AudioDeviceKind::Output)
+    }
+}
@@ -1,12 +1,12 @@
-use std::{cell::Cell, fmt::Debug, hash::Hash, sync::Arc};
+use std::{cell::Cell, fmt::Debug, hash::Hash, path::PathBuf, sync::Arc};
 
 use futures::channel::oneshot;
 use libc::getpid;
 use parking_lot::Mutex;
 
-use crate::{capturable_content::{CapturableContentError, CapturableContentFilter}, prelude::{CapturableContent, CapturableWindow}, util::{Point, Rect, Size}};
+use crate::{capturable_content::{CapturableApplicationIcon, CapturableContentError, CapturableContentFilter, CapturableWindowState}, prelude::{CapturableContent, CapturableWindow}, util::{Point, Rect, Size}};
 
-use super::objc_wrap::{get_window_description, get_window_levels, CGMainDisplayID, CGWindowID, SCDisplay, SCRunningApplication, SCShareableContent, SCWindow};
+use super::objc_wrap::{get_active_display_bounds, get_display_hardware_id, get_onscreen_window_ids, get_window_description, get_window_levels, CGMainDisplayID, CGWindowID, NSRunningApplication, NSWorkspaceChangeObserver, SCDisplay, SCRunningApplication, SCShareableContent, SCWindow};
 
 pub struct MacosCapturableContent {
     pub windows: Vec<SCWindow>,
@@ -17,7 +17,7 @@ impl MacosCapturableContent {
     pub async fn new(filter: CapturableContentFilter) -> Result<Self, CapturableContentError> {
         // Force core graphics initialization
         unsafe { CGMainDisplayID() };
-        let (exclude_desktop, onscreen_only) = filter.windows.map_or((false, true), |filter| (!filter.desktop_windows, filter.onscreen_only));
+        let (exclude_desktop, onscreen_only) = filter.windows.as_ref().map_or((false, true), |filter| (!filter.desktop_windows, filter.onscreen_only));
         let (tx, rx) = oneshot::channel();
         let mut tx = Mutex::new(Some(tx));
         SCShareableContent::get_shareable_content_with_completion_handler(exclude_desktop, onscreen_only, move |result| {
@@ -31,6 +31,7 @@ impl MacosCapturableContent {
                 let windows = content.windows()
                     .into_iter()
                     .filter(|window| filter.impl_capturable_content_filter.filter_scwindow(window))
+                    .filter(|window| filter.windows.as_ref().map_or(true, |window_filter| window_filter.matches(&CapturableWindow { impl_capturable_window: MacosCapturableWindow::from_impl(window.clone()) })))
                     .collect();
                 let displays = content.displays()
                     .into_iter()
@@ -88,6 +89,56 @@ impl MacosCapturableWindow {
     pub fn is_visible(&self) -> bool {
         self.window.on_screen()
     }
+
+    pub fn frame_rect(&self) -> Rect {
+        self.rect()
+    }
+
+    pub fn client_rect(&self) -> Option<Rect> {
+        None
+    }
+
+    pub fn z_order(&self) -> Option<usize> {
+        let window_ids = get_onscreen_window_ids().ok()?;
+        window_ids.iter().position(|window_id| *window_id == self.window.id())
+    }
+
+    pub fn state(&self) -> Option<CapturableWindowState> {
+        // Off-screen windows may be minimized, or simply on another space
+        if !self.window.on_screen() {
+            return None;
+        }
+        let frame = self.window.frame();
+        let is_fullscreen = get_active_display_bounds().iter().any(|display_bounds| {
+            display_bounds.origin.x == frame.origin.x &&
+            display_bounds.origin.y == frame.origin.y &&
+            display_bounds.size.x == frame.size.x &&
+            display_bounds.size.y == frame.size.y
+        });
+        if is_fullscreen {
+            Some(CapturableWindowState::Fullscreen)
+        } else {
+            Some(CapturableWindowState::Normal)
+        }
+    }
+
+    pub fn owner(&self) -> Option<MacosCapturableWindow> {
+        None
+    }
+
+    pub fn native_id(&self) -> u64 {
+        self.window.id().0 as u64
+    }
+
+    pub fn is_focused(&self) -> Option<bool> {
+        // The focused window is the front-most on-screen window in the normal window layer
+        let window_ids = get_onscreen_window_ids().ok()?;
+        let normal_level = get_window_levels().normal;
+        let focused_window_id = window_ids.into_iter().find(|window_id| {
+            get_window_description(*window_id).is_ok_and(|description| description.window_layer == normal_level)
+        })?;
+        Some(focused_window_id == self.window.id())
+    }
 }
 
 impl Debug for MacosCapturableWindow {
@@ -135,6 +186,14 @@ impl MacosCapturableDisplay {
             }
         }
     }
+
+    pub fn native_id(&self) -> u64 {
+        self.display.raw_id() as u64
+    }
+
+    pub fn hardware_id(&self) -> Option<String> {
+        get_display_hardware_id(self.display.raw_id())
+    }
 }
 
 impl PartialEq for MacosCapturableDisplay {
@@ -174,6 +233,24 @@ impl MacosCapturableApplication {
     pub fn pid(&self) -> i32 {
         self.running_application.pid()
     }
+
+    pub fn executable_path(&self) -> Option<PathBuf> {
+        NSRunningApplication::from_pid(self.pid())
+            .and_then(|application| application.executable_path())
+            .map(PathBuf::from)
+    }
+
+    pub fn icon(&self, size: usize) -> Result<CapturableApplicationIcon, CapturableContentError> {
+        let application = NSRunningApplication::from_pid(self.pid())
+            .ok_or(CapturableContentError::Other(format!("No running application with pid: {}", self.pid())))?;
+        let data = application.icon_rgba8(size)
+            .map_err(|_| CapturableContentError::Other("Failed to render application icon".into()))?;
+        Ok(CapturableApplicationIcon {
+            data: data.into_boxed_slice(),
+            width: size,
+            height: size,
+        })
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -295,6 +372,19 @@ impl MacosCapturableWindowExt for CapturableWindow {
      }
 }
 
+/// Notifies of application, space and display changes using NSWorkspace notifications and display reconfiguration callbacks
+pub(crate) struct MacosCapturableContentNotifier {
+    _observer: NSWorkspaceChangeObserver,
+}
+
+impl MacosCapturableContentNotifier {
+    pub fn new(callback: impl Fn() + Send + Sync + 'static) -> Option<Self> {
+        Some(Self {
+            _observer: NSWorkspaceChangeObserver::new(callback),
+        })
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct MacosCapturableContentFilter {
     pub window_level_range: (Option<MacosWindowLevel>, Option<MacosWindowLevel>),
@@ -4,8 +4,8 @@ use futures::executor::block_on;
 use objc2::runtime::AnyObject;
 use parking_lot::Mutex;
 
-use crate::{capture_stream::{CaptureConfig, StreamCreateError, StreamError, StreamEvent}, platform::platform_impl::{frame::MacosSCStreamVideoFrame, objc_wrap::NSNumber}, prelude::{AudioCaptureConfig, AudioFrame, Capturable, CaptureConfigError, CapturePixelFormat, Point, StreamStopError, VideoFrame}, util::{Rect, Size}};
-use super::{frame::{MacosAudioFrame, MacosCGDisplayStreamVideoFrame, MacosVideoFrame}, objc_wrap::{kCFBooleanFalse, kCFBooleanTrue, kCGDisplayStreamDestinationRect, kCGDisplayStreamMinimumFrameTime, kCGDisplayStreamPreserveAspectRatio, kCGDisplayStreamQueueDepth, kCGDisplayStreamShowCursor, kCGDisplayStreamSourceRect, CFNumber, CGDisplayStream, CGDisplayStreamFrameStatus, CGPoint, CGRect, CGSize, CMSampleBuffer, CMTime, DispatchQueue, IOSurface, NSArray, NSDictionary, NSString, SCContentFilter, SCFrameStatus, SCStream, SCStreamCallbackError, SCStreamColorMatrix, SCStreamConfiguration, SCStreamFrameInfoStatus, SCStreamHandler, SCStreamOutputType, SCStreamPixelFormat, SCStreamSampleRate}};
+use crate::{capture_stream::{CaptureConfig, StreamCreateError, StreamError, StreamEvent}, platform::platform_impl::{frame::MacosSCStreamVideoFrame, objc_wrap::NSNumber}, prelude::{AudioCaptureConfig, AudioFrame, AudioSource, Capturable, CaptureConfigError, CapturePixelFormat, Point, StreamClock, StreamStopError, VideoFrame}, util::{PixelSize, Rect, Size}};
+use super::{frame::{MacosAudioConverter, MacosAudioFrame, MacosCGDisplayStreamVideoFrame, MacosVideoFrame}, objc_wrap::{kCFBooleanFalse, kCFBooleanTrue, kCGDisplayStreamDestinationRect, kCGDisplayStreamMinimumFrameTime, kCGDisplayStreamPreserveAspectRatio, kCGDisplayStreamQueueDepth, kCGDisplayStreamShowCursor, kCGDisplayStreamSourceRect, CFNumber, CGDisplayStream, CGDisplayStreamFrameStatus, CGPoint, CGRect, CGSize, CMSampleBuffer, CMTime, DispatchQueue, IOSurface, NSArray, NSDictionary, NSString, SCContentFilter, SCFrameStatus, SCShareableContent, CGMainDisplayID, SCStream, SCStreamCallbackError, SCStreamColorMatrix, SCStreamConfiguration, SCStreamFrameInfoStatus, SCStreamHandler, SCStreamOutputType, SCStreamPixelFormat, SCStreamSampleRate}};
 
 pub type MacosPixelFormat = SCStreamPixelFormat;
 
@@ -177,7 +177,22 @@ impl MacosCaptureStream {
         }
     }
 
-    pub fn new(token: MacosCaptureAccessToken, capture_config: CaptureConfig, mut callback: Box<impl FnMut(Result<StreamEvent, StreamError>) + Send + 'static>) -> Result<Self, StreamCreateError> {
+    fn audio_only_content_filter() -> Result<SCContentFilter, StreamCreateError> {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+        SCShareableContent::get_shareable_content_with_completion_handler(true, true, move |result| {
+            let _ = sender.send(result.map(|content| content.displays()).map_err(|error| error.description()));
+        });
+        let displays = receiver.recv()
+            .map_err(|_| StreamCreateError::Other("Failed to get shareable content".into()))?
+            .map_err(|error| StreamCreateError::Other(format!("Failed to get shareable content: {}", error)))?;
+        let main_display_id = unsafe { CGMainDisplayID() };
+        let display = displays.iter().find(|display| display.raw_id() == main_display_id)
+            .or(displays.first())
+            .ok_or(StreamCreateError::Other("No display available for audio capture".into()))?;
+        Ok(SCContentFilter::new_with_display_excluding_apps_excepting_windows(display.clone(), NSArray::new(), NSArray::new()))
+    }
+
+    pub fn new(token: MacosCaptureAccessToken, capture_config: CaptureConfig, clock: StreamClock, mut callback: Box<impl FnMut(Result<StreamEvent, StreamError>) + Send + 'static>) -> Result<Self, StreamCreateError> {
         let _ = token;
         let shared_callback = Arc::new(Mutex::new(callback as Box<dyn FnMut(Result<StreamEvent, StreamError>) + Send + 'static>));
         let stream_shared_callback = shared_callback.clone();
@@ -198,7 +213,7 @@ impl MacosCaptureStream {
         #[cfg(feature = "wgpu")]
         let callback_wgpu_device = wgpu_device.clone();
         match capture_config.target {
-            Capturable::Window(window) => {
+            target @ (Some(Capturable::Window(_)) | None) => {
                 let mut config = SCStreamConfiguration::new();
                 let (pixel_format, set_color_matrix) = match capture_config.pixel_format {
                     CapturePixelFormat::Bgra8888 =>    (SCStreamPixelFormat::BGRA8888, false),
@@ -210,7 +225,11 @@ impl MacosCaptureStream {
                     config.set_color_matrix(SCStreamColorMatrix::ItuR709_2);
                 }
                 config.set_pixel_format(pixel_format);
-                config.set_minimum_time_interval(CMTime::new_with_seconds(capture_config.impl_capture_config.maximum_fps.map(|x| 1.0 / x).unwrap_or(1.0 / 120.0) as f64, 240));
+                if target.is_some() {
+                    config.set_minimum_time_interval(CMTime::new_with_seconds(capture_config.impl_capture_config.maximum_fps.map(|x| 1.0 / x).unwrap_or(1.0 / 120.0) as f64, 240));
+                } else {
+                    config.set_minimum_time_interval(CMTime::new_with_seconds(1.0, 240));
+                }
                 /*config.set_source_rect(CGRect {
                     origin: CGPoint {
                         x: capture_config.source_rect.origin.x,
@@ -221,19 +240,24 @@ impl MacosCaptureStream {
                         y: capture_config.source_rect.size.height
                     }
                 });*/
-                config.set_size(CGSize {
-                    x: capture_config.output_size.width,
-                    y: capture_config.output_size.height,
+                // ScreenCaptureKit always captures video, so audio-only streams capture the smallest frames they can and never output them
+                config.set_size(match target {
+                    Some(_) => CGSize {
+                        x: capture_config.output_size.width,
+                        y: capture_config.output_size.height,
+                    },
+                    None => CGSize { x: 2.0, y: 2.0 },
                 });
                 config.set_scales_to_fit(capture_config.impl_capture_config.scale_to_fit);
                 config.set_queue_depth(capture_config.buffer_count as isize);
                 config.set_show_cursor(capture_config.show_cursor);
-                match capture_config.capture_audio {
+                match &capture_config.capture_audio {
                     Some(audio_config) => {
                         config.set_capture_audio(true);
+                        // ScreenCaptureKit only supports mono or stereo at some sample rates, anything else is converted from the nearest supported format
                         let channel_count = match audio_config.channel_count {
                             crate::prelude::AudioChannelCount::Mono => 1,
-                            crate::prelude::AudioChannelCount::Stereo => 2,
+                            _ => 2,
                         };
                         config.set_channel_count(channel_count);
                         config.set_exclude_current_process_audio(audio_config.impl_capture_audio_config.exclude_current_process_audio);
@@ -241,21 +265,52 @@ impl MacosCaptureStream {
                             crate::prelude::AudioSampleRate::Hz8000 =>  SCStreamSampleRate::R8000,
                             crate::prelude::AudioSampleRate::Hz16000 => SCStreamSampleRate::R16000,
                             crate::prelude::AudioSampleRate::Hz24000 => SCStreamSampleRate::R24000,
-                            crate::prelude::AudioSampleRate::Hz48000 => SCStreamSampleRate::R48000,
+                            _ => SCStreamSampleRate::R48000,
                         };
                         config.set_sample_rate(sample_rate);
+                        if audio_config.device.is_some() {
+                            return Err(StreamCreateError::Other("Choosing the system audio device is unsupported on MacOS".into()));
+                        }
+                        if audio_config.capture_microphone {
+                            if !config.set_capture_microphone(true) {
+                                return Err(StreamCreateError::Other("Microphone capture requires MacOS 15 or later".into()));
+                            }
+                            if let Some(device) = &audio_config.microphone_device {
+                                config.set_microphone_capture_device_id(&device.impl_audio_device.id);
+                            }
+                        }
                     },
                     None => {
                         config.set_capture_audio(false);
                     }
                 }
 
-                let filter = SCContentFilter::new_with_desktop_independent_window(&window.impl_capturable_window.window);
+                let filter = match &target {
+                    Some(Capturable::Window(window)) => SCContentFilter::new_with_desktop_independent_window(&window.impl_capturable_window.window),
+                    _ => Self::audio_only_content_filter()?,
+                };
 
                 let handler_queue = DispatchQueue::make_concurrent("com.augmend.crabgrab.window_capture".into());
 
                 let mut audio_frame_id_counter = AtomicU64::new(0);
                 let mut video_frame_id_counter = AtomicU64::new(0);
+                let audio_converter = Mutex::new(capture_config.capture_audio.as_ref().map(|audio_config| {
+                    MacosAudioConverter::new(audio_config.sample_rate, audio_config.channel_count, audio_config.sample_format, AudioSource::System)
+                }));
+                // The microphone has its own input format, so it gets its own converter
+                let microphone_converter = Mutex::new(capture_config.capture_audio.as_ref().filter(|audio_config| audio_config.capture_microphone).map(|audio_config| {
+                    MacosAudioConverter::new(audio_config.sample_rate, audio_config.channel_count, audio_config.sample_format, AudioSource::Microphone)
+                }));
+                let mut output_types = Vec::new();
+                if target.is_some() {
+                    output_types.push(SCStreamOutputType::Screen);
+                }
+                if let Some(audio_config) = &capture_config.capture_audio {
+                    output_types.push(SCStreamOutputType::Audio);
+                    if audio_config.capture_microphone {
+                        output_types.push(SCStreamOutputType::Microphone);
+                    }
+                }
 
                 let stopped_flag = Arc::new(AtomicBool::new(false));
                 let callback_stopped_flag = stopped_flag.clone();
@@ -266,9 +321,28 @@ impl MacosCaptureStream {
                     match stream_result {
                         Ok((sample_buffer, output_type)) => {
                             match output_type {
-                                SCStreamOutputType::Audio => {
+                                SCStreamOutputType::Audio | SCStreamOutputType::Microphone => {
+                                    if callback_stopped_flag.load(atomic::Ordering::Acquire) {
+                                        return;
+                                    }
+                                    let mut audio_converter = match output_type {
+                                        SCStreamOutputType::Microphone => microphone_converter.lock(),
+                                        _ => audio_converter.lock(),
+                                    };
+                                    let Some(audio_converter) = audio_converter.as_mut() else {
+                                        return;
+                                    };
                                     let frame_id = audio_frame_id_counter.fetch_add(1, atomic::Ordering::AcqRel);
-                                    // TODO...
+                                    match audio_converter.convert(sample_buffer, clock, capture_time, frame_id) {
+                                        Ok(audio_frame) => {
+                                            (callback)(Ok(StreamEvent::Audio(AudioFrame {
+                                                impl_audio_frame: audio_frame
+                                            })));
+                                        },
+                                        Err(error) => {
+                                            (callback)(Err(StreamError::Other(error)));
+                                        }
+                                    }
                                 },
                                 SCStreamOutputType::Screen => {
                                     let attachments = sample_buffer.get_sample_attachment_array();
@@ -293,6 +367,7 @@ impl MacosCaptureStream {
                                             let video_frame = VideoFrame {
                                                 impl_video_frame: MacosVideoFrame::SCStream(MacosSCStreamVideoFrame {
                                                     sample_buffer,
+                                                    clock,
                                                     capture_time,
                                                     dictionary: RefCell::new(None),
                                                     frame_id,
@@ -340,7 +415,7 @@ impl MacosCaptureStream {
                     }
                 }));
 
-                let mut sc_stream = SCStream::new(filter, config, handler_queue, handler)
+                let mut sc_stream = SCStream::new(filter, config, handler_queue, handler, &output_types)
                     .map_err(|error| StreamCreateError::Other(error))?;
 
                 sc_stream.start();
@@ -355,7 +430,7 @@ impl MacosCaptureStream {
                     wgpu_device
                 })
             },
-            Capturable::Display(display) => {
+            Some(Capturable::Display(display)) => {
                 let options_dict = NSDictionary::new_mutable();
 
                 #[cfg(feature = "metal")]
@@ -380,12 +455,17 @@ impl MacosCaptureStream {
                 let stopped_flag = Arc::new(AtomicBool::new(false));
                 let callback_stopped_flag = stopped_flag.clone();
 
-                let capture_time = Instant::now();
+                let last_origin_time = Mutex::new(None);
 
-                let stream_callback = move |status, duration, io_surface: IOSurface| {
+                let stream_callback = move |status, host_time, io_surface: IOSurface| {
                     let now = Instant::now();
                     match status {
                         CGDisplayStreamFrameStatus::Complete => {
+                            let origin_time = clock.stream_time_from_host_time(host_time);
+                            let duration = match last_origin_time.lock().replace(origin_time) {
+                                Some(last_origin_time) => origin_time.saturating_sub(last_origin_time),
+                                None => Duration::ZERO,
+                            };
                             let frame_id = video_frame_id_counter.fetch_add(1, atomic::Ordering::AcqRel);
                             let rect = display.impl_capturable_display.display.frame();
                             let w = io_surface.get_width();
@@ -396,13 +476,14 @@ impl MacosCaptureStream {
                                         io_surface,
                                         duration,
                                         capture_timestamp: now,
-                                        capture_time: now - capture_time,
+                                        capture_time: origin_time,
+                                        clock,
                                         frame_id,
                                         source_rect: Rect {
                                             origin: Point { x: rect.origin.x, y: rect.origin.y },
                                             size: Size { width: rect.size.x, height: rect.size.y },
                                         },
-                                        dest_size: Size { width: w as f64, height: h as f64 },
+                                        dest_size: PixelSize { width: w as u32, height: h as u32 }.into(),
                                         #[cfg(feature = "metal")]
                                         metal_device: callback_metal_device.clone(),
                                         #[cfg(feature = "wgpu")]
@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+use mach2::mach_time::{mach_absolute_time, mach_timebase_info, mach_timebase_info_data_t};
+
+/// Get the current host time, from the mach absolute time clock
+pub(crate) fn host_time_now() -> Duration {
+    host_time_from_mach_absolute_time(unsafe { mach_absolute_time() })
+}
+
+/// Convert a mach absolute time to a host time
+pub(crate) fn host_time_from_mach_absolute_time(time: u64) -> Duration {
+    let mut timebase_info: mach_timebase_info_data_t = Default::default();
+    unsafe { mach_timebase_info(&mut timebase_info as *mut _); }
+    if timebase_info.denom == 0 {
+        return Duration::ZERO;
+    }
+    let nanos = time as u128 * timebase_info.numer as u128 / timebase_info.denom as u128;
+    Duration::from_nanos(nanos as u64)
+}
+
+/// Convert a time on the CoreMedia host time clock (such as ScreenCaptureKit presentation timestamps) to a host time
+pub(crate) fn host_time_from_host_clock_seconds(seconds: f64) -> Duration {
+    Duration::from_secs_f64(seconds.max(0.0))
+}
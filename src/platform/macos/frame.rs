@@ -2,12 +2,14 @@ use std::{cell::{Ref, RefCell}, marker::PhantomData, sync::Arc, time::{Duration,
 
 use objc2::runtime::AnyObject;
 
-use crate::{frame::{AudioCaptureFrame, VideoCaptureFrame}, prelude::{AudioBufferError, AudioChannelCount, AudioChannelData, AudioChannelDataSamples, AudioSampleRate, Point}, util::{Rect, Size}};
+use crate::{frame::{AudioCaptureFrame, VideoCaptureFrame}, prelude::{AudioBufferError, AudioSource, StreamClock, AudioChannelCount, AudioChannelData, AudioChannelDataSamples, AudioSampleFormat, AudioSampleRate, Point}, util::{PixelSize, Rect, Size}};
 
-use super::objc_wrap::{kAudioFormatFlagIsBigEndian, kAudioFormatFlagIsPacked, kAudioFormatFlagsCanonical, kAudioFormatNativeEndian, AVAudioFormat, AVAudioPCMBuffer, AudioBufferList, AudioStreamBasicDescription, CFDictionary, CGRect, CGRectMakeWithDictionaryRepresentation, CMBlockBuffer, CMSampleBuffer, IOSurface, NSDictionary, NSNumber, NSScreen, SCStreamFrameInfoBoundingRect, SCStreamFrameInfoContentRect, SCStreamFrameInfoScaleFactor, SCStreamFrameInfoScreenRect};
+use super::clock::host_time_from_host_clock_seconds;
+use super::objc_wrap::{kAudioChannelLayoutTag_WAVE_5_1_B, kAudioChannelLayoutTag_WAVE_7_1, kAudioFormatFlagIsBigEndian, kAudioFormatFlagIsPacked, kAudioFormatFlagsCanonical, kAudioFormatNativeEndian, AVAudioConverter, AVAudioFormat, AVAudioPCMBuffer, AVAudioPCMFormatFloat32, AVAudioPCMFormatInt16, AudioBufferList, AudioStreamBasicDescription, CFDictionary, CGRect, CGRectMakeWithDictionaryRepresentation, CMBlockBuffer, CMSampleBuffer, IOSurface, NSDictionary, NSNumber, NSScreen, SCStreamFrameInfoBoundingRect, SCStreamFrameInfoContentRect, SCStreamFrameInfoScaleFactor, SCStreamFrameInfoScreenRect};
 
 pub(crate) struct MacosSCStreamVideoFrame {
     pub(crate) sample_buffer: CMSampleBuffer,
+    pub(crate) clock: StreamClock,
     pub(crate) capture_time: Instant,
     pub(crate) dictionary: RefCell<Option<CFDictionary>>,
     pub(crate) frame_id: u64,
@@ -22,6 +24,7 @@ pub(crate) struct MacosCGDisplayStreamVideoFrame {
     pub(crate) duration: Duration,
     pub(crate) capture_time: Duration,
     pub(crate) capture_timestamp: Instant,
+    pub(crate) clock: StreamClock,
     pub(crate) frame_id: u64,
     pub(crate) source_rect: Rect,
     pub(crate) dest_size: Size,
@@ -52,10 +55,10 @@ impl VideoCaptureFrame for MacosVideoFrame {
         match self {
             MacosVideoFrame::SCStream(sc_frame) => {
                 sc_frame.sample_buffer.get_image_buffer().map(|image_buffer| {
-                    Size {
-                        width: image_buffer.get_width() as f64,
-                        height: image_buffer.get_height() as f64,
-                    }
+                    PixelSize {
+                        width: image_buffer.get_width() as u32,
+                        height: image_buffer.get_height() as u32,
+                    }.into()
                 }).unwrap_or(Size { width: 0.0, height: 0.0})
             }
             MacosVideoFrame::CGDisplayStream(cgd_frame) => cgd_frame.dest_size
@@ -94,7 +97,8 @@ impl VideoCaptureFrame for MacosVideoFrame {
 
     fn origin_time(&self) -> Duration {
         match self {
-            MacosVideoFrame::SCStream(sc_frame) => std::time::Duration::from_secs_f64(sc_frame.sample_buffer.get_presentation_timestamp().seconds_f64()),
+            // Presentation timestamps are on the host time clock, the same clock as audio sample buffers
+            MacosVideoFrame::SCStream(sc_frame) => sc_frame.clock.stream_time_from_host_time(host_time_from_host_clock_seconds(sc_frame.sample_buffer.get_presentation_timestamp().seconds_f64())),
             MacosVideoFrame::CGDisplayStream(cgd_frame) => cgd_frame.capture_time
         }
     }
@@ -113,6 +117,13 @@ impl VideoCaptureFrame for MacosVideoFrame {
         }
     }
 
+    fn stream_clock(&self) -> StreamClock {
+        match self {
+            MacosVideoFrame::SCStream(sc_frame) => sc_frame.clock,
+            MacosVideoFrame::CGDisplayStream(cgd_frame) => cgd_frame.clock
+        }
+    }
+
     fn content_rect(&self) -> Rect {
         match self {
             MacosVideoFrame::SCStream(sc_frame) => {
@@ -120,6 +131,9 @@ impl VideoCaptureFrame for MacosVideoFrame {
                 let content_rect_ptr = unsafe { info_dict.get_value(SCStreamFrameInfoContentRect) };
                 let content_rect_dict = unsafe { NSDictionary::from_id_unretained(content_rect_ptr as *mut AnyObject) };
                 let frame_content_rect = unsafe { CGRect::create_from_dictionary_representation(&content_rect_dict) };
+                // The content rect is reported in points, so convert it to the frame's pixels
+                let scale_factor_ptr = unsafe { info_dict.get_value(SCStreamFrameInfoScaleFactor) };
+                let scale_factor = unsafe { NSNumber::from_id_unretained(scale_factor_ptr as *mut AnyObject).as_f64() };
                 Rect {
                     origin: Point {
                         x: frame_content_rect.origin.x,
@@ -129,91 +143,183 @@ impl VideoCaptureFrame for MacosVideoFrame {
                         width: frame_content_rect.size.x,
                         height: frame_content_rect.size.y
                     }
-                }
+                }.scaled(scale_factor)
             },
-            MacosVideoFrame::CGDisplayStream(sc_fame) => {
-                todo!()
+            MacosVideoFrame::CGDisplayStream(cgd_frame) => {
+                Rect {
+                    origin: Point::ZERO,
+                    size: cgd_frame.dest_size,
+                }
             }
         }
     }
+
+    fn screen_rect(&self) -> Rect {
+        match self {
+            MacosVideoFrame::SCStream(sc_frame) => {
+                let info_dict = sc_frame.get_info_dict();
+                let screen_rect_ptr = unsafe { info_dict.get_value(SCStreamFrameInfoScreenRect) };
+                let screen_rect_dict = unsafe { NSDictionary::from_id_unretained(screen_rect_ptr as *mut AnyObject) };
+                let frame_screen_rect = unsafe { CGRect::create_from_dictionary_representation(&screen_rect_dict) };
+                Rect {
+                    origin: Point {
+                        x: frame_screen_rect.origin.x,
+                        y: frame_screen_rect.origin.y,
+                    },
+                    size: Size {
+                        width: frame_screen_rect.size.x,
+                        height: frame_screen_rect.size.y
+                    }
+                }
+            },
+            MacosVideoFrame::CGDisplayStream(cgd_frame) => cgd_frame.source_rect,
+        }
+    }
+}
+
+/// Converts captured audio sample buffers to the configured sample rate, channel layout and sample format
+pub(crate) struct MacosAudioConverter {
+    sample_rate: AudioSampleRate,
+    channel_count: AudioChannelCount,
+    sample_format: AudioSampleFormat,
+    source: AudioSource,
+    output_format: AVAudioFormat,
+    converter: Option<(AVAudioFormat, AVAudioConverter)>,
+}
+
+impl MacosAudioConverter {
+    pub(crate) fn new(sample_rate: AudioSampleRate, channel_count: AudioChannelCount, sample_format: AudioSampleFormat, source: AudioSource) -> Self {
+        let common_format = match sample_format {
+            AudioSampleFormat::F32 => AVAudioPCMFormatFloat32,
+            AudioSampleFormat::I16 => AVAudioPCMFormatInt16,
+        };
+        let rate = sample_rate.samples_per_second() as f64;
+        let output_format = match channel_count {
+            AudioChannelCount::Mono => AVAudioFormat::new_with_common_format_sample_rate_channels(common_format, rate, 1, false),
+            AudioChannelCount::Stereo => AVAudioFormat::new_with_common_format_sample_rate_channels(common_format, rate, 2, false),
+            AudioChannelCount::Surround5_1 => AVAudioFormat::new_with_common_format_sample_rate_layout_tag(common_format, rate, kAudioChannelLayoutTag_WAVE_5_1_B, false),
+            AudioChannelCount::Surround7_1 => AVAudioFormat::new_with_common_format_sample_rate_layout_tag(common_format, rate, kAudioChannelLayoutTag_WAVE_7_1, false),
+        };
+        Self {
+            sample_rate,
+            channel_count,
+            sample_format,
+            source,
+            output_format,
+            converter: None,
+        }
+    }
+
+    pub(crate) fn convert(&mut self, sample_buffer: CMSampleBuffer, clock: StreamClock, capture_time: Instant, frame_id: u64) -> Result<MacosAudioFrame, String> {
+        let input_format = AVAudioFormat::new_with_cm_audio_format_description(&sample_buffer.get_format_description())
+            .map_err(|_| "Failed to get audio format of sample buffer".to_string())?;
+        let input_buffer = AVAudioPCMBuffer::new_with_format_frame_capacity(&input_format, sample_buffer.get_num_samples())
+            .map_err(|_| "Failed to allocate audio buffer".to_string())?;
+        sample_buffer.copy_pcm_data_into_audio_buffer(&input_buffer)
+            .map_err(|_| "Failed to copy audio data from sample buffer".to_string())?;
+        let pcm_audio_buffer = if input_format.is_equal(&self.output_format) {
+            input_buffer
+        } else {
+            // The converter carries resampling state between buffers, so it's only recreated if the input format changes
+            let needs_converter = self.converter.as_ref().map_or(true, |(converter_input_format, _)| !converter_input_format.is_equal(&input_format));
+            if needs_converter {
+                let converter = AVAudioConverter::new(&input_format, &self.output_format)
+                    .map_err(|_| "Failed to create audio converter".to_string())?;
+                self.converter = Some((input_format.clone(), converter));
+            }
+            let (_, converter) = self.converter.as_ref().unwrap();
+            let output_capacity = (input_buffer.frame_length() as f64 * self.output_format.sample_rate() / input_format.sample_rate()).ceil() as usize + 1;
+            converter.convert(&input_buffer, &self.output_format, output_capacity)
+                .map_err(|_| "Failed to convert audio".to_string())?
+        };
+        let origin_time = clock.stream_time_from_host_time(host_time_from_host_clock_seconds(sample_buffer.get_presentation_timestamp().seconds_f64()));
+        let duration = std::time::Duration::from_secs_f64(pcm_audio_buffer.frame_length() as f64 / self.sample_rate.samples_per_second() as f64);
+        Ok(MacosAudioFrame {
+            pcm_audio_buffer,
+            sample_rate: self.sample_rate,
+            channel_count: self.channel_count,
+            sample_format: self.sample_format,
+            source: self.source,
+            duration,
+            origin_time,
+            clock,
+            capture_time,
+            frame_id,
+        })
+    }
 }
 
 pub struct MacosAudioFrame {
-    pub(crate) sample_buffer: CMSampleBuffer,
-    pub(crate) audio_format_description: AudioStreamBasicDescription,
-    pub(crate) pcm_audio_buffer: Option<AVAudioPCMBuffer>,
-    pub(crate) block_buffer: Option<CMBlockBuffer>,
-    pub(crate) buffer_list: Option<AudioBufferList>,
+    pub(crate) pcm_audio_buffer: AVAudioPCMBuffer,
+    pub(crate) sample_rate: AudioSampleRate,
+    pub(crate) channel_count: AudioChannelCount,
+    pub(crate) sample_format: AudioSampleFormat,
+    pub(crate) source: AudioSource,
+    pub(crate) duration: Duration,
+    pub(crate) origin_time: Duration,
+    pub(crate) clock: StreamClock,
     pub(crate) capture_time: Instant,
     pub(crate) frame_id: u64,
 }
 
 impl AudioCaptureFrame for MacosAudioFrame {
     fn sample_rate(&self) -> crate::prelude::AudioSampleRate {
-        if self.audio_format_description.sample_rate >= 15500.0 && self.audio_format_description.sample_rate <= 16500.0 {
-            AudioSampleRate::Hz16000
-        } else if self.audio_format_description.sample_rate >= 23500.0 && self.audio_format_description.sample_rate <= 24500.0 {
-            AudioSampleRate::Hz24000
-        } else if self.audio_format_description.sample_rate >= 47500.0 && self.audio_format_description.sample_rate <= 48500.0 {
-            AudioSampleRate::Hz48000
-        } else {
-            AudioSampleRate::Hz8000
-        }
+        self.sample_rate
     }
 
     fn channel_count(&self) -> crate::prelude::AudioChannelCount {
-        if self.audio_format_description.channels_per_frame == 1 {
-            AudioChannelCount::Mono
-        } else {
-            AudioChannelCount::Stereo
-        }
+        self.channel_count
+    }
+
+    fn sample_format(&self) -> AudioSampleFormat {
+        self.sample_format
+    }
+
+    fn source(&self) -> AudioSource {
+        self.source
     }
 
     fn audio_channel_buffer(&mut self, channel: usize) -> Result<AudioChannelData<'_>, AudioBufferError> {
-        let pcm_audio_buffer_ref = if self.pcm_audio_buffer.is_some() {
-            self.pcm_audio_buffer.as_ref().unwrap()
-        } else {
-            if self.audio_format_description.format_flags == kAudioFormatFlagsCanonical {
-                let (audio_buffer_list, block_buffer) = match unsafe { self.sample_buffer.get_audio_buffer_list_with_block_buffer() } {
-                    Ok(x) => x,
-                    Err(()) => return Err(AudioBufferError::Other("CMSampleBuffer::get_audio_buffer_list_with_block_buffer() failed".into()))
-                };
-                self.buffer_list = Some(audio_buffer_list);
-                self.block_buffer = Some(block_buffer);
-                let audio_buffer_list = self.buffer_list.as_ref().unwrap();
-                let av_audio_format = AVAudioFormat::new_with_standard_format_sample_rate_channels(self.audio_format_description.sample_rate, self.audio_format_description.channels_per_frame);
-                if let Ok(pcm_audio_buffer) = AVAudioPCMBuffer::new_with_format_buffer_list_no_copy_deallocator(av_audio_format, audio_buffer_list as *const _) {
-                    self.pcm_audio_buffer = Some(pcm_audio_buffer);
-                    self.pcm_audio_buffer.as_ref().unwrap()
-                } else {
-                    return Err(AudioBufferError::Other("Failed to build PCM audio buffer".into()));
-                }
-            } else {
-                return Err(AudioBufferError::UnsupportedFormat);
-            }
-        };
-        if channel >= pcm_audio_buffer_ref.channel_count() {
+        if channel >= self.pcm_audio_buffer.channel_count() {
             return Err(AudioBufferError::InvalidChannel);
         }
-        let stride = pcm_audio_buffer_ref.stride();
-        if let Some(f32_ptr) = pcm_audio_buffer_ref.f32_buffer(channel) {
-            let data_samples = AudioChannelDataSamples {
-                data: f32_ptr as *const u8,
-                stride,
-                length: pcm_audio_buffer_ref.frame_capacity(),
-                phantom_lifetime: PhantomData
-            };
-            return Ok(AudioChannelData::F32(data_samples));
+        let stride = self.pcm_audio_buffer.stride();
+        let length = self.pcm_audio_buffer.frame_length();
+        match self.sample_format {
+            AudioSampleFormat::F32 => {
+                if let Some(f32_ptr) = self.pcm_audio_buffer.f32_buffer(channel) {
+                    return Ok(AudioChannelData::F32(AudioChannelDataSamples {
+                        data: f32_ptr as *const u8,
+                        stride: stride * std::mem::size_of::<f32>(),
+                        length,
+                        phantom_lifetime: PhantomData
+                    }));
+                }
+            },
+            AudioSampleFormat::I16 => {
+                if let Some(i16_ptr) = self.pcm_audio_buffer.i16_buffer(channel) {
+                    return Ok(AudioChannelData::I16(AudioChannelDataSamples {
+                        data: i16_ptr as *const u8,
+                        stride: stride * std::mem::size_of::<i16>(),
+                        length,
+                        phantom_lifetime: PhantomData
+                    }));
+                }
+            },
         }
         return Err(AudioBufferError::Other("Failed to get audio buffer".into()))
     }
 
     fn duration(&self) -> std::time::Duration {
-        std::time::Duration::from_secs_f64(self.sample_buffer.get_duration().seconds_f64())
+        self.duration
     }
 
     fn origin_time(&self) -> std::time::Duration {
-        std::time::Duration::from_secs_f64(self.sample_buffer.get_presentation_timestamp().seconds_f64())
+        self.origin_time
+    }
+
+    fn stream_clock(&self) -> StreamClock {
+        self.clock
     }
 
     fn frame_id(&self) -> u64 {
@@ -4,6 +4,8 @@ pub(crate) mod capture_stream;
 pub(crate) mod frame;
 pub(crate) mod capturable_content;
 pub(crate) mod objc_wrap;
+pub(crate) mod clock;
+pub(crate) mod audio_device;
 
 pub(crate) use capture_stream::MacosCaptureStream as ImplCaptureStream;
 pub(crate) use capture_stream::MacosAudioCaptureConfig as ImplAudioCaptureConfig;
@@ -14,11 +16,16 @@ pub(crate) use capture_stream::MacosCaptureAccessToken as ImplCaptureAccessToken
 pub(crate) use frame::MacosAudioFrame as ImplAudioFrame;
 pub(crate) use frame::MacosVideoFrame as ImplVideoFrame;
 
+pub(crate) use clock::host_time_now;
+
+pub(crate) use audio_device::MacosAudioDevice as ImplAudioDevice;
+
 pub(crate) use capturable_content::MacosCapturableContent as ImplCapturableContent;
 pub(crate) use capturable_content::MacosCapturableWindow as ImplCapturableWindow;
 pub(crate) use capturable_content::MacosCapturableDisplay as ImplCapturableDisplay;
 pub(crate) use capturable_content::MacosCapturableContentFilter as ImplCapturableContentFilter;
 pub(crate) use capturable_content::MacosCapturableApplication as ImplCapturableApplication;
+pub(crate) use capturable_content::MacosCapturableContentNotifier as ImplCapturableContentNotifier;
 
 /// Mac OS specific extensions for audio capture configs
 pub use capture_stream::MacosAudioCaptureConfigExt;
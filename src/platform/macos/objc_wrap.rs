@@ -13,12 +13,11 @@
 #[link(name = "AVFoundation", kind = "framework")]
 extern "C" {}
 
-use std::{cell::RefCell, collections::HashMap, ffi::CString, ops::{Add, Mul, Sub}, ptr::{addr_of_mut, null, null_mut, NonNull}, sync::Arc, time::{Duration, Instant}};
+use std::{cell::{Cell, RefCell}, collections::HashMap, ffi::CString, ops::{Add, Mul, Sub}, ptr::{addr_of_mut, null, null_mut, NonNull}, sync::Arc, time::{Duration, Instant}};
 
 use block2::{ffi::Class, Block, RcBlock, StackBlock};
 use libc::{c_void, strlen};
 use objc2::{class, declare::ClassBuilder, ffi::{objc_getClass, objc_getProtocol}, msg_send, rc::Id, runtime::{AnyClass, AnyObject, AnyProtocol, Bool, Ivar, Sel}, sel, Encode, Encoding, RefEncode};
-use mach2::mach_time::{mach_timebase_info, mach_timebase_info_data_t};
 
 use crate::{prelude::{AudioSampleRate, StreamCreateError, StreamError, StreamEvent, StreamStopError}};
 
@@ -26,6 +25,7 @@ use lazy_static::lazy_static;
 use parking_lot::Mutex;
 
 use super::ImplPixelFormat;
+use super::clock::host_time_from_mach_absolute_time;
 
 type CFTypeRef = *const c_void;
 type CFStringRef = CFTypeRef;
@@ -46,6 +46,8 @@ type CVPixelBufferRef = CFTypeRef;
 type CGImageRef = CFTypeRef;
 type CGDataProviderRef = CFTypeRef;
 type CFDataRef = CFTypeRef;
+type CGColorSpaceRef = CFTypeRef;
+type CGContextRef = CFTypeRef;
 
 #[repr(C)]
 struct CFStringRefEncoded(CFStringRef);
@@ -100,6 +102,7 @@ extern "C" {
     fn CMSampleBufferGetNumSamples(sbuf: CMSampleBufferRef) -> isize;
     fn CMSampleBufferGetPresentationTimeStamp(sbuf: CMSampleBufferRef) -> CMTime;
     fn CMSampleBufferGetDuration(sbuf: CMSampleBufferRef) -> CMTime;
+    fn CMSampleBufferCopyPCMDataIntoAudioBufferList(sbuf: CMSampleBufferRef, frame_offset: i32, num_frames: i32, buffer_list: *mut AudioBufferList) -> OSStatus;
     fn CMSampleBufferGetFormatDescription(sbuf: CMSampleBufferRef) -> CMFormatDescriptionRef;
     fn CMSampleBufferGetSampleAttachmentsArray(sbuf: CMSampleBufferRef, create_if_necessary: Bool) -> CFArrayRef;
 
@@ -141,6 +144,15 @@ extern "C" {
     static kCGWindowLayer: CFStringRef;
 
     fn CGWindowListCreateDescriptionFromArray(window_array: CFArrayRef) -> CFArrayRef;
+    fn CGWindowListCreate(option: u32, relative_to_window: u32) -> CFArrayRef;
+
+    fn CGGetActiveDisplayList(max_displays: u32, active_displays: *mut u32, display_count: *mut u32) -> i32;
+    fn CGDisplayBounds(display: u32) -> CGRect;
+    fn CGDisplayRegisterReconfigurationCallback(callback: extern "C" fn(u32, u32, *mut c_void), user_info: *mut c_void) -> i32;
+    fn CGDisplayRemoveReconfigurationCallback(callback: extern "C" fn(u32, u32, *mut c_void), user_info: *mut c_void) -> i32;
+    fn CGDisplayVendorNumber(display: u32) -> u32;
+    fn CGDisplayModelNumber(display: u32) -> u32;
+    fn CGDisplaySerialNumber(display: u32) -> u32;
 
     fn CGImageRetain(image: CGImageRef);
     fn CGImageRelease(image: CGImageRef);
@@ -159,6 +171,12 @@ extern "C" {
 
     fn CGWindowLevelForKey(key: i32) -> i32;
 
+    fn CGColorSpaceCreateDeviceRGB() -> CGColorSpaceRef;
+    fn CGColorSpaceRelease(color_space: CGColorSpaceRef);
+    fn CGBitmapContextCreate(data: *mut c_void, width: usize, height: usize, bits_per_component: usize, bytes_per_row: usize, color_space: CGColorSpaceRef, bitmap_info: u32) -> CGContextRef;
+    fn CGContextDrawImage(context: CGContextRef, rect: CGRect, image: CGImageRef);
+    fn CGContextRelease(context: CGContextRef);
+
     pub(crate) fn IOSurfaceIncrementUseCount(r: IOSurfaceRef);
     pub(crate) fn IOSurfaceDecrementUseCount(r: IOSurfaceRef);
 
@@ -190,6 +208,10 @@ extern "C" {
     fn dispatch_retain(AnyObject: *mut AnyObject);
     fn dispatch_release(AnyObject: *mut AnyObject);
 
+    static mut _dispatch_main_q: c_void;
+
+    fn dispatch_async_f(queue: *mut c_void, context: *mut c_void, work: extern "C" fn(*mut c_void));
+
     pub(crate) static SCStreamFrameInfoStatus       : CFStringRef;
     pub(crate) static SCStreamFrameInfoDisplayTime  : CFStringRef;
     pub(crate) static SCStreamFrameInfoScaleFactor  : CFStringRef;
@@ -527,6 +549,10 @@ unsafe impl Encode for CGRect {
     const ENCODING: Encoding = Encoding::Struct("CGRect", &[CGPoint::ENCODING, CGSize::ENCODING]);
 }
 
+unsafe impl RefEncode for CGRect {
+    const ENCODING_REF: Encoding = Encoding::Pointer(&Self::ENCODING);
+}
+
 impl CGRect {
     pub(crate) const ZERO: CGRect = CGRect {
         origin: CGPoint::ZERO,
@@ -956,6 +982,27 @@ impl SCStreamConfiguration {
             *exclude_current_process_audio_ivar.load_mut(&mut *self.0) = Bool::new(exclude_current_process_audio);
         }
     }
+
+    /// Set whether to capture the microphone, returning false if microphone capture isn't supported (before MacOS 15)
+    pub(crate) fn set_capture_microphone(&mut self, capture_microphone: bool) -> bool {
+        unsafe {
+            let supported: bool = msg_send![self.0, respondsToSelector: sel!(setCaptureMicrophone:)];
+            if supported {
+                let _: () = msg_send![self.0, setCaptureMicrophone: Bool::new(capture_microphone)];
+            }
+            supported
+        }
+    }
+
+    pub(crate) fn set_microphone_capture_device_id(&mut self, device_id: &str) {
+        unsafe {
+            let supported: bool = msg_send![self.0, respondsToSelector: sel!(setMicrophoneCaptureDeviceID:)];
+            if supported {
+                let device_id = NSString::new(device_id);
+                let _: () = msg_send![self.0, setMicrophoneCaptureDeviceID: device_id.0];
+            }
+        }
+    }
 }
 
 impl Clone for SCStreamConfiguration {
@@ -1237,6 +1284,7 @@ impl SCStreamCallbackContainer {
 pub enum SCStreamOutputType {
     Screen,
     Audio,
+    Microphone,
 }
 
 impl SCStreamOutputType {
@@ -1244,6 +1292,7 @@ impl SCStreamOutputType {
         SCStreamOutputTypeEncoded(match *self {
             Self::Screen => 0,
             Self::Audio => 1,
+            Self::Microphone => 2,
         })
     }
 
@@ -1251,6 +1300,7 @@ impl SCStreamOutputType {
         match x {
             0 => Some(Self::Screen),
             1 => Some(Self::Audio),
+            2 => Some(Self::Microphone),
             _ => None
         }
     }
@@ -1380,15 +1430,17 @@ impl SCStream {
         self.0.is_null()
     }
 
-    pub fn new(filter: SCContentFilter, config: SCStreamConfiguration, handler_queue: DispatchQueue, handler: SCStreamHandler) -> Result<Self, String> {
+    pub fn new(filter: SCContentFilter, config: SCStreamConfiguration, handler_queue: DispatchQueue, handler: SCStreamHandler, output_types: &[SCStreamOutputType]) -> Result<Self, String> {
         unsafe {
             let instance: *mut AnyObject = msg_send![class!(SCStream), alloc];
             let instance: *mut AnyObject = msg_send![instance, initWithFilter: filter.0 configuration: config.0 delegate: SCStreamDelegate(handler.0)];
-            let mut error: *mut AnyObject = std::ptr::null_mut();
-            let result: bool = msg_send![instance, addStreamOutput: SCStreamOutput(handler.0) type: SCStreamOutputType::Screen.to_encoded() sampleHandlerQueue: handler_queue error: &mut error as *mut _];
-            if !error.is_null() {
-                let error = NSError::from_id_retained(error);
-                println!("error: {}, reason: {}", error.description(), error.reason());
+            for output_type in output_types {
+                let mut error: *mut AnyObject = std::ptr::null_mut();
+                let result: bool = msg_send![instance, addStreamOutput: SCStreamOutput(handler.0) type: output_type.to_encoded() sampleHandlerQueue: handler_queue.clone() error: &mut error as *mut _];
+                if !error.is_null() {
+                    let error = NSError::from_id_retained(error);
+                    return Err(format!("Failed to add {:?} stream output - error: {}, reason: {}", output_type, error.description(), error.reason()));
+                }
             }
             Ok(SCStream(instance))
         }
@@ -1448,6 +1500,22 @@ impl CMSampleBuffer {
         unsafe { CMSampleBufferGetDuration(self.0) }
     }
 
+    pub(crate) fn get_num_samples(&self) -> usize {
+        unsafe { CMSampleBufferGetNumSamples(self.0).max(0) as usize }
+    }
+
+    pub(crate) fn copy_pcm_data_into_audio_buffer(&self, buffer: &AVAudioPCMBuffer) -> Result<(), ()> {
+        let frame_count = self.get_num_samples().min(buffer.frame_capacity());
+        unsafe {
+            let status = CMSampleBufferCopyPCMDataIntoAudioBufferList(self.0, 0, frame_count as i32, buffer.mutable_audio_buffer_list());
+            if status != 0 {
+                return Err(());
+            }
+        }
+        buffer.set_frame_length(frame_count);
+        Ok(())
+    }
+
     pub(crate) fn get_format_description(&self) -> CMFormatDescription {
         let format_desc_ref = unsafe { CMSampleBufferGetFormatDescription(self.0) };
         CMFormatDescription::from_ref_unretained(format_desc_ref)
@@ -1629,12 +1697,156 @@ impl Drop for CMAudioFormatDescription {
     }
 }
 
+#[link(name = "CoreAudio", kind = "framework")]
+extern "C" {
+    fn AudioObjectGetPropertyDataSize(object_id: u32, address: *const AudioObjectPropertyAddress, qualifier_data_size: u32, qualifier_data: *const c_void, out_data_size: *mut u32) -> i32;
+    fn AudioObjectGetPropertyData(object_id: u32, address: *const AudioObjectPropertyAddress, qualifier_data_size: u32, qualifier_data: *const c_void, io_data_size: *mut u32, out_data: *mut c_void) -> i32;
+}
+
+#[repr(C)]
+struct AudioObjectPropertyAddress {
+    selector: u32,
+    scope: u32,
+    element: u32,
+}
+
+const fn four_char_code(code: &[u8; 4]) -> u32 {
+    ((code[0] as u32) << 24) | ((code[1] as u32) << 16) | ((code[2] as u32) << 8) | (code[3] as u32)
+}
+
+const kAudioObjectSystemObject: u32 = 1;
+const kAudioObjectPropertyElementMain: u32 = 0;
+const kAudioObjectPropertyScopeGlobal: u32 = four_char_code(b"glob");
+const kAudioObjectPropertyScopeInput: u32 = four_char_code(b"inpt");
+const kAudioObjectPropertyScopeOutput: u32 = four_char_code(b"outp");
+const kAudioObjectPropertyName: u32 = four_char_code(b"lnam");
+const kAudioHardwarePropertyDevices: u32 = four_char_code(b"dev#");
+const kAudioHardwarePropertyDefaultInputDevice: u32 = four_char_code(b"dIn ");
+const kAudioHardwarePropertyDefaultOutputDevice: u32 = four_char_code(b"dOut");
+const kAudioDevicePropertyDeviceUID: u32 = four_char_code(b"uid ");
+const kAudioDevicePropertyStreamConfiguration: u32 = four_char_code(b"slay");
+const kAudioDevicePropertyNominalSampleRate: u32 = four_char_code(b"nsrt");
+
+/// A CoreAudio hardware device
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct CoreAudioDevice(u32);
+
+impl CoreAudioDevice {
+    fn property_address(selector: u32, scope: u32) -> AudioObjectPropertyAddress {
+        AudioObjectPropertyAddress {
+            selector,
+            scope,
+            element: kAudioObjectPropertyElementMain,
+        }
+    }
+
+    /// Get a variable-length property as raw bytes (with 8 byte alignment)
+    fn get_property_bytes(object_id: u32, selector: u32, scope: u32) -> Option<Vec<u64>> {
+        let address = Self::property_address(selector, scope);
+        unsafe {
+            let mut size = 0u32;
+            if AudioObjectGetPropertyDataSize(object_id, &address as *const _, 0, std::ptr::null(), &mut size as *mut _) != 0 {
+                return None;
+            }
+            let mut data = vec![0u64; (size as usize).div_ceil(8)];
+            if AudioObjectGetPropertyData(object_id, &address as *const _, 0, std::ptr::null(), &mut size as *mut _, data.as_mut_ptr() as *mut c_void) != 0 {
+                return None;
+            }
+            data.truncate((size as usize).div_ceil(8));
+            Some(data)
+        }
+    }
+
+    fn get_property<T: Copy>(object_id: u32, selector: u32, scope: u32) -> Option<T> {
+        let address = Self::property_address(selector, scope);
+        unsafe {
+            let mut value = std::mem::MaybeUninit::<T>::uninit();
+            let mut size = std::mem::size_of::<T>() as u32;
+            if AudioObjectGetPropertyData(object_id, &address as *const _, 0, std::ptr::null(), &mut size as *mut _, value.as_mut_ptr() as *mut c_void) != 0 || size as usize != std::mem::size_of::<T>() {
+                return None;
+            }
+            Some(value.assume_init())
+        }
+    }
+
+    fn get_string_property(&self, selector: u32) -> Option<String> {
+        let cf_string: CFStringRef = Self::get_property(self.0, selector, kAudioObjectPropertyScopeGlobal)?;
+        if cf_string.is_null() {
+            return None;
+        }
+        let string = NSString::from_ref_retained(cf_string).as_string();
+        unsafe { CFRelease(cf_string); }
+        Some(string)
+    }
+
+    /// Get all audio hardware devices
+    pub(crate) fn all_devices() -> Vec<Self> {
+        let Some(data) = Self::get_property_bytes(kAudioObjectSystemObject, kAudioHardwarePropertyDevices, kAudioObjectPropertyScopeGlobal) else {
+            return Vec::new();
+        };
+        let device_ids = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u32, data.len() * 2) };
+        device_ids.iter().filter(|id| **id != 0).map(|id| Self(*id)).collect()
+    }
+
+    /// Get the system default input or output device
+    pub(crate) fn default_device(input: bool) -> Option<Self> {
+        let selector = if input { kAudioHardwarePropertyDefaultInputDevice } else { kAudioHardwarePropertyDefaultOutputDevice };
+        let device_id: u32 = Self::get_property(kAudioObjectSystemObject, selector, kAudioObjectPropertyScopeGlobal)?;
+        if device_id == 0 {
+            None
+        } else {
+            Some(Self(device_id))
+        }
+    }
+
+    /// Get the persistent unique id of the device (the same as the AVCaptureDevice unique id for input devices)
+    pub(crate) fn uid(&self) -> Option<String> {
+        self.get_string_property(kAudioDevicePropertyDeviceUID)
+    }
+
+    pub(crate) fn name(&self) -> Option<String> {
+        self.get_string_property(kAudioObjectPropertyName)
+    }
+
+    /// Get the total number of input or output channels over all streams of the device
+    pub(crate) fn channel_count(&self, input: bool) -> usize {
+        let scope = if input { kAudioObjectPropertyScopeInput } else { kAudioObjectPropertyScopeOutput };
+        let Some(data) = Self::get_property_bytes(self.0, kAudioDevicePropertyStreamConfiguration, scope) else {
+            return 0;
+        };
+        // The data is an AudioBufferList: a u32 buffer count, followed by pointer-aligned AudioBuffers of { channels: u32, size: u32, data: *mut c_void }
+        let words = unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u32, data.len() * 2) };
+        let Some(&buffer_count) = words.first() else {
+            return 0;
+        };
+        (0..buffer_count as usize)
+            .filter_map(|i| words.get(2 + i * 4))
+            .map(|channels| *channels as usize)
+            .sum()
+    }
+
+    pub(crate) fn nominal_sample_rate(&self) -> Option<f64> {
+        Self::get_property(self.0, kAudioDevicePropertyNominalSampleRate, kAudioObjectPropertyScopeGlobal)
+    }
+}
+
 pub(crate) struct AVAudioFormat(*mut AnyObject);
 
+unsafe impl Send for AVAudioFormat {}
+unsafe impl Sync for AVAudioFormat {}
+
 unsafe impl Encode for AVAudioFormat {
     const ENCODING: Encoding = Encoding::Object;
 }
 
+pub(crate) const AVAudioPCMFormatFloat32: usize = 1;
+pub(crate) const AVAudioPCMFormatInt16: usize = 3;
+
+// L R C LFE Rls Rrs
+pub(crate) const kAudioChannelLayoutTag_WAVE_5_1_B: u32 = (190 << 16) | 6;
+// L R C LFE Rls Rrs Ls Rs
+pub(crate) const kAudioChannelLayoutTag_WAVE_7_1: u32 = (192 << 16) | 8;
+
 impl AVAudioFormat {
     pub fn new_with_standard_format_sample_rate_channels(sample_rate: f64, channel_count: u32) -> Self {
         unsafe {
@@ -1643,6 +1855,59 @@ impl AVAudioFormat {
             Self(id)
         }
     }
+
+    pub fn new_with_common_format_sample_rate_channels(common_format: usize, sample_rate: f64, channel_count: u32, interleaved: bool) -> Self {
+        unsafe {
+            let id: *mut AnyObject = msg_send![class!(AVAudioFormat), alloc];
+            let _: *mut AnyObject = msg_send![id, initWithCommonFormat: common_format sampleRate: sample_rate channels: channel_count interleaved: Bool::new(interleaved)];
+            Self(id)
+        }
+    }
+
+    pub fn new_with_common_format_sample_rate_layout_tag(common_format: usize, sample_rate: f64, layout_tag: u32, interleaved: bool) -> Self {
+        unsafe {
+            let layout: *mut AnyObject = msg_send![class!(AVAudioChannelLayout), alloc];
+            let _: *mut AnyObject = msg_send![layout, initWithLayoutTag: layout_tag];
+            let id: *mut AnyObject = msg_send![class!(AVAudioFormat), alloc];
+            let _: *mut AnyObject = msg_send![id, initWithCommonFormat: common_format sampleRate: sample_rate interleaved: Bool::new(interleaved) channelLayout: layout];
+            let _: () = msg_send![layout, release];
+            Self(id)
+        }
+    }
+
+    pub fn new_with_cm_audio_format_description(format_description: &CMFormatDescription) -> Result<Self, ()> {
+        unsafe {
+            let id: *mut AnyObject = msg_send![class!(AVAudioFormat), alloc];
+            let result: *mut AnyObject = msg_send![id, initWithCMAudioFormatDescription: format_description.0];
+            if result.is_null() {
+                Err(())
+            } else {
+                Ok(Self(result))
+            }
+        }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        unsafe { msg_send![self.0, sampleRate] }
+    }
+
+    pub fn channel_count(&self) -> u32 {
+        unsafe { msg_send![self.0, channelCount] }
+    }
+
+    pub fn is_equal(&self, other: &AVAudioFormat) -> bool {
+        unsafe {
+            let equal: Bool = msg_send![self.0, isEqual: other.0];
+            equal.as_bool()
+        }
+    }
+}
+
+impl Clone for AVAudioFormat {
+    fn clone(&self) -> Self {
+        unsafe { let _: *mut AnyObject = msg_send![self.0, retain]; }
+        Self(self.0)
+    }
 }
 
 impl Drop for AVAudioFormat {
@@ -1715,6 +1980,9 @@ impl Drop for CMBlockBuffer {
 
 pub(crate) struct AVAudioPCMBuffer(*mut AnyObject);
 
+unsafe impl Send for AVAudioPCMBuffer {}
+unsafe impl Sync for AVAudioPCMBuffer {}
+
 impl AVAudioPCMBuffer {
     pub fn new_with_format_buffer_list_no_copy_deallocator(format: AVAudioFormat, buffer_list_no_copy: *const AudioBufferList) -> Result<Self, ()> {
         unsafe {
@@ -1729,76 +1997,151 @@ impl AVAudioPCMBuffer {
         }
     }
 
+    pub fn new_with_format_frame_capacity(format: &AVAudioFormat, frame_capacity: usize) -> Result<Self, ()> {
+        unsafe {
+            let id: *mut AnyObject = msg_send![class!(AVAudioPCMBuffer), alloc];
+            let result: *mut AnyObject = msg_send![id, initWithPCMFormat: format.0 frameCapacity: frame_capacity as u32];
+            if result.is_null() {
+                Err(())
+            } else {
+                Ok(Self(result))
+            }
+        }
+    }
+
+    pub fn format(&self) -> AVAudioFormat {
+        unsafe {
+            let format: *mut AnyObject = msg_send![self.0, format];
+            let _: *mut AnyObject = msg_send![format, retain];
+            AVAudioFormat(format)
+        }
+    }
+
+    /// The distance between consecutive samples of a channel, in samples
     pub fn stride(&self) -> usize {
         unsafe { msg_send![self.0, stride] }
     }
 
     pub fn frame_capacity(&self) -> usize {
-        unsafe { msg_send![self.0, frameCapacity] }
+        unsafe {
+            let frame_capacity: u32 = msg_send![self.0, frameCapacity];
+            frame_capacity as usize
+        }
+    }
+
+    pub fn frame_length(&self) -> usize {
+        unsafe {
+            let frame_length: u32 = msg_send![self.0, frameLength];
+            frame_length as usize
+        }
+    }
+
+    pub fn set_frame_length(&self, frame_length: usize) {
+        unsafe { let _: () = msg_send![self.0, setFrameLength: frame_length as u32]; }
     }
 
     pub fn channel_count(&self) -> usize {
-        unsafe { msg_send![self.0, stride] }
+        self.format().channel_count() as usize
     }
 
-    pub fn f32_buffer(&self, channel: usize) -> Option<*const f32> {
-        let channel_count = self.stride();
-        if channel >= channel_count {
+    pub fn mutable_audio_buffer_list(&self) -> *mut AudioBufferList {
+        unsafe { msg_send![self.0, mutableAudioBufferList] }
+    }
+
+    unsafe fn channel_data<T>(&self, channel: usize, all_channels_data_ptr: *const *const T) -> Option<*const T> {
+        let channel_count = self.channel_count();
+        if channel >= channel_count || all_channels_data_ptr.is_null() {
             return None;
         }
+        let all_channels_data = std::slice::from_raw_parts(all_channels_data_ptr, channel_count);
+        let channel_data = all_channels_data[channel];
+        if channel_data.is_null() {
+            None
+        } else {
+            Some(channel_data)
+        }
+    }
+
+    pub fn f32_buffer(&self, channel: usize) -> Option<*const f32> {
         unsafe {
             let all_channels_data_ptr: *const *const f32 = msg_send![self.0, floatChannelData];
-            if all_channels_data_ptr.is_null() {
-                return None;
-            }
-            let all_channels_data = std::slice::from_raw_parts(all_channels_data_ptr, channel_count);
-            let channel_data = all_channels_data[channel];
-            if channel_data.is_null() {
-                None
-            } else {
-                Some(channel_data)
-            }
+            self.channel_data(channel, all_channels_data_ptr)
         }
     }
 
     pub fn i32_buffer(&self, channel: usize) -> Option<*const i32> {
-        let channel_count = self.stride();
-        if channel >= channel_count {
-            return None;
-        }
         unsafe {
             let all_channels_data_ptr: *const *const i32 = msg_send![self.0, int32ChannelData];
-            if all_channels_data_ptr.is_null() {
-                return None;
-            }
-            let all_channels_data = std::slice::from_raw_parts(all_channels_data_ptr, channel_count);
-            let channel_data = all_channels_data[channel];
-            if channel_data.is_null() {
-                None
-            } else {
-                Some(channel_data)
-            }
+            self.channel_data(channel, all_channels_data_ptr)
         }
     }
 
     pub fn i16_buffer(&self, channel: usize) -> Option<*const i16> {
-        let channel_count = self.stride();
-        if channel >= channel_count {
-            return None;
+        unsafe {
+            let all_channels_data_ptr: *const *const i16 = msg_send![self.0, int16ChannelData];
+            self.channel_data(channel, all_channels_data_ptr)
         }
+    }
+}
+
+impl Drop for AVAudioPCMBuffer {
+    fn drop(&mut self) {
+        unsafe { let _: () = msg_send![self.0, release]; }
+    }
+}
+
+const AVAudioConverterInputStatus_HaveData: isize = 0;
+const AVAudioConverterInputStatus_NoDataNow: isize = 1;
+const AVAudioConverterOutputStatus_Error: isize = 3;
+
+pub(crate) struct AVAudioConverter(*mut AnyObject);
+
+unsafe impl Send for AVAudioConverter {}
+unsafe impl Sync for AVAudioConverter {}
+
+impl AVAudioConverter {
+    pub fn new(from_format: &AVAudioFormat, to_format: &AVAudioFormat) -> Result<Self, ()> {
         unsafe {
-            let all_channels_data_ptr: *const *const i16 = msg_send![self.0, int32ChannelData];
-            if all_channels_data_ptr.is_null() {
-                return None;
-            }
-            let all_channels_data = std::slice::from_raw_parts(all_channels_data_ptr, channel_count);
-            let channel_data = all_channels_data[channel];
-            if channel_data.is_null() {
-                None
+            let id: *mut AnyObject = msg_send![class!(AVAudioConverter), alloc];
+            let result: *mut AnyObject = msg_send![id, initFromFormat: from_format.0 toFormat: to_format.0];
+            if result.is_null() {
+                Err(())
             } else {
-                Some(channel_data)
+                Ok(Self(result))
+            }
+        }
+    }
+
+    /// Convert one buffer of input, keeping any resampler state for the next buffer
+    pub fn convert(&self, input: &AVAudioPCMBuffer, output_format: &AVAudioFormat, output_capacity: usize) -> Result<AVAudioPCMBuffer, ()> {
+        let output = AVAudioPCMBuffer::new_with_format_frame_capacity(output_format, output_capacity)?;
+        let input_ptr = input.0 as usize;
+        let input_consumed = Cell::new(false);
+        let input_block = RcBlock::new(move |_packet_count: u32, input_status: *mut isize| -> *mut AnyObject {
+            unsafe {
+                if input_consumed.replace(true) {
+                    *input_status = AVAudioConverterInputStatus_NoDataNow;
+                    null_mut()
+                } else {
+                    *input_status = AVAudioConverterInputStatus_HaveData;
+                    input_ptr as *mut AnyObject
+                }
+            }
+        });
+        unsafe {
+            let mut error: *mut AnyObject = null_mut();
+            let status: isize = msg_send![self.0, convertToBuffer: output.0 error: &mut error as *mut *mut AnyObject withInputFromBlock: &*input_block];
+            if status == AVAudioConverterOutputStatus_Error {
+                return Err(());
             }
         }
+        Ok(output)
+    }
+}
+
+impl Drop for AVAudioConverter {
+    fn drop(&mut self) {
+        unsafe { let _: () = msg_send![self.0, release]; }
     }
 }
 
@@ -1969,6 +2312,165 @@ impl Drop for SCRunningApplication {
     }
 }
 
+pub(crate) struct NSRunningApplication(*mut AnyObject);
+
+impl NSRunningApplication {
+    pub(crate) fn from_pid(pid: i32) -> Option<Self> {
+        unsafe {
+            let id: *mut AnyObject = msg_send![class!(NSRunningApplication), runningApplicationWithProcessIdentifier: pid];
+            if id.is_null() {
+                return None;
+            }
+            let _: *mut AnyObject = msg_send![id, retain];
+            Some(Self(id))
+        }
+    }
+
+    pub(crate) fn executable_path(&self) -> Option<String> {
+        unsafe {
+            let url: *mut AnyObject = msg_send![self.0, executableURL];
+            if url.is_null() {
+                return None;
+            }
+            let path: *mut AnyObject = msg_send![url, path];
+            if path.is_null() {
+                return None;
+            }
+            Some(NSString::from_id_unretained(path).as_string())
+        }
+    }
+
+    /// Render the application icon into a non-premultiplied RGBA8 bitmap of the given size
+    pub(crate) fn icon_rgba8(&self, size: usize) -> Result<Vec<[u8; 4]>, ()> {
+        unsafe {
+            let image: *mut AnyObject = msg_send![self.0, icon];
+            if image.is_null() {
+                return Err(());
+            }
+            let mut proposed_rect = CGRect {
+                origin: CGPoint::ZERO,
+                size: CGSize { x: size as f64, y: size as f64 },
+            };
+            let cg_image: CGImageRef = msg_send![image, CGImageForProposedRect: &mut proposed_rect as *mut CGRect, context: null_mut::<AnyObject>(), hints: null_mut::<AnyObject>()];
+            if cg_image.is_null() {
+                return Err(());
+            }
+            let mut data = vec![[0u8; 4]; size * size];
+            let color_space = CGColorSpaceCreateDeviceRGB();
+            let context = CGBitmapContextCreate(data.as_mut_ptr() as *mut c_void, size, size, 8, size * 4, color_space, kCGImageAlphaPremultipliedLast | kCGBitmapInfoByteOrder32Big);
+            CGColorSpaceRelease(color_space);
+            if context.is_null() {
+                return Err(());
+            }
+            CGContextDrawImage(context, CGRect { origin: CGPoint::ZERO, size: CGSize { x: size as f64, y: size as f64 } }, cg_image);
+            CGContextRelease(context);
+            for pixel in data.iter_mut() {
+                let alpha = pixel[3] as u32;
+                if alpha != 0 && alpha != 255 {
+                    for channel in pixel.iter_mut().take(3) {
+                        *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+                    }
+                }
+            }
+            Ok(data)
+        }
+    }
+}
+
+impl Drop for NSRunningApplication {
+    fn drop(&mut self) {
+        unsafe { let _: () = msg_send![self.0, release]; }
+    }
+}
+
+const WORKSPACE_CHANGE_NOTIFICATIONS: [&str; 5] = [
+    "NSWorkspaceDidLaunchApplicationNotification",
+    "NSWorkspaceDidTerminateApplicationNotification",
+    "NSWorkspaceDidHideApplicationNotification",
+    "NSWorkspaceDidUnhideApplicationNotification",
+    "NSWorkspaceActiveSpaceDidChangeNotification",
+];
+
+type WorkspaceChangeCallback = Arc<dyn Fn() + Send + Sync>;
+
+extern "C" fn workspace_display_reconfiguration_callback(_display: u32, _flags: u32, user_info: *mut c_void) {
+    unsafe {
+        let callback = &*(user_info as *const WorkspaceChangeCallback);
+        (callback)();
+    }
+}
+
+extern "C" fn release_workspace_display_reconfiguration_context(context: *mut c_void) {
+    unsafe {
+        drop(Box::from_raw(context as *mut WorkspaceChangeCallback));
+    }
+}
+
+/// Observes NSWorkspace application and space notifications, and display reconfigurations
+/// 
+/// Note: these notifications are delivered through the main run loop
+pub(crate) struct NSWorkspaceChangeObserver {
+    notification_center: *mut AnyObject,
+    observers: Vec<*mut AnyObject>,
+    display_reconfiguration_context: *mut WorkspaceChangeCallback,
+}
+
+// Safety: the raw pointers are only used to register observers in `new` and to remove and release them in `drop`.
+// NSNotificationCenter is documented as thread-safe, so observers can be added and removed from any thread, and the
+// retained center and observer tokens may be released from any thread. The callback is `Send + Sync`, and the
+// notification block and the display reconfiguration context each own a reference to it, which they release
+// independently (see `drop`), so a callback that's already running or queued never sees it freed.
+unsafe impl Send for NSWorkspaceChangeObserver {}
+unsafe impl Sync for NSWorkspaceChangeObserver {}
+
+impl NSWorkspaceChangeObserver {
+    pub(crate) fn new(callback: impl Fn() + Send + Sync + 'static) -> Self {
+        let callback: WorkspaceChangeCallback = Arc::new(callback);
+        unsafe {
+            let workspace: *mut AnyObject = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let notification_center: *mut AnyObject = msg_send![workspace, notificationCenter];
+            let _: *mut AnyObject = msg_send![notification_center, retain];
+            // The block owns its own reference, which is released when the notification center releases the block
+            let block_callback = callback.clone();
+            let block = RcBlock::new(move |_notification: *mut AnyObject| {
+                (block_callback)();
+            });
+            let mut observers = Vec::new();
+            for name in WORKSPACE_CHANGE_NOTIFICATIONS {
+                let name = NSString::new(name);
+                let observer: *mut AnyObject = msg_send![notification_center, addObserverForName: name.0 object: null_mut::<AnyObject>() queue: null_mut::<AnyObject>() usingBlock: &*block];
+                if !observer.is_null() {
+                    let _: *mut AnyObject = msg_send![observer, retain];
+                    observers.push(observer);
+                }
+            }
+            let display_reconfiguration_context = Box::into_raw(Box::new(callback));
+            CGDisplayRegisterReconfigurationCallback(workspace_display_reconfiguration_callback, display_reconfiguration_context as *mut c_void);
+            Self {
+                notification_center,
+                observers,
+                display_reconfiguration_context,
+            }
+        }
+    }
+}
+
+impl Drop for NSWorkspaceChangeObserver {
+    fn drop(&mut self) {
+        unsafe {
+            CGDisplayRemoveReconfigurationCallback(workspace_display_reconfiguration_callback, self.display_reconfiguration_context as *mut c_void);
+            for observer in self.observers.drain(..) {
+                let _: () = msg_send![self.notification_center, removeObserver: observer];
+                let _: () = msg_send![observer, release];
+            }
+            let _: () = msg_send![self.notification_center, release];
+            // Display reconfiguration callbacks run on the main thread, so the context is released from the main queue,
+            // after any callback that's already running or queued there
+            dispatch_async_f(addr_of_mut!(_dispatch_main_q), self.display_reconfiguration_context as *mut c_void, release_workspace_display_reconfiguration_context);
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub(crate) enum CGDisplayStreamFrameStatus {
     Complete,
@@ -1995,25 +2497,16 @@ pub(crate) struct CGDisplayStream{
 }
 
 impl CGDisplayStream {
+    /// Create a display stream, calling back with the status, host time, and surface of each frame
     pub fn new(callback: impl Fn(CGDisplayStreamFrameStatus, Duration, IOSurface) + 'static, display_id: u32, size: (usize, usize), pixel_format: SCStreamPixelFormat, options_dict: NSDictionary, dispatch_queue: DispatchQueue) -> Self {
-        let absolute_time_start = Arc::new(Mutex::new(None));
         let callback = Arc::new(callback);
         let callback_block = StackBlock::new(move |status: i32, display_time: u64, iosurface_ref: IOSurfaceRef, stream_update_ref: CGDisplayStreamUpdateRef| {
             if let Some(status) = CGDisplayStreamFrameStatus::from_i32(status) {
-                let abs_time_start_opt = { *absolute_time_start.lock() };
-                let relative_time = if let Some(absolute_time_start) = abs_time_start_opt {
-                    display_time.saturating_sub(absolute_time_start)
-                } else {
-                    *absolute_time_start.lock() = Some(display_time);
-                    0
-                };
+                // The display time is a mach absolute time
+                let host_time = host_time_from_mach_absolute_time(display_time);
                 unsafe {
-                    let mut timebase_info: mach_timebase_info_data_t = Default::default();
-                    mach_timebase_info(&mut timebase_info as *mut _);
-                    let time_ns = ((relative_time as u128 * timebase_info.numer as u128) / timebase_info.denom as u128);
-                    let time = Duration::from_nanos(time_ns as u64);
                     let io_surface = IOSurface::from_ref_unretained(iosurface_ref);
-                    (callback)(status, time, io_surface);
+                    (callback)(status, host_time, io_surface);
                 }
             }
         }).copy();
@@ -3146,6 +3639,53 @@ pub(crate) fn get_window_description(window: CGWindowID) -> Result<WindowDescrip
     }
 }
 
+const kCGWindowListOptionOnScreenOnly: u32 = 1 << 0;
+const kCGNullWindowID: u32 = 0;
+
+/// Get the ids of all on-screen windows, ordered from front to back
+pub(crate) fn get_onscreen_window_ids() -> Result<Vec<CGWindowID>, ()> {
+    unsafe {
+        let window_list = CGWindowListCreate(kCGWindowListOptionOnScreenOnly, kCGNullWindowID);
+        if window_list.is_null() {
+            return Err(());
+        }
+        let window_list = CFArray::from_ref_retained(window_list);
+        let mut window_ids = Vec::with_capacity(window_list.get_count() as usize);
+        for i in 0..window_list.get_count() {
+            window_ids.push(CGWindowID(window_list.get_value_at_index(i) as usize as u32));
+        }
+        Ok(window_ids)
+    }
+}
+
+/// Get the bounds of all active displays in global display coordinates
+pub(crate) fn get_active_display_bounds() -> Vec<CGRect> {
+    unsafe {
+        let mut display_count = 0u32;
+        if CGGetActiveDisplayList(0, std::ptr::null_mut(), &mut display_count as *mut _) != 0 {
+            return Vec::new();
+        }
+        let mut display_ids = vec![0u32; display_count as usize];
+        if CGGetActiveDisplayList(display_count, display_ids.as_mut_ptr(), &mut display_count as *mut _) != 0 {
+            return Vec::new();
+        }
+        display_ids.truncate(display_count as usize);
+        display_ids.into_iter().map(|display_id| CGDisplayBounds(display_id)).collect()
+    }
+}
+
+/// Get an identifier for the display hardware, built from the vendor, model and serial numbers in the display's EDID
+pub(crate) fn get_display_hardware_id(display_id: u32) -> Option<String> {
+    let (vendor, model, serial) = unsafe {
+        (CGDisplayVendorNumber(display_id), CGDisplayModelNumber(display_id), CGDisplaySerialNumber(display_id))
+    };
+    // 0xFFFFFFFF indicates the value is unknown
+    if vendor == 0xFFFFFFFF || model == 0xFFFFFFFF {
+        return None;
+    }
+    Some(format!("{:08X}:{:08X}:{:08X}", vendor, model, serial))
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct WindowLevels {
     pub base                : i32,
@@ -1,12 +1,41 @@
-use std::{ffi::c_void, time::Duration};
+use std::{sync::{atomic::{self, AtomicBool}, mpsc, Arc}, thread::JoinHandle, time::{Duration, Instant}};
 
-use windows::{core::Interface, Win32::{Media::Audio::{eConsole, eRender, IAudioCaptureClient, IAudioClient, IMMDeviceEnumerator, MMDeviceEnumerator, AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_LOOPBACK, WAVEFORMATEX, WAVE_FORMAT_PCM}, System::Com::{CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED}}};
+use windows::{core::HSTRING, Win32::{Media::{Audio::{eConsole, IAudioCaptureClient, IAudioClient, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator, AUDCLNT_BUFFERFLAGS_SILENT, AUDCLNT_E_DEVICE_INVALIDATED, AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM, AUDCLNT_STREAMFLAGS_LOOPBACK, AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY, WAVEFORMATEX, WAVEFORMATEXTENSIBLE, WAVEFORMATEXTENSIBLE_0}, KernelStreaming::{KSDATAFORMAT_SUBTYPE_PCM, SPEAKER_BACK_LEFT, SPEAKER_BACK_RIGHT, SPEAKER_FRONT_CENTER, SPEAKER_FRONT_LEFT, SPEAKER_FRONT_RIGHT, SPEAKER_LOW_FREQUENCY, SPEAKER_SIDE_LEFT, SPEAKER_SIDE_RIGHT, WAVE_FORMAT_EXTENSIBLE}, Multimedia::KSDATAFORMAT_SUBTYPE_IEEE_FLOAT}, System::Com::{CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_ALL, COINIT_MULTITHREADED}}};
 
-use crate::prelude::{AudioCaptureConfig, AudioChannelCount, AudioSampleRate};
+use crate::prelude::{AudioCaptureConfig, AudioChannelPosition, AudioDeviceKind, AudioSampleFormat, AudioSampleRate, StreamClock};
+
+use super::{audio_device::{data_flow_for_kind, device_id}, clock::host_time_from_100ns};
+
+/// The endpoint that an audio capture stream records from
+pub(crate) enum WindowsAudioEndpoint {
+    /// The default device of a kind, following changes to the default device
+    Default(AudioDeviceKind),
+    /// A specific device
+    Device {
+        id: String,
+        kind: AudioDeviceKind,
+    },
+}
+
+impl WindowsAudioEndpoint {
+    fn kind(&self) -> AudioDeviceKind {
+        match self {
+            Self::Default(kind) => *kind,
+            Self::Device { kind, .. } => *kind,
+        }
+    }
+
+    unsafe fn device(&self, mm_device_enumerator: &IMMDeviceEnumerator) -> windows::core::Result<IMMDevice> {
+        match self {
+            Self::Default(kind) => mm_device_enumerator.GetDefaultAudioEndpoint(data_flow_for_kind(*kind), eConsole),
+            Self::Device { id, .. } => mm_device_enumerator.GetDevice(&HSTRING::from(id.as_str())),
+        }
+    }
+}
 
 pub struct WindowsAudioCaptureStream {
-    should_couninit: bool,
-    audio_client: IAudioClient,
+    stop_flag: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
 }
 
 pub enum WindowsAudioCaptureStreamCreateError {
@@ -20,156 +49,347 @@ pub enum WindowsAudioCaptureStreamCreateError {
 
 pub enum WindowsAudioCaptureStreamError {
     Other(String),
-    GetBufferFailed,
 }
 
 #[allow(unused)]
 pub struct WindowsAudioCaptureStreamPacket<'a> {
-    pub(crate) data: &'a [i16],
+    /// Interleaved samples in the requested sample format, or None if the packet is silent
+    pub(crate) data: Option<&'a [u8]>,
+    pub(crate) frame_count: usize,
     pub(crate) channel_count: u32,
+    /// The time since the start of the stream clock of the first frame in the packet
     pub(crate) origin_time: Duration,
     pub(crate) duration: Duration,
+    /// The stream clock sample index of the first frame in the packet
     pub(crate) sample_index: u64,
 }
 
-struct SendCaptureClient(*mut c_void);
+pub type WindowsAudioCaptureStreamCallback = Box<dyn for <'a> FnMut(Result<WindowsAudioCaptureStreamPacket<'a>, WindowsAudioCaptureStreamError>) + Send + 'static>;
+
+/// How often to check whether the default device has changed
+const DEFAULT_DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Places audio packets on the stream clock's timeline, as sample indices
+///
+/// Each client's device position restarts from zero, so the first packet from a client is anchored to the stream clock by
+/// its host time, and later packets advance from there by device position, keeping them sample-accurate. A client opened
+/// for a new default device is anchored again, but never before the end of the samples already delivered, so the timeline
+/// stays monotonic across the switch, with a gap for any audio that was missed while switching.
+struct WindowsAudioPacketTimeline {
+    clock: StreamClock,
+    sample_rate: AudioSampleRate,
+    /// The sample index of the current client's device position zero, once its first packet has arrived
+    client_origin_index: Option<u64>,
+    /// The sample index just after the last packet
+    next_sample_index: u64,
+}
+
+impl WindowsAudioPacketTimeline {
+    fn new(clock: StreamClock, sample_rate: AudioSampleRate) -> Self {
+        Self {
+            clock,
+            sample_rate,
+            client_origin_index: None,
+            next_sample_index: 0,
+        }
+    }
 
-unsafe impl Send for SendCaptureClient {}
-unsafe impl Sync for SendCaptureClient {}
+    /// Anchor the next packet again, for a newly opened client
+    fn reopen(&mut self) {
+        self.client_origin_index = None;
+    }
 
-impl SendCaptureClient {
-    fn from_iaudiocaptureclient(client: IAudioCaptureClient) -> Self {
-        SendCaptureClient(client.into_raw())
+    /// Get the sample index of a packet from its device position (in frames) and the host time of its first frame
+    fn place(&mut self, device_position: u64, host_time: Duration, frame_count: usize) -> u64 {
+        let client_origin_index = *self.client_origin_index.get_or_insert_with(|| {
+            let host_sample_index = self.clock.sample_index(self.clock.stream_time_from_host_time(host_time), self.sample_rate);
+            host_sample_index.saturating_sub(device_position).max(self.next_sample_index)
+        });
+        let sample_index = (client_origin_index + device_position).max(self.next_sample_index);
+        self.next_sample_index = sample_index + frame_count as u64;
+        sample_index
     }
 
-    fn into_iaudiocaptureclient(self) -> IAudioCaptureClient {
-        unsafe { IAudioCaptureClient::from_raw(self.0) }
+    fn origin_time(&self, sample_index: u64) -> Duration {
+        self.clock.sample_time(sample_index, self.sample_rate)
     }
 }
 
-impl WindowsAudioCaptureStream {
-    pub fn new(config: AudioCaptureConfig, mut callback: Box<dyn for <'a> FnMut(Result<WindowsAudioCaptureStreamPacket<'a>, WindowsAudioCaptureStreamError>) + Send + 'static>) -> Result<Self, WindowsAudioCaptureStreamCreateError> {
-        unsafe {
-            let should_couninit = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
-
-            let mm_device_enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
-                .map_err(|e| WindowsAudioCaptureStreamCreateError::Other(format!("Failed to create MMDeviceEnumerator: {}", e.to_string())))?;
-            let device = mm_device_enumerator.GetDefaultAudioEndpoint(eRender, eConsole)
-                .map_err(|_| WindowsAudioCaptureStreamCreateError::EndpointEnumerationFailed)?;
-            
-            let audio_client: IAudioClient = device.Activate(CLSCTX_ALL, None)
-                .map_err(|_| WindowsAudioCaptureStreamCreateError::AudioClientActivationFailed)?;
-
-            let mut format = WAVEFORMATEX::default();
-            format.wFormatTag = WAVE_FORMAT_PCM as u16;
-            format.nSamplesPerSec = match config.sample_rate {
-                AudioSampleRate::Hz8000  =>  8000,
-                AudioSampleRate::Hz16000 => 16000,
-                AudioSampleRate::Hz24000 => 24000,
-                AudioSampleRate::Hz48000 => 48000,
-            };
-            format.wBitsPerSample = 16;
-            format.nChannels = match config.channel_count {
-                AudioChannelCount::Mono   => 1,
-                AudioChannelCount::Stereo => 2,
+/// An initialized and started audio client for one device
+struct WindowsAudioClient {
+    device_id: String,
+    audio_client: IAudioClient,
+    capture_client: IAudioCaptureClient,
+    channel_count: u16,
+    sample_rate: u32,
+    block_align: usize,
+    half_buffer_duration: Duration,
+}
+
+impl WindowsAudioClient {
+    unsafe fn open(mm_device_enumerator: &IMMDeviceEnumerator, config: &AudioCaptureConfig, endpoint: &WindowsAudioEndpoint) -> Result<Self, WindowsAudioCaptureStreamCreateError> {
+        let device = endpoint.device(mm_device_enumerator)
+            .map_err(|_| WindowsAudioCaptureStreamCreateError::EndpointEnumerationFailed)?;
+        let device_id = device_id(&device)
+            .map_err(|_| WindowsAudioCaptureStreamCreateError::EndpointEnumerationFailed)?;
+
+        let audio_client: IAudioClient = device.Activate(CLSCTX_ALL, None)
+            .map_err(|_| WindowsAudioCaptureStreamCreateError::AudioClientActivationFailed)?;
+
+        let channel_count = config.channel_count.count() as u16;
+        let (bits_per_sample, sub_format) = match config.sample_format {
+            AudioSampleFormat::F32 => (32, KSDATAFORMAT_SUBTYPE_IEEE_FLOAT),
+            AudioSampleFormat::I16 => (16, KSDATAFORMAT_SUBTYPE_PCM),
+        };
+        let channel_mask = config.channel_count.positions().iter().fold(0u32, |mask, position| mask | match position {
+            AudioChannelPosition::FrontLeft    => SPEAKER_FRONT_LEFT,
+            AudioChannelPosition::FrontRight   => SPEAKER_FRONT_RIGHT,
+            AudioChannelPosition::FrontCenter  => SPEAKER_FRONT_CENTER,
+            AudioChannelPosition::LowFrequency => SPEAKER_LOW_FREQUENCY,
+            AudioChannelPosition::BackLeft     => SPEAKER_BACK_LEFT,
+            AudioChannelPosition::BackRight    => SPEAKER_BACK_RIGHT,
+            AudioChannelPosition::SideLeft     => SPEAKER_SIDE_LEFT,
+            AudioChannelPosition::SideRight    => SPEAKER_SIDE_RIGHT,
+        });
+        let format = WAVEFORMATEXTENSIBLE {
+            Format: WAVEFORMATEX {
+                wFormatTag: WAVE_FORMAT_EXTENSIBLE as u16,
+                nChannels: channel_count,
+                nSamplesPerSec: config.sample_rate.samples_per_second(),
+                nAvgBytesPerSec: config.sample_rate.samples_per_second() * (channel_count * bits_per_sample / 8) as u32,
+                nBlockAlign: channel_count * bits_per_sample / 8,
+                wBitsPerSample: bits_per_sample,
+                cbSize: (std::mem::size_of::<WAVEFORMATEXTENSIBLE>() - std::mem::size_of::<WAVEFORMATEX>()) as u16,
+            },
+            Samples: WAVEFORMATEXTENSIBLE_0 { wValidBitsPerSample: bits_per_sample },
+            dwChannelMask: channel_mask,
+            SubFormat: sub_format,
+        };
+
+        let sample_rate = format.Format.nSamplesPerSec;
+        let block_align = format.Format.nBlockAlign as usize;
+
+        let buffer_size = 512;
+        let buffer_time = buffer_size as i64 * 10000000i64 / sample_rate as i64;
+
+        let buffer_duration = Duration::from_nanos(buffer_time as u64 * 100);
+        let half_buffer_duration = buffer_duration / 2;
+
+        // Let the audio engine convert from the mix format (rate, channel layout, and sample format) to the requested format
+        let mut stream_flags = AUDCLNT_STREAMFLAGS_AUTOCONVERTPCM | AUDCLNT_STREAMFLAGS_SRC_DEFAULT_QUALITY;
+        // Output devices are captured as what they play
+        if endpoint.kind() == AudioDeviceKind::Output {
+            stream_flags |= AUDCLNT_STREAMFLAGS_LOOPBACK;
+        }
+        audio_client.Initialize(AUDCLNT_SHAREMODE_SHARED, stream_flags, buffer_time, 0, &format as *const _ as *const WAVEFORMATEX, None)
+            .map_err(|_| WindowsAudioCaptureStreamCreateError::AudioClientInitializeFailed)?;
+
+        let capture_client : IAudioCaptureClient = audio_client.GetService()
+            .map_err(|_| WindowsAudioCaptureStreamCreateError::AudioCaptureCreationFailed)?;
+
+        audio_client.Start()
+            .map_err(|_| WindowsAudioCaptureStreamCreateError::StreamStartFailed)?;
+
+        Ok(Self {
+            device_id,
+            audio_client,
+            capture_client,
+            channel_count,
+            sample_rate,
+            block_align,
+            half_buffer_duration,
+        })
+    }
+
+    /// Deliver all available packets to the callback
+    unsafe fn read_packets(&self, timeline: &mut WindowsAudioPacketTimeline, callback: &mut WindowsAudioCaptureStreamCallback) -> windows::core::Result<()> {
+        loop {
+            if self.capture_client.GetNextPacketSize()? == 0 {
+                return Ok(());
+            }
+
+            let mut data_ptr: *mut u8 = std::ptr::null_mut();
+
+            let mut num_frames = 0u32;
+            let mut flags = 0u32;
+            let mut device_position = 0u64;
+            let mut qpc_position = 0u64;
+
+            self.capture_client.GetBuffer(&mut data_ptr as *mut _, &mut num_frames as *mut _, &mut flags as *mut _, Some(&mut device_position as *mut _), Some(&mut qpc_position as *mut _))?;
+            let silent = (flags & AUDCLNT_BUFFERFLAGS_SILENT.0 as u32) != 0;
+            // The device position is measured in frames, and the QPC position in 100ns units of the performance counter
+            let sample_index = timeline.place(device_position, host_time_from_100ns(qpc_position as i64), num_frames as usize);
+            let packet = WindowsAudioCaptureStreamPacket {
+                data: if silent { None } else { Some(std::slice::from_raw_parts(data_ptr as *const u8, num_frames as usize * self.block_align)) },
+                frame_count: num_frames as usize,
+                channel_count: self.channel_count as u32,
+                origin_time: timeline.origin_time(sample_index),
+                duration: Duration::from_secs_f64(num_frames as f64 / self.sample_rate as f64),
+                sample_index,
             };
-            format.nBlockAlign = format.nChannels * 2;
-            format.nAvgBytesPerSec = format.nSamplesPerSec * format.nBlockAlign as u32;
-            format.cbSize = 0;
-
-            let callback_format = format.clone();
-
-            let buffer_size = 512;
-            let buffer_time = buffer_size as i64 * 10000000i64 / format.nSamplesPerSec as i64;
-
-            let buffer_duration = Duration::from_nanos(buffer_time as u64 * 100);
-            let half_buffer_duration = buffer_duration / 2;
-
-            audio_client.Initialize(AUDCLNT_SHAREMODE_SHARED, AUDCLNT_STREAMFLAGS_LOOPBACK, buffer_time, buffer_time, &format as *const _, None)
-                .map_err(|_| WindowsAudioCaptureStreamCreateError::AudioClientInitializeFailed)?;
-
-            let capture_client : IAudioCaptureClient = audio_client.GetService()
-                .map_err(|_| WindowsAudioCaptureStreamCreateError::AudioCaptureCreationFailed)?;
-
-            let capture_client_send = SendCaptureClient::from_iaudiocaptureclient(capture_client);
-
-            std::thread::spawn(move || {
-                {
-                    let should_couninit = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
-
-                    let mut last_device_position = 0u64;
-                    let mut sample_count = 0u64;
-
-                    let capture_client = capture_client_send.into_iaudiocaptureclient();
-                    loop {
-                        std::thread::sleep(half_buffer_duration);
-
-                        let _buffered_count = match capture_client.GetNextPacketSize() {
-                            Ok(count) => count,
-                            Err(_) => {
-                                (callback)(Err(WindowsAudioCaptureStreamError::Other(format!("Stream failed - couldn't fetch packet size"))));
-                                break;
-                            }
-                        };
-
-                        let mut data_ptr: *mut u8 = std::ptr::null_mut();
-
-                        let mut num_frames = 0u32;
-                        let mut flags = 0u32;
-                        let mut device_position = 0u64;
-
-                        match capture_client.GetBuffer(&mut data_ptr as *mut _, &mut num_frames as *mut _, &mut flags as *mut _, Some(&mut device_position as *mut _), None) {
-                            Ok(_) => {
-                                let packet = WindowsAudioCaptureStreamPacket {
-                                    data: std::slice::from_raw_parts(data_ptr as *const i16, num_frames as usize * 2),
-                                    channel_count: callback_format.nChannels as u32,
-                                    origin_time: Duration::from_nanos(device_position as u64 * 100),
-                                    duration: Duration::from_nanos((device_position - last_device_position) as u64),
-                                    sample_index: sample_count
-                                };
-                                (callback)(Ok(packet));
-                                let _ = capture_client.ReleaseBuffer(num_frames);
-                                last_device_position = device_position;
-                                sample_count += num_frames as u64;
-                            },
-                            Err(_) => {
-                                (callback)(Err(WindowsAudioCaptureStreamError::GetBufferFailed));
-                                break;
-                            }
-                        }
+            (callback)(Ok(packet));
+            let _ = self.capture_client.ReleaseBuffer(num_frames);
+        }
+    }
+}
+
+impl Drop for WindowsAudioClient {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.audio_client.Stop();
+        }
+    }
+}
 
+impl WindowsAudioCaptureStream {
+    pub fn new(config: AudioCaptureConfig, endpoint: WindowsAudioEndpoint, clock: StreamClock, mut callback: WindowsAudioCaptureStreamCallback) -> Result<Self, WindowsAudioCaptureStreamCreateError> {
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread_stop_flag = stop_flag.clone();
+        let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+
+        // The audio client lives on the capture thread, so it can be re-opened when the default device changes
+        let thread = std::thread::spawn(move || {
+            unsafe {
+                let should_couninit = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+
+                let opened = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
+                    .map_err(|e| WindowsAudioCaptureStreamCreateError::Other(format!("Failed to create MMDeviceEnumerator: {}", e)))
+                    .and_then(|mm_device_enumerator: IMMDeviceEnumerator| {
+                        let client = WindowsAudioClient::open(&mm_device_enumerator, &config, &endpoint)?;
+                        Ok((mm_device_enumerator, client))
+                    });
+                let (mm_device_enumerator, client) = match opened {
+                    Ok(opened) => {
+                        let _ = ready_tx.send(Ok(()));
+                        opened
+                    },
+                    Err(error) => {
+                        let _ = ready_tx.send(Err(error));
+                        if should_couninit {
+                            CoUninitialize();
+                        }
+                        return;
+                    }
+                };
+
+                let follows_default = matches!(endpoint, WindowsAudioEndpoint::Default(_));
+                let half_buffer_duration = client.half_buffer_duration;
+                let mut client = Some(client);
+                let mut last_default_check = Instant::now();
+                let mut timeline = WindowsAudioPacketTimeline::new(clock, config.sample_rate);
+
+                while !thread_stop_flag.load(atomic::Ordering::Acquire) {
+                    std::thread::sleep(half_buffer_duration);
+
+                    if follows_default && last_default_check.elapsed() >= DEFAULT_DEVICE_POLL_INTERVAL {
+                        last_default_check = Instant::now();
+                        let default_id = endpoint.device(&mm_device_enumerator).ok().and_then(|device| device_id(&device).ok());
+                        let current_id = client.as_ref().map(|client| client.device_id.clone());
+                        if default_id != current_id {
+                            // Drop the old client before opening the new one, and keep retrying while there's no default device
+                            drop(client.take());
+                            // The new client's device position starts from zero, so its packets are anchored to the timeline again
+                            timeline.reopen();
+                            client = WindowsAudioClient::open(&mm_device_enumerator, &config, &endpoint).ok();
+                        }
                     }
 
-                    if should_couninit {
-                        CoUninitialize();
+                    let Some(current_client) = client.as_ref() else {
+                        continue;
+                    };
+                    match current_client.read_packets(&mut timeline, &mut callback) {
+                        Ok(()) => {},
+                        // The device was removed or reconfigured, so wait for the next default device check to re-open it
+                        Err(error) if follows_default && error.code() == AUDCLNT_E_DEVICE_INVALIDATED => {
+                            client = None;
+                        },
+                        Err(error) => {
+                            (callback)(Err(WindowsAudioCaptureStreamError::Other(format!("Stream failed - couldn't read packets: {}", error))));
+                            break;
+                        }
                     }
                 }
-            });
-
-            audio_client.Start()
-                .map_err(|_| WindowsAudioCaptureStreamCreateError::StreamStartFailed)?;
 
-            Ok(WindowsAudioCaptureStream {
-                should_couninit,
-                audio_client
-            })
+                drop(client);
+                drop(mm_device_enumerator);
+                if should_couninit {
+                    CoUninitialize();
+                }
+            }
+        });
+
+        match ready_rx.recv() {
+            Ok(Ok(())) => Ok(WindowsAudioCaptureStream {
+                stop_flag,
+                thread: Some(thread),
+            }),
+            Ok(Err(error)) => {
+                let _ = thread.join();
+                Err(error)
+            },
+            Err(_) => Err(WindowsAudioCaptureStreamCreateError::Other("Audio capture thread exited unexpectedly".into())),
         }
     }
 
     pub fn stop(&mut self) {
-        unsafe {
-            let _ = self.audio_client.Stop();
-        }
+        self.stop_flag.store(true, atomic::Ordering::Release);
     }
 }
 
 impl Drop for WindowsAudioCaptureStream {
     fn drop(&mut self) {
-        unsafe {
-            let _ = self.audio_client.Stop();
-            if self.should_couninit {
-                CoUninitialize();
-            }
+        self.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_HOST_TIME: Duration = Duration::from_secs(50);
+
+    fn host_time_of_sample(sample_index: u64) -> Duration {
+        START_HOST_TIME + Duration::from_nanos(sample_index * 1_000_000_000 / 48_000)
+    }
+
+    #[test]
+    fn packets_advance_by_device_position() {
+        let mut timeline = WindowsAudioPacketTimeline::new(StreamClock::from_host_time(START_HOST_TIME), AudioSampleRate::Hz48000);
+        // The first packet arrives 480 samples after the stream started
+        assert_eq!(timeline.place(0, host_time_of_sample(480), 480), 480);
+        // Later packets follow the device position, even if their host times jitter
+        assert_eq!(timeline.place(480, host_time_of_sample(960) + Duration::from_micros(300), 480), 960);
+        assert_eq!(timeline.place(960, host_time_of_sample(1440) - Duration::from_micros(300), 480), 1440);
+        assert_eq!(timeline.origin_time(1440), Duration::from_millis(30));
+    }
+
+    #[test]
+    fn reopening_continues_the_timeline() {
+        let mut timeline = WindowsAudioPacketTimeline::new(StreamClock::from_host_time(START_HOST_TIME), AudioSampleRate::Hz48000);
+        let mut sample_index = 0;
+        for packet in 0..10 {
+            sample_index = timeline.place(packet * 480, host_time_of_sample(packet * 480), 480);
         }
+        assert_eq!(sample_index, 4320);
+
+        // The new client's device position restarts from zero, 100ms (4800 samples) after the last packet ended
+        timeline.reopen();
+        let reopened_index = timeline.place(0, host_time_of_sample(4800 + 4800), 480);
+        assert_eq!(reopened_index, 9600);
+        assert_eq!(timeline.place(480, host_time_of_sample(10_080), 480), 10_080);
+        assert_eq!(timeline.origin_time(reopened_index), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn reopening_never_overlaps_delivered_samples() {
+        let mut timeline = WindowsAudioPacketTimeline::new(StreamClock::from_host_time(START_HOST_TIME), AudioSampleRate::Hz48000);
+        assert_eq!(timeline.place(0, host_time_of_sample(0), 960), 0);
+        // A new client whose first host time is earlier than the end of the delivered audio picks up right after it
+        timeline.reopen();
+        assert_eq!(timeline.place(0, host_time_of_sample(500), 480), 960);
+        assert_eq!(timeline.place(480, host_time_of_sample(980), 480), 1440);
     }
 }
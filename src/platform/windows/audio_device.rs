@@ -0,0 +1,105 @@
+use windows::{core::PWSTR, Win32::{Devices::FunctionDiscovery::PKEY_Device_FriendlyName, Media::Audio::{eCapture, eConsole, eRender, EDataFlow, IAudioClient, IMMDevice, IMMDeviceEnumerator, MMDeviceEnumerator, DEVICE_STATE_ACTIVE}, System::Com::{CoCreateInstance, CoInitializeEx, CoTaskMemFree, CoUninitialize, StructuredStorage::{PropVariantClear, PropVariantToStringAlloc}, CLSCTX_ALL, COINIT_MULTITHREADED, STGM_READ}}};
+
+use crate::audio_device::{AudioDeviceError, AudioDeviceFormat, AudioDeviceKind};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub(crate) struct WindowsAudioDevice {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) is_default: bool,
+    pub(crate) kind: AudioDeviceKind,
+    pub(crate) native_format: Option<AudioDeviceFormat>,
+}
+
+/// Take ownership of a COM-allocated string
+unsafe fn take_pwstr(pwstr: PWSTR) -> String {
+    let string = pwstr.to_string().unwrap_or_default();
+    CoTaskMemFree(Some(pwstr.0 as *const _));
+    string
+}
+
+pub(crate) unsafe fn device_id(device: &IMMDevice) -> Result<String, AudioDeviceError> {
+    device.GetId()
+        .map(|id| take_pwstr(id))
+        .map_err(|e| AudioDeviceError::Other(format!("Failed to get device id: {}", e)))
+}
+
+unsafe fn device_name(device: &IMMDevice) -> Result<String, AudioDeviceError> {
+    let property_store = device.OpenPropertyStore(STGM_READ)
+        .map_err(|e| AudioDeviceError::Other(format!("Failed to open device property store: {}", e)))?;
+    let mut name_variant = property_store.GetValue(&PKEY_Device_FriendlyName as *const _)
+        .map_err(|e| AudioDeviceError::Other(format!("Failed to get device name: {}", e)))?;
+    let name = PropVariantToStringAlloc(&name_variant as *const _).map(|name| take_pwstr(name));
+    let _ = PropVariantClear(&mut name_variant as *mut _);
+    name.map_err(|e| AudioDeviceError::Other(format!("Failed to get device name: {}", e)))
+}
+
+/// Get the shared mode mix format of the device, which is the format the audio engine runs the device at
+unsafe fn device_native_format(device: &IMMDevice) -> Option<AudioDeviceFormat> {
+    let audio_client: IAudioClient = device.Activate(CLSCTX_ALL, None).ok()?;
+    let mix_format = audio_client.GetMixFormat().ok()?;
+    if mix_format.is_null() {
+        return None;
+    }
+    let native_format = AudioDeviceFormat {
+        samples_per_second: (*mix_format).nSamplesPerSec,
+        channel_count: (*mix_format).nChannels as usize,
+    };
+    CoTaskMemFree(Some(mix_format as *const _));
+    Some(native_format)
+}
+
+pub(crate) fn data_flow_for_kind(kind: AudioDeviceKind) -> EDataFlow {
+    match kind {
+        AudioDeviceKind::Input => eCapture,
+        AudioDeviceKind::Output => eRender,
+    }
+}
+
+impl WindowsAudioDevice {
+    fn enumerate(kind: AudioDeviceKind) -> Result<Vec<Self>, AudioDeviceError> {
+        let data_flow = data_flow_for_kind(kind);
+        unsafe {
+            let should_couninit = CoInitializeEx(None, COINIT_MULTITHREADED).is_ok();
+            let result = (|| {
+                let mm_device_enumerator: IMMDeviceEnumerator = CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)
+                    .map_err(|e| AudioDeviceError::Other(format!("Failed to create MMDeviceEnumerator: {}", e)))?;
+                // There may be no default device, for example if no devices are connected
+                let default_id = match mm_device_enumerator.GetDefaultAudioEndpoint(data_flow, eConsole) {
+                    Ok(default_device) => Some(device_id(&default_device)?),
+                    Err(_) => None,
+                };
+                let collection = mm_device_enumerator.EnumAudioEndpoints(data_flow, DEVICE_STATE_ACTIVE)
+                    .map_err(|e| AudioDeviceError::Other(format!("Failed to enumerate audio endpoints: {}", e)))?;
+                let count = collection.GetCount()
+                    .map_err(|e| AudioDeviceError::Other(format!("Failed to get audio endpoint count: {}", e)))?;
+                let mut devices = Vec::new();
+                for i in 0..count {
+                    let device = collection.Item(i)
+                        .map_err(|e| AudioDeviceError::Other(format!("Failed to get audio endpoint: {}", e)))?;
+                    let id = device_id(&device)?;
+                    devices.push(Self {
+                        name: device_name(&device)?,
+                        is_default: default_id.as_ref() == Some(&id),
+                        kind,
+                        native_format: device_native_format(&device),
+                        id,
+                    });
+                }
+                Ok(devices)
+            })();
+            if should_couninit {
+                CoUninitialize();
+            }
+            result
+        }
+    }
+
+    pub(crate) fn input_devices() -> Result<Vec<Self>, AudioDeviceError> {
+        Self::enumerate(AudioDeviceKind::Input)
+    }
+
+    pub(crate) fn output_devices() -> Result<Vec<Self>, AudioDeviceError> {
+        Self::enumerate(AudioDeviceKind::Output)
+    }
+}
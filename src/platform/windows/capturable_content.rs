@@ -1,16 +1,20 @@
-use std::{ffi::OsString, hash::Hash, os::{raw::c_void, windows::ffi::OsStringExt}, sync::Arc};
+use std::{cell::RefCell, ffi::OsString, hash::Hash, os::{raw::c_void, windows::ffi::{OsStrExt, OsStringExt}}, path::PathBuf, sync::Arc};
 
-use windows::Win32::{Foundation::{BOOL, LPARAM, RECT, TRUE}, Graphics::Gdi::{EnumDisplayMonitors, HDC, HMONITOR}, System::{ProcessStatus::GetModuleFileNameExW, Threading::{OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ}}, UI::WindowsAndMessaging::{EnumWindows, GetWindowDisplayAffinity, GetWindowRect, GetWindowTextA, GetWindowTextLengthA, GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, IsWindow, IsWindowVisible, WDA_EXCLUDEFROMCAPTURE}};
+use windows::{core::{w, HSTRING, PCWSTR}, Win32::{Foundation::{BOOL, HMODULE, LPARAM, POINT, RECT, TRUE, WPARAM}, Graphics::{Dwm::{DwmGetWindowAttribute, DWMWA_EXTENDED_FRAME_BOUNDS}, Gdi::{ClientToScreen, DeleteObject, EnumDisplayDevicesW, EnumDisplayMonitors, GetDC, GetDIBits, GetMonitorInfoW, MonitorFromWindow, ReleaseDC, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, DISPLAY_DEVICEW, HDC, HMONITOR, MONITORINFO, MONITORINFOEXW, MONITOR_DEFAULTTONEAREST}}, Storage::FileSystem::{GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW}, System::{ProcessStatus::GetModuleFileNameExW, Threading::{GetCurrentThreadId, OpenProcess, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ}}, UI::{Accessibility::{SetWinEventHook, UnhookWinEvent, HWINEVENTHOOK}, WindowsAndMessaging::{CHILDID_SELF, DestroyIcon, DispatchMessageW, EVENT_OBJECT_CREATE, EVENT_OBJECT_HIDE, EVENT_OBJECT_LOCATIONCHANGE, EVENT_OBJECT_NAMECHANGE, EVENT_SYSTEM_MINIMIZEEND, EVENT_SYSTEM_MINIMIZESTART, GetMessageW, OBJID_WINDOW, PeekMessageW, PostThreadMessageW, TranslateMessage, MSG, PM_NOREMOVE, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS, WM_QUIT, EDD_GET_DEVICE_INTERFACE_NAME, EnumWindows, GetClientRect, GetForegroundWindow, GetIconInfo, GetWindow, GetWindowDisplayAffinity, GetWindowRect, GetWindowTextA, GetWindowTextLengthA, GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId, IsIconic, IsWindow, IsWindowVisible, IsZoomed, PrivateExtractIconsW, GW_HWNDPREV, GW_OWNER, HICON, ICONINFO, WDA_EXCLUDEFROMCAPTURE}}}};
 
 pub use windows::Win32::Foundation::HWND;
 
-use crate::{prelude::{CapturableContentError, CapturableContentFilter, CapturableWindow}, util::{Point, Rect, Size}};
+use crate::{prelude::{CapturableApplicationIcon, CapturableContentError, CapturableContentFilter, CapturableWindow, CapturableWindowState}, util::{PixelRect, Rect}};
 
 use super::AutoHandle;
 
 #[derive(Debug, Clone)]
 pub struct WindowsCapturableWindow(pub(crate) HWND);
 
+fn rect_from_win32(rect: RECT) -> Rect {
+    PixelRect::from_edges(rect.left, rect.top, rect.right, rect.bottom).into()
+}
+
 fn hwnd_pid(hwnd: HWND) -> u32 {
     unsafe {
         let mut pid = 0u32;
@@ -43,16 +47,7 @@ impl WindowsCapturableWindow {
         unsafe {
             let mut rect = RECT::default();
             let _ = GetWindowRect(self.0, &mut rect);
-            Rect {
-                origin: Point {
-                    x: rect.left as f64,
-                    y: rect.top as f64
-                },
-                size: Size {
-                    width: (rect.right - rect.left) as f64,
-                    height: (rect.bottom - rect.top) as f64,
-                }
-            }
+            rect_from_win32(rect)
         }
     }
 
@@ -63,6 +58,89 @@ impl WindowsCapturableWindow {
     pub fn is_visible(&self) -> bool {
         unsafe { IsWindowVisible(self.0).as_bool() }
     }
+
+    pub fn frame_rect(&self) -> Rect {
+        unsafe {
+            // The extended frame bounds exclude the invisible resize borders that GetWindowRect includes
+            let mut rect = RECT::default();
+            let result = DwmGetWindowAttribute(self.0, DWMWA_EXTENDED_FRAME_BOUNDS, &mut rect as *mut _ as *mut c_void, std::mem::size_of::<RECT>() as u32);
+            if result.is_err() {
+                return self.rect();
+            }
+            rect_from_win32(rect)
+        }
+    }
+
+    pub fn client_rect(&self) -> Option<Rect> {
+        unsafe {
+            let mut rect = RECT::default();
+            GetClientRect(self.0, &mut rect).ok()?;
+            let mut origin = POINT { x: rect.left, y: rect.top };
+            if !ClientToScreen(self.0, &mut origin).as_bool() {
+                return None;
+            }
+            Some(PixelRect::from_edges(origin.x, origin.y, origin.x + rect.right - rect.left, origin.y + rect.bottom - rect.top).into())
+        }
+    }
+
+    pub fn z_order(&self) -> Option<usize> {
+        unsafe {
+            if !IsWindow(self.0).as_bool() {
+                return None;
+            }
+            let mut z_order = 0;
+            let mut hwnd = GetWindow(self.0, GW_HWNDPREV);
+            while hwnd.0 != 0 {
+                z_order += 1;
+                hwnd = GetWindow(hwnd, GW_HWNDPREV);
+            }
+            Some(z_order)
+        }
+    }
+
+    pub fn state(&self) -> Option<CapturableWindowState> {
+        unsafe {
+            if !IsWindow(self.0).as_bool() {
+                return None;
+            }
+            if IsIconic(self.0).as_bool() {
+                return Some(CapturableWindowState::Minimized);
+            }
+            let monitor = MonitorFromWindow(self.0, MONITOR_DEFAULTTONEAREST);
+            let mut monitor_info = MONITORINFO {
+                cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+                ..Default::default()
+            };
+            if GetMonitorInfoW(monitor, &mut monitor_info).as_bool() {
+                let mut window_rect = RECT::default();
+                if GetWindowRect(self.0, &mut window_rect).is_ok() && window_rect == monitor_info.rcMonitor {
+                    return Some(CapturableWindowState::Fullscreen);
+                }
+            }
+            if IsZoomed(self.0).as_bool() {
+                Some(CapturableWindowState::Maximized)
+            } else {
+                Some(CapturableWindowState::Normal)
+            }
+        }
+    }
+
+    pub fn owner(&self) -> Option<WindowsCapturableWindow> {
+        let owner = unsafe { GetWindow(self.0, GW_OWNER) };
+        if owner.0 == 0 {
+            None
+        } else {
+            Some(WindowsCapturableWindow(owner))
+        }
+    }
+
+    pub fn is_focused(&self) -> Option<bool> {
+        Some(unsafe { GetForegroundWindow() } == self.0)
+    }
+
+    pub fn native_id(&self) -> u64 {
+        self.0.0 as u64
+    }
 }
 
 impl Hash for WindowsCapturableWindow {
@@ -88,14 +166,36 @@ impl WindowsCapturableDisplay {
     }
 
     pub fn rect(&self) -> Rect {
-        Rect {
-            origin: Point {
-                x: self.1.left as f64,
-                y: self.1.top as f64
-            },
-            size: Size {
-                width: (self.1.right - self.1.left) as f64,
-                height: (self.1.bottom - self.1.top) as f64
+        rect_from_win32(self.1)
+    }
+
+    pub fn native_id(&self) -> u64 {
+        self.0.0 as u64
+    }
+
+    pub fn hardware_id(&self) -> Option<String> {
+        unsafe {
+            let mut monitor_info = MONITORINFOEXW::default();
+            monitor_info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+            if !GetMonitorInfoW(self.0, &mut monitor_info as *mut _ as *mut MONITORINFO).as_bool() {
+                return None;
+            }
+            let mut display_device = DISPLAY_DEVICEW {
+                cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
+                ..Default::default()
+            };
+            if !EnumDisplayDevicesW(PCWSTR(monitor_info.szDevice.as_ptr()), 0, &mut display_device as *mut _, EDD_GET_DEVICE_INTERFACE_NAME).as_bool() {
+                return None;
+            }
+            let device_id_len = display_device.DeviceID.iter().position(|c| *c == 0).unwrap_or(display_device.DeviceID.len());
+            let device_id = String::from_utf16_lossy(&display_device.DeviceID[..device_id_len]);
+            // The device interface name looks like "\\?\DISPLAY#DEL40F6#<instance>#{<interface guid>}",
+            // where the second component is the manufacturer and product code from the display's EDID, and the third
+            // identifies this particular display, so that two displays of the same model get different identifiers
+            let mut components = device_id.split('#').skip(1);
+            match (components.next(), components.next()) {
+                (Some(product), Some(instance)) => Some(format!("{}#{}", product, instance)),
+                _ => None,
             }
         }
     }
@@ -120,50 +220,167 @@ impl Eq for WindowsCapturableDisplay {}
 #[derive(Clone, Debug)]
 pub struct WindowsCapturableApplication(pub(crate) u32);
 
-impl WindowsCapturableApplication {
-    pub fn identifier(&self) -> String {
-        unsafe {
-            let process = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, self.0);
-            if process.is_err() {
-                return "".into();
-            }
-            let process = process.unwrap();
-            // TODO: If OpenProcess fails we could fall back to GetProcessHandleFromHwnd, in oleacc.dll
-            //       Alternatively, it might be better to use the accessibility APIs.
-            let process = AutoHandle(process);
-            let mut process_name = vec![0u16; 64];
-            let mut len = GetModuleFileNameExW (process.0, None, process_name.as_mut_slice()) as usize;
-            while len == process_name.len() - 1 {
-                process_name = vec![0u16; process_name.len() * 2];
-                len = GetModuleFileNameExW (process.0, None, process_name.as_mut_slice()) as usize;
-            }
+fn process_image_path(pid: u32) -> Option<OsString> {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid).ok()?;
+        // TODO: If OpenProcess fails we could fall back to GetProcessHandleFromHwnd, in oleacc.dll
+        //       Alternatively, it might be better to use the accessibility APIs.
+        let process = AutoHandle(process);
+        let mut process_name = vec![0u16; 64];
+        let mut len = GetModuleFileNameExW (process.0, None, process_name.as_mut_slice()) as usize;
+        while len == process_name.len() - 1 {
+            process_name = vec![0u16; process_name.len() * 2];
+            len = GetModuleFileNameExW (process.0, None, process_name.as_mut_slice()) as usize;
+        }
 
-            if len == 0 {
-                return "".into();
-            }
+        if len == 0 {
+            return None;
+        }
 
-            let os_string = OsString::from_wide(&process_name[..len as usize]);
-            let path = std::path::Path::new(&os_string);
-            let file_name = path.file_name();
+        Some(OsString::from_wide(&process_name[..len as usize]))
+    }
+}
+
+fn version_info_string(path: &OsString, key: &str) -> Option<String> {
+    unsafe {
+        let path = HSTRING::from(path);
+        let size = GetFileVersionInfoSizeW(&path, None);
+        if size == 0 {
+            return None;
+        }
+        let mut version_info = vec![0u8; size as usize];
+        GetFileVersionInfoW(&path, 0, size, version_info.as_mut_ptr() as *mut c_void).ok()?;
 
-            if let Some(file_name) = file_name {
-                if let Some(name_str) = file_name.to_str() {
-                    return name_str.to_string()
+        let mut translations_ptr: *mut c_void = std::ptr::null_mut();
+        let mut translations_len = 0u32;
+        if !VerQueryValueW(version_info.as_ptr() as *const c_void, w!("\\VarFileInfo\\Translation"), &mut translations_ptr as *mut _, &mut translations_len as *mut _).as_bool() {
+            return None;
+        }
+        // Each translation is a pair of (language id, code page)
+        let translations = std::slice::from_raw_parts(translations_ptr as *const [u16; 2], translations_len as usize / 4);
+        for [language, code_page] in translations {
+            let sub_block = HSTRING::from(format!("\\StringFileInfo\\{:04x}{:04x}\\{}", language, code_page, key));
+            let mut value_ptr: *mut c_void = std::ptr::null_mut();
+            let mut value_len = 0u32;
+            if VerQueryValueW(version_info.as_ptr() as *const c_void, &sub_block, &mut value_ptr as *mut _, &mut value_len as *mut _).as_bool() && value_len > 1 {
+                let value = std::slice::from_raw_parts(value_ptr as *const u16, value_len as usize - 1);
+                let value = String::from_utf16_lossy(value).trim().to_string();
+                if !value.is_empty() {
+                    return Some(value);
                 }
             }
+        }
+        None
+    }
+}
 
-            let result = String::from_utf16(&process_name[..len as usize]);
-            result.unwrap_or("".into())
+impl WindowsCapturableApplication {
+    pub fn identifier(&self) -> String {
+        let process_name = match process_image_path(self.0) {
+            Some(process_name) => process_name,
+            None => return "".into(),
+        };
+
+        let path = std::path::Path::new(&process_name);
+        let file_name = path.file_name();
+
+        if let Some(file_name) = file_name {
+            if let Some(name_str) = file_name.to_str() {
+                return name_str.to_string()
+            }
         }
+
+        process_name.to_string_lossy().into_owned()
     }
 
     pub fn name(&self) -> String {
-        self.identifier()
+        process_image_path(self.0)
+            .and_then(|path| version_info_string(&path, "FileDescription").or_else(|| version_info_string(&path, "ProductName")))
+            .unwrap_or_else(|| self.identifier())
     }
 
     pub fn pid(&self) -> i32 {
         self.0 as i32
     }
+
+    pub fn executable_path(&self) -> Option<PathBuf> {
+        process_image_path(self.0).map(PathBuf::from)
+    }
+
+    pub fn icon(&self, size: usize) -> Result<CapturableApplicationIcon, CapturableContentError> {
+        let path = process_image_path(self.0)
+            .ok_or(CapturableContentError::Other("Failed to get application executable path".into()))?;
+        let mut file_name = [0u16; 260];
+        let path_wide: Vec<u16> = path.encode_wide().collect();
+        if path_wide.len() >= file_name.len() {
+            return Err(CapturableContentError::Other("Application executable path is too long".into()));
+        }
+        file_name[..path_wide.len()].copy_from_slice(&path_wide);
+        unsafe {
+            let mut icons = [HICON::default()];
+            let icon_count = PrivateExtractIconsW(&file_name, 0, size as i32, size as i32, Some(&mut icons), None, 0);
+            if icon_count == 0 || icon_count == u32::MAX || icons[0].is_invalid() {
+                return Err(CapturableContentError::Other("Application has no icon".into()));
+            }
+            let icon = icons[0];
+            let mut icon_info = ICONINFO::default();
+            let icon_info_result = GetIconInfo(icon, &mut icon_info as *mut _);
+            let _ = DestroyIcon(icon);
+            icon_info_result.map_err(|error| CapturableContentError::Other(format!("Failed to get icon info: {}", error)))?;
+
+            let mut bitmap_info = BITMAPINFO::default();
+            bitmap_info.bmiHeader.biSize = std::mem::size_of::<BITMAPINFOHEADER>() as u32;
+            bitmap_info.bmiHeader.biWidth = size as i32;
+            // A negative height requests top-down row order
+            bitmap_info.bmiHeader.biHeight = -(size as i32);
+            bitmap_info.bmiHeader.biPlanes = 1;
+            bitmap_info.bmiHeader.biBitCount = 32;
+            bitmap_info.bmiHeader.biCompression = BI_RGB.0;
+
+            // GetDIBits may fill in the header, so the mask is read with a fresh copy of it
+            let mut mask_bitmap_info = bitmap_info;
+
+            let mut data = vec![[0u8; 4]; size * size];
+            let mut mask = vec![[0u8; 4]; size * size];
+            let dc = GetDC(HWND(0));
+            let line_count = if icon_info.hbmColor.is_invalid() {
+                0
+            } else {
+                GetDIBits(dc, icon_info.hbmColor, 0, size as u32, Some(data.as_mut_ptr() as *mut c_void), &mut bitmap_info as *mut _, DIB_RGB_COLORS)
+            };
+            // Icons without an alpha channel rely on their AND mask for transparency
+            let has_alpha = data.iter().any(|pixel| pixel[3] != 0);
+            let mask_line_count = if has_alpha || icon_info.hbmMask.is_invalid() {
+                0
+            } else {
+                GetDIBits(dc, icon_info.hbmMask, 0, size as u32, Some(mask.as_mut_ptr() as *mut c_void), &mut mask_bitmap_info as *mut _, DIB_RGB_COLORS)
+            };
+            ReleaseDC(HWND(0), dc);
+            let _ = DeleteObject(icon_info.hbmColor);
+            let _ = DeleteObject(icon_info.hbmMask);
+            if line_count != size as i32 {
+                return Err(CapturableContentError::Other("Failed to read icon bitmap".into()));
+            }
+
+            // Set mask bits (read back as white) are transparent. If the mask couldn't be read, treat the icon as opaque.
+            let has_mask = mask_line_count == size as i32;
+            for (pixel, mask_pixel) in data.iter_mut().zip(mask.iter()) {
+                let [b, g, r, a] = *pixel;
+                let a = match (has_alpha, has_mask) {
+                    (true, _) => a,
+                    (false, true) if mask_pixel[0] != 0 => 0,
+                    (false, _) => 255,
+                };
+                *pixel = [r, g, b, a];
+            }
+
+            Ok(CapturableApplicationIcon {
+                data: data.into_boxed_slice(),
+                width: size,
+                height: size,
+            })
+        }
+    }
 }
 
 pub struct WindowsCapturableContent {
@@ -209,6 +426,9 @@ impl WindowsCapturableContent {
                     if !filter.impl_capturable_content_filter.filter_window_handle(hwnd) {
                         return false;
                     }
+                    if !window_filter.matches(&CapturableWindow { impl_capturable_window: WindowsCapturableWindow(**hwnd) }) {
+                        return false;
+                    }
                     // TODO: filter desktop windows
                     true
                 }).map(|hwnd| *hwnd).collect();
@@ -221,6 +441,82 @@ impl WindowsCapturableContent {
     }
 }
 
+thread_local! {
+    static WIN_EVENT_CALLBACK: RefCell<Option<Box<dyn Fn() + Send + Sync>>> = RefCell::new(None);
+}
+
+unsafe extern "system" fn win_event_callback(_hook: HWINEVENTHOOK, _event: u32, _hwnd: HWND, object_id: i32, child_id: i32, _thread_id: u32, _time: u32) {
+    if object_id != OBJID_WINDOW.0 || child_id != CHILDID_SELF as i32 {
+        return;
+    }
+    WIN_EVENT_CALLBACK.with(|callback| {
+        if let Some(callback) = callback.borrow().as_ref() {
+            (callback)();
+        }
+    });
+}
+
+/// Notifies of window changes using WinEvent hooks, serviced by a dedicated message loop thread
+pub(crate) struct WindowsCapturableContentNotifier {
+    thread_id: u32,
+    thread_handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl WindowsCapturableContentNotifier {
+    pub fn new(callback: impl Fn() + Send + Sync + 'static) -> Option<Self> {
+        let (thread_id_tx, thread_id_rx) = std::sync::mpsc::channel();
+        let thread_handle = std::thread::spawn(move || {
+            WIN_EVENT_CALLBACK.with(|thread_callback| *thread_callback.borrow_mut() = Some(Box::new(callback)));
+            unsafe {
+                // Ensure the thread has a message queue before anyone can post WM_QUIT to it
+                let mut msg = MSG::default();
+                let _ = PeekMessageW(&mut msg as *mut _, HWND(0), 0, 0, PM_NOREMOVE);
+                let event_ranges = [
+                    (EVENT_SYSTEM_MINIMIZESTART, EVENT_SYSTEM_MINIMIZEEND),
+                    (EVENT_OBJECT_CREATE, EVENT_OBJECT_HIDE),
+                    (EVENT_OBJECT_LOCATIONCHANGE, EVENT_OBJECT_NAMECHANGE),
+                ];
+                let hooks: Vec<HWINEVENTHOOK> = event_ranges.iter()
+                    .map(|(event_min, event_max)| SetWinEventHook(*event_min, *event_max, HMODULE(0), Some(win_event_callback), 0, 0, WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS))
+                    .filter(|hook| !hook.is_invalid())
+                    .collect();
+                if hooks.is_empty() {
+                    let _ = thread_id_tx.send(None);
+                    return;
+                }
+                let _ = thread_id_tx.send(Some(GetCurrentThreadId()));
+                while GetMessageW(&mut msg as *mut _, HWND(0), 0, 0).0 > 0 {
+                    TranslateMessage(&msg as *const _);
+                    DispatchMessageW(&msg as *const _);
+                }
+                for hook in hooks {
+                    UnhookWinEvent(hook);
+                }
+            }
+            WIN_EVENT_CALLBACK.with(|thread_callback| *thread_callback.borrow_mut() = None);
+        });
+        match thread_id_rx.recv() {
+            Ok(Some(thread_id)) => Some(Self {
+                thread_id,
+                thread_handle: Some(thread_handle),
+            }),
+            _ => {
+                let _ = thread_handle.join();
+                None
+            }
+        }
+    }
+}
+
+impl Drop for WindowsCapturableContentNotifier {
+    fn drop(&mut self) {
+        unsafe { let _ = PostThreadMessageW(self.thread_id, WM_QUIT, WPARAM(0), LPARAM(0)); }
+        if let Some(thread_handle) = self.thread_handle.take() {
+            let _ = thread_handle.join();
+        }
+    }
+}
+
 /// Windows-specific extensions for capturable windows
 pub trait WindowsCapturableWindowExt {
     /// Get the HWND for this capturable window.
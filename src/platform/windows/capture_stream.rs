@@ -1,11 +1,13 @@
-use std::{sync::{atomic::{self, AtomicBool, AtomicU64}, Arc}, time::{Duration, Instant}, fmt::Debug};
+use std::{sync::{atomic::{self, AtomicBool, AtomicU64}, Arc, OnceLock}, time::{Duration, Instant}, fmt::Debug};
 
-use crate::prelude::{AudioFrame, Capturable, CaptureConfig, CapturePixelFormat, StreamCreateError, StreamError, StreamEvent, StreamStopError, VideoFrame};
+use crate::prelude::{AudioCaptureConfig, AudioDevice, AudioDeviceKind, AudioFrame, AudioSource, Capturable, CaptureConfig, CapturePixelFormat, StreamClock, StreamCreateError, StreamError, StreamEvent, StreamStopError, VideoFrame};
 
 use parking_lot::Mutex;
 use windows::{core::{ComInterface, IInspectable, HSTRING}, Foundation::TypedEventHandler, Graphics::{Capture::{Direct3D11CaptureFramePool, GraphicsCaptureAccess, GraphicsCaptureAccessKind, GraphicsCaptureItem, GraphicsCaptureSession}, DirectX::{Direct3D11::IDirect3DDevice, DirectXPixelFormat}, SizeInt32}, Security::Authorization::AppCapabilityAccess::{AppCapability, AppCapabilityAccessStatus}, Win32::{Graphics::{Direct3D::{D3D_DRIVER_TYPE_UNKNOWN, D3D_FEATURE_LEVEL_11_0}, Direct3D11::{D3D11CreateDevice, ID3D11Device, D3D11_CREATE_DEVICE_BGRA_SUPPORT, D3D11_SDK_VERSION}, Dxgi::{CreateDXGIFactory, IDXGIAdapter, IDXGIDevice, IDXGIFactory}}, System::{Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED}, WinRT::{Direct3D11::CreateDirect3D11DeviceFromDXGIDevice, Graphics::Capture::IGraphicsCaptureItemInterop}}, UI::HiDpi::{GetDpiForMonitor, GetDpiForWindow, MDT_RAW_DPI}}};
 
-use super::{audio_capture_stream::{WindowsAudioCaptureStream, WindowsAudioCaptureStreamError, WindowsAudioCaptureStreamPacket}, frame::WindowsVideoFrame, frame::WindowsAudioFrame};
+use super::{clock::host_time_from_100ns, audio_capture_stream::{WindowsAudioCaptureStream, WindowsAudioCaptureStreamError, WindowsAudioEndpoint, WindowsAudioCaptureStreamPacket}, frame::WindowsVideoFrame, frame::WindowsAudioFrame, frame::WindowsAudioFrameData};
+#[cfg(feature = "bitmap")]
+use crate::feature::bitmap::StagingTextureCache;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[allow(unused)]
@@ -96,19 +98,30 @@ impl WindowsCaptureConfigExt for CaptureConfig {
     }
 }
 
+/// The graphics devices that a capture stream's frames are rendered with
 #[allow(unused)]
-pub struct WindowsCaptureStream {
+pub(crate) struct WindowsGraphicsDevices {
     pub(crate) dxgi_adapter: Option<IDXGIAdapter>,
     pub(crate) dxgi_adapter_error: Option<String>,
     pub(crate) dxgi_device: IDXGIDevice,
     pub(crate) d3d11_device: ID3D11Device,
+    direct3d_device: IDirect3DDevice,
+}
+
+#[allow(unused)]
+pub struct WindowsCaptureStream {
+    // Created with the stream when capturing video, and only on first use for audio-only streams
+    graphics_devices: OnceLock<WindowsGraphicsDevices>,
+    configured_dxgi_adapter: Option<IDXGIAdapter>,
+    configured_d3d11_device: Option<ID3D11Device>,
     #[cfg(feature = "wgpu")]
     pub(crate) wgpu_device: Option<Arc<dyn AsRef<wgpu::Device> + Send + Sync + 'static>>,
-    pub(crate) frame_pool: Direct3D11CaptureFramePool,
-    pub(crate) capture_session: GraphicsCaptureSession,
+    pub(crate) frame_pool: Option<Direct3D11CaptureFramePool>,
+    pub(crate) capture_session: Option<GraphicsCaptureSession>,
     should_couninit: bool,
     shared_handler_data: Arc<SharedHandlerData>,
     audio_stream: Option<WindowsAudioCaptureStream>,
+    microphone_stream: Option<WindowsAudioCaptureStream>,
 }
 
 pub(crate) struct SharedHandlerData {
@@ -199,39 +212,8 @@ impl WindowsCaptureStream {
         }
     }
 
-    pub fn new(token: WindowsCaptureAccessToken, config: CaptureConfig, callback: Box<impl FnMut(Result<StreamEvent, StreamError>) + Send + 'static>) -> Result<Self, StreamCreateError> {
-        let _ = token;
-        let should_couninit = unsafe {
-            CoInitializeEx(None, COINIT_APARTMENTTHREADED).is_ok()
-        };
-
-        if config.impl_capture_config.borderless && !token.borderless {
-            return Err(StreamCreateError::UnauthorizedFeature("Borderless Capture".to_string()));
-        }
-        
-        let pixel_format = match config.pixel_format {
-            CapturePixelFormat::Bgra8888 => DirectXPixelFormat::B8G8R8A8UIntNormalized,
-            CapturePixelFormat::Argb2101010 => DirectXPixelFormat::R10G10B10A2UIntNormalized,
-            _ => return Err(StreamCreateError::UnsupportedPixelFormat),
-        };
-
-        let interop: IGraphicsCaptureItemInterop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()
-            .map_err(|_| StreamCreateError::Other("Failed to create IGraphicsCaptureInterop factory".into()))?;
-
-        let callback_target = config.target.clone();
-
-        let graphics_capture_item: GraphicsCaptureItem = unsafe {
-            match config.target {
-                Capturable::Window(window) =>
-                    interop.CreateForWindow(window.impl_capturable_window.0)
-                        .map_err(|e| StreamCreateError::Other(format!("Failed to create graphics capture item from HWND: {}", e.to_string())))?,
-                Capturable::Display(display) => 
-                    interop.CreateForMonitor(display.impl_capturable_display.0)
-                        .map_err(|_| StreamCreateError::Other("Failed to create graphics capture item from HMONITOR".into()))?,
-            }
-        };
-
-        let (dxgi_adapter, dxgi_adapter_error, d3d11_device) = match (config.impl_capture_config.dxgi_adapter, config.impl_capture_config.d3d11_device) {
+    fn create_graphics_devices(dxgi_adapter: Option<IDXGIAdapter>, d3d11_device: Option<ID3D11Device>) -> Result<WindowsGraphicsDevices, StreamCreateError> {
+        let (dxgi_adapter, dxgi_adapter_error, d3d11_device) = match (dxgi_adapter, d3d11_device) {
             (_, Some(d3d11_device)) => {
                 let dxgi_adapter = d3d11_device.cast().map_err(|error| format!("Failed to create IDXGIAdapter from ID3D11Device: {}", error.to_string()));
                 match dxgi_adapter {
@@ -256,16 +238,107 @@ impl WindowsCaptureStream {
         let direct3d_device: IDirect3DDevice = direct3d_device_iinspectible.cast()
             .map_err(|_| StreamCreateError::Other("Failed to cast IInspectible to IDirect3DDevice".into()))?;
 
-        let callback_direct3d_device = d3d11_device.clone();
+        Ok(WindowsGraphicsDevices {
+            dxgi_adapter,
+            dxgi_adapter_error,
+            dxgi_device,
+            d3d11_device,
+            direct3d_device,
+        })
+    }
+
+    /// Get the stream's graphics devices, creating them if this is an audio-only stream which hasn't needed them yet
+    #[cfg(any(feature = "dx11", feature = "dxgi"))]
+    pub(crate) fn graphics_devices(&self) -> Result<&WindowsGraphicsDevices, StreamCreateError> {
+        if let Some(graphics_devices) = self.graphics_devices.get() {
+            return Ok(graphics_devices);
+        }
+        let graphics_devices = Self::create_graphics_devices(self.configured_dxgi_adapter.clone(), self.configured_d3d11_device.clone())?;
+        Ok(self.graphics_devices.get_or_init(|| graphics_devices))
+    }
 
-        let (width, height) = ((config.output_size.width + 0.1) as usize, (config.output_size.height + 0.1) as usize);
+    fn start_audio_stream(audio_config: AudioCaptureConfig, endpoint: WindowsAudioEndpoint, source: AudioSource, audio_handler_data: Arc<SharedHandlerData>, clock: StreamClock) -> Result<WindowsAudioCaptureStream, StreamCreateError> {
+        let handler_config = audio_config.clone();
+        let audio_handler = Box::new(move |audio_result: Result<WindowsAudioCaptureStreamPacket<'_>, WindowsAudioCaptureStreamError>| {
+            if audio_handler_data.closed.load(atomic::Ordering::Acquire) {
+                return;
+            }
+            match audio_result {
+                Ok(packet) => {
+                    let audio_frame_id = audio_handler_data.audio_frame_id_counter.fetch_add(1, atomic::Ordering::AcqRel);
+                    let event = StreamEvent::Audio(AudioFrame {
+                        impl_audio_frame: WindowsAudioFrame {
+                            data: WindowsAudioFrameData::from_packet_bytes(handler_config.sample_format, packet.data, packet.frame_count * packet.channel_count as usize),
+                            channel_count: handler_config.channel_count,
+                            sample_rate: handler_config.sample_rate,
+                            source,
+                            duration: packet.duration,
+                            origin_time: packet.origin_time,
+                            clock,
+                            frame_id: audio_frame_id
+                        }
+                    });
+                    (*audio_handler_data.callback.lock())(Ok(event));
+                },
+                Err(WindowsAudioCaptureStreamError::Other(message)) => {
+                    (*audio_handler_data.callback.lock())(Err(StreamError::Other(format!("Audio stream error: {}", message))));
+                }
+            }
+        });
 
-        let frame_pool = Direct3D11CaptureFramePool::CreateFreeThreaded(
-            &direct3d_device,
-            pixel_format,
-            config.buffer_count as i32,
-            SizeInt32 { Width: width as i32, Height: height as i32 },
-        ).map_err(|e| StreamCreateError::Other(format!("Failed to create Direct3D11CaptureFramePool: {}", e.to_string())))?;
+        WindowsAudioCaptureStream::new(audio_config, endpoint, clock, audio_handler)
+            .map_err(|_| match source {
+                AudioSource::System => StreamCreateError::Other("Failed to create audio stream".into()),
+                AudioSource::Microphone => StreamCreateError::Other("Failed to create microphone audio stream".into()),
+            })
+    }
+
+    pub fn new(token: WindowsCaptureAccessToken, config: CaptureConfig, clock: StreamClock, callback: Box<impl FnMut(Result<StreamEvent, StreamError>) + Send + 'static>) -> Result<Self, StreamCreateError> {
+        let _ = token;
+        let should_couninit = unsafe {
+            CoInitializeEx(None, COINIT_APARTMENTTHREADED).is_ok()
+        };
+
+        if config.impl_capture_config.borderless && !token.borderless {
+            return Err(StreamCreateError::UnauthorizedFeature("Borderless Capture".to_string()));
+        }
+        
+        let pixel_format = match config.pixel_format {
+            CapturePixelFormat::Bgra8888 => DirectXPixelFormat::B8G8R8A8UIntNormalized,
+            CapturePixelFormat::Argb2101010 => DirectXPixelFormat::R10G10B10A2UIntNormalized,
+            _ => return Err(StreamCreateError::UnsupportedPixelFormat),
+        };
+
+        let callback_target = config.target.clone();
+
+        // Audio-only streams have no capture target, and never create a graphics capture item or frame pool
+        let graphics_capture_item: Option<GraphicsCaptureItem> = match config.target {
+            Some(target) => {
+                let interop: IGraphicsCaptureItemInterop = windows::core::factory::<GraphicsCaptureItem, IGraphicsCaptureItemInterop>()
+                    .map_err(|_| StreamCreateError::Other("Failed to create IGraphicsCaptureInterop factory".into()))?;
+                Some(unsafe {
+                    match target {
+                        Capturable::Window(window) =>
+                            interop.CreateForWindow(window.impl_capturable_window.0)
+                                .map_err(|e| StreamCreateError::Other(format!("Failed to create graphics capture item from HWND: {}", e.to_string())))?,
+                        Capturable::Display(display) => 
+                            interop.CreateForMonitor(display.impl_capturable_display.0)
+                                .map_err(|_| StreamCreateError::Other("Failed to create graphics capture item from HMONITOR".into()))?,
+                    }
+                })
+            },
+            None => None,
+        };
+
+        // Audio-only streams have no frames to render, so they leave creating graphics devices until they're asked for
+        let graphics_devices = OnceLock::new();
+        if graphics_capture_item.is_some() {
+            let _ = graphics_devices.set(Self::create_graphics_devices(config.impl_capture_config.dxgi_adapter.clone(), config.impl_capture_config.d3d11_device.clone())?);
+        }
+
+        let callback_direct3d_device = graphics_devices.get().map(|graphics_devices| graphics_devices.d3d11_device.clone());
+
+        let (width, height) = ((config.output_size.width + 0.1) as usize, (config.output_size.height + 0.1) as usize);
 
         let shared_handler_data = Arc::new(
             SharedHandlerData {
@@ -281,7 +354,7 @@ impl WindowsCaptureStream {
         let audio_handler_data = shared_handler_data.clone();
 
         let close_handler = TypedEventHandler::new(move |_, _| {
-            let alread_closed = close_handler_data.closed.fetch_and(true, atomic::Ordering::AcqRel);
+            let alread_closed = close_handler_data.closed.fetch_or(true, atomic::Ordering::AcqRel);
             if !alread_closed {
                 let mut callback = close_handler_data.callback.lock();
                 (*callback)(Ok(StreamEvent::End));
@@ -289,11 +362,13 @@ impl WindowsCaptureStream {
             Ok(())
         });
 
-        let mut t_first_frame = None;
-        let mut t_last_frame = None;
+        let mut t_last_origin = None;
+        let callback_clock = clock;
 
         #[cfg(feature = "wgpu")]
         let callback_wgpu_device = config.impl_capture_config.wgpu_device.clone();
+        #[cfg(feature = "bitmap")]
+        let callback_staging_textures = Arc::new(StagingTextureCache::default());
         #[cfg(feature = "wgpu")]
         let wgpu_device = config.impl_capture_config.wgpu_device.clone();
 
@@ -305,23 +380,12 @@ impl WindowsCaptureStream {
             if frame_handler_data.closed.load(atomic::Ordering::Acquire) {
                 return Ok(());
             }
-            let t_capture = Instant::now();
-            let t_origin = match t_first_frame {
-                Some(t_first_frame) => t_capture - t_first_frame,
-                None => {
-                    t_first_frame = Some(t_capture);
-                    Duration::ZERO
-                }
-            };
-            let duration = match t_last_frame {
-                Some(t_last_frame) => t_capture - t_last_frame,
-                None => {
-                    t_last_frame = Some(t_capture);
-                    Duration::ZERO
-                }
+            let (Some(callback_target), Some(callback_direct3d_device)) = (&callback_target, &callback_direct3d_device) else {
+                return Ok(());
             };
+            let t_capture = Instant::now();
             let dpi = unsafe { 
-                match &callback_target {
+                match callback_target {
                     Capturable::Window(window) => GetDpiForWindow(window.impl_capturable_window.0),
                     Capturable::Display(display) => {
                         let mut dpi_x = 0u32;
@@ -331,6 +395,10 @@ impl WindowsCaptureStream {
                     }
                 }
             };
+            let screen_rect = match callback_target {
+                Capturable::Window(window) => window.frame_rect(),
+                Capturable::Display(display) => display.rect(),
+            };
             let mut callback = frame_handler_data.callback.lock();
             //let window_rect = RECT::default();
             let frame = match frame_pool.TryGetNextFrame() {
@@ -341,6 +409,17 @@ impl WindowsCaptureStream {
                 }
             };
 
+            // The frame's system relative time is taken from the performance counter, the same clock as audio packets
+            let t_origin = match frame.SystemRelativeTime() {
+                Ok(time_span) => callback_clock.stream_time_from_host_time(host_time_from_100ns(time_span.Duration)),
+                Err(_) => callback_clock.from_instant(t_capture),
+            };
+            let duration = match t_last_origin {
+                Some(t_last_origin) => t_origin.saturating_sub(t_last_origin),
+                None => Duration::ZERO,
+            };
+            t_last_origin = Some(t_origin);
+
             let frame_id = frame_handler_data.frame_id_counter.fetch_add(1, atomic::Ordering::AcqRel);
             let impl_video_frame = WindowsVideoFrame {
                 device: callback_direct3d_device.clone(),
@@ -349,11 +428,15 @@ impl WindowsCaptureStream {
                 frame_size: (width, height),
                 pixel_format,
                 dpi,
+                screen_rect,
                 t_capture,
                 t_origin,
                 duration,
+                clock: callback_clock,
                 #[cfg(feature = "wgpu")]
-                wgpu_device: callback_wgpu_device.clone()
+                wgpu_device: callback_wgpu_device.clone(),
+                #[cfg(feature = "bitmap")]
+                staging_textures: callback_staging_textures.clone(),
             };
             let video_frame = VideoFrame {
                 impl_video_frame
@@ -362,78 +445,77 @@ impl WindowsCaptureStream {
             Ok(())
         });
 
-        frame_pool.FrameArrived(&frame_handler).map_err(|_| StreamCreateError::Other("Failed to listen to FrameArrived event".into()))?;
-        graphics_capture_item.Closed(&close_handler).map_err(|_| StreamCreateError::Other("Failed to listen to Closed event".into()))?;
-
-        let capture_session = frame_pool.CreateCaptureSession(&graphics_capture_item)
-            .map_err(|_| StreamCreateError::Other("Failed to create GraphicsCaptureSession".into()))?;
-        let _ = capture_session.SetIsBorderRequired(!config.impl_capture_config.borderless);
-        let _ = capture_session.SetIsCursorCaptureEnabled(config.show_cursor);
-
-        let audio_stream = if let Some(audio_config) = config.capture_audio {
-            let handler_config = audio_config.clone();
-            let audio_handler = Box::new(move |audio_result: Result<WindowsAudioCaptureStreamPacket<'_>, WindowsAudioCaptureStreamError>| {
-                if audio_handler_data.closed.load(atomic::Ordering::Acquire) {
-                    return;
-                }
-                match audio_result {
-                    Ok(packet) => {
-                        let audio_frame_id = audio_handler_data.audio_frame_id_counter.fetch_add(1, atomic::Ordering::AcqRel);
-                        let event = StreamEvent::Audio(AudioFrame {
-                            impl_audio_frame: WindowsAudioFrame {
-                                data: packet.data.to_owned().into_boxed_slice(),
-                                channel_count: handler_config.channel_count,
-                                sample_rate: handler_config.sample_rate,
-                                duration: packet.duration,
-                                origin_time: packet.origin_time,
-                                frame_id: audio_frame_id
-                            }
-                        });
-                        (*audio_handler_data.callback.lock())(Ok(event));
-                    },
-                    Err(_) => {
-                        (*audio_handler_data.callback.lock())(Err(StreamError::Other("Audio stream error".to_string())));
-                    }
-                }
-            });
+        let (frame_pool, capture_session) = match (graphics_capture_item, graphics_devices.get()) {
+            (Some(graphics_capture_item), Some(graphics_devices)) => {
+                let frame_pool = Direct3D11CaptureFramePool::CreateFreeThreaded(
+                    &graphics_devices.direct3d_device,
+                    pixel_format,
+                    config.buffer_count as i32,
+                    SizeInt32 { Width: width as i32, Height: height as i32 },
+                ).map_err(|e| StreamCreateError::Other(format!("Failed to create Direct3D11CaptureFramePool: {}", e.to_string())))?;
+
+                frame_pool.FrameArrived(&frame_handler).map_err(|_| StreamCreateError::Other("Failed to listen to FrameArrived event".into()))?;
+                graphics_capture_item.Closed(&close_handler).map_err(|_| StreamCreateError::Other("Failed to listen to Closed event".into()))?;
+
+                let capture_session = frame_pool.CreateCaptureSession(&graphics_capture_item)
+                    .map_err(|_| StreamCreateError::Other("Failed to create GraphicsCaptureSession".into()))?;
+                let _ = capture_session.SetIsBorderRequired(!config.impl_capture_config.borderless);
+                let _ = capture_session.SetIsCursorCaptureEnabled(config.show_cursor);
+                (Some(frame_pool), Some(capture_session))
+            },
+            _ => (None, None),
+        };
 
-            match WindowsAudioCaptureStream::new(audio_config, audio_handler) {
-                Ok(audio_stream) => {
-                    Some(audio_stream)
+        let (audio_stream, microphone_stream) = if let Some(audio_config) = config.capture_audio {
+            let endpoint = |device: &Option<AudioDevice>, default_kind: AudioDeviceKind| match device {
+                Some(device) => WindowsAudioEndpoint::Device {
+                    id: device.impl_audio_device.id.clone(),
+                    kind: device.impl_audio_device.kind,
                 },
-                Err(_) => {
-                    return Err(StreamCreateError::Other("Failed to create audio stream".into()))
-                }
-            }
+                None => WindowsAudioEndpoint::Default(default_kind),
+            };
+            let microphone_stream = if audio_config.capture_microphone {
+                let microphone_endpoint = endpoint(&audio_config.microphone_device, AudioDeviceKind::Input);
+                Some(Self::start_audio_stream(audio_config.clone(), microphone_endpoint, AudioSource::Microphone, audio_handler_data.clone(), clock)?)
+            } else {
+                None
+            };
+            let system_endpoint = endpoint(&audio_config.device, AudioDeviceKind::Output);
+            let audio_stream = Self::start_audio_stream(audio_config, system_endpoint, AudioSource::System, audio_handler_data, clock)?;
+            (Some(audio_stream), microphone_stream)
         } else {
-            None
+            (None, None)
         };
 
-        capture_session.StartCapture().map_err(|_| StreamCreateError::Other("Failed to start capture".into()))?;
+        if let Some(capture_session) = &capture_session {
+            capture_session.StartCapture().map_err(|_| StreamCreateError::Other("Failed to start capture".into()))?;
+        }
 
         let stream = WindowsCaptureStream {
-            dxgi_adapter,
-            dxgi_adapter_error,
-            dxgi_device,
-            d3d11_device,
+            graphics_devices,
+            configured_dxgi_adapter: config.impl_capture_config.dxgi_adapter,
+            configured_d3d11_device: config.impl_capture_config.d3d11_device,
             #[cfg(feature = "wgpu")]
             wgpu_device,
             frame_pool,
             capture_session,
             should_couninit,
             shared_handler_data,
-            audio_stream
+            audio_stream,
+            microphone_stream,
         };
 
         Ok(stream)
     }
 
     pub fn stop(&self) -> Result<(), StreamStopError> {
-        let already_closed = self.shared_handler_data.closed.fetch_and(true, atomic::Ordering::AcqRel);
+        let already_closed = self.shared_handler_data.closed.fetch_or(true, atomic::Ordering::AcqRel);
         if !already_closed {
             (*self.shared_handler_data.callback.lock())(Ok(StreamEvent::End));
         }
-        self.capture_session.Close().map_err(|_| StreamStopError::Other("Failed to close capture session".into()))?;
+        if let Some(capture_session) = &self.capture_session {
+            capture_session.Close().map_err(|_| StreamStopError::Other("Failed to close capture session".into()))?;
+        }
         Ok(())
     }
 }
@@ -444,6 +526,9 @@ impl Drop for WindowsCaptureStream {
         if let Some(audio_stream) = &mut self.audio_stream {
             audio_stream.stop();
         }
+        if let Some(microphone_stream) = &mut self.microphone_stream {
+            microphone_stream.stop();
+        }
         if self.should_couninit {
             unsafe { CoUninitialize(); }
         }
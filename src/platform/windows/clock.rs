@@ -0,0 +1,23 @@
+use std::time::Duration;
+
+use windows::Win32::System::Performance::{QueryPerformanceCounter, QueryPerformanceFrequency};
+
+/// Get the current host time, from the performance counter
+pub(crate) fn host_time_now() -> Duration {
+    let mut counter = 0i64;
+    let mut frequency = 0i64;
+    unsafe {
+        let _ = QueryPerformanceCounter(&mut counter as *mut _);
+        let _ = QueryPerformanceFrequency(&mut frequency as *mut _);
+    }
+    if frequency <= 0 {
+        return Duration::ZERO;
+    }
+    let nanos = counter.max(0) as u128 * 1_000_000_000 / frequency as u128;
+    Duration::from_nanos(nanos as u64)
+}
+
+/// Convert a performance counter time in 100ns units (as used by WASAPI and Windows.Graphics.Capture) to a host time
+pub(crate) fn host_time_from_100ns(time: i64) -> Duration {
+    Duration::from_nanos(time.max(0) as u64 * 100)
+}
@@ -2,7 +2,9 @@ use std::{marker::PhantomData, sync::Arc, time::Duration};
 
 use windows::{Graphics::{Capture::Direct3D11CaptureFrame, DirectX::DirectXPixelFormat, SizeInt32}, Win32::Graphics::Direct3D11::ID3D11Device};
 
-use crate::{prelude::{AudioBufferError, AudioCaptureFrame, AudioChannelCount, AudioChannelDataSamples, AudioSampleRate, Point, Rect, VideoCaptureFrame}, util::Size};
+#[cfg(feature = "bitmap")]
+use crate::feature::bitmap::StagingTextureCache;
+use crate::{prelude::{AudioBufferError, AudioSource, StreamClock, AudioCaptureFrame, AudioChannelCount, AudioChannelDataSamples, AudioSampleFormat, AudioSampleRate, Point, Rect, VideoCaptureFrame}, util::{PixelSize, Size}};
 
 pub struct WindowsVideoFrame {
     pub(crate) device       : ID3D11Device,
@@ -11,20 +13,24 @@ pub struct WindowsVideoFrame {
     pub(crate) pixel_format : DirectXPixelFormat,
     pub(crate) frame_id     : u64,
     pub(crate) dpi          : u32,
+    pub(crate) screen_rect  : Rect,
     pub(crate) t_capture    : std::time::Instant,
     pub(crate) t_origin     : std::time::Duration,
     pub(crate) duration     : std::time::Duration,
+    pub(crate) clock        : StreamClock,
     #[cfg(feature = "wgpu")]
     pub(crate) wgpu_device  : Option<Arc<dyn AsRef<wgpu::Device> + Send + Sync + 'static>>,
+    #[cfg(feature = "bitmap")]
+    pub(crate) staging_textures: Arc<StagingTextureCache>,
 }
 
 impl VideoCaptureFrame for WindowsVideoFrame {
     fn size(&self) -> Size {
         let size = self.frame.ContentSize().unwrap_or(SizeInt32::default());
-        Size {
-            width: size.Width as f64,
-            height: size.Height as f64,
-        }
+        PixelSize {
+            width: size.Width.max(0) as u32,
+            height: size.Height.max(0) as u32,
+        }.into()
     }
 
     fn dpi(&self) -> f64 {
@@ -43,24 +49,52 @@ impl VideoCaptureFrame for WindowsVideoFrame {
         self.t_capture
     }
 
+    fn stream_clock(&self) -> StreamClock {
+        self.clock
+    }
+
     fn frame_id(&self) -> u64 {
         self.frame_id
     }
 
     fn content_rect(&self) -> Rect {
+        // ContentSize is in pixels, and the content is always placed at the top-left of the frame's surface
         Rect {
             origin: Point::ZERO,
             size: self.size()
         }
     }
+
+    fn screen_rect(&self) -> Rect {
+        self.screen_rect
+    }
+}
+
+pub(crate) enum WindowsAudioFrameData {
+    F32(Box<[f32]>),
+    I16(Box<[i16]>),
+}
+
+impl WindowsAudioFrameData {
+    /// Copy interleaved samples out of a WASAPI packet, or produce silence if there is no packet data
+    pub(crate) fn from_packet_bytes(sample_format: AudioSampleFormat, data: Option<&[u8]>, sample_count: usize) -> Self {
+        match (sample_format, data) {
+            (AudioSampleFormat::F32, Some(data)) => Self::F32(data.chunks_exact(4).map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).collect()),
+            (AudioSampleFormat::I16, Some(data)) => Self::I16(data.chunks_exact(2).map(|bytes| i16::from_ne_bytes([bytes[0], bytes[1]])).collect()),
+            (AudioSampleFormat::F32, None) => Self::F32(vec![0.0; sample_count].into_boxed_slice()),
+            (AudioSampleFormat::I16, None) => Self::I16(vec![0; sample_count].into_boxed_slice()),
+        }
+    }
 }
 
 pub struct WindowsAudioFrame {
-    pub(crate) data: Box<[i16]>,
+    pub(crate) data: WindowsAudioFrameData,
     pub(crate) channel_count: AudioChannelCount,
     pub(crate) sample_rate: AudioSampleRate,
+    pub(crate) source: AudioSource,
     pub(crate) duration: Duration,
     pub(crate) origin_time: Duration,
+    pub(crate) clock: StreamClock,
     pub(crate) frame_id: u64,
 }
 
@@ -73,28 +107,38 @@ impl AudioCaptureFrame for WindowsAudioFrame {
         self.channel_count
     }
 
+    fn sample_format(&self) -> AudioSampleFormat {
+        match &self.data {
+            WindowsAudioFrameData::F32(_) => AudioSampleFormat::F32,
+            WindowsAudioFrameData::I16(_) => AudioSampleFormat::I16,
+        }
+    }
+
+    fn source(&self) -> AudioSource {
+        self.source
+    }
+
     fn audio_channel_buffer(&mut self, channel: usize) -> Result<crate::prelude::AudioChannelData<'_>, crate::prelude::AudioBufferError> {
-        let element_stride = match self.channel_count {
-            AudioChannelCount::Mono => {
-                if channel != 0 {
-                    return Err(AudioBufferError::InvalidChannel)
-                }
-                0
-            },
-            AudioChannelCount::Stereo => {
-                if channel > 1 {
-                    return Err(AudioBufferError::InvalidChannel)
-                }
-                channel
-            },
-        };
-        let data = &self.data[element_stride] as *const i16 as *const u8;
-        Ok(crate::prelude::AudioChannelData::I16(AudioChannelDataSamples {
-            data,
-            stride: element_stride / 2,
-            length: self.data.len() / element_stride,
-            phantom_lifetime: PhantomData
-        }))
+        let channel_count = self.channel_count.count();
+        if channel >= channel_count {
+            return Err(AudioBufferError::InvalidChannel);
+        }
+        // Samples are interleaved, so each channel starts at its index and steps over a whole frame
+        // An empty or short packet has no whole frames, so its channels start at an empty slice rather than out of bounds
+        match &self.data {
+            WindowsAudioFrameData::F32(data) => Ok(crate::prelude::AudioChannelData::F32(AudioChannelDataSamples {
+                data: data.get(channel..).unwrap_or(&[]).as_ptr() as *const u8,
+                stride: channel_count * std::mem::size_of::<f32>(),
+                length: data.len() / channel_count,
+                phantom_lifetime: PhantomData
+            })),
+            WindowsAudioFrameData::I16(data) => Ok(crate::prelude::AudioChannelData::I16(AudioChannelDataSamples {
+                data: data.get(channel..).unwrap_or(&[]).as_ptr() as *const u8,
+                stride: channel_count * std::mem::size_of::<i16>(),
+                length: data.len() / channel_count,
+                phantom_lifetime: PhantomData
+            })),
+        }
     }
 
     fn duration(&self) -> std::time::Duration {
@@ -108,5 +152,9 @@ impl AudioCaptureFrame for WindowsAudioFrame {
     fn frame_id(&self) -> u64 {
         self.frame_id
     }
+
+    fn stream_clock(&self) -> StreamClock {
+        self.clock
+    }
 }
 
@@ -5,6 +5,8 @@ pub(crate) mod capture_stream;
 mod capturable_content;
 mod audio_capture_stream;
 pub(crate) mod frame;
+pub(crate) mod clock;
+pub(crate) mod audio_device;
 
 pub(crate) struct AutoHandle(HANDLE);
 impl Drop for AutoHandle {
@@ -18,6 +20,7 @@ pub(crate) use capturable_content::WindowsCapturableDisplay as ImplCapturableDis
 pub(crate) use capturable_content::WindowsCapturableWindow as ImplCapturableWindow;
 pub(crate) use capturable_content::WindowsCapturableContent as ImplCapturableContent;
 pub(crate) use capturable_content::WindowsCapturableContentFilter as ImplCapturableContentFilter;
+pub(crate) use capturable_content::WindowsCapturableContentNotifier as ImplCapturableContentNotifier;
 
 pub(crate) use capture_stream::WindowsCaptureStream as ImplCaptureStream;
 pub(crate) use capture_stream::WindowsCaptureConfig as ImplCaptureConfig;
@@ -27,6 +30,10 @@ pub(crate) use capture_stream::WindowsCaptureAccessToken as ImplCaptureAccessTok
 pub(crate) use frame::WindowsVideoFrame as ImplVideoFrame;
 pub(crate) use frame::WindowsAudioFrame as ImplAudioFrame;
 
+pub(crate) use clock::host_time_now;
+
+pub(crate) use audio_device::WindowsAudioDevice as ImplAudioDevice;
+
 pub use capture_stream::WindowsCaptureConfigExt;
 
 /// Windows-specific extensions to capturable windows
@@ -1,6 +1,8 @@
 pub use crate::capturable_content::*;
 pub use crate::frame::*;
+pub use crate::clock::*;
 pub use crate::capture_stream::*;
+pub use crate::audio_device::*;
 pub use crate::util::*;
 
 #[cfg(feature = "wgpu")]
@@ -9,6 +11,8 @@ pub use crate::feature::wgpu::*;
 pub use crate::feature::bitmap::*;
 #[cfg(feature = "screenshot")]
 pub use crate::feature::screenshot::*;
+#[cfg(feature = "audio")]
+pub use crate::feature::audio::*;
 #[cfg(target_os = "macos")]
 #[cfg(feature = "iosurface")]
 pub use crate::feature::iosurface::*;
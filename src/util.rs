@@ -1,5 +1,6 @@
 /// Represents a 2D size
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Size {
     pub width: f64,
     pub height: f64,
@@ -21,10 +22,40 @@ impl Size {
             height: self.height * scale.1
         }
     }
+
+    /// The ratio of width to height
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width / self.height
+    }
+
+    /// Whether either dimension is zero or negative
+    pub fn is_empty(&self) -> bool {
+        !(self.width > 0.0 && self.height > 0.0)
+    }
+
+    /// The largest size with this size's aspect ratio which fits within `bounds`
+    pub fn aspect_fit(&self, bounds: Size) -> Size {
+        self.scaled((bounds.width / self.width).min(bounds.height / self.height))
+    }
+
+    /// The smallest size with this size's aspect ratio which covers `bounds`
+    pub fn aspect_fill(&self, bounds: Size) -> Size {
+        self.scaled((bounds.width / self.width).max(bounds.height / self.height))
+    }
+
+    /// Convert a size in logical units (points) to physical pixels, rounding up
+    pub fn to_physical(&self, scale_factor: f64) -> PixelSize {
+        let size = self.scaled(scale_factor);
+        PixelSize {
+            width: size.width.ceil().max(0.0) as u32,
+            height: size.height.ceil().max(0.0) as u32,
+        }
+    }
 }
 
 /// Represents a 2D point
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     pub x: f64,
     pub y: f64,
@@ -52,10 +83,27 @@ impl Point {
             y: self.y * scale.1
         }
     }
+
+    /// Round the point to the nearest pixel
+    pub fn round(&self) -> PixelPoint {
+        PixelPoint {
+            x: self.x.round() as i32,
+            y: self.y.round() as i32,
+        }
+    }
+
+    /// Get the pixel containing this point
+    pub fn floor(&self) -> PixelPoint {
+        PixelPoint {
+            x: self.x.floor() as i32,
+            y: self.y.floor() as i32,
+        }
+    }
 }
 
 /// Represents an axis-aligned rectangle
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     pub origin: Point,
     pub size: Size,
@@ -77,4 +125,487 @@ impl Rect {
             size: self.size.scaled_2d(scale)
         }
     }
+
+    /// Whether this rectangle and another overlap with a non-zero area
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.origin.x < other.origin.x + other.size.width &&
+        other.origin.x < self.origin.x + self.size.width &&
+        self.origin.y < other.origin.y + other.size.height &&
+        other.origin.y < self.origin.y + self.size.height
+    }
+
+    /// Create a rectangle from its minimum and maximum corners
+    pub fn from_corners(min: Point, max: Point) -> Self {
+        Self {
+            origin: min,
+            size: Size {
+                width: max.x - min.x,
+                height: max.y - min.y,
+            }
+        }
+    }
+
+    /// The corner of the rectangle with the largest coordinates
+    pub fn max(&self) -> Point {
+        Point {
+            x: self.origin.x + self.size.width,
+            y: self.origin.y + self.size.height,
+        }
+    }
+
+    /// The center of the rectangle
+    pub fn center(&self) -> Point {
+        Point {
+            x: self.origin.x + self.size.width * 0.5,
+            y: self.origin.y + self.size.height * 0.5,
+        }
+    }
+
+    /// Whether the rectangle has a zero or negative area
+    pub fn is_empty(&self) -> bool {
+        self.size.is_empty()
+    }
+
+    /// Whether the point is within this rectangle (including the minimum edges, excluding the maximum edges)
+    pub fn contains(&self, point: Point) -> bool {
+        let max = self.max();
+        point.x >= self.origin.x && point.x < max.x &&
+        point.y >= self.origin.y && point.y < max.y
+    }
+
+    /// Whether another rectangle lies entirely within this one
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        let (max, other_max) = (self.max(), other.max());
+        other.origin.x >= self.origin.x && other_max.x <= max.x &&
+        other.origin.y >= self.origin.y && other_max.y <= max.y
+    }
+
+    /// The overlapping area of this rectangle and another, if they intersect
+    pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let (max, other_max) = (self.max(), other.max());
+        Some(Rect::from_corners(
+            Point { x: self.origin.x.max(other.origin.x), y: self.origin.y.max(other.origin.y) },
+            Point { x: max.x.min(other_max.x), y: max.y.min(other_max.y) },
+        ))
+    }
+
+    /// The smallest rectangle containing both this rectangle and another
+    pub fn union(&self, other: &Rect) -> Rect {
+        let (max, other_max) = (self.max(), other.max());
+        Rect::from_corners(
+            Point { x: self.origin.x.min(other.origin.x), y: self.origin.y.min(other.origin.y) },
+            Point { x: max.x.max(other_max.x), y: max.y.max(other_max.y) },
+        )
+    }
+
+    /// Clamp a point to lie within this rectangle (inclusive of the maximum edges)
+    pub fn clamp_point(&self, point: Point) -> Point {
+        let max = self.max();
+        Point {
+            x: point.x.max(self.origin.x).min(max.x),
+            y: point.y.max(self.origin.y).min(max.y),
+        }
+    }
+
+    /// Clamp this rectangle to lie within `bounds`, producing an empty rectangle at the nearest edge if they don't overlap
+    pub fn clamped_to(&self, bounds: &Rect) -> Rect {
+        Rect::from_corners(bounds.clamp_point(self.origin), bounds.clamp_point(self.max()))
+    }
+
+    /// The largest rectangle with the aspect ratio of `content` which fits within this rectangle, centered
+    pub fn aspect_fit(&self, content: Size) -> Rect {
+        self.centered(content.aspect_fit(self.size))
+    }
+
+    /// The smallest rectangle with the aspect ratio of `content` which covers this rectangle, centered
+    pub fn aspect_fill(&self, content: Size) -> Rect {
+        self.centered(content.aspect_fill(self.size))
+    }
+
+    fn centered(&self, size: Size) -> Rect {
+        let center = self.center();
+        Rect {
+            origin: Point {
+                x: center.x - size.width * 0.5,
+                y: center.y - size.height * 0.5,
+            },
+            size
+        }
+    }
+
+    /// The smallest pixel rectangle containing this rectangle
+    pub fn round_out(&self) -> PixelRect {
+        let max = self.max();
+        PixelRect::from_corners(
+            PixelPoint { x: self.origin.x.floor() as i32, y: self.origin.y.floor() as i32 },
+            PixelPoint { x: max.x.ceil() as i32, y: max.y.ceil() as i32 },
+        )
+    }
+
+    /// The largest pixel rectangle contained by this rectangle
+    pub fn round_in(&self) -> PixelRect {
+        let max = self.max();
+        PixelRect::from_corners(
+            PixelPoint { x: self.origin.x.ceil() as i32, y: self.origin.y.ceil() as i32 },
+            PixelPoint { x: max.x.floor() as i32, y: max.y.floor() as i32 },
+        )
+    }
+
+    /// The pixel rectangle with this rectangle's corners rounded to the nearest pixel
+    pub fn round(&self) -> PixelRect {
+        PixelRect::from_corners(self.origin.round(), self.max().round())
+    }
+
+    /// Convert a rectangle in logical units (points) to the physical pixels it covers
+    pub fn to_physical(&self, scale_factor: f64) -> PixelRect {
+        self.scaled(scale_factor).round_out()
+    }
+}
+
+/// Represents a 2D point on a pixel grid
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PixelPoint {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl PixelPoint {
+    /// The point at (0, 0)
+    pub const ZERO: PixelPoint = PixelPoint {
+        x: 0,
+        y: 0
+    };
+
+    /// Convert a point in physical pixels to logical units (points)
+    pub fn to_logical(&self, scale_factor: f64) -> Point {
+        Point::from(*self).scaled(1.0 / scale_factor)
+    }
+}
+
+impl From<PixelPoint> for Point {
+    fn from(point: PixelPoint) -> Self {
+        Point {
+            x: point.x as f64,
+            y: point.y as f64,
+        }
+    }
+}
+
+/// Represents a 2D size in whole pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PixelSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelSize {
+    /// Whether either dimension is zero
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The number of pixels in this size
+    pub fn area(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Convert a size in physical pixels to logical units (points)
+    pub fn to_logical(&self, scale_factor: f64) -> Size {
+        Size::from(*self).scaled(1.0 / scale_factor)
+    }
+}
+
+impl From<PixelSize> for Size {
+    fn from(size: PixelSize) -> Self {
+        Size {
+            width: size.width as f64,
+            height: size.height as f64,
+        }
+    }
+}
+
+/// Represents an axis-aligned rectangle on a pixel grid
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PixelRect {
+    pub origin: PixelPoint,
+    pub size: PixelSize,
+}
+
+impl PixelRect {
+    /// Create a rectangle from its minimum and maximum corners
+    /// 
+    /// If the maximum corner is less than the minimum corner in either axis, the rectangle is empty in that axis
+    pub fn from_corners(min: PixelPoint, max: PixelPoint) -> Self {
+        Self {
+            origin: min,
+            size: PixelSize {
+                width: (max.x as i64 - min.x as i64).max(0) as u32,
+                height: (max.y as i64 - min.y as i64).max(0) as u32,
+            }
+        }
+    }
+
+    /// Create a rectangle from its left, top, right and bottom edges
+    pub fn from_edges(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Self::from_corners(PixelPoint { x: left, y: top }, PixelPoint { x: right, y: bottom })
+    }
+
+    /// The corner of the rectangle with the largest coordinates (exclusive)
+    /// 
+    /// Note: This saturates at `i32::MAX` for rectangles which extend past the largest coordinate
+    pub fn max(&self) -> PixelPoint {
+        PixelPoint {
+            x: self.origin.x.saturating_add_unsigned(self.size.width),
+            y: self.origin.y.saturating_add_unsigned(self.size.height),
+        }
+    }
+
+    /// Whether the rectangle has a zero area
+    pub fn is_empty(&self) -> bool {
+        self.size.is_empty()
+    }
+
+    /// Whether the pixel is within this rectangle
+    pub fn contains(&self, point: PixelPoint) -> bool {
+        let max = self.max();
+        point.x >= self.origin.x && point.x < max.x &&
+        point.y >= self.origin.y && point.y < max.y
+    }
+
+    /// Whether another rectangle lies entirely within this one
+    pub fn contains_rect(&self, other: &PixelRect) -> bool {
+        let (max, other_max) = (self.max(), other.max());
+        other.origin.x >= self.origin.x && other_max.x <= max.x &&
+        other.origin.y >= self.origin.y && other_max.y <= max.y
+    }
+
+    /// Whether this rectangle and another overlap with a non-zero area
+    pub fn intersects(&self, other: &PixelRect) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    /// The overlapping area of this rectangle and another, if they intersect
+    pub fn intersection(&self, other: &PixelRect) -> Option<PixelRect> {
+        let (max, other_max) = (self.max(), other.max());
+        let intersection = PixelRect::from_corners(
+            PixelPoint { x: self.origin.x.max(other.origin.x), y: self.origin.y.max(other.origin.y) },
+            PixelPoint { x: max.x.min(other_max.x), y: max.y.min(other_max.y) },
+        );
+        if intersection.is_empty() {
+            None
+        } else {
+            Some(intersection)
+        }
+    }
+
+    /// The smallest rectangle containing both this rectangle and another
+    pub fn union(&self, other: &PixelRect) -> PixelRect {
+        let (max, other_max) = (self.max(), other.max());
+        PixelRect::from_corners(
+            PixelPoint { x: self.origin.x.min(other.origin.x), y: self.origin.y.min(other.origin.y) },
+            PixelPoint { x: max.x.max(other_max.x), y: max.y.max(other_max.y) },
+        )
+    }
+
+    /// Clamp this rectangle to lie within `bounds`, producing an empty rectangle at the nearest edge if they don't overlap
+    pub fn clamped_to(&self, bounds: &PixelRect) -> PixelRect {
+        let bounds_max = bounds.max();
+        let clamp = |point: PixelPoint| PixelPoint {
+            x: point.x.max(bounds.origin.x).min(bounds_max.x),
+            y: point.y.max(bounds.origin.y).min(bounds_max.y),
+        };
+        PixelRect::from_corners(clamp(self.origin), clamp(self.max()))
+    }
+
+    /// Convert a rectangle in physical pixels to logical units (points)
+    pub fn to_logical(&self, scale_factor: f64) -> Rect {
+        Rect::from(*self).scaled(1.0 / scale_factor)
+    }
+}
+
+impl From<PixelRect> for Rect {
+    fn from(rect: PixelRect) -> Self {
+        Rect {
+            origin: rect.origin.into(),
+            size: rect.size.into(),
+        }
+    }
+}
+
+/// An axis-aligned mapping from one 2D coordinate space to another: a non-uniform scale followed by a translation
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CoordinateTransform {
+    /// The scale applied in x and y
+    pub scale: (f64, f64),
+    /// The offset added after scaling
+    pub offset: Point,
+}
+
+impl CoordinateTransform {
+    /// The transform which maps every point to itself
+    pub const IDENTITY: CoordinateTransform = CoordinateTransform {
+        scale: (1.0, 1.0),
+        offset: Point::ZERO,
+    };
+
+    /// Create the transform mapping the `from` rectangle onto the `to` rectangle
+    /// 
+    /// Note: If `from` has a zero width or height, the scale in that axis will be infinite or NaN
+    pub fn from_rects(from: Rect, to: Rect) -> Self {
+        let scale = (
+            to.size.width / from.size.width,
+            to.size.height / from.size.height,
+        );
+        Self {
+            scale,
+            offset: Point {
+                x: to.origin.x - from.origin.x * scale.0,
+                y: to.origin.y - from.origin.y * scale.1,
+            }
+        }
+    }
+
+    /// Map a point through this transform
+    pub fn transform_point(&self, point: Point) -> Point {
+        Point {
+            x: point.x * self.scale.0 + self.offset.x,
+            y: point.y * self.scale.1 + self.offset.y,
+        }
+    }
+
+    /// Map a rectangle through this transform
+    pub fn transform_rect(&self, rect: Rect) -> Rect {
+        Rect {
+            origin: self.transform_point(rect.origin),
+            size: rect.size.scaled_2d(self.scale),
+        }
+    }
+
+    /// Get the transform which undoes this one
+    pub fn inverse(&self) -> Self {
+        let scale = (1.0 / self.scale.0, 1.0 / self.scale.1);
+        Self {
+            scale,
+            offset: Point {
+                x: -self.offset.x * scale.0,
+                y: -self.offset.y * scale.1,
+            }
+        }
+    }
+
+    /// Get the transform which applies this transform, and then `next`
+    pub fn then(&self, next: &CoordinateTransform) -> Self {
+        Self {
+            scale: (self.scale.0 * next.scale.0, self.scale.1 * next.scale.1),
+            offset: next.transform_point(self.offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_point_eq(a: Point, b: Point) {
+        assert!((a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    fn pixel_rect(x: i32, y: i32, width: u32, height: u32) -> PixelRect {
+        PixelRect { origin: PixelPoint { x, y }, size: PixelSize { width, height } }
+    }
+
+    #[test]
+    fn pixel_rect_max_saturates() {
+        assert_eq!(pixel_rect(10, -5, 20, 30).max(), PixelPoint { x: 30, y: 25 });
+        assert_eq!(pixel_rect(i32::MAX - 1, 0, 10, u32::MAX).max(), PixelPoint { x: i32::MAX, y: i32::MAX });
+        assert_eq!(pixel_rect(i32::MIN, 0, u32::MAX, 0).max(), PixelPoint { x: i32::MAX, y: 0 });
+    }
+
+    #[test]
+    fn pixel_rect_intersection() {
+        let a = pixel_rect(0, 0, 100, 100);
+        let b = pixel_rect(50, -20, 100, 40);
+        assert_eq!(a.intersection(&b), Some(pixel_rect(50, 0, 50, 20)));
+        assert_eq!(b.intersection(&a), a.intersection(&b));
+        assert!(a.intersects(&b));
+        assert_eq!(a.intersection(&pixel_rect(200, 200, 10, 10)), None);
+        // Rects near the edge of the coordinate space don't overflow
+        let far = pixel_rect(i32::MAX - 10, i32::MAX - 10, 100, 100);
+        assert_eq!(far.intersection(&pixel_rect(i32::MAX - 20, i32::MAX - 20, 15, 15)), Some(pixel_rect(i32::MAX - 10, i32::MAX - 10, 5, 5)));
+    }
+
+    #[test]
+    fn pixel_rect_containment() {
+        let outer = pixel_rect(-10, -10, 50, 50);
+        assert!(outer.contains_rect(&pixel_rect(-10, -10, 50, 50)));
+        assert!(outer.contains_rect(&pixel_rect(0, 0, 40, 40)));
+        assert!(!outer.contains_rect(&pixel_rect(0, 0, 41, 40)));
+        assert!(outer.contains(PixelPoint { x: -10, y: -10 }));
+        assert!(outer.contains(PixelPoint { x: 39, y: 39 }));
+        assert!(!outer.contains(PixelPoint { x: 40, y: 0 }));
+        assert!(!outer.contains(PixelPoint { x: 0, y: -11 }));
+    }
+
+    #[test]
+    fn pixel_rect_edge_touching() {
+        // Rects which share only an edge or a corner don't intersect, but their union spans both
+        let left = pixel_rect(0, 0, 10, 10);
+        let right = pixel_rect(10, 0, 10, 10);
+        let corner = pixel_rect(10, 10, 5, 5);
+        assert!(!left.intersects(&right));
+        assert_eq!(left.intersection(&right), None);
+        assert!(!left.intersects(&corner));
+        assert_eq!(left.union(&right), pixel_rect(0, 0, 20, 10));
+        assert_eq!(left.union(&corner), pixel_rect(0, 0, 15, 15));
+    }
+
+    #[test]
+    fn transform_from_rects_maps_corners() {
+        let from = Rect { origin: Point { x: 0.0, y: 0.0 }, size: Size { width: 200.0, height: 100.0 } };
+        let to = Rect { origin: Point { x: 50.0, y: 20.0 }, size: Size { width: 100.0, height: 50.0 } };
+        let transform = CoordinateTransform::from_rects(from, to);
+        assert_eq!(transform.scale, (0.5, 0.5));
+        assert_point_eq(transform.transform_point(Point { x: 0.0, y: 0.0 }), Point { x: 50.0, y: 20.0 });
+        assert_point_eq(transform.transform_point(Point { x: 200.0, y: 100.0 }), Point { x: 150.0, y: 70.0 });
+        assert_eq!(transform.transform_rect(from), to);
+    }
+
+    #[test]
+    fn transform_from_rects_with_offset_source() {
+        // A 2x retina frame whose content starts part way into the frame
+        let from = Rect { origin: Point { x: 20.0, y: 40.0 }, size: Size { width: 400.0, height: 300.0 } };
+        let to = Rect { origin: Point { x: -100.0, y: 10.0 }, size: Size { width: 200.0, height: 150.0 } };
+        let transform = CoordinateTransform::from_rects(from, to);
+        assert_point_eq(transform.transform_point(from.origin), to.origin);
+        assert_point_eq(transform.transform_point(Point { x: 220.0, y: 190.0 }), Point { x: 0.0, y: 85.0 });
+    }
+
+    #[test]
+    fn transform_inverse_round_trips() {
+        let transform = CoordinateTransform { scale: (2.0, 0.25), offset: Point { x: -3.0, y: 7.5 } };
+        let inverse = transform.inverse();
+        for point in [Point { x: 0.0, y: 0.0 }, Point { x: 12.5, y: -4.0 }, Point { x: -100.0, y: 1000.0 }] {
+            assert_point_eq(inverse.transform_point(transform.transform_point(point)), point);
+            assert_point_eq(transform.transform_point(inverse.transform_point(point)), point);
+        }
+        assert_eq!(transform.then(&inverse).scale, (1.0, 1.0));
+        assert_point_eq(transform.then(&inverse).offset, Point::ZERO);
+    }
+
+    #[test]
+    fn transform_then_composes_in_order() {
+        let first = CoordinateTransform { scale: (2.0, 3.0), offset: Point { x: 1.0, y: 1.0 } };
+        let second = CoordinateTransform { scale: (0.5, 2.0), offset: Point { x: -4.0, y: 5.0 } };
+        let composed = first.then(&second);
+        let point = Point { x: 3.0, y: -2.0 };
+        assert_point_eq(composed.transform_point(point), second.transform_point(first.transform_point(point)));
+        assert_eq!(CoordinateTransform::IDENTITY.then(&first), first);
+    }
 }